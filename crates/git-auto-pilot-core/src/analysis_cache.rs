@@ -0,0 +1,149 @@
+//! # Analysis Cache Module
+//!
+//! Editors and build tools often emit a burst of events for a single save
+//! (data, metadata, close), each of which would run the full status and diff
+//! analysis of the repository again. The result of an analysis is kept for a
+//! short time, keyed by `HEAD`, the modification time of the index and the
+//! size and modification time of the event's paths, so repeated events for
+//! unchanged files reuse it. The entry of a repository is dropped after each
+//! commit.
+
+use git2::Repository;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::trace;
+
+use crate::git::{self, FileChangeStats};
+
+/// How long an analysis result is reused
+const ANALYSIS_TTL: Duration = Duration::from_secs(2);
+
+/// Changes of a repository, grouped by file
+type Changes = HashMap<String, Vec<FileChangeStats>>;
+
+/// State of the repository and the analyzed paths a result is valid for
+#[derive(Debug, PartialEq, Eq)]
+struct AnalysisKey {
+    head: Option<git2::Oid>,
+    index_modified: Option<SystemTime>,
+    paths: Vec<(PathBuf, Option<(u64, SystemTime)>)>,
+}
+
+impl AnalysisKey {
+    fn new(repo: &Repository, paths: &[PathBuf]) -> Self {
+        let modified = |path: &Path| {
+            fs::symlink_metadata(path)
+                .and_then(|metadata| Ok((metadata.len(), metadata.modified()?)))
+                .ok()
+        };
+        AnalysisKey {
+            head: repo.head().ok().and_then(|head| head.target()),
+            index_modified: modified(&repo.path().join("index")).map(|(_, at)| at),
+            paths: paths
+                .iter()
+                .map(|path| (path.clone(), modified(path)))
+                .collect(),
+        }
+    }
+}
+
+/// Latest analysis result of each repository
+#[derive(Debug, Default)]
+pub struct AnalysisCache {
+    repos: Mutex<HashMap<PathBuf, (AnalysisKey, Instant, Changes)>>,
+}
+
+impl AnalysisCache {
+    /// Analyzes the changes of a repository for the paths of an event, reusing
+    /// a recent result if neither the repository nor the paths changed since
+    ///
+    /// Full scans (no paths) are not cached, as changes elsewhere in the
+    /// working tree do not show in the key.
+    ///
+    /// # Arguments
+    /// - `repo_key`: Key of the repository (see `state::repo_key`).
+    /// - `repo`: The repository.
+    /// - `paths`: Paths reported by the event.
+    ///
+    /// # Errors
+    /// Returns a `git2::Error` if the analysis fails.
+    pub fn analyze(
+        &self,
+        repo_key: &Path,
+        repo: &Repository,
+        paths: &[PathBuf],
+    ) -> Result<Changes, git2::Error> {
+        if paths.is_empty() {
+            return git::analyze_repository_changes(repo, paths);
+        }
+
+        let key = AnalysisKey::new(repo, paths);
+        {
+            let repos = self.repos.lock().unwrap_or_else(|p| p.into_inner());
+            if let Some((cached_key, at, changes)) = repos.get(repo_key) {
+                if *cached_key == key && at.elapsed() < ANALYSIS_TTL {
+                    trace!("Reusing the analysis of {}", repo_key.display());
+                    return Ok(changes.clone());
+                }
+            }
+        }
+
+        let changes = git::analyze_repository_changes(repo, paths)?;
+        self.repos.lock().unwrap_or_else(|p| p.into_inner()).insert(
+            repo_key.to_path_buf(),
+            (key, Instant::now(), changes.clone()),
+        );
+        Ok(changes)
+    }
+
+    /// Drops the cached analysis of a repository, e.g. after a commit
+    ///
+    /// # Arguments
+    /// - `repo_key`: Key of the repository (see `state::repo_key`).
+    pub fn invalidate(&self, repo_key: &Path) {
+        self.repos
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .remove(repo_key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reuses_analysis_until_invalidated() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        {
+            let mut config = repo.config().unwrap();
+            config.set_str("user.name", "Test").unwrap();
+            config.set_str("user.email", "test@example.com").unwrap();
+        }
+        fs::write(dir.path().join("notes.md"), "todo\n").unwrap();
+        git::stage_file(&repo, "notes.md", false).unwrap();
+        git::commit(&repo, "initial", None, None, None, None, None).unwrap();
+
+        let new_file = dir.path().join("new.md");
+        fs::write(&new_file, "draft\n").unwrap();
+        let cache = AnalysisCache::default();
+        let paths = vec![new_file];
+
+        // A new file falls back to a full scan, which sees every file
+        let changes = cache.analyze(dir.path(), &repo, &paths).unwrap();
+        assert_eq!(changes.len(), 1);
+
+        // A file outside the event does not change the key
+        fs::write(dir.path().join("other.md"), "other\n").unwrap();
+        let changes = cache.analyze(dir.path(), &repo, &paths).unwrap();
+        assert_eq!(changes.len(), 1);
+
+        cache.invalidate(dir.path());
+        let changes = cache.analyze(dir.path(), &repo, &paths).unwrap();
+        assert_eq!(changes.len(), 2);
+    }
+}
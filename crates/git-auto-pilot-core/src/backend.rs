@@ -0,0 +1,363 @@
+//! # Backend Module
+//!
+//! Status and commit operations behind the `VcsBackend` trait. They are
+//! served by libgit2 (`git2`) unless the `gix` feature is enabled, which
+//! serves them with gitoxide instead. Everything else (staging, pushing,
+//! fetching, tagging and maintenance) still goes through libgit2.
+
+use git2::{Error as GitError, Repository, Status, StatusOptions, Time};
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+
+use crate::git::{self, CommitAuthor};
+
+/// Reads the status of a working tree and commits its index
+pub trait VcsBackend: Debug + Send + Sync {
+    /// Name of the backend, e.g. `git2`
+    fn name(&self) -> &'static str;
+
+    /// Returns the paths that differ between the working tree, the index and
+    /// HEAD, relative to the repository root; ignored files are left out
+    ///
+    /// # Arguments
+    /// - `workdir` - The working tree of the repository.
+    /// - `paths` - Repository-relative paths to restrict the status to,
+    ///   matched literally; an empty slice reads the whole status.
+    ///
+    /// # Errors
+    /// Returns a `GitError` if the repository cannot be opened or its status
+    /// cannot be read.
+    fn changed_paths(&self, workdir: &Path, paths: &[PathBuf]) -> Result<Vec<PathBuf>, GitError>;
+
+    /// Commits the index on top of HEAD, with the identity the repository
+    /// configures as committer
+    ///
+    /// # Arguments
+    /// - `workdir` - The working tree of the repository.
+    /// - `message` - The full commit message.
+    /// - `time` - Time of the author and committer signatures, now if `None`.
+    /// - `author` - Author replacing the committer as author.
+    /// - `fallback` - Identity used when the repository configures none.
+    ///
+    /// # Returns
+    /// The id of the new commit.
+    ///
+    /// # Errors
+    /// Returns a `GitError` if no identity is available or the commit cannot
+    /// be written.
+    fn commit(
+        &self,
+        workdir: &Path,
+        message: &str,
+        time: Option<Time>,
+        author: Option<&CommitAuthor>,
+        fallback: Option<&CommitAuthor>,
+    ) -> Result<String, GitError>;
+}
+
+/// Backend built on libgit2
+#[derive(Debug, Default)]
+pub struct Git2Backend;
+
+impl VcsBackend for Git2Backend {
+    fn name(&self) -> &'static str {
+        "git2"
+    }
+
+    fn changed_paths(&self, workdir: &Path, paths: &[PathBuf]) -> Result<Vec<PathBuf>, GitError> {
+        let repo = Repository::open(workdir)?;
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .exclude_submodules(true)
+            .disable_pathspec_match(true);
+        for path in paths {
+            options.pathspec(path);
+        }
+        let statuses = repo.statuses(Some(&mut options))?;
+        Ok(statuses
+            .iter()
+            .filter(|entry| !entry.status().contains(Status::IGNORED))
+            .filter_map(|entry| entry.path().map(PathBuf::from))
+            .collect())
+    }
+
+    fn commit(
+        &self,
+        workdir: &Path,
+        message: &str,
+        time: Option<Time>,
+        author: Option<&CommitAuthor>,
+        fallback: Option<&CommitAuthor>,
+    ) -> Result<String, GitError> {
+        let repo = Repository::open(workdir)?;
+        git::commit(&repo, message, None, time, None, author, fallback)?;
+        let commit_id = repo.head()?.peel_to_commit()?.id();
+        Ok(commit_id.to_string())
+    }
+}
+
+/// Backend built on gitoxide
+#[cfg(feature = "gix")]
+#[derive(Debug, Default)]
+pub struct GixBackend;
+
+#[cfg(feature = "gix")]
+impl GixBackend {
+    /// Opens a repository, applying `fallback` to the `user.name` and
+    /// `user.email` it does not configure
+    fn open(workdir: &Path, fallback: Option<&CommitAuthor>) -> Result<gix::Repository, GitError> {
+        let repo = gix::open(workdir).map_err(to_git_error)?;
+        let Some(fallback) = fallback else {
+            return Ok(repo);
+        };
+        let config = repo.config_snapshot();
+        let mut overrides = Vec::new();
+        if config.string("user.name").is_none() {
+            overrides.push(format!("user.name={}", fallback.name));
+        }
+        if config.string("user.email").is_none() {
+            overrides.push(format!("user.email={}", fallback.email));
+        }
+        if overrides.is_empty() {
+            return Ok(repo);
+        }
+        gix::open_opts(
+            workdir,
+            gix::open::Options::default().config_overrides(overrides),
+        )
+        .map_err(to_git_error)
+    }
+
+    /// Writes the trees of the index, returning the id of the root tree
+    fn write_index_tree(repo: &gix::Repository) -> Result<gix::ObjectId, GitError> {
+        use gix::bstr::{BString, ByteSlice};
+        use gix::objs::tree::{Entry, EntryKind};
+        use std::collections::BTreeMap;
+
+        let index = repo.index_or_empty().map_err(to_git_error)?;
+        // Entries of every directory, by the directory's path
+        let mut dirs: BTreeMap<BString, Vec<Entry>> = BTreeMap::new();
+        dirs.insert(BString::default(), Vec::new());
+        for entry in index.entries() {
+            let path = entry.path(&index);
+            let mode = entry.mode.to_tree_entry_mode().ok_or_else(|| {
+                GitError::from_str(&format!("{} cannot be written to a tree", path))
+            })?;
+            let (dir, name) = match path.rfind_byte(b'/') {
+                Some(at) => (&path[..at], &path[at + 1..]),
+                None => (&path[..0], path),
+            };
+            dirs.entry(dir.into()).or_default().push(Entry {
+                mode,
+                filename: name.into(),
+                oid: entry.id,
+            });
+            // Directories only holding directories get an entry as well
+            let mut parent = dir;
+            while let Some(at) = parent.rfind_byte(b'/') {
+                parent = &parent[..at];
+                dirs.entry(parent.into()).or_default();
+            }
+        }
+
+        // Subtrees are written before the directories holding them
+        let mut paths: Vec<BString> = dirs.keys().cloned().collect();
+        paths.sort_by_key(|path| {
+            (
+                path.is_empty(),
+                std::cmp::Reverse(path.find_iter("/").count()),
+            )
+        });
+        for path in paths {
+            let mut entries = dirs.remove(&path).unwrap_or_default();
+            entries.sort();
+            let tree_id = repo
+                .write_object(&gix::objs::Tree { entries })
+                .map_err(to_git_error)?
+                .detach();
+            if path.is_empty() {
+                return Ok(tree_id);
+            }
+            let (parent, name) = match path.rfind_byte(b'/') {
+                Some(at) => (&path[..at], &path[at + 1..]),
+                None => (&path[..0], &path[..]),
+            };
+            dirs.entry(parent.into()).or_default().push(Entry {
+                mode: EntryKind::Tree.into(),
+                filename: name.into(),
+                oid: tree_id,
+            });
+        }
+        Err(GitError::from_str("the index has no root tree"))
+    }
+}
+
+#[cfg(feature = "gix")]
+impl VcsBackend for GixBackend {
+    fn name(&self) -> &'static str {
+        "gix"
+    }
+
+    fn changed_paths(&self, workdir: &Path, paths: &[PathBuf]) -> Result<Vec<PathBuf>, GitError> {
+        let repo = gix::open(workdir).map_err(to_git_error)?;
+        // Paths are file names, not patterns: `[` or `*` must not match others
+        let patterns = paths
+            .iter()
+            .map(|path| format!(":(literal){}", path.display()).into());
+        let status = repo
+            .status(gix::progress::Discard)
+            .map_err(to_git_error)?
+            .untracked_files(gix::status::UntrackedFiles::Files)
+            .into_iter(patterns)
+            .map_err(to_git_error)?;
+        let mut paths = Vec::new();
+        for item in status {
+            let item = item.map_err(to_git_error)?;
+            paths.push(PathBuf::from(item.location().to_string()));
+        }
+        Ok(paths)
+    }
+
+    fn commit(
+        &self,
+        workdir: &Path,
+        message: &str,
+        time: Option<Time>,
+        author: Option<&CommitAuthor>,
+        fallback: Option<&CommitAuthor>,
+    ) -> Result<String, GitError> {
+        let repo = Self::open(workdir, fallback)?;
+        let mut committer: gix::actor::Signature = repo
+            .committer()
+            .ok_or_else(|| GitError::from_str("no committer identity is configured"))?
+            .map_err(to_git_error)?
+            .into();
+        if let Some(time) = time {
+            committer.time = gix::date::Time {
+                seconds: time.seconds(),
+                offset: time.offset_minutes() * 60,
+            };
+        }
+        let author = match author {
+            Some(author) => gix::actor::Signature {
+                name: author.name.as_str().into(),
+                email: author.email.as_str().into(),
+                time: committer.time,
+            },
+            None => committer.clone(),
+        };
+
+        let tree_id = Self::write_index_tree(&repo)?;
+        let parents: Vec<gix::ObjectId> = repo
+            .head_id()
+            .ok()
+            .map(|id| id.detach())
+            .into_iter()
+            .collect();
+        let (mut committer_time, mut author_time) = Default::default();
+        let commit_id = repo
+            .commit_as(
+                committer.to_ref(&mut committer_time),
+                author.to_ref(&mut author_time),
+                "HEAD",
+                message,
+                tree_id,
+                parents,
+            )
+            .map_err(to_git_error)?;
+        tracing::info!(
+            "Created commit with id: {}\nMessage: {}",
+            commit_id,
+            message
+        );
+        Ok(commit_id.to_string())
+    }
+}
+
+/// Converts a gitoxide error to the error type of the git operations
+#[cfg(feature = "gix")]
+fn to_git_error(err: impl std::fmt::Display) -> GitError {
+    GitError::from_str(&err.to_string())
+}
+
+/// Creates the backend selected by the enabled features
+pub fn default_backend() -> Box<dyn VcsBackend> {
+    #[cfg(feature = "gix")]
+    return Box::new(GixBackend);
+    #[cfg(not(feature = "gix"))]
+    Box::new(Git2Backend)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_and_commit() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let fallback = CommitAuthor {
+            name: "Test".to_string(),
+            email: "test@example.com".to_string(),
+        };
+        let author = CommitAuthor {
+            name: "Docs".to_string(),
+            email: "docs@example.com".to_string(),
+        };
+        let backend = default_backend();
+        assert!(backend.changed_paths(dir.path(), &[]).unwrap().is_empty());
+
+        std::fs::create_dir(dir.path().join("notes")).unwrap();
+        std::fs::write(dir.path().join("notes/todo.md"), "- write tests\n").unwrap();
+        std::fs::write(dir.path().join("notes/[draft].md"), "- draft\n").unwrap();
+        let mut changed = backend.changed_paths(dir.path(), &[]).unwrap();
+        changed.sort();
+        assert_eq!(
+            changed,
+            [
+                PathBuf::from("notes/[draft].md"),
+                PathBuf::from("notes/todo.md")
+            ]
+        );
+        // Scoped paths are matched literally
+        assert_eq!(
+            backend
+                .changed_paths(dir.path(), &[PathBuf::from("notes/[draft].md")])
+                .unwrap(),
+            [PathBuf::from("notes/[draft].md")]
+        );
+
+        git::stage_file(&repo, "notes/todo.md", false).unwrap();
+        let time = Time::new(1_700_000_000, 120);
+        let commit_id = backend
+            .commit(
+                dir.path(),
+                "Add todo",
+                Some(time),
+                Some(&author),
+                Some(&fallback),
+            )
+            .unwrap();
+
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.id().to_string(), commit_id);
+        assert_eq!(head.message(), Some("Add todo"));
+        assert_eq!(head.author().name(), Some("Docs"));
+        assert_eq!(head.committer().name(), Some("Test"));
+        assert_eq!(head.time(), time);
+        assert_eq!(head.author().when(), time);
+        assert!(head
+            .tree()
+            .unwrap()
+            .get_path(Path::new("notes/todo.md"))
+            .is_ok());
+        assert_eq!(
+            backend
+                .changed_paths(dir.path(), &[PathBuf::from("notes/todo.md")])
+                .unwrap(),
+            Vec::<PathBuf>::new()
+        );
+    }
+}
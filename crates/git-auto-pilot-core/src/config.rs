@@ -0,0 +1,2918 @@
+//! # Configuration Module for Git Commit and Description Management
+//!
+//! This module provides a flexible configuration system for generating
+//! commit messages and descriptions with customizable templates and variables.
+//!
+//! ## Features
+//! - Customizable commit message templates
+//! - Flexible variable substitution
+//! - Serializable and deserializable configuration
+//! - Default configurations with easy customization
+
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+use crate::redact;
+
+/// Represents credentials for authenticating with a Git repository.
+///
+/// This structure is used to store and manage the authentication
+/// details required for operations such as cloning, pushing, or pulling.
+/// The password is zeroized when dropped and its `Debug` output is masked.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GitCred {
+    /// The username for committing.
+    pub username: String,
+
+    /// The email address associated with the Git user.
+    pub email: String,
+
+    /// The username for authentication.
+    pub login_username: Option<String>,
+
+    /// The password or personal access token for authentication.
+    #[serde(default, serialize_with = "redact::serialize_secret")]
+    pub password: Option<SecretString>,
+}
+
+impl GitCred {
+    /// Layers credentials over others field by field, empty fields of
+    /// `other` keep the current ones
+    ///
+    /// Merged apart from the other settings, so the password never ends up
+    /// in a plain `serde_json::Value`.
+    fn merged(current: Option<GitCred>, other: Option<GitCred>) -> Option<GitCred> {
+        match (current, other) {
+            (Some(mut current), Some(other)) => {
+                if !other.username.is_empty() {
+                    current.username = other.username;
+                }
+                if !other.email.is_empty() {
+                    current.email = other.email;
+                }
+                if other
+                    .login_username
+                    .as_deref()
+                    .is_some_and(|username| !username.is_empty())
+                {
+                    current.login_username = other.login_username;
+                }
+                if other
+                    .password
+                    .as_ref()
+                    .is_some_and(|password| !password.expose_secret().is_empty())
+                {
+                    current.password = other.password;
+                }
+                Some(current)
+            }
+            (current, other) => other.or(current),
+        }
+    }
+}
+
+impl PartialEq for GitCred {
+    fn eq(&self, other: &Self) -> bool {
+        self.username == other.username
+            && self.email == other.email
+            && self.login_username == other.login_username
+            && self.password.as_ref().map(ExposeSecret::expose_secret)
+                == other.password.as_ref().map(ExposeSecret::expose_secret)
+    }
+}
+
+impl Eq for GitCred {}
+
+impl std::fmt::Debug for GitCred {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GitCred")
+            .field("username", &self.username)
+            .field("email", &self.email)
+            .field("login_username", &self.login_username)
+            .field("password", &self.password.as_ref().map(|_| redact::MASK))
+            .finish()
+    }
+}
+
+/// Represents a message template with prefix, comment, and suffix
+///
+/// This struct defines the format for generating commit messages. It includes:
+/// - `prefix`: Text that appears before the main comment (e.g., "[Create]").
+/// - `comment`: The main body of the message, which may include placeholders for variables (e.g., "File {{FILE_NAME}} created").
+/// - `suffix`: Text that appears after the main comment (e.g., a timestamp or additional info).
+/// - `comment_file`: File the comment is read from instead, for long multi-line templates.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Message {
+    /// Prefix text for the message
+    pub prefix: String,
+
+    /// Main comment body with potential variable placeholders
+    pub comment: String,
+
+    /// Suffix text for the message
+    pub suffix: String,
+
+    /// File the comment is read from when the configuration is loaded,
+    /// replacing `comment`; relative paths are resolved against the directory
+    /// of the configuration file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment_file: Option<String>,
+}
+
+/// Defines commit summary message templates for different operation types
+///
+/// This struct contains templates for generating commit summaries based on
+/// file operations. It supports three operations:
+/// - `create`: Template for file creation events
+/// - `modify`: Template for file modification events
+/// - `remove`: Template for file removal events
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitSummary {
+    /// Template for file creation events
+    pub create: Message,
+
+    /// Template for file modification events
+    pub modify: Message,
+
+    /// Template for file removal events
+    pub remove: Message,
+
+    /// Template for file rename events
+    pub rename: Message,
+
+    /// Template for directory rename events
+    pub rename_dir: Message,
+
+    /// Template for project scaffolding events
+    pub scaffold: Message,
+}
+
+/// Defines detailed description templates for different operation types
+///
+/// This struct contains templates for generating commit descriptions based on
+/// file operations. It includes detailed information about the file, such as:
+/// - `create`: Description template for file creation events
+/// - `modify`: Description template for file modification events
+/// - `remove`: Description template for file removal events
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Description {
+    /// Template for file creation descriptions
+    pub create: Message,
+
+    /// Template for file modification descriptions
+    pub modify: Message,
+
+    /// Template for file removal descriptions
+    pub remove: Message,
+
+    /// Template for file rename descriptions
+    pub rename: Message,
+
+    /// Template for directory rename descriptions
+    pub rename_dir: Message,
+
+    /// Template for project scaffolding descriptions
+    pub scaffold: Message,
+}
+
+/// Configuration error types
+///
+/// This enum defines the types of errors that may occur when working with the
+/// configuration. These errors include:
+/// - `JsonParseError`: Triggered when JSON parsing fails.
+/// - `FileError`: Triggered when file operations (reading or writing) fail.
+/// - `UnsupportedVersion`: Triggered when the schema version is newer than supported.
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    /// Occurs when JSON parsing fails
+    #[error("Failed to parse configuration JSON: {0}")]
+    JsonParseError(#[from] serde_json::Error),
+
+    /// Occurs when file operations fail
+    #[error("File operation error: {0}")]
+    FileError(String),
+
+    /// Occurs when a message template cannot be loaded or rendered
+    #[error("Template error: {0}")]
+    TemplateError(String),
+
+    /// Occurs when an environment variable override has an invalid value
+    #[error("Invalid environment override {0}")]
+    InvalidOverride(String),
+
+    /// Occurs when the configuration was written by a newer, unknown schema version
+    #[error("Unsupported configuration version {0} (latest known is {CONFIG_VERSION})")]
+    UnsupportedVersion(u32),
+
+    /// Occurs when a repository selected on the command line is not configured
+    #[error("{0} is not a configured repository")]
+    UnknownRepository(String),
+}
+
+// Log the error details when the ConfigError is being dropped
+impl Drop for ConfigError {
+    fn drop(&mut self) {
+        tracing::error!("{}", self);
+    }
+}
+
+/// Main configuration structure
+///
+/// This struct holds the entire configuration for generating commit messages
+/// and descriptions. It includes:
+/// - `message`: Commit summary message templates
+/// - `description`: Detailed description templates
+/// - `variables`: Custom variables for template substitution
+/// - `repos`: List of repository paths to track
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// Schema version of the configuration file
+    #[serde(default)]
+    pub version: u32,
+
+    /// Configuration files layered under this one (see `resolve_includes`)
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Commit summary message templates
+    pub message: CommitSummary,
+
+    /// Detailed description templates
+    pub description: Description,
+
+    /// What happens when a template references a variable that is not defined
+    #[serde(default)]
+    pub unknown_variables: UnknownVariables,
+
+    /// How repeated commits of the same file on the same day are marked in the summary
+    #[serde(default)]
+    pub repeat_annotation: RepeatAnnotation,
+
+    /// Merges the repository's commit template into generated messages
+    #[serde(default)]
+    pub commit_conventions: Option<CommitConventions>,
+
+    /// Custom variables for template substitution
+    #[serde(default = "default_variables")]
+    pub variables: serde_json::Value,
+
+    /// List of repository paths to track; an entry pointing at a single file
+    /// (e.g. `~/notes/todo.md`) tracks only that file of its repository
+    #[serde(default)]
+    pub repos: Vec<RepoEntry>,
+
+    /// List of dirs to ignore events
+    #[serde(default)]
+    pub ignored_dirs: Vec<String>,
+
+    /// contains git credentials
+    #[serde(default)]
+    pub git_credentials: Option<GitCred>,
+
+    /// Which moment the commit timestamp should reflect
+    #[serde(default)]
+    pub commit_time: CommitTime,
+
+    /// UTC offset in minutes used when `commit_time` is `fixed-offset`
+    #[serde(default)]
+    pub commit_time_offset_minutes: i32,
+
+    /// Which branch auto-commits are made on
+    #[serde(default)]
+    pub branch_strategy: BranchStrategy,
+
+    /// Prefix of the branches created by the `per-day` and `per-session` strategies
+    #[serde(default = "default_branch_prefix")]
+    pub branch_prefix: String,
+
+    /// Optional rule for creating milestone tags
+    #[serde(default)]
+    pub tagging: Option<TagRule>,
+
+    /// Which refs are pushed after a commit
+    #[serde(default)]
+    pub push_mode: PushMode,
+
+    /// Pushes commits to the remote; when disabled commits stay local
+    #[serde(default = "default_push_enabled")]
+    pub push_enabled: bool,
+
+    /// What to do when the remote branch has commits the local branch lacks
+    #[serde(default)]
+    pub divergence_policy: DivergencePolicy,
+
+    /// Branch and tag name patterns (e.g. `main`, `release/*`) that are never
+    /// pushed by the `all-branches` and `mirror` push modes
+    #[serde(default)]
+    pub protected_refs: Vec<String>,
+
+    /// Remote host/path patterns pushes are restricted to (e.g. `github.com/myorg/*`),
+    /// all remotes are allowed when empty
+    #[serde(default)]
+    pub allowed_push_hosts: Vec<String>,
+
+    /// Maximum number of pushes running at once, pushes to the same host are
+    /// always serialized; `0` disables the global limit
+    #[serde(default = "default_max_concurrent_pushes")]
+    pub max_concurrent_pushes: usize,
+
+    /// Batch pushes: push once this many commits are waiting; when neither this
+    /// nor `push_interval_secs` is set every commit is pushed right away
+    #[serde(default)]
+    pub push_every_n_commits: Option<u64>,
+
+    /// Batch pushes: push waiting commits once the oldest is this many seconds old
+    #[serde(default)]
+    pub push_interval_secs: Option<u64>,
+
+    /// Alert once commits have waited this many seconds without a successful
+    /// push; `0` disables the alert
+    #[serde(default = "default_push_alert_secs")]
+    pub push_alert_secs: u64,
+
+    /// Push options sent with every push (`git push -o`), e.g. `ci.skip` so
+    /// autopilot pushes do not trigger CI pipelines on GitLab; the server must
+    /// support push options
+    #[serde(default)]
+    pub push_options: Vec<String>,
+
+    /// Minimum number of new files appearing in a new directory at once that are
+    /// committed as a single scaffold commit; `0` disables scaffold detection
+    #[serde(default = "default_scaffold_threshold")]
+    pub scaffold_threshold: usize,
+
+    /// Number of consecutive failures after which a path is quarantined
+    /// (skipped for `quarantine_secs`); `0` disables the quarantine
+    #[serde(default = "default_quarantine_after_failures")]
+    pub quarantine_after_failures: u32,
+
+    /// Seconds a failing path stays quarantined
+    #[serde(default = "default_quarantine_secs")]
+    pub quarantine_secs: u64,
+
+    /// Milliseconds a file must remain unwritten (by its modification time)
+    /// before it is staged, so files still being copied in are not committed
+    /// truncated; their events are retried once settled. `0` stages right away
+    #[serde(default)]
+    pub write_settle_ms: u64,
+
+    /// Milliseconds after which an event deferred during a manual git
+    /// operation (a held `.git/index.lock` or a merge message being edited) is
+    /// retried; it waits longer if the repository is still busy by then
+    #[serde(default = "default_busy_wait_ms")]
+    pub busy_wait_ms: u64,
+
+    /// Commits the changes made while the daemon was not running on startup,
+    /// instead of waiting for the next file system event
+    #[serde(default)]
+    pub catch_up_on_start: bool,
+
+    /// Records every handled file system event in the journal of the state
+    /// directory, for `git-auto-pilot replay`
+    #[serde(default)]
+    pub event_journal: bool,
+
+    /// How new files too large for plain history are handled
+    #[serde(default)]
+    pub large_files: Option<LargeFileSettings>,
+
+    /// Compares a blake3 hash of changed files with the last seen one and
+    /// drops events that leave the content unchanged; meant for network file
+    /// systems, where the poll watcher's own content comparison is expensive
+    #[serde(default)]
+    pub content_hashing: bool,
+
+    /// Seconds during which a file flipping back to the content it had before
+    /// its last autopilot commit (e.g. an editor autosave undone right away)
+    /// is not committed again; the change is committed with the next one
+    /// after the window, `0` commits every flip
+    #[serde(default = "default_revert_window_secs")]
+    pub revert_window_secs: u64,
+
+    /// Number of lines at the top of a file searched for the
+    /// `git-auto-pilot: skip` comment, which excludes the file from
+    /// auto-commits; `0` disables the marker
+    #[serde(default = "default_skip_marker_lines")]
+    pub skip_marker_lines: usize,
+
+    /// Path patterns (e.g. `**/.env`, `secrets/**`) that are never staged or
+    /// committed, whatever other rules say; patterns follow `AuthorRule`
+    #[serde(default)]
+    pub sensitive_paths: Vec<String>,
+
+    /// Shell command run in the working tree before every commit (e.g.
+    /// `cargo check`), a change passes verification if it exits successfully;
+    /// the daemon runs it in the background and holds back the changes until
+    /// it finished
+    #[serde(default)]
+    pub verify_command: Option<String>,
+
+    /// Shell command run in the working tree after every successful push,
+    /// e.g. to send a chat notification; it finds `COMMIT_SHA`, `COMMIT_URL`
+    /// (the web page of the commit, empty for unknown hosts), `BRANCH` and
+    /// `REFSPECS` in its environment
+    #[serde(default)]
+    pub post_push_command: Option<String>,
+
+    /// What happens with a change failing `verify_command`
+    #[serde(default)]
+    pub verify_failure: VerifyFailure,
+
+    /// Seconds after which `verify_command` is killed and counts as failed
+    #[serde(default = "default_verify_timeout_secs")]
+    pub verify_timeout_secs: u64,
+
+    /// How uncommitted changes found in a newly added repository are handled
+    #[serde(default)]
+    pub backlog_policy: BacklogPolicy,
+
+    /// HTTP(S) proxy URL used for remote operations (e.g. `http://proxy:3128`),
+    /// falls back to the `https_proxy` environment variable when unset
+    #[serde(default)]
+    pub proxy: Option<String>,
+
+    /// Custom CA bundle (PEM file or certificate directory) trusted for remote operations
+    #[serde(default)]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Where the daemon writes its log, overridden by `--log-file`/`--log-target`
+    #[serde(default)]
+    pub log_target: LogTarget,
+
+    /// TCP port serving the `/healthz` and `/readyz` probes, overridden by
+    /// `--health-port`; disabled when unset
+    #[serde(default)]
+    pub health_port: Option<u16>,
+
+    /// Address the health probes are served on, only reachable from this
+    /// machine by default; `0.0.0.0` serves them on all interfaces, e.g. in a
+    /// container
+    #[serde(default = "default_health_address")]
+    pub health_address: IpAddr,
+
+    /// Skips TLS certificate verification for remote operations, insecure and
+    /// only meant for testing against self-signed servers
+    #[serde(default)]
+    pub insecure_skip_tls_verify: bool,
+
+    /// Seconds a push or fetch may take, and a connection may stall, before it
+    /// is cancelled; `0` disables the timeout. The stall timeout applies to
+    /// the whole process: a changed value takes effect after a restart
+    #[serde(default = "default_network_timeout_secs")]
+    pub network_timeout_secs: u64,
+
+    /// Number of times a push that timed out or lost its connection is
+    /// retried; the watch loop keeps handling events until a retry is due
+    #[serde(default = "default_network_retries")]
+    pub network_retries: u32,
+
+    /// Seconds before the first retry of a push, doubled for every further retry
+    #[serde(default = "default_network_retry_backoff_secs")]
+    pub network_retry_backoff_secs: u64,
+
+    /// Name of the commit message generator (`template`, `conventional`, `script` or `llm`)
+    #[serde(default = "default_generator")]
+    pub generator: String,
+
+    /// Command run by the `script` generator, receiving the change as JSON on stdin
+    #[serde(default)]
+    pub generator_script: Option<String>,
+
+    /// Settings of the `llm` generator
+    #[serde(default)]
+    pub llm: Option<LlmSettings>,
+
+    /// Signs autopilot commits with an external `gpg` or `ssh-keygen` program
+    #[serde(default)]
+    pub signing: Option<SigningSettings>,
+
+    /// Verifies the signatures of commits pulled from `origin` (in follow
+    /// mode and when rebasing onto the remote) and refuses unverified ones
+    #[serde(default)]
+    pub verify_pulled_commits: Option<SignatureVerification>,
+
+    /// Authors of commits by changed path, CODEOWNERS-style: the last matching
+    /// rule wins, unmatched paths are authored by the configured git user
+    #[serde(default)]
+    pub authors: Vec<AuthorRule>,
+
+    /// Processing priority of the repository's events, see `Priority`
+    #[serde(default)]
+    pub priority: Priority,
+
+    /// Priority of events by changed path, overriding `priority`: the last
+    /// matching rule wins
+    #[serde(default)]
+    pub priority_rules: Vec<PriorityRule>,
+
+    /// Seconds low priority events are collected before they are processed
+    /// as one batch
+    #[serde(default = "default_low_priority_batch_secs")]
+    pub low_priority_batch_secs: u64,
+
+    /// Commit a batch of low priority events as one commit per package (see
+    /// the `PACKAGE` variable), e.g. `chore(api): update 3 files`, instead of
+    /// one commit per file
+    #[serde(default)]
+    pub batch_by_package: bool,
+
+    /// Message templates by changed file (e.g. `*.md`), taking precedence over
+    /// the `message`/`description` templates of the change; the last matching
+    /// rule wins
+    #[serde(default)]
+    pub file_templates: Vec<FileTemplate>,
+
+    /// Pauses auto-commits of a repository while a build is running in it
+    #[serde(default)]
+    pub build_detection: Option<BuildDetectionSettings>,
+
+    /// Tracks the growth of each repository's object database
+    #[serde(default)]
+    pub disk_usage: Option<DiskUsageSettings>,
+
+    /// Periodic housekeeping of each repository
+    #[serde(default)]
+    pub maintenance: Option<MaintenanceSettings>,
+
+    /// Periodic `git bundle` snapshots of each repository
+    #[serde(default)]
+    pub bundle_backup: Option<BundleBackupSettings>,
+
+    /// Local copies of deleted files, kept before their removal is committed
+    #[serde(default)]
+    pub trash: Option<TrashSettings>,
+
+    /// Machine-readable metadata attached to every autopilot commit as a git note
+    #[serde(default)]
+    pub notes: Option<NotesSettings>,
+
+    /// Fast-forwards the checked out branch to commits pushed to `origin` by others
+    #[serde(default)]
+    pub follow: Option<FollowSettings>,
+
+    /// Forge on which missing remote repositories are created before the first push
+    #[serde(default)]
+    pub forge: Option<ForgeSettings>,
+
+    /// Named groups of repositories sharing settings that differ from the top level
+    #[serde(default)]
+    pub groups: BTreeMap<String, RepoGroup>,
+
+    /// Directory the `comment_file`s were read from, to read those set by
+    /// groups; set by `load_template_files`
+    #[serde(skip)]
+    pub template_dir: Option<PathBuf>,
+}
+
+/// Maps a path pattern to the author of commits changing matching paths
+///
+/// Patterns follow CODEOWNERS: `docs/` matches everything below `docs`,
+/// `*.md` (no `/`) matches file names anywhere and other patterns are globs
+/// matched against the path relative to the repository root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthorRule {
+    /// Path pattern
+    pub pattern: String,
+
+    /// Author name
+    pub name: String,
+
+    /// Author email
+    pub email: String,
+}
+
+/// Maps a path pattern to the processing priority of events on matching paths
+///
+/// Patterns follow `AuthorRule`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriorityRule {
+    /// Path pattern
+    pub pattern: String,
+
+    /// Priority of matching paths
+    pub priority: Priority,
+}
+
+/// Maps a path pattern to the templates of commits changing matching files
+///
+/// Applies to file creations, modifications, removals and renames; directory
+/// renames and scaffolds always use their own templates. Patterns follow
+/// `AuthorRule`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileTemplate {
+    /// Path pattern
+    pub pattern: String,
+
+    /// Summary template (e.g. `docs: update {{FILE_NAME_SHORT}}`)
+    pub message: Message,
+
+    /// Description template, the one of the change kind if unset
+    #[serde(default)]
+    pub description: Option<Message>,
+}
+
+/// Settings for signing commits with an external program
+///
+/// Useful for keys on hardware tokens: the commit is handed to the same
+/// program `git` itself would run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningSettings {
+    /// Signature format
+    #[serde(default)]
+    pub format: SigningFormat,
+
+    /// Key to sign with: a gpg key id, or the path of an ssh key (or its public key for agents)
+    pub key: String,
+
+    /// Program to run instead of `gpg` or `ssh-keygen`
+    #[serde(default)]
+    pub program: Option<String>,
+}
+
+/// Settings for verifying the signatures of pulled commits
+///
+/// Every incoming commit is checked with `git verify-commit`. If any of them
+/// is unsigned or signed by a key that is not trusted, the commits are not
+/// merged: follow mode skips the fast-forward and a rebase onto the remote
+/// fails the push.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureVerification {
+    /// GnuPG home directory holding the trusted keys (`GNUPGHOME`), a leading
+    /// `~` is expanded; the user's keyring if unset
+    #[serde(default)]
+    pub keyring: Option<String>,
+
+    /// File listing the trusted signers of SSH signatures
+    /// (`gpg.ssh.allowedSignersFile`), a leading `~` is expanded
+    #[serde(default)]
+    pub allowed_signers: Option<String>,
+}
+
+/// Settings for detecting builds running in a repository
+///
+/// A build is considered running while its output keeps changing (until no
+/// indicator changed for `quiet_secs`) or while the `busy_file` exists.
+/// Events arriving meanwhile are retried once the build is over.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildDetectionSettings {
+    /// Path patterns (see `AuthorRule`) whose changes indicate a running build
+    #[serde(default = "default_build_indicators")]
+    pub indicators: Vec<String>,
+
+    /// File, relative to the repository root, pausing auto-commits while it exists
+    #[serde(default)]
+    pub busy_file: Option<String>,
+
+    /// Seconds without indicator changes after which the build is considered over
+    #[serde(default = "default_build_quiet_secs")]
+    pub quiet_secs: u64,
+}
+
+/// Default build indicators: cargo and npm output and lock files
+fn default_build_indicators() -> Vec<String> {
+    [
+        "target/",
+        "node_modules/",
+        ".cargo-lock",
+        ".package-lock.json",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+/// Default time low priority events are collected in seconds
+fn default_low_priority_batch_secs() -> u64 {
+    5 * 60
+}
+
+/// Default time without build output changes after which a build is over
+fn default_build_quiet_secs() -> u64 {
+    10
+}
+
+/// Settings for tracking the object database growth caused by autopilot commits
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsageSettings {
+    /// Warn once the object database grew by this many MiB since it was first
+    /// measured (or since the last `git gc`)
+    #[serde(default = "default_disk_usage_warn_growth_mb")]
+    pub warn_growth_mb: u64,
+
+    /// Seconds between two measurements of a repository
+    #[serde(default = "default_disk_usage_check_interval_secs")]
+    pub check_interval_secs: u64,
+
+    /// Run `git gc` once the threshold is exceeded instead of only warning
+    #[serde(default)]
+    pub auto_gc: bool,
+}
+
+/// Default object database growth (in MiB) triggering a warning
+fn default_disk_usage_warn_growth_mb() -> u64 {
+    100
+}
+
+/// Default interval between two disk usage measurements in seconds
+fn default_disk_usage_check_interval_secs() -> u64 {
+    600
+}
+
+/// Settings for local `git bundle` backups
+///
+/// Every `interval_secs` a bundle with all refs of each repository is written
+/// to `<dir>/<repository path>/<timestamp>.bundle`, unless no ref changed since
+/// the previous one. A bundle is restored with `git clone <file> <dir>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleBackupSettings {
+    /// Directory the bundles are written to, a leading `~` is expanded
+    pub dir: String,
+
+    /// Seconds between two bundles of a repository
+    #[serde(default = "default_bundle_interval_secs")]
+    pub interval_secs: u64,
+
+    /// Number of bundles kept per repository, older ones are deleted
+    #[serde(default = "default_bundle_keep")]
+    pub keep: usize,
+}
+
+/// Default interval between two bundles in seconds
+fn default_bundle_interval_secs() -> u64 {
+    60 * 60
+}
+
+/// Default number of bundles kept per repository
+fn default_bundle_keep() -> usize {
+    5
+}
+
+/// Settings for the local trash of deleted files
+///
+/// Before the deletion of a file is committed, its last committed version is
+/// copied to `<dot dir>/trash/<repository path>/<timestamp>/<file path>`, so
+/// a deletion that was committed and pushed can be restored by copying the
+/// file back. Entries older than `retention_days` are deleted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashSettings {
+    /// Days a deleted file is kept in the trash
+    #[serde(default = "default_trash_retention_days")]
+    pub retention_days: u64,
+}
+
+/// Default number of days a deleted file is kept in the trash
+fn default_trash_retention_days() -> u64 {
+    30
+}
+
+/// Settings for the metadata notes of autopilot commits
+///
+/// Every autopilot commit gets a note in `refs/notes/git-auto-pilot` holding
+/// JSON metadata: the change and event that caused it, when the event was
+/// received and committed and the line statistics. The commit message stays
+/// clean; `git log --notes=git-auto-pilot` shows the metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotesSettings {
+    /// Pushes the notes ref after every successful push of the branch,
+    /// merging the notes pushed from other clones first; a failure to push
+    /// the notes is only logged
+    #[serde(default)]
+    pub push: bool,
+}
+
+/// Settings for following the remote branch
+///
+/// Every `interval_secs` the checked out branch is fetched from `origin`. If
+/// the remote has new commits and the local branch has none of its own, the
+/// branch and the working tree are fast-forwarded. Diverged branches are left
+/// to the `divergence_policy` of the next push. A `POST` to `/hooks/follow` on
+/// the `health_port` polls right away.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FollowSettings {
+    /// Seconds between two polls of the remote branch
+    #[serde(default = "default_follow_interval_secs")]
+    pub interval_secs: u64,
+}
+
+/// Default interval between two polls of the remote branch in seconds
+fn default_follow_interval_secs() -> u64 {
+    60
+}
+
+/// Settings for periodic repository housekeeping
+///
+/// The tasks run through the `git` CLI in the background, while no commit is
+/// made in the repository, every `interval_secs` (the first run happens one
+/// interval after startup).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceSettings {
+    /// Seconds between two maintenance runs of a repository
+    #[serde(default = "default_maintenance_interval_secs")]
+    pub interval_secs: u64,
+
+    /// Run `git gc` to pack loose objects
+    #[serde(default = "default_true")]
+    pub gc: bool,
+
+    /// Run `git prune` to delete unreachable loose objects older than two weeks
+    #[serde(default = "default_true")]
+    pub prune: bool,
+
+    /// Run `git fetch --prune origin` to drop stale remote-tracking refs
+    #[serde(default = "default_true")]
+    pub fetch_prune: bool,
+
+    /// Delete stale branches below `branch_prefix`
+    #[serde(default)]
+    pub branch_cleanup: Option<BranchCleanupSettings>,
+}
+
+/// Settings for deleting stale autopilot branches
+///
+/// The `per-day` and `per-session` branch strategies leave one branch behind
+/// per day or session. Branches below `branch_prefix` are deleted once a
+/// branch outside the prefix merged them (a merge commit, fast-forwards do
+/// not count), or once their last commit is older than `retention_days` even
+/// if unmerged. The checked out branch, `protected_refs`, the quarantine
+/// branch and the side branches of diverged pushes are kept.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BranchCleanupSettings {
+    /// Days after the last commit an unmerged branch is kept
+    #[serde(default = "default_branch_retention_days")]
+    pub retention_days: u64,
+
+    /// Delete the branches on `origin` as well (off by default)
+    ///
+    /// A remote branch is only deleted if its tip (as of the last fetch)
+    /// matches the local branch and it is still there when deleting.
+    #[serde(default)]
+    pub remote: bool,
+}
+
+/// Default number of days an unmerged autopilot branch is kept
+fn default_branch_retention_days() -> u64 {
+    30
+}
+
+/// Default interval between two maintenance runs in seconds (one day)
+fn default_maintenance_interval_secs() -> u64 {
+    24 * 60 * 60
+}
+
+/// Default for settings that are enabled unless turned off
+fn default_true() -> bool {
+    true
+}
+
+/// Format of commit signatures
+///
+/// - `gpg`: OpenPGP signatures made with `gpg -bsau <key>` (default)
+/// - `ssh`: SSH signatures made with `ssh-keygen -Y sign -n git -f <key>`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SigningFormat {
+    /// OpenPGP signatures
+    #[default]
+    Gpg,
+
+    /// SSH signatures
+    Ssh,
+}
+
+/// Settings for creating missing remote repositories on a forge
+///
+/// The repository is named after its working directory and created under
+/// the user owning the token unless `owner` is set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForgeSettings {
+    /// The kind of forge
+    pub kind: ForgeKind,
+
+    /// API base URL for self-hosted instances, e.g. `https://gitlab.example.com/api/v4`
+    #[serde(default)]
+    pub api_url: Option<String>,
+
+    /// Environment variable holding the API token
+    #[serde(default = "default_forge_token_env")]
+    pub token_env: String,
+
+    /// Organization (GitHub) or namespace path (GitLab) to create repositories in
+    #[serde(default)]
+    pub owner: Option<String>,
+
+    /// Whether created repositories are private
+    #[serde(default = "default_true")]
+    pub private: bool,
+
+    /// Creates missing repositories without asking
+    #[serde(default)]
+    pub auto_create: bool,
+}
+
+/// Default environment variable holding the forge API token
+fn default_forge_token_env() -> String {
+    "GIT_AUTO_PILOT_FORGE_TOKEN".to_string()
+}
+
+/// Forge hosting the remote repositories
+///
+/// - `github`: GitHub or GitHub Enterprise
+/// - `gitlab`: GitLab
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForgeKind {
+    /// GitHub or GitHub Enterprise
+    Github,
+
+    /// GitLab
+    Gitlab,
+}
+
+/// An entry of `repos`
+///
+/// Either a plain path (or glob), or an object with the path and `variables`
+/// layered over the top-level (or group) `variables` for the matching
+/// repositories, e.g. `{ "path": "~/code/api", "variables": { "PROJECT": "backend" } }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RepoEntry {
+    /// Path of a repository, a single file or a glob
+    Path(PathBuf),
+
+    /// Path with repository-specific settings
+    Detailed {
+        /// Path of a repository, a single file or a glob
+        path: PathBuf,
+
+        /// Custom variables overriding those of the configuration
+        #[serde(default)]
+        variables: serde_json::Map<String, serde_json::Value>,
+    },
+}
+
+impl RepoEntry {
+    /// Path (or glob) of the entry
+    pub fn path(&self) -> &Path {
+        match self {
+            RepoEntry::Path(path) | RepoEntry::Detailed { path, .. } => path,
+        }
+    }
+
+    /// Custom variables of the entry, if it defines any
+    pub fn variables(&self) -> Option<&serde_json::Map<String, serde_json::Value>> {
+        match self {
+            RepoEntry::Detailed { variables, .. } if !variables.is_empty() => Some(variables),
+            _ => None,
+        }
+    }
+}
+
+impl From<PathBuf> for RepoEntry {
+    fn from(path: PathBuf) -> Self {
+        RepoEntry::Path(path)
+    }
+}
+
+/// A group of repositories sharing settings
+///
+/// Per-repository settings (templates, `variables`, `tagging`, `commit_time`,
+/// `branch_strategy`, `allowed_push_hosts`, ...) may be given here and override
+/// the top-level value for the repositories of the group. Objects are merged,
+/// so e.g. overriding `message.create` keeps the other top-level templates,
+/// and `comment_file`s are read relative to the configuration file.
+///
+/// Settings of the whole process are only read from the top level and have no
+/// effect in a group: `log_target`, `health_port`, `health_address`, `proxy`,
+/// `ca_bundle`, `insecure_skip_tls_verify`, `network_timeout_secs`,
+/// `max_concurrent_pushes`, `generator`, `generator_script` and `llm`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RepoGroup {
+    /// Repositories belonging to the group, watched like the top-level `repos`
+    #[serde(default)]
+    pub repos: Vec<RepoEntry>,
+
+    /// Settings overriding the top-level configuration
+    #[serde(flatten)]
+    pub settings: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Settings of the `llm` commit message generator
+///
+/// The endpoint must accept OpenAI-compatible chat completion requests.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LlmSettings {
+    /// Chat completions URL, e.g. `https://api.openai.com/v1/chat/completions`
+    pub endpoint: String,
+
+    /// Model name sent with each request
+    pub model: String,
+
+    /// Environment variable holding the API key
+    #[serde(default = "default_llm_api_key_env")]
+    pub api_key_env: String,
+
+    /// Request timeout in seconds
+    #[serde(default = "default_llm_timeout_secs")]
+    pub timeout_secs: u64,
+
+    /// Approximate maximum number of tokens of the diff sent to the endpoint
+    #[serde(default = "default_llm_max_input_tokens")]
+    pub max_input_tokens: usize,
+
+    /// Maximum number of tokens of the generated message
+    #[serde(default = "default_llm_max_output_tokens")]
+    pub max_output_tokens: usize,
+}
+
+/// Default environment variable holding the LLM API key
+fn default_llm_api_key_env() -> String {
+    "OPENAI_API_KEY".to_string()
+}
+
+/// Default LLM request timeout in seconds
+fn default_llm_timeout_secs() -> u64 {
+    15
+}
+
+/// Default maximum number of diff tokens sent to the LLM
+fn default_llm_max_input_tokens() -> usize {
+    2000
+}
+
+/// Default maximum number of tokens generated by the LLM
+fn default_llm_max_output_tokens() -> usize {
+    200
+}
+
+/// Default commit message generator
+fn default_generator() -> String {
+    crate::generator::DEFAULT_GENERATOR.to_string()
+}
+
+/// Default number of consecutive failures quarantining a path
+fn default_quarantine_after_failures() -> u32 {
+    3
+}
+
+/// Default quarantine period of a failing path in seconds
+fn default_quarantine_secs() -> u64 {
+    600
+}
+
+/// Default delay before retrying an event deferred during a manual git
+/// operation in milliseconds
+fn default_busy_wait_ms() -> u64 {
+    2000
+}
+
+/// Commits are pushed unless disabled
+fn default_push_enabled() -> bool {
+    true
+}
+
+/// Default number of lines searched for the skip marker
+fn default_skip_marker_lines() -> usize {
+    5
+}
+
+/// Default window in which reverted file contents are not committed, in seconds
+fn default_revert_window_secs() -> u64 {
+    30
+}
+
+/// Default timeout of network operations in seconds
+fn default_network_timeout_secs() -> u64 {
+    300
+}
+
+/// Default time commits may wait without a successful push before alerting, in seconds
+fn default_push_alert_secs() -> u64 {
+    24 * 60 * 60
+}
+
+/// Default number of retries of a failed push
+fn default_network_retries() -> u32 {
+    2
+}
+
+/// Default delay before the first retry of a push in seconds
+fn default_network_retry_backoff_secs() -> u64 {
+    5
+}
+
+/// Default minimum number of files forming a project scaffold
+fn default_scaffold_threshold() -> usize {
+    5
+}
+
+/// Default maximum number of concurrent pushes
+fn default_max_concurrent_pushes() -> usize {
+    4
+}
+
+/// Rule for creating annotated milestone tags on auto-committed repositories
+///
+/// A tag is created (and pushed) whenever either trigger fires. The `name` and
+/// `message` templates support the regular template variables as well as
+/// `{{DATE}}`, `{{TIME}}` and `{{COMMIT_COUNT}}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagRule {
+    /// Create a tag after this many autopilot commits
+    #[serde(default)]
+    pub every_n_commits: Option<u64>,
+
+    /// Create a tag once this many seconds have passed since the last one
+    #[serde(default)]
+    pub interval_secs: Option<u64>,
+
+    /// Template for the tag name
+    #[serde(default = "default_tag_name")]
+    pub name: String,
+
+    /// Template for the annotated tag message
+    #[serde(default = "default_tag_message")]
+    pub message: String,
+}
+
+/// Default template for milestone tag names
+fn default_tag_name() -> String {
+    "autopilot-{{DATE}}-{{TIME}}".to_string()
+}
+
+/// Default template for milestone tag messages
+fn default_tag_message() -> String {
+    "Autopilot milestone on {{BRANCH}} after {{COMMIT_COUNT}} commits".to_string()
+}
+
+/// Strategy for choosing the branch auto-commits are made on
+///
+/// - `current`: Commit on whatever branch is checked out (default)
+/// - `per-day`: Commit on a date-stamped branch, e.g. `autopilot/2024-06-01`
+/// - `per-session`: Commit on a branch created when the daemon starts,
+///   e.g. `autopilot/session-20240601T120000Z`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BranchStrategy {
+    /// Commit on the currently checked-out branch
+    #[default]
+    Current,
+
+    /// Commit on one branch per (UTC) day
+    PerDay,
+
+    /// Commit on one branch per daemon session
+    PerSession,
+}
+
+/// How template placeholders without a matching variable are handled
+///
+/// Unknown placeholders (e.g. a misspelled `{{BRANHC}}`) are always left
+/// verbatim in the message and counted in the status output.
+///
+/// - `ignore`: Commit without further notice
+/// - `warn`: Log a warning and commit (default)
+/// - `fail`: Fail the commit, so the change is retried once the template is fixed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnknownVariables {
+    /// Commit without further notice
+    Ignore,
+
+    /// Log a warning and commit
+    #[default]
+    Warn,
+
+    /// Fail the commit
+    Fail,
+}
+
+/// Processing lane of file system events
+///
+/// An event takes the highest priority of its paths.
+///
+/// - `low`: Collected for `low_priority_batch_secs` and processed as one batch
+/// - `normal`: Processed as events arrive, with the configured write settling
+///   and push batching (default)
+/// - `high`: Processed right away without waiting for writes to settle, and
+///   pushed immediately regardless of push batching
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Priority {
+    /// Batched aggressively
+    Low,
+
+    /// Processed as events arrive
+    #[default]
+    Normal,
+
+    /// Processed and pushed immediately
+    High,
+}
+
+/// How repeated commits of the same file on the same day are marked
+///
+/// Only the second and later commits of a file on a day are marked (see the
+/// `SEQ` variable), so the first commit keeps its plain summary.
+///
+/// - `off`: Leave the summary alone (default)
+/// - `counter`: Append the occurrence counter, e.g. `Update notes.md #3`
+/// - `today`: Append the number of commits today, e.g. `Update notes.md (x3 today)`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RepeatAnnotation {
+    /// Leave the summary alone
+    #[default]
+    Off,
+
+    /// Append `#<SEQ>`
+    Counter,
+
+    /// Append `(x<SEQ> today)`
+    Today,
+}
+
+/// Settings for new files above a size threshold
+///
+/// Instead of committing such a file straight into history, it is either left
+/// uncommitted or tracked with Git LFS by its exact path (`git lfs track
+/// --filename <path>`). This applies to single files, scaffolds and the
+/// backlog alike. The decision is logged and recorded in the event journal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LargeFileSettings {
+    /// Size in MiB above which a new file counts as large
+    #[serde(default = "default_large_file_threshold_mb")]
+    pub threshold_mb: u64,
+
+    /// What happens with a large file
+    #[serde(default)]
+    pub action: LargeFileAction,
+}
+
+/// Default size (in MiB) above which a new file counts as large
+fn default_large_file_threshold_mb() -> u64 {
+    50
+}
+
+/// How a new large file is handled
+///
+/// - `skip`: Leave it uncommitted (default)
+/// - `lfs`: Track it with Git LFS and commit the pointer; needs `git lfs`
+///   installed, the LFS objects are uploaded before every push
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LargeFileAction {
+    /// Leave the file uncommitted
+    #[default]
+    Skip,
+
+    /// Track the file with Git LFS
+    Lfs,
+}
+
+/// Commit conventions of a repository merged into the generated messages
+///
+/// The convention is read from the file of the repository's `commit.template`
+/// git setting, or else from `file`. Lines starting with `#` are dropped like
+/// git does; in a Markdown file (e.g. `COMMIT_CONVENTION.md`) only its first
+/// fenced code block is used. The convention may use the regular template
+/// variables as well as `{{SUMMARY}}` and `{{DESCRIPTION}}`, the generated
+/// message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitConventions {
+    /// Reads the file of the `commit.template` git setting
+    #[serde(default = "default_use_commit_template")]
+    pub commit_template: bool,
+
+    /// Convention file relative to the repository root, used without a `commit.template`
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+
+    /// How the convention is merged into the message
+    #[serde(default)]
+    pub mode: ConventionMode,
+}
+
+/// The `commit.template` git setting is honored unless disabled
+fn default_use_commit_template() -> bool {
+    true
+}
+
+/// How a commit convention is merged into the generated message
+///
+/// - `prefix`: The first line of the convention is put before the summary, or
+///   replaces it if it contains `{{SUMMARY}}` (default)
+/// - `scaffold`: Like `prefix` for the summary; the other lines of the
+///   convention form the description, which holds the generated one at
+///   `{{DESCRIPTION}}` or else before them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConventionMode {
+    /// Prefix the summary with the first line of the convention
+    #[default]
+    Prefix,
+
+    /// Build the whole message from the convention
+    Scaffold,
+}
+
+/// What happens with a change failing `verify_command`
+///
+/// - `skip`: Leave the change uncommitted until a later change passes (default)
+/// - `quarantine`: Commit it to `<branch_prefix>/quarantine` instead of the
+///   current branch; the change stays uncommitted on the current branch
+/// - `mark`: Commit it as usual with `[verify-failed]` before the summary
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VerifyFailure {
+    /// Leave the change uncommitted
+    #[default]
+    Skip,
+
+    /// Commit to the quarantine branch
+    Quarantine,
+
+    /// Commit with a marker in the summary
+    Mark,
+}
+
+/// Default time `verify_command` may run in seconds
+fn default_verify_timeout_secs() -> u64 {
+    300
+}
+
+/// Default address of the health probes, the loopback interface
+fn default_health_address() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::LOCALHOST)
+}
+
+/// How the uncommitted changes of a newly added repository are handled
+///
+/// - `ask`: Ask on the terminal at startup; without a terminal, or for a
+///   repository added while running, the backlog is left alone and the
+///   question is asked again on the next start
+/// - `snapshot`: Commit everything as one "Initial autopilot snapshot" commit
+/// - `per-directory`: Commit one snapshot per top-level directory
+/// - `per-package`: Commit one snapshot per package of a monorepo (see the
+///   `PACKAGE` variable)
+/// - `ignore`: Leave the changes alone; they are committed once touched
+///   (default)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BacklogPolicy {
+    /// Ask the user
+    Ask,
+
+    /// Commit the whole backlog at once
+    Snapshot,
+
+    /// Commit the backlog per top-level directory
+    PerDirectory,
+
+    /// Commit the backlog per package
+    PerPackage,
+
+    /// Leave the backlog alone
+    #[default]
+    Ignore,
+}
+
+/// Which refs are pushed to `origin` after a commit
+///
+/// - `branch`: Push the branch the commit was made on (default)
+/// - `all-branches`: Push every local branch
+/// - `mirror`: Force-push every local branch and tag, for using the autopilot
+///   as a backup agent; refs deleted locally are kept on the remote
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PushMode {
+    /// Push the branch the commit was made on
+    #[default]
+    Branch,
+
+    /// Push every local branch
+    AllBranches,
+
+    /// Force-push every local branch and tag
+    Mirror,
+}
+
+/// What to do before pushing a branch someone else pushed to in the meantime
+///
+/// - `pause`: Pause the repository until resumed (default)
+/// - `rebase`: Rebase the local commits onto the remote branch, then push
+/// - `side-branch`: Push the local commits to `<branch_prefix>/diverged/<branch>`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DivergencePolicy {
+    /// Pause the repository until resumed
+    #[default]
+    Pause,
+
+    /// Rebase onto the remote branch, then push
+    Rebase,
+
+    /// Push to a side branch instead
+    SideBranch,
+}
+
+/// Where the daemon writes its log
+///
+/// - `stdout`: Standard output (default)
+/// - `{"file": "<path>"}`: Append to a file, for running under `nohup`
+/// - `syslog`: The local syslog daemon
+/// - `journald`: The systemd journal, for running as a systemd service
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogTarget {
+    /// Write to standard output
+    #[default]
+    Stdout,
+
+    /// Append to a file
+    File(PathBuf),
+
+    /// Send to the local syslog daemon
+    Syslog,
+
+    /// Send to the systemd journal
+    Journald,
+}
+
+/// Default prefix for branches created by the autopilot
+fn default_branch_prefix() -> String {
+    "autopilot".to_string()
+}
+
+/// Strategy for choosing the author/committer timestamp of auto-commits
+///
+/// - `now`: The time the commit is created (default)
+/// - `event`: The time the triggering file system event was received
+/// - `fixed-offset`: The time the commit is created, in a fixed UTC offset
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CommitTime {
+    /// Use the time the commit is created
+    #[default]
+    Now,
+
+    /// Use the time the file system event was received
+    Event,
+
+    /// Use the time the commit is created with `commit_time_offset_minutes` as timezone
+    FixedOffset,
+}
+
+/// Current version of the configuration schema
+///
+/// Bump this whenever the on-disk format changes and register the matching
+/// upgrade step in `CONFIG_MIGRATIONS`.
+pub const CONFIG_VERSION: u32 = 3;
+
+/// Prefix of the environment variables overriding settings (see `Config::apply_env_overrides`)
+pub const ENV_OVERRIDE_PREFIX: &str = "GIT_AUTO_PILOT_";
+
+/// Settings describing the configuration file itself, which no environment
+/// variable overrides
+const NOT_OVERRIDABLE: &[&str] = &["version", "include"];
+
+/// A single schema upgrade step, transforming a raw configuration object from
+/// version `N` to version `N + 1` in place
+type Migration = fn(&mut serde_json::Map<String, serde_json::Value>);
+
+/// Ordered schema migrations, where the entry at index `N` upgrades version `N`
+const CONFIG_MIGRATIONS: &[Migration] = &[migrate_v0_to_v1, migrate_v1_to_v2, migrate_v2_to_v3];
+
+/// Default system variables
+///
+/// These are system-defined variables that can be substituted in the message
+/// and description templates. These include:
+/// - `INSERTIONS`: Number of lines inserted
+/// - `DELETIONS`: Number of lines deleted
+/// - `LINES_MODIFIED`: Number of lines modified
+/// - `BRANCH`: Current branch name
+/// - `STATUS`: Current status (e.g., staged, modified)
+/// - `FILE_NAME_SHORT`: Short file name
+/// - `FILE_NAME_FULL`: Full file name
+/// - `OLD_DIR`: Original directory of a directory rename
+/// - `NEW_DIR`: New directory of a directory rename
+/// - `FILE_COUNT`: Number of files covered by the commit
+/// - `DIR`: Directory of a project scaffold
+/// - `EVENT_KIND`: Granular kind of the triggering event (e.g. `data-modify`,
+///   `metadata-modify`, `rename-from`), `sync` for a force-sync
+/// - `EVENT_SOURCE`: Where the triggering event came from (the watcher backend,
+///   e.g. `inotify`, or `force-sync`)
+/// - `FILE_SIZE`: Size of the changed file (e.g. `12.5 KiB`), `0 B` once removed
+/// - `FILE_MIME`: MIME type of the changed file (e.g. `image/png`), detected from
+///   its extension or its first bytes
+/// - `TODAY_COMMITS`: Number of autopilot commits made in the repository today,
+///   including the current one
+/// - `TOTAL_COMMITS`: Number of autopilot commits made in the repository so far,
+///   including the current one
+/// - `STREAK`: Number of consecutive days with autopilot commits in the repository
+/// - `SEQ`: Number of autopilot commits of the changed file today, including
+///   the current one
+/// - `PACKAGE`: Package of the changed file in a monorepo, named after the
+///   directory of the nearest `Cargo.toml`, `package.json` or `go.mod`; empty
+///   when there is none
+pub const SYSTEM_VARIABLES: &[(&str, &str)] = &[
+    ("INSERTIONS", "INSERTIONS"),
+    ("DELETIONS", "DELETIONS"),
+    ("LINES_MODIFIED", "LINES_MODIFIED"),
+    ("BRANCH", "BRANCH"),
+    ("STATUS", "STATUS"),
+    ("FILE_NAME_SHORT", "FILE_NAME_SHORT"),
+    ("FILE_NAME_FULL", "FILE_NAME_FULL"),
+    ("FILE_OLD_NAME", "FILE_OLD_NAME"),
+    ("OLD_DIR", "OLD_DIR"),
+    ("NEW_DIR", "NEW_DIR"),
+    ("FILE_COUNT", "FILE_COUNT"),
+    ("DIR", "DIR"),
+    ("EVENT_KIND", "EVENT_KIND"),
+    ("EVENT_SOURCE", "EVENT_SOURCE"),
+    ("FILE_SIZE", "FILE_SIZE"),
+    ("FILE_MIME", "FILE_MIME"),
+    ("TODAY_COMMITS", "TODAY_COMMITS"),
+    ("TOTAL_COMMITS", "TOTAL_COMMITS"),
+    ("STREAK", "STREAK"),
+    ("SEQ", "SEQ"),
+    ("PACKAGE", "PACKAGE"),
+];
+
+/// Creates default variables with system and custom variables
+///
+/// This function initializes a `serde_json::Value::Object` that contains both
+/// system-defined variables and any additional custom variables. By default,
+/// an example custom variable (`example_var`) is included in the generated map.
+///
+/// # Returns
+/// Returns a `serde_json::Value::Object` containing the default variables.
+fn default_variables() -> serde_json::Value {
+    let mut vars = serde_json::Map::new();
+
+    // Add system variables
+    for (key, value) in SYSTEM_VARIABLES {
+        vars.insert(
+            key.to_string(),
+            serde_json::Value::String(value.to_string()),
+        );
+    }
+
+    serde_json::Value::Object(vars)
+}
+
+impl Default for CommitSummary {
+    /// Provides a default configuration for commit summaries
+    ///
+    /// This function initializes a `CommitSummary` struct with default message
+    /// templates for file creation, modification, and removal events. Each of
+    /// these templates contains a simple comment with the placeholder `{{FILE_NAME_SHORT}}`.
+    ///
+    /// # Returns
+    /// Returns a `CommitSummary` with the default commit message templates.
+    fn default() -> Self {
+        Self {
+            create: Message {
+                prefix: String::new(),
+                comment: "New File Created: {{FILE_NAME_SHORT}}".to_string(),
+                suffix: String::new(),
+                comment_file: None,
+            },
+            modify: Message {
+                prefix: String::new(),
+                comment: "File Modified: {{FILE_NAME_SHORT}}".to_string(),
+                suffix: String::new(),
+                comment_file: None,
+            },
+            remove: Message {
+                prefix: String::new(),
+                comment: "File Removed: {{FILE_NAME_SHORT}}".to_string(),
+                suffix: String::new(),
+                comment_file: None,
+            },
+            rename: Message {
+                prefix: String::new(),
+                comment: "File Renamed: {{FILE_NAME_SHORT}}".to_string(),
+                suffix: String::new(),
+                comment_file: None,
+            },
+            rename_dir: Message {
+                prefix: String::new(),
+                comment: "Directory Renamed: {{OLD_DIR}} -> {{NEW_DIR}}".to_string(),
+                suffix: String::new(),
+                comment_file: None,
+            },
+            scaffold: Message {
+                prefix: String::new(),
+                comment: "Scaffold {{DIR}} ({{FILE_COUNT}} files)".to_string(),
+                suffix: String::new(),
+                comment_file: None,
+            },
+        }
+    }
+}
+
+impl Default for Description {
+    /// Provides a default configuration for detailed descriptions
+    ///
+    /// This function initializes a `Description` struct with default message
+    /// templates for file creation, modification, and removal events. Each of
+    /// these templates provides detailed information, such as the file name
+    /// and number of lines inserted, deleted, and modified.
+    ///
+    /// # Returns
+    /// Returns a `Description` with the default detailed description templates.
+    fn default() -> Self {
+        Self {
+            create: Message {
+                prefix: String::new(),
+                comment: concat!(
+                    "New File Created\n",
+                    "File short name: {{FILE_NAME_SHORT}}\n",
+                    "File full name: {{FILE_NAME_FULL}}\n",
+                    "No. of lines inserted: {{INSERTIONS}}\n",
+                    "No. of lines deleted: {{DELETIONS}}\n",
+                    "No. of lines modified: {{LINES_MODIFIED}}"
+                )
+                .to_string(),
+                suffix: String::new(),
+                comment_file: None,
+            },
+            modify: Message {
+                prefix: String::new(),
+                comment: concat!(
+                    "File Modified\n",
+                    "File short name: {{FILE_NAME_SHORT}}\n",
+                    "File full name: {{FILE_NAME_FULL}}\n",
+                    "No. of lines inserted: {{INSERTIONS}}\n",
+                    "No. of lines deleted: {{DELETIONS}}\n",
+                    "No. of lines modified: {{LINES_MODIFIED}}"
+                )
+                .to_string(),
+                suffix: String::new(),
+                comment_file: None,
+            },
+            remove: Message {
+                prefix: String::new(),
+                comment: concat!(
+                    "File Removed\n",
+                    "File short name: {{FILE_NAME_SHORT}}\n",
+                    "File full name: {{FILE_NAME_FULL}}\n",
+                    "No. of lines inserted: {{INSERTIONS}}\n",
+                    "No. of lines deleted: {{DELETIONS}}\n",
+                    "No. of lines modified: {{LINES_MODIFIED}}"
+                )
+                .to_string(),
+                suffix: String::new(),
+                comment_file: None,
+            },
+            rename: Message {
+                prefix: String::new(),
+                comment: concat!(
+                    "File Renamed\n",
+                    "File short name: {{FILE_NAME_SHORT}}\n",
+                    "File full name: {{FILE_NAME_FULL}}\n",
+                    "No. of lines inserted: {{INSERTIONS}}\n",
+                    "No. of lines deleted: {{DELETIONS}}\n",
+                    "No. of lines modified: {{LINES_MODIFIED}}"
+                )
+                .to_string(),
+                suffix: String::new(),
+                comment_file: None,
+            },
+            rename_dir: Message {
+                prefix: String::new(),
+                comment: concat!(
+                    "Directory Renamed\n",
+                    "Old directory: {{OLD_DIR}}\n",
+                    "New directory: {{NEW_DIR}}\n",
+                    "No. of files: {{FILE_COUNT}}"
+                )
+                .to_string(),
+                suffix: String::new(),
+                comment_file: None,
+            },
+            scaffold: Message {
+                prefix: String::new(),
+                comment: concat!(
+                    "Project Scaffolded\n",
+                    "Directory: {{DIR}}\n",
+                    "No. of files: {{FILE_COUNT}}\n",
+                    "No. of lines inserted: {{INSERTIONS}}"
+                )
+                .to_string(),
+                suffix: String::new(),
+                comment_file: None,
+            },
+        }
+    }
+}
+
+impl Default for Config {
+    /// Creates a default configuration with system and custom variables
+    ///
+    /// This function initializes a `Config` struct with default templates for
+    /// commit messages and descriptions, default variables, and an empty list
+    /// of repositories.
+    ///
+    /// # Returns
+    /// Returns a `Config` struct with default settings for messages, descriptions,
+    /// variables, and repositories.
+    fn default() -> Self {
+        Config {
+            version: CONFIG_VERSION,
+            include: Vec::new(),
+            message: CommitSummary::default(),
+            description: Description::default(),
+            unknown_variables: UnknownVariables::default(),
+            repeat_annotation: RepeatAnnotation::default(),
+            commit_conventions: None,
+            variables: default_variables(),
+            repos: Vec::new(),
+            ignored_dirs: vec![".git".to_string()],
+            git_credentials: None,
+            commit_time: CommitTime::default(),
+            commit_time_offset_minutes: 0,
+            branch_strategy: BranchStrategy::default(),
+            branch_prefix: default_branch_prefix(),
+            push_mode: PushMode::default(),
+            push_enabled: default_push_enabled(),
+            divergence_policy: DivergencePolicy::default(),
+            protected_refs: Vec::new(),
+            tagging: None,
+            allowed_push_hosts: Vec::new(),
+            max_concurrent_pushes: default_max_concurrent_pushes(),
+            push_every_n_commits: None,
+            push_interval_secs: None,
+            push_alert_secs: default_push_alert_secs(),
+            push_options: Vec::new(),
+            scaffold_threshold: default_scaffold_threshold(),
+            quarantine_after_failures: default_quarantine_after_failures(),
+            quarantine_secs: default_quarantine_secs(),
+            catch_up_on_start: false,
+            event_journal: false,
+            large_files: None,
+            write_settle_ms: 0,
+            busy_wait_ms: default_busy_wait_ms(),
+            content_hashing: false,
+            revert_window_secs: default_revert_window_secs(),
+            skip_marker_lines: default_skip_marker_lines(),
+            sensitive_paths: Vec::new(),
+            verify_command: None,
+            post_push_command: None,
+            verify_failure: VerifyFailure::default(),
+            verify_timeout_secs: default_verify_timeout_secs(),
+            backlog_policy: BacklogPolicy::default(),
+            proxy: None,
+            ca_bundle: None,
+            log_target: LogTarget::default(),
+            health_port: None,
+            health_address: default_health_address(),
+            insecure_skip_tls_verify: false,
+            network_timeout_secs: default_network_timeout_secs(),
+            network_retries: default_network_retries(),
+            network_retry_backoff_secs: default_network_retry_backoff_secs(),
+            generator: default_generator(),
+            generator_script: None,
+            llm: None,
+            signing: None,
+            verify_pulled_commits: None,
+            authors: Vec::new(),
+            priority: Priority::default(),
+            priority_rules: Vec::new(),
+            low_priority_batch_secs: default_low_priority_batch_secs(),
+            batch_by_package: false,
+            file_templates: Vec::new(),
+            build_detection: None,
+            disk_usage: None,
+            maintenance: None,
+            bundle_backup: None,
+            trash: None,
+            notes: None,
+            follow: None,
+            forge: None,
+            groups: BTreeMap::new(),
+            template_dir: None,
+        }
+    }
+}
+
+/// Upgrades a version 0 (unversioned) configuration
+///
+/// Unversioned files predate the rename templates, so any missing template
+/// (in `message` or `description`) is filled in from the defaults.
+fn migrate_v0_to_v1(raw: &mut serde_json::Map<String, serde_json::Value>) {
+    fill_missing_templates(raw);
+}
+
+/// Upgrades a version 1 configuration
+///
+/// Version 2 adds the directory rename (`rename_dir`) templates.
+fn migrate_v1_to_v2(raw: &mut serde_json::Map<String, serde_json::Value>) {
+    fill_missing_templates(raw);
+}
+
+/// Upgrades a version 2 configuration
+///
+/// Version 3 adds the project scaffolding (`scaffold`) templates.
+fn migrate_v2_to_v3(raw: &mut serde_json::Map<String, serde_json::Value>) {
+    fill_missing_templates(raw);
+}
+
+/// Fills every template missing from `message` or `description` with its default
+fn fill_missing_templates(raw: &mut serde_json::Map<String, serde_json::Value>) {
+    let sections = [
+        ("message", serde_json::to_value(CommitSummary::default())),
+        ("description", serde_json::to_value(Description::default())),
+    ];
+
+    for (name, defaults) in sections {
+        let Ok(serde_json::Value::Object(defaults)) = defaults else {
+            continue;
+        };
+        let section = raw
+            .entry(name)
+            .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+        if let serde_json::Value::Object(section) = section {
+            for (key, value) in defaults {
+                section.entry(key).or_insert(value);
+            }
+        }
+    }
+}
+
+/// Upgrades a raw configuration to the current schema version
+///
+/// The configuration is read as plain JSON so that older formats, which may no
+/// longer deserialize into `Config`, can still be transformed step by step.
+///
+/// # Arguments
+/// - `raw`: The raw JSON configuration, modified in place.
+///
+/// # Returns
+/// Returns `Some(version)` with the original version if a migration was applied,
+/// or `None` if the configuration was already up to date.
+///
+/// # Errors
+/// Returns a `ConfigError` if the configuration is not a JSON object or was
+/// written by a newer schema version.
+pub fn migrate(raw: &mut serde_json::Value) -> Result<Option<u32>, ConfigError> {
+    let raw = raw.as_object_mut().ok_or_else(|| {
+        ConfigError::FileError("Configuration root must be a JSON object".to_string())
+    })?;
+
+    let version = raw
+        .get("version")
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(0) as u32;
+
+    if version > CONFIG_VERSION {
+        return Err(ConfigError::UnsupportedVersion(version));
+    }
+    if version == CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    for migration in &CONFIG_MIGRATIONS[version as usize..] {
+        migration(raw);
+    }
+    raw.insert("version".to_string(), CONFIG_VERSION.into());
+
+    Ok(Some(version))
+}
+
+/// Lists that are concatenated instead of replaced when layering configurations
+const CONCATENATED_LISTS: &[&str] = &["repos", "ignored_dirs"];
+
+/// Layers the files listed in `include` under a raw configuration
+///
+/// Included files are applied in order, later files overriding earlier ones,
+/// and the including file overrides all of them, see `layer_json`.
+/// Included files may include further files, relative paths are resolved
+/// against the including file and a leading `~` is expanded. Templates none
+/// of the files set are taken from the defaults.
+///
+/// # Arguments
+/// - `raw`: The raw JSON configuration, modified in place.
+/// - `path`: Path of the file `raw` was read from.
+///
+/// # Returns
+/// Returns `true` if any file was included.
+///
+/// # Errors
+/// Returns a `ConfigError` if an included file cannot be read or parsed, or
+/// files include each other.
+pub fn resolve_includes(raw: &mut serde_json::Value, path: &Path) -> Result<bool, ConfigError> {
+    let mut including = vec![path.canonicalize().unwrap_or_else(|_| path.to_path_buf())];
+    if !resolve_includes_from(raw, path, &mut including)? {
+        return Ok(false);
+    }
+
+    // Files may set single templates (e.g. only `message.create`), the
+    // others come from the defaults
+    if let serde_json::Value::Object(raw) = raw {
+        fill_missing_templates(raw);
+    }
+    Ok(true)
+}
+
+/// Resolves the includes of `raw`, `including` holds the files being resolved
+fn resolve_includes_from(
+    raw: &mut serde_json::Value,
+    path: &Path,
+    including: &mut Vec<PathBuf>,
+) -> Result<bool, ConfigError> {
+    let includes: Vec<String> = raw
+        .get("include")
+        .and_then(serde_json::Value::as_array)
+        .map(|includes| {
+            includes
+                .iter()
+                .filter_map(|include| include.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    if includes.is_empty() {
+        return Ok(false);
+    }
+
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    let mut layered = serde_json::Value::Object(serde_json::Map::new());
+    for include in includes {
+        let include_path = base_dir.join(crate::helper::expand_tilde(Path::new(&include)));
+        let include_path = include_path
+            .canonicalize()
+            .map_err(|e| ConfigError::FileError(format!("{}: {}", include_path.display(), e)))?;
+        if including.contains(&include_path) {
+            return Err(ConfigError::FileError(format!(
+                "Configuration include cycle through {}",
+                include_path.display()
+            )));
+        }
+
+        let contents = std::fs::read_to_string(&include_path)
+            .map_err(|e| ConfigError::FileError(format!("{}: {}", include_path.display(), e)))?;
+        let mut included: serde_json::Value = serde_json::from_str(&contents)?;
+        including.push(include_path.clone());
+        resolve_includes_from(&mut included, &include_path, including)?;
+        including.pop();
+
+        tracing::debug!("Including configuration {}", include_path.display());
+        layer_json(&mut layered, &included);
+    }
+    layer_json(&mut layered, raw);
+    *raw = layered;
+    Ok(true)
+}
+
+/// Layers a raw configuration over another
+///
+/// The single merge of configurations, used for includes, repository groups
+/// and `Config::merge`. Objects (the templates, `variables`, nested settings)
+/// are merged key by key, the `repos` and `ignored_dirs` lists are
+/// concatenated without duplicates and any other value is replaced.
+fn layer_json(base: &mut serde_json::Value, overrides: &serde_json::Value) {
+    let (serde_json::Value::Object(base_map), serde_json::Value::Object(overrides_map)) =
+        (&mut *base, overrides)
+    else {
+        *base = overrides.clone();
+        return;
+    };
+
+    for (key, value) in overrides_map {
+        match (base_map.get_mut(key), value) {
+            (Some(serde_json::Value::Array(list)), serde_json::Value::Array(more))
+                if CONCATENATED_LISTS.contains(&key.as_str()) =>
+            {
+                for item in more {
+                    if !list.contains(item) {
+                        list.push(item.clone());
+                    }
+                }
+            }
+            (Some(existing), value) => layer_json(existing, value),
+            (None, value) => {
+                base_map.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Returns the path of the backup `Config::save_to_file` keeps of a configuration file
+pub fn backup_path(path: &Path) -> PathBuf {
+    path_with_suffix(path, ".bak")
+}
+
+/// Appends a suffix to the file name of a path (`config.json` -> `config.json.bak`)
+/// Follows symlinks to the file they point to
+///
+/// A dangling link resolves to its target, so saving creates the target
+/// instead of replacing the link.
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    if let Ok(resolved) = std::fs::canonicalize(path) {
+        return resolved;
+    }
+    let mut path = path.to_path_buf();
+    // Bounded like the symlink resolution of the kernel
+    for _ in 0..40 {
+        let Ok(target) = std::fs::read_link(&path) else {
+            break;
+        };
+        path = match path.parent() {
+            Some(parent) => parent.join(target),
+            None => target,
+        };
+    }
+    path
+}
+
+fn path_with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Reads the raw JSON of a configuration file, recovering from its backup
+///
+/// When the file cannot be read or is not valid JSON (e.g. truncated by a
+/// crash while writing it), the backup kept by `Config::save_to_file` is read
+/// instead and a warning is logged.
+///
+/// # Arguments
+/// - `path`: Path to the configuration file.
+///
+/// # Errors
+/// Returns the `ConfigError` of the file if its backup cannot be read either.
+pub fn read_json(path: &Path) -> Result<serde_json::Value, ConfigError> {
+    let read = |path: &Path| -> Result<serde_json::Value, ConfigError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| ConfigError::FileError(format!("{}: {}", path.display(), e)))?;
+        Ok(serde_json::from_str(&contents)?)
+    };
+
+    let err = match read(path) {
+        Ok(raw) => return Ok(raw),
+        Err(err) => err,
+    };
+    let backup = backup_path(path);
+    if !backup.exists() {
+        return Err(err);
+    }
+    let raw = read(&backup).map_err(|_| err)?;
+    tracing::warn!(
+        "Configuration {} is unreadable, recovered it from the backup {}",
+        path.display(),
+        backup.display()
+    );
+    Ok(raw)
+}
+
+impl Config {
+    /// Checks whether batched commits must be pushed now
+    ///
+    /// # Arguments
+    /// - `unpushed`: Number of commits waiting to be pushed.
+    /// - `since`: Time of the oldest waiting commit.
+    ///
+    /// # Returns
+    /// `true` if pushes are not batched or a batching limit is reached.
+    pub fn is_push_due(&self, unpushed: u64, since: SystemTime) -> bool {
+        if self.push_every_n_commits.is_none() && self.push_interval_secs.is_none() {
+            return true;
+        }
+        self.push_every_n_commits.is_some_and(|n| unpushed >= n)
+            || self.push_interval_secs.is_some_and(|secs| {
+                since.elapsed().unwrap_or_default() >= Duration::from_secs(secs)
+            })
+    }
+
+    /// Resolves the effective configuration of a repository group
+    ///
+    /// Templates of the group setting a `comment_file` are read like those
+    /// of the top level if `load_template_files` was called.
+    ///
+    /// # Arguments
+    /// - `name`: Name of the group.
+    ///
+    /// # Errors
+    /// Returns a `ConfigError` if the group does not exist, its settings are
+    /// invalid or one of its template files cannot be read.
+    pub fn for_group(&self, name: &str) -> Result<Config, ConfigError> {
+        let group = self.groups.get(name).ok_or_else(|| {
+            ConfigError::FileError(format!("Unknown repository group '{}'", name))
+        })?;
+
+        let mut raw = self.settings_value()?;
+        layer_json(&mut raw, &serde_json::Value::Object(group.settings.clone()));
+
+        let mut resolved: Config = serde_json::from_value(raw)?;
+        resolved.git_credentials = GitCred::merged(
+            self.git_credentials.clone(),
+            resolved.git_credentials.take(),
+        );
+        resolved.repos = group.repos.clone();
+        resolved.groups = BTreeMap::new();
+        if let Some(template_dir) = &self.template_dir {
+            resolved.load_template_files(template_dir)?;
+        }
+        Ok(resolved)
+    }
+
+    /// Layers the variables of a repository entry over `variables`
+    ///
+    /// # Arguments
+    /// - `variables`: The variables of the entry, overriding those of the same name.
+    pub fn layer_variables(&mut self, variables: &serde_json::Map<String, serde_json::Value>) {
+        if !self.variables.is_object() {
+            self.variables = serde_json::Value::Object(serde_json::Map::new());
+        }
+        if let serde_json::Value::Object(own) = &mut self.variables {
+            own.extend(
+                variables
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.clone())),
+            );
+        }
+    }
+
+    /// Overrides top-level settings with `GIT_AUTO_PILOT_<SETTING>` variables
+    ///
+    /// The setting name is the upper-cased key of the configuration file,
+    /// e.g. `GIT_AUTO_PILOT_PUSH_ENABLED=false` or
+    /// `GIT_AUTO_PILOT_WRITE_SETTLE_MS=500`. Values are parsed as JSON and
+    /// taken as a string if they are no JSON or their JSON does not fit the
+    /// setting (e.g. `GIT_AUTO_PILOT_BRANCH_PREFIX=2024`);
+    /// `GIT_AUTO_PILOT_REPOS` is a list of paths separated like `PATH`.
+    /// Variables not naming a setting, `version` or `include` are ignored.
+    ///
+    /// # Arguments
+    /// - `vars`: The environment variables, usually `std::env::vars()`.
+    ///
+    /// # Errors
+    /// Returns a `ConfigError` if a value does not fit its setting.
+    pub fn apply_env_overrides(
+        &mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<(), ConfigError> {
+        let mut raw = self.settings_value()?;
+        let Some(settings) = raw.as_object_mut() else {
+            return Ok(());
+        };
+
+        let mut overridden = false;
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix(ENV_OVERRIDE_PREFIX) else {
+                continue;
+            };
+            let key = key.to_lowercase();
+            if !settings.contains_key(&key) || NOT_OVERRIDABLE.contains(&key.as_str()) {
+                continue;
+            }
+            let candidates = if key == "repos" {
+                vec![serde_json::Value::Array(
+                    std::env::split_paths(&value)
+                        .map(|path| serde_json::Value::String(path.display().to_string()))
+                        .collect(),
+                )]
+            } else {
+                match serde_json::from_str::<serde_json::Value>(&value) {
+                    Ok(parsed) if parsed.is_string() => vec![parsed],
+                    Ok(parsed) => vec![parsed, serde_json::Value::String(value)],
+                    Err(_) => vec![serde_json::Value::String(value)],
+                }
+            };
+
+            // Validated one by one to name the offending variable
+            let mut error = None;
+            for candidate in candidates {
+                settings.insert(key.clone(), candidate);
+                match serde_json::from_value::<Config>(serde_json::Value::Object(settings.clone()))
+                {
+                    Ok(_) => {
+                        error = None;
+                        break;
+                    }
+                    Err(e) => error = error.or(Some(e)),
+                }
+            }
+            if let Some(e) = error {
+                return Err(ConfigError::InvalidOverride(format!("{}: {}", name, e)));
+            }
+            tracing::debug!("Setting {} overridden by {}", key, name);
+            overridden = true;
+        }
+
+        if overridden {
+            let credentials = self.git_credentials.take();
+            let template_dir = self.template_dir.take();
+            *self = serde_json::from_value(raw)?;
+            self.git_credentials = GitCred::merged(credentials, self.git_credentials.take());
+            self.template_dir = template_dir;
+        }
+        Ok(())
+    }
+
+    /// Serializes the settings but `git_credentials`, keeping the password
+    /// out of plain `serde_json::Value`s that are not zeroized
+    fn settings_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::to_value(Config {
+            git_credentials: None,
+            ..self.clone()
+        })
+    }
+
+    /// Reads the comments of the templates that set a `comment_file`
+    ///
+    /// The files are read once, the comments are kept in memory until the
+    /// configuration is loaded again.
+    ///
+    /// # Arguments
+    /// - `base_dir`: Directory relative `comment_file` paths are resolved
+    ///   against, usually the directory of the configuration file.
+    ///
+    /// # Errors
+    /// Returns a `ConfigError` if a template file cannot be read.
+    pub fn load_template_files(&mut self, base_dir: &Path) -> Result<(), ConfigError> {
+        let mut templates = vec![
+            &mut self.message.create,
+            &mut self.message.modify,
+            &mut self.message.remove,
+            &mut self.message.rename,
+            &mut self.message.rename_dir,
+            &mut self.message.scaffold,
+            &mut self.description.create,
+            &mut self.description.modify,
+            &mut self.description.remove,
+            &mut self.description.rename,
+            &mut self.description.rename_dir,
+            &mut self.description.scaffold,
+        ];
+        for rule in &mut self.file_templates {
+            templates.push(&mut rule.message);
+            templates.extend(rule.description.as_mut());
+        }
+
+        for template in templates {
+            let Some(comment_file) = template.comment_file.as_deref() else {
+                continue;
+            };
+            let path = base_dir.join(crate::helper::expand_tilde(Path::new(comment_file)));
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|e| ConfigError::TemplateError(format!("{}: {}", path.display(), e)))?;
+            tracing::debug!("Loaded template {}", path.display());
+            template.comment = contents.trim_end_matches(['\r', '\n']).to_string();
+        }
+        self.template_dir = Some(base_dir.to_path_buf());
+        Ok(())
+    }
+
+    /// Loads configuration from a JSON file
+    ///
+    /// This function reads the configuration from the specified file and
+    /// parses it into a `Config` struct. If an error occurs during reading or
+    /// parsing, it returns a `ConfigError`.
+    ///
+    /// # Arguments
+    /// - `path`: Path to the JSON file containing the configuration.
+    ///
+    /// # Errors
+    /// Returns a `ConfigError` if the file cannot be read or parsed.
+    pub fn load_from_file(path: &PathBuf) -> Result<Self, ConfigError> {
+        let config_contents =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::FileError(e.to_string()))?;
+
+        let config: Config = serde_json::from_str(&config_contents)?;
+        Ok(config)
+    }
+
+    /// Loads configuration from a JSON file, recovering from its backup
+    ///
+    /// Unlike `load_from_file`, a file that cannot be read or parsed is
+    /// replaced by the backup kept by `save_to_file` (see `read_json`).
+    ///
+    /// # Arguments
+    /// - `path`: Path to the JSON file containing the configuration.
+    ///
+    /// # Errors
+    /// Returns a `ConfigError` if neither the file nor its backup can be read
+    /// or parsed.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        Ok(serde_json::from_value(read_json(path)?)?)
+    }
+
+    /// Saves the configuration to a JSON file
+    ///
+    /// The configuration is written to a temporary file first, synced and
+    /// renamed into place, so a crash never leaves a truncated file behind.
+    /// A symlinked file is replaced at its target, keeping the link. A new
+    /// file is only readable by its owner as it may hold credentials, an
+    /// existing one keeps its permissions. The previous file is kept as
+    /// backup next to it (see `backup_path`), unless it is corrupt itself.
+    ///
+    /// # Arguments
+    /// - `path`: Path to the file where the configuration should be saved.
+    ///
+    /// # Errors
+    /// Returns a `ConfigError` if the file cannot be written.
+    pub fn save_to_file(&self, path: &Path) -> Result<(), ConfigError> {
+        use std::io::Write;
+
+        // Holds the password in plain text
+        let config_json = secrecy::zeroize::Zeroizing::new(
+            serde_json::to_string_pretty(self).map_err(ConfigError::from)?,
+        );
+        let file_error = |e: std::io::Error| ConfigError::FileError(e.to_string());
+
+        let path = resolve_symlinks(path);
+        let temp_path = path_with_suffix(&path, ".tmp");
+        // A leftover temporary file would keep its permissions
+        let _ = std::fs::remove_file(&temp_path);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut temp_file = options.open(&temp_path).map_err(file_error)?;
+        if let Ok(metadata) = std::fs::metadata(&path) {
+            temp_file
+                .set_permissions(metadata.permissions())
+                .map_err(file_error)?;
+        }
+        temp_file
+            .write_all(config_json.as_bytes())
+            .and_then(|()| temp_file.sync_all())
+            .map_err(file_error)?;
+        drop(temp_file);
+
+        let current_is_valid = std::fs::read_to_string(&path)
+            .ok()
+            .is_some_and(|contents| serde_json::from_str::<serde_json::Value>(&contents).is_ok());
+        if current_is_valid {
+            std::fs::copy(&path, backup_path(&path)).map_err(file_error)?;
+        }
+        std::fs::rename(&temp_path, &path).map_err(file_error)?;
+        // Persist the rename itself
+        #[cfg(unix)]
+        {
+            let dir = path
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            std::fs::File::open(dir)
+                .and_then(|dir| dir.sync_all())
+                .map_err(file_error)?;
+        }
+        Ok(())
+    }
+
+    /// Merges another configuration into the current one
+    ///
+    /// Settings of `other` that are unset (equal to their default) leave the
+    /// current ones untouched. Set settings take precedence as follows:
+    /// - `message`/`description`: every template of `other` with a non-empty
+    ///   comment replaces the current one (including the rename and scaffold
+    ///   templates)
+    /// - `variables`: merged key by key, the values of `other` win
+    /// - `repos`/`ignored_dirs`: concatenated, without duplicates
+    /// - `git_credentials`: merged field by field, empty fields of `other` are
+    ///   ignored
+    /// - nested settings: merged key by key, see `layer_json`
+    /// - any other setting: replaced by the one of `other`
+    ///
+    /// # Arguments
+    /// - `other`: The configuration to merge into the current one.
+    pub fn merge(&mut self, mut other: Config) {
+        let credentials =
+            GitCred::merged(self.git_credentials.take(), other.git_credentials.take());
+        self.merge_settings(other);
+        self.git_credentials = credentials;
+    }
+
+    /// Merges the settings of another configuration but `git_credentials`,
+    /// see `merge`
+    fn merge_settings(&mut self, other: Config) {
+        let values = (
+            serde_json::to_value(&*self),
+            serde_json::to_value(other),
+            serde_json::to_value(Config::default()),
+        );
+        let (
+            Ok(mut merged),
+            Ok(serde_json::Value::Object(mut other)),
+            Ok(serde_json::Value::Object(defaults)),
+        ) = values
+        else {
+            tracing::warn!("Failed to serialize the configurations to merge");
+            return;
+        };
+
+        // Unset settings, templates without a comment and default variables
+        // leave the current ones untouched
+        other.retain(|key, value| Some(&*value) != defaults.get(key));
+        for (key, value) in other.iter_mut() {
+            let default = defaults.get(key).unwrap_or(&serde_json::Value::Null);
+            let serde_json::Value::Object(entries) = value else {
+                continue;
+            };
+            match key.as_str() {
+                "message" | "description" => entries.retain(|kind, template| {
+                    template["comment"]
+                        .as_str()
+                        .is_some_and(|comment| !comment.is_empty())
+                        && Some(&*template) != default.get(kind)
+                }),
+                "variables" => {
+                    entries.retain(|name, variable| Some(&*variable) != default.get(name))
+                }
+                _ => {}
+            }
+        }
+        layer_json(&mut merged, &serde_json::Value::Object(other));
+
+        match serde_json::from_value(merged) {
+            Ok(config) => *self = config,
+            Err(err) => tracing::warn!("Failed to merge the configurations: {}", err),
+        }
+    }
+}
+
+/// Example usage
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config() {
+        let config = Config::default();
+        assert!(config.repos.is_empty());
+        assert!(!config.variables.is_null());
+    }
+
+    #[test]
+    fn test_config_merge() {
+        let mut base_config = Config::default();
+
+        // Create a configuration with specific fields to update
+        let update_config = Config {
+            message: CommitSummary {
+                create: Message {
+                    comment: "Custom Create Message".to_string(),
+                    ..Default::default() // Use default values for other fields
+                },
+                ..Default::default() // Use default values for other fields
+            },
+            variables: serde_json::json!({"new_var": "test_value"}),
+            repos: vec![PathBuf::from("/test/repo").into()],
+            ..Default::default() // Use default values for other fields
+        };
+
+        // Merge update_config into base_config
+        base_config.merge(update_config);
+
+        // Test that the "create" commit message was updated
+        assert_eq!(base_config.message.create.comment, "Custom Create Message");
+
+        // Test that the new variable "new_var" was added to variables
+        assert!(base_config.variables["new_var"].as_str().is_some());
+        assert_eq!(
+            base_config.variables["new_var"].as_str().unwrap(),
+            "test_value"
+        );
+
+        // Test that the repository was added
+        assert_eq!(base_config.repos.len(), 1);
+        assert_eq!(base_config.repos[0].path(), Path::new("/test/repo"));
+
+        // Ensure that other fields are not overwritten by the merge
+        // The default values should remain as-is for fields that are not updated in update_config
+        assert_eq!(
+            base_config.message.modify.comment,
+            "File Modified: {{FILE_NAME_SHORT}}"
+        );
+        assert_eq!(
+            base_config.message.remove.comment,
+            "File Removed: {{FILE_NAME_SHORT}}"
+        );
+
+        // Test that variables not included in the update remain unchanged
+        assert!(base_config.variables["INSERTIONS"].as_str().is_some());
+    }
+
+    #[test]
+    fn test_config_merge_every_field() {
+        let mut base_config = Config {
+            repos: vec![PathBuf::from("/a").into(), PathBuf::from("/b").into()],
+            git_credentials: Some(GitCred {
+                username: "Base".to_string(),
+                email: "base@example.com".to_string(),
+                login_username: Some("base-login".to_string()),
+                password: Some("base-token".into()),
+            }),
+            quarantine_secs: 60,
+            ..Default::default()
+        };
+        let mut message = CommitSummary::default();
+        message.rename.comment = "Moved {{FILE_OLD_NAME}}".to_string();
+
+        base_config.merge(Config {
+            message,
+            repos: vec![PathBuf::from("/b").into(), PathBuf::from("/c").into()],
+            ignored_dirs: vec![".git".to_string(), "target".to_string()],
+            git_credentials: Some(GitCred {
+                username: String::new(),
+                email: "other@example.com".to_string(),
+                login_username: None,
+                password: Some("other-token".into()),
+            }),
+            write_settle_ms: 500,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            base_config.message.rename.comment,
+            "Moved {{FILE_OLD_NAME}}"
+        );
+        assert_eq!(base_config.message.modify, CommitSummary::default().modify);
+        assert_eq!(
+            base_config.repos,
+            ["/a", "/b", "/c"]
+                .map(|path| RepoEntry::from(PathBuf::from(path)))
+                .to_vec()
+        );
+        assert_eq!(base_config.ignored_dirs, [".git", "target"]);
+        let credentials = base_config.git_credentials.unwrap();
+        assert_eq!(credentials.username, "Base");
+        assert_eq!(credentials.email, "other@example.com");
+        assert_eq!(credentials.login_username.as_deref(), Some("base-login"));
+        assert_eq!(
+            credentials
+                .password
+                .as_ref()
+                .map(ExposeSecret::expose_secret),
+            Some("other-token")
+        );
+        assert_eq!(base_config.write_settle_ms, 500);
+        assert_eq!(base_config.quarantine_secs, 60);
+    }
+
+    #[test]
+    fn test_is_push_due() {
+        let now = SystemTime::now();
+        let an_hour_ago = now - Duration::from_secs(3600);
+
+        let config = Config::default();
+        assert!(config.is_push_due(1, now));
+
+        let config = Config {
+            push_every_n_commits: Some(3),
+            push_interval_secs: Some(600),
+            ..Default::default()
+        };
+        assert!(!config.is_push_due(2, now));
+        assert!(config.is_push_due(3, now));
+        assert!(config.is_push_due(1, an_hour_ago));
+    }
+
+    #[test]
+    fn test_group_overrides_settings() {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "message": CommitSummary::default(),
+            "description": Description::default(),
+            "repos": ["/code/app"],
+            "groups": {
+                "notes": {
+                    "repos": ["/notes/work", "/notes/home"],
+                    "message": { "create": { "prefix": "", "comment": "note", "suffix": "" } },
+                    "branch_strategy": "per-day"
+                }
+            }
+        }))
+        .unwrap();
+
+        let notes = config.for_group("notes").unwrap();
+        assert_eq!(notes.message.create.comment, "note");
+        assert_eq!(notes.message.modify, config.message.modify);
+        assert_eq!(notes.branch_strategy, BranchStrategy::PerDay);
+        assert_eq!(config.branch_strategy, BranchStrategy::Current);
+        assert!(config.for_group("missing").is_err());
+    }
+
+    #[test]
+    fn test_priority_lanes() {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "message": CommitSummary::default(),
+            "description": Description::default(),
+            "priority": "low",
+            "priority_rules": [{ "pattern": "inbox.md", "priority": "high" }],
+            "groups": {
+                "code": { "repos": ["/code/app"], "low_priority_batch_secs": 900 }
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(config.priority, Priority::Low);
+        assert_eq!(config.priority_rules[0].priority, Priority::High);
+        assert_eq!(config.low_priority_batch_secs, 300);
+        assert_eq!(
+            config.for_group("code").unwrap().low_priority_batch_secs,
+            900
+        );
+        assert!(Priority::Low < Priority::Normal && Priority::Normal < Priority::High);
+    }
+
+    #[test]
+    fn test_migrate_unversioned_config() {
+        let mut raw = serde_json::json!({
+            "message": {
+                "create": { "prefix": "", "comment": "Created", "suffix": "" },
+                "modify": { "prefix": "", "comment": "Modified", "suffix": "" },
+                "remove": { "prefix": "", "comment": "Removed", "suffix": "" }
+            },
+            "description": {
+                "create": { "prefix": "", "comment": "", "suffix": "" },
+                "modify": { "prefix": "", "comment": "", "suffix": "" },
+                "remove": { "prefix": "", "comment": "", "suffix": "" }
+            }
+        });
+
+        assert_eq!(migrate(&mut raw).unwrap(), Some(0));
+
+        let config: Config = serde_json::from_value(raw.clone()).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.message.create.comment, "Created");
+        assert_eq!(
+            config.message.rename.comment,
+            "File Renamed: {{FILE_NAME_SHORT}}"
+        );
+        assert_eq!(
+            config.message.rename_dir.comment,
+            "Directory Renamed: {{OLD_DIR}} -> {{NEW_DIR}}"
+        );
+
+        // Already migrated configs are left untouched
+        assert_eq!(migrate(&mut raw).unwrap(), None);
+    }
+
+    #[test]
+    fn test_resolve_includes_layers_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("common.json"),
+            r#"{
+                "message": { "create": { "prefix": "", "comment": "Shared create", "suffix": "" } },
+                "variables": { "TEAM": "docs", "HOST": "shared" },
+                "repos": ["/shared/notes"],
+                "push_mode": "mirror"
+            }"#,
+        )
+        .unwrap();
+        let main_path = dir.path().join("config.json");
+        let mut raw = serde_json::json!({
+            "version": CONFIG_VERSION,
+            "include": ["common.json"],
+            "variables": { "HOST": "laptop" },
+            "repos": ["/home/user/code"]
+        });
+
+        assert!(resolve_includes(&mut raw, &main_path).unwrap());
+        migrate(&mut raw).unwrap();
+        let config: Config = serde_json::from_value(raw).unwrap();
+
+        assert_eq!(config.message.create.comment, "Shared create");
+        assert_eq!(config.variables["TEAM"], "docs");
+        assert_eq!(config.variables["HOST"], "laptop");
+        assert_eq!(
+            config.repos,
+            vec![
+                PathBuf::from("/shared/notes").into(),
+                PathBuf::from("/home/user/code").into()
+            ]
+        );
+        assert_eq!(config.push_mode, PushMode::Mirror);
+    }
+
+    #[test]
+    fn test_resolve_includes_rejects_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.json"), r#"{ "include": ["b.json"] }"#).unwrap();
+        std::fs::write(dir.path().join("b.json"), r#"{ "include": ["a.json"] }"#).unwrap();
+
+        let mut raw = serde_json::json!({ "include": ["a.json"] });
+        assert!(resolve_includes(&mut raw, &dir.path().join("config.json")).is_err());
+    }
+
+    #[test]
+    fn test_save_keeps_backup_and_load_recovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        let mut config = Config::default();
+        config.save_to_file(&path).unwrap();
+        assert!(!backup_path(&path).exists());
+
+        config.repos = vec![PathBuf::from("/home/user/notes").into()];
+        config.save_to_file(&path).unwrap();
+        assert!(backup_path(&path).exists());
+        assert!(!dir.path().join("config.json.tmp").exists());
+
+        // A truncated file falls back to the previous configuration
+        std::fs::write(&path, "{ \"repos\": [").unwrap();
+        assert!(Config::load_from_file(&path).is_err());
+        assert!(Config::load(&path).unwrap().repos.is_empty());
+
+        // A corrupt file never replaces the backup
+        config.save_to_file(&path).unwrap();
+        assert_eq!(Config::load(&path).unwrap().repos, config.repos);
+        std::fs::remove_file(&path).unwrap();
+        assert!(Config::load(&path).unwrap().repos.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_save_keeps_symlink_and_private_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("dotfiles").join("config.json");
+        std::fs::create_dir(target.parent().unwrap()).unwrap();
+        let link = dir.path().join("config.json");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        Config::default().save_to_file(&link).unwrap();
+        assert!(std::fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&target), 0o600);
+
+        // Permissions chosen for an existing file are kept
+        std::fs::set_permissions(&target, std::fs::Permissions::from_mode(0o640)).unwrap();
+        Config::default().save_to_file(&link).unwrap();
+        assert_eq!(mode(&target), 0o640);
+        assert!(backup_path(&target).exists());
+    }
+
+    #[test]
+    fn test_migrate_rejects_newer_version() {
+        let mut raw = serde_json::json!({ "version": CONFIG_VERSION + 1 });
+        assert!(matches!(
+            migrate(&mut raw),
+            Err(ConfigError::UnsupportedVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_apply_env_overrides() {
+        let mut config = Config {
+            git_credentials: Some(GitCred {
+                username: "Test".to_string(),
+                email: "test@example.com".to_string(),
+                login_username: Some("test".to_string()),
+                password: Some("token".into()),
+            }),
+            ..Config::default()
+        };
+        let vars = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        config
+            .apply_env_overrides(vars(&[
+                ("GIT_AUTO_PILOT_PUSH_ENABLED", "false"),
+                ("GIT_AUTO_PILOT_WRITE_SETTLE_MS", "500"),
+                ("GIT_AUTO_PILOT_PUSH_MODE", "mirror"),
+                ("GIT_AUTO_PILOT_PASSWORD", "not a setting"),
+                ("HOME", "/root"),
+            ]))
+            .unwrap();
+        assert!(!config.push_enabled);
+        assert_eq!(config.write_settle_ms, 500);
+        assert_eq!(config.push_mode, PushMode::Mirror);
+
+        // JSON not fitting a text setting is taken as text
+        config
+            .apply_env_overrides(vars(&[
+                ("GIT_AUTO_PILOT_BRANCH_PREFIX", "2024"),
+                ("GIT_AUTO_PILOT_VERIFY_COMMAND", "true"),
+                ("GIT_AUTO_PILOT_VERSION", "99"),
+                ("GIT_AUTO_PILOT_INCLUDE", "[\"/etc/passwd\"]"),
+            ]))
+            .unwrap();
+        assert_eq!(config.branch_prefix, "2024");
+        assert_eq!(config.verify_command.as_deref(), Some("true"));
+        assert_eq!(config.version, Config::default().version);
+        assert!(config.include.is_empty());
+        let password = config.git_credentials.as_ref().unwrap().password.as_ref();
+        assert_eq!(password.unwrap().expose_secret(), "token");
+
+        let repos = std::env::join_paths(["/work/notes", "/work/wiki"]).unwrap();
+        config
+            .apply_env_overrides(vec![(
+                "GIT_AUTO_PILOT_REPOS".to_string(),
+                repos.to_string_lossy().to_string(),
+            )])
+            .unwrap();
+        assert_eq!(
+            config.repos,
+            vec![
+                PathBuf::from("/work/notes").into(),
+                PathBuf::from("/work/wiki").into()
+            ]
+        );
+
+        let err = config
+            .apply_env_overrides(vars(&[("GIT_AUTO_PILOT_BUSY_WAIT_MS", "soon")]))
+            .unwrap_err();
+        assert!(err.to_string().contains("GIT_AUTO_PILOT_BUSY_WAIT_MS"));
+    }
+
+    #[test]
+    fn test_repo_entry_variables_layer_over_globals() {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "message": CommitSummary::default(),
+            "description": Description::default(),
+            "variables": { "PROJECT": "shared", "TEAM": "core" },
+            "repos": [
+                "/work/notes",
+                { "path": "/work/api", "variables": { "PROJECT": "backend" } }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            config.repos[0],
+            RepoEntry::Path(PathBuf::from("/work/notes"))
+        );
+        assert_eq!(config.repos[0].variables(), None);
+        assert_eq!(config.repos[1].path(), Path::new("/work/api"));
+
+        let mut repo_config = config.clone();
+        repo_config.layer_variables(config.repos[1].variables().unwrap());
+        assert_eq!(repo_config.variables["PROJECT"], "backend");
+        assert_eq!(repo_config.variables["TEAM"], "core");
+        assert_eq!(config.variables["PROJECT"], "shared");
+    }
+
+    #[test]
+    fn test_load_template_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("templates")).unwrap();
+        std::fs::write(
+            dir.path().join("templates/modify.txt"),
+            "Update {{FILE_NAME_SHORT}}\n\nLines added: {{INSERTIONS}}\n",
+        )
+        .unwrap();
+        let mut config = Config::default();
+        config.message.modify =
+            serde_json::from_str(r#"{ "comment_file": "templates/modify.txt" }"#).unwrap();
+
+        config.load_template_files(dir.path()).unwrap();
+        assert_eq!(
+            config.message.modify.comment,
+            "Update {{FILE_NAME_SHORT}}\n\nLines added: {{INSERTIONS}}"
+        );
+        assert_eq!(config.message.create, CommitSummary::default().create);
+
+        config.description.create.comment_file = Some("templates/missing.txt".to_string());
+        assert!(matches!(
+            config.load_template_files(dir.path()),
+            Err(ConfigError::TemplateError(_))
+        ));
+    }
+
+    #[test]
+    fn test_group_template_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("note.txt"), "Note {{FILE_NAME_SHORT}}\n").unwrap();
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "message": CommitSummary::default(),
+            "description": Description::default(),
+            "groups": {
+                "notes": {
+                    "repos": ["/notes/work"],
+                    "message": { "create": { "comment_file": "note.txt" } }
+                }
+            }
+        }))
+        .unwrap();
+
+        config.load_template_files(dir.path()).unwrap();
+        let notes = config.for_group("notes").unwrap();
+        assert_eq!(notes.message.create.comment, "Note {{FILE_NAME_SHORT}}");
+        assert_eq!(notes.message.modify, config.message.modify);
+
+        std::fs::remove_file(dir.path().join("note.txt")).unwrap();
+        assert!(matches!(
+            config.for_group("notes"),
+            Err(ConfigError::TemplateError(_))
+        ));
+    }
+}
@@ -0,0 +1,235 @@
+//! # Content Hash Module
+//!
+//! On network file systems (NFS, SMB) change notifications are unreliable and
+//! the poll watcher has to compare file contents itself, which is expensive
+//! and still reports files whose content did not change. Repositories with
+//! `content_hashing` enabled keep a blake3 hash of every file they committed,
+//! so events that leave the committed content unchanged are dropped before the
+//! repository status is scanned. A hash is only kept once the commit is made:
+//! events deferred or held back meanwhile are still handled when they repeat.
+//!
+//! The blob each file had before its latest autopilot commit is remembered as
+//! well, so a change that merely reverts that commit shortly after is not
+//! committed again.
+
+use git2::Oid;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tracing::trace;
+
+/// Number of committed hashes kept per repository, the oldest are dropped
+/// beyond it
+const MAX_HASHED_FILES: usize = 10_000;
+
+/// Committed content hashes, per repository and file
+#[derive(Debug, Default)]
+pub struct ContentHashCache {
+    repos: Mutex<HashMap<PathBuf, RepoHashes>>,
+}
+
+/// Content hashes of the files of a repository
+#[derive(Debug, Default)]
+struct RepoHashes {
+    /// Hashes of the contents seen in events, until they are committed
+    seen: HashMap<PathBuf, blake3::Hash>,
+
+    /// Hashes of the committed contents and when they were recorded
+    committed: HashMap<PathBuf, (blake3::Hash, u64)>,
+
+    /// Number of hashes recorded so far
+    recorded: u64,
+}
+
+impl ContentHashCache {
+    /// Filters the paths of an event down to those whose content differs
+    /// from the committed one
+    ///
+    /// A file changed if it was not committed before, its hash differs from
+    /// the committed one or it no longer exists. Directories and files that
+    /// cannot be read are always kept.
+    ///
+    /// # Arguments
+    /// - `repo`: Key of the repository (see `state::repo_key`).
+    /// - `paths`: Paths reported by the event.
+    pub fn changed_paths(&self, repo: &Path, paths: &[PathBuf]) -> Vec<PathBuf> {
+        let mut repos = self.repos.lock().unwrap_or_else(|p| p.into_inner());
+        let hashes = repos.entry(repo.to_path_buf()).or_default();
+
+        paths
+            .iter()
+            .filter(|path| match hash_file(path) {
+                Ok(Some(hash)) => {
+                    let unchanged = hashes
+                        .committed
+                        .get(*path)
+                        .is_some_and(|(committed, _)| *committed == hash);
+                    if unchanged {
+                        trace!("Content of {} is unchanged", path.display());
+                    } else {
+                        if hashes.seen.len() >= MAX_HASHED_FILES {
+                            hashes.seen.clear();
+                        }
+                        hashes.seen.insert(path.to_path_buf(), hash);
+                    }
+                    !unchanged
+                }
+                Ok(None) => true,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    hashes.seen.remove(*path);
+                    hashes.committed.remove(*path);
+                    true
+                }
+                Err(err) => {
+                    trace!("Cannot hash {}: {}", path.display(), err);
+                    true
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Records the contents seen in the events of committed files as committed
+    ///
+    /// # Arguments
+    /// - `repo`: Key of the repository (see `state::repo_key`).
+    /// - `paths`: The committed files.
+    pub fn record_committed(&self, repo: &Path, paths: impl IntoIterator<Item = PathBuf>) {
+        let mut repos = self.repos.lock().unwrap_or_else(|p| p.into_inner());
+        let Some(hashes) = repos.get_mut(repo) else {
+            return;
+        };
+        for path in paths {
+            if let Some(hash) = hashes.seen.remove(&path) {
+                hashes.committed.insert(path, (hash, hashes.recorded));
+                hashes.recorded += 1;
+            }
+        }
+        if hashes.committed.len() > MAX_HASHED_FILES {
+            let oldest_kept = hashes.recorded - (MAX_HASHED_FILES / 2) as u64;
+            hashes
+                .committed
+                .retain(|_, (_, recorded)| *recorded >= oldest_kept);
+        }
+    }
+}
+
+/// Content each file had before its latest autopilot commit, per repository
+///
+/// Used to hold back commits that merely revert the latest one, e.g. an
+/// editor autosave flipping a file back and forth.
+#[derive(Debug, Default)]
+pub struct RevertMemory {
+    repos: Mutex<HashMap<PathBuf, PreviousContents>>,
+}
+
+/// Blob ID each file of a repository had before its latest commit, and when
+/// it was remembered
+type PreviousContents = HashMap<String, (Oid, SystemTime)>;
+
+impl RevertMemory {
+    /// Remembers the content a file had before it was committed
+    ///
+    /// # Arguments
+    /// - `repo`: Key of the repository (see `state::repo_key`).
+    /// - `path`: Path of the file, relative to the repository root.
+    /// - `previous`: Blob ID of the content replaced by the commit.
+    /// - `at`: Time of the commit.
+    pub fn record(&self, repo: &Path, path: &str, previous: Oid, at: SystemTime) {
+        let mut repos = self.repos.lock().unwrap_or_else(|p| p.into_inner());
+        repos
+            .entry(repo.to_path_buf())
+            .or_default()
+            .insert(path.to_string(), (previous, at));
+    }
+
+    /// Checks whether a change restores the content a file had before its
+    /// latest commit, within `window` of that commit
+    ///
+    /// # Arguments
+    /// - `repo`: Key of the repository (see `state::repo_key`).
+    /// - `path`: Path of the file, relative to the repository root.
+    /// - `current`: Blob ID of the new content.
+    /// - `now`: The current time.
+    /// - `window`: How long after a commit a revert is held back.
+    pub fn is_revert(
+        &self,
+        repo: &Path,
+        path: &str,
+        current: Oid,
+        now: SystemTime,
+        window: Duration,
+    ) -> bool {
+        let repos = self.repos.lock().unwrap_or_else(|p| p.into_inner());
+        repos
+            .get(repo)
+            .and_then(|paths| paths.get(path))
+            .is_some_and(|&(previous, at)| {
+                previous == current && now.duration_since(at).unwrap_or_default() < window
+            })
+    }
+}
+
+/// Hashes the content of a file, `None` for directories
+fn hash_file(path: &Path) -> io::Result<Option<blake3::Hash>> {
+    let file = File::open(path)?;
+    if file.metadata()?.is_dir() {
+        return Ok(None);
+    }
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(file)?;
+    Ok(Some(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drops_unchanged_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.md");
+        let paths = vec![file.clone(), dir.path().to_path_buf()];
+        let cache = ContentHashCache::default();
+
+        std::fs::write(&file, "todo\n").unwrap();
+        assert_eq!(cache.changed_paths(dir.path(), &paths), paths);
+
+        // Not committed yet, e.g. the commit was deferred
+        std::fs::write(&file, "todo\n").unwrap();
+        assert_eq!(cache.changed_paths(dir.path(), &paths), paths);
+        cache.record_committed(dir.path(), [file.clone()]);
+
+        // Rewriting the same content, e.g. a touch or an editor saving twice
+        std::fs::write(&file, "todo\n").unwrap();
+        assert_eq!(
+            cache.changed_paths(dir.path(), &paths),
+            vec![dir.path().to_path_buf()]
+        );
+
+        std::fs::write(&file, "done\n").unwrap();
+        assert_eq!(cache.changed_paths(dir.path(), &paths[..1]), paths[..1]);
+
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(cache.changed_paths(dir.path(), &paths[..1]), paths[..1]);
+    }
+
+    #[test]
+    fn test_detects_quick_reverts() {
+        let repo = Path::new("/work/notes");
+        let memory = RevertMemory::default();
+        let before = Oid::hash_object(git2::ObjectType::Blob, b"draft\n").unwrap();
+        let after = Oid::hash_object(git2::ObjectType::Blob, b"final\n").unwrap();
+        let committed_at = SystemTime::now();
+        let window = Duration::from_secs(30);
+
+        memory.record(repo, "notes.md", before, committed_at);
+        assert!(memory.is_revert(repo, "notes.md", before, committed_at, window));
+        assert!(!memory.is_revert(repo, "notes.md", after, committed_at, window));
+        assert!(!memory.is_revert(repo, "other.md", before, committed_at, window));
+        assert!(!memory.is_revert(repo, "notes.md", before, committed_at + window, window));
+    }
+}
@@ -0,0 +1,363 @@
+//! # Error Module
+//!
+//! `GitAutoPilotError` is the error type of the whole crate. Every error
+//! belongs to an `ErrorCategory`, which the binary reports as a
+//! machine-readable prefix and exit code.
+
+use std::io;
+use thiserror::Error;
+use tokio::task::JoinError;
+
+use tracing::error;
+
+use crate::config::ConfigError;
+
+/// Messages of libgit2 errors caused by a full or read-only file system
+const STORAGE_ERROR_MESSAGES: &[&str] = &[
+    "no space left on device",
+    "disk quota exceeded",
+    "read-only file system",
+];
+
+/// Messages of libgit2 errors caused by a device that disappeared, e.g. an
+/// unplugged drive
+const DEVICE_GONE_MESSAGES: &[&str] =
+    &["no such device", "input/output error", "stale file handle"];
+
+/// `errno` values of a device that disappeared (`EIO`, `ENXIO`, `ENODEV`)
+#[cfg(unix)]
+const DEVICE_GONE_ERRNOS: &[i32] = &[5, 6, 19];
+#[cfg(not(unix))]
+const DEVICE_GONE_ERRNOS: &[i32] = &[];
+
+/// Custom error types for GitAutoPilot operations
+#[derive(Error, Debug)]
+pub enum GitAutoPilotError {
+    /// Error when home directory cannot be determined
+    #[error("Unable to determine home directory")]
+    HomeDirError,
+
+    /// Error during directory creation
+    #[error("Failed to create dot directory: {0}")]
+    DirCreationError(String),
+
+    /// Errors related to configuration file and parsing
+    #[error("Configuration error: {0}")]
+    ConfigError(#[from] ConfigError),
+
+    /// Wrapper for standard IO errors
+    #[error(transparent)]
+    IOError(#[from] std::io::Error),
+
+    /// Wrapper for standard notify errors
+    #[error(transparent)]
+    NotifyError(#[from] notify::Error),
+
+    /// Wrapper for standard tokio join errors
+    #[error(transparent)]
+    TokioJoinError(#[from] JoinError),
+
+    /// Wrapper for standard git2 errors
+    #[error(transparent)]
+    Git2Error(#[from] git2::Error),
+
+    /// Error when a push targets a remote outside the allowed push hosts
+    #[error("Push to remote not allowed: {0}")]
+    PushNotAllowedError(String),
+
+    /// Error when another daemon instance already holds the instance lock
+    #[error("Another git-auto-pilot instance is running: {0}")]
+    InstanceLockedError(String),
+
+    /// Error when no credentials are available (push login, user name or email)
+    #[error("Credentials missing: {0}")]
+    CredentialsMissing(String),
+
+    /// Error when a credentials file (`.git-credentials`, `.gitconfig`) cannot be parsed
+    #[error("Failed to parse credentials: {0}")]
+    CredentialsParse(String),
+
+    /// Error when the remote rejects the credentials
+    #[error("Authentication failed: {0}")]
+    AuthFailed(String),
+
+    /// Error when the remote rejects a pushed ref (e.g. not a fast-forward)
+    #[error("Push rejected: {0}")]
+    PushRejected(String),
+
+    /// Error when a message generator fails to produce a commit message
+    #[error("Message generator error: {0}")]
+    MessageGeneratorError(String),
+
+    /// Error when the remote branch has commits the local branch lacks
+    #[error("Remote branch diverged: {0}")]
+    DivergedError(String),
+
+    /// Error when a commit pulled from the remote paused automation
+    #[error("Paused by remote commit: {0}")]
+    RemotePausedError(String),
+
+    /// Error when a remote repository cannot be created on the forge
+    #[error("Forge error: {0}")]
+    ForgeError(String),
+
+    /// Error when a path matching `sensitive_paths` was about to be staged
+    #[error("Refusing to stage sensitive path: {0}")]
+    SensitivePathError(String),
+}
+
+/// Machine-readable category of a `GitAutoPilotError`
+///
+/// Each category maps to a distinct process exit code, so wrappers (systemd,
+/// scripts) can react differently to different failures. Exit code `2` is left
+/// to command line usage errors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// Unexpected failures (I/O, task errors)
+    Internal,
+
+    /// Invalid or unreadable configuration
+    Config,
+
+    /// Missing or rejected credentials
+    Auth,
+
+    /// File system watching could not be set up or failed
+    Watch,
+
+    /// A git operation failed
+    Git,
+}
+
+impl ErrorCategory {
+    /// Returns the process exit code for this category
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorCategory::Internal => 1,
+            ErrorCategory::Config => 3,
+            ErrorCategory::Auth => 4,
+            ErrorCategory::Watch => 5,
+            ErrorCategory::Git => 6,
+        }
+    }
+
+    /// Returns the stable, machine-readable name of this category
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCategory::Internal => "internal",
+            ErrorCategory::Config => "config",
+            ErrorCategory::Auth => "auth",
+            ErrorCategory::Watch => "watch",
+            ErrorCategory::Git => "git",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl GitAutoPilotError {
+    /// Returns the category of this error
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            GitAutoPilotError::HomeDirError
+            | GitAutoPilotError::DirCreationError(_)
+            | GitAutoPilotError::ConfigError(_)
+            | GitAutoPilotError::PushNotAllowedError(_)
+            | GitAutoPilotError::SensitivePathError(_) => ErrorCategory::Config,
+            GitAutoPilotError::CredentialsMissing(_)
+            | GitAutoPilotError::CredentialsParse(_)
+            | GitAutoPilotError::AuthFailed(_) => ErrorCategory::Auth,
+            GitAutoPilotError::NotifyError(_) | GitAutoPilotError::InstanceLockedError(_) => {
+                ErrorCategory::Watch
+            }
+            GitAutoPilotError::Git2Error(err) if err.code() == git2::ErrorCode::Auth => {
+                ErrorCategory::Auth
+            }
+            GitAutoPilotError::Git2Error(_)
+            | GitAutoPilotError::PushRejected(_)
+            | GitAutoPilotError::DivergedError(_)
+            | GitAutoPilotError::RemotePausedError(_)
+            | GitAutoPilotError::ForgeError(_) => ErrorCategory::Git,
+            GitAutoPilotError::IOError(_)
+            | GitAutoPilotError::TokioJoinError(_)
+            | GitAutoPilotError::MessageGeneratorError(_) => ErrorCategory::Internal,
+        }
+    }
+
+    /// Whether the error comes from reaching a remote (network, TLS, credentials,
+    /// push restrictions) rather than from the local change being processed
+    pub fn is_remote_error(&self) -> bool {
+        match self {
+            GitAutoPilotError::PushNotAllowedError(_)
+            | GitAutoPilotError::CredentialsMissing(_)
+            | GitAutoPilotError::CredentialsParse(_)
+            | GitAutoPilotError::AuthFailed(_)
+            | GitAutoPilotError::PushRejected(_)
+            | GitAutoPilotError::DivergedError(_)
+            | GitAutoPilotError::RemotePausedError(_)
+            | GitAutoPilotError::ForgeError(_) => true,
+            GitAutoPilotError::Git2Error(err) => {
+                err.code() == git2::ErrorCode::Auth
+                    || matches!(
+                        err.class(),
+                        git2::ErrorClass::Net
+                            | git2::ErrorClass::Http
+                            | git2::ErrorClass::Ssh
+                            | git2::ErrorClass::Ssl
+                    )
+            }
+            _ => false,
+        }
+    }
+
+    /// Converts the error of a push into a dedicated variant where one applies
+    ///
+    /// Rejected credentials become `AuthFailed` and refs rejected by the remote
+    /// `PushRejected`; other errors are kept as `Git2Error`.
+    pub fn from_push_error(err: git2::Error) -> Self {
+        match err.code() {
+            git2::ErrorCode::Auth => GitAutoPilotError::AuthFailed(err.message().to_string()),
+            git2::ErrorCode::NotFastForward => {
+                GitAutoPilotError::PushRejected(err.message().to_string())
+            }
+            _ => GitAutoPilotError::Git2Error(err),
+        }
+    }
+
+    /// Whether the error comes from a full or read-only file system, or from
+    /// a device that disappeared
+    ///
+    /// Retrying does not help until space is freed, the file system is
+    /// remounted writable or the device returns, so the affected repository
+    /// is paused instead.
+    pub fn is_storage_error(&self) -> bool {
+        match self {
+            GitAutoPilotError::IOError(err) => is_storage_io_error(err),
+            GitAutoPilotError::Git2Error(err) => {
+                let message = err.message().to_lowercase();
+                STORAGE_ERROR_MESSAGES
+                    .iter()
+                    .chain(DEVICE_GONE_MESSAGES)
+                    .any(|storage_message| message.contains(storage_message))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the error comes from a device that disappeared
+    pub fn is_device_gone(&self) -> bool {
+        match self {
+            GitAutoPilotError::IOError(err) => is_device_gone_io_error(err),
+            GitAutoPilotError::Git2Error(err) => {
+                let message = err.message().to_lowercase();
+                DEVICE_GONE_MESSAGES
+                    .iter()
+                    .any(|device_message| message.contains(device_message))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whether an IO error comes from a full or read-only file system, or from a
+/// device that disappeared
+pub fn is_storage_io_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::StorageFull
+            | io::ErrorKind::QuotaExceeded
+            | io::ErrorKind::ReadOnlyFilesystem
+    ) || is_device_gone_io_error(err)
+}
+
+/// Whether an IO error comes from a device that disappeared
+pub fn is_device_gone_io_error(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::StaleNetworkFileHandle
+        || err
+            .raw_os_error()
+            .is_some_and(|errno| DEVICE_GONE_ERRNOS.contains(&errno))
+}
+
+// Log the error details when the GitAutoPilotError is being dropped
+impl Drop for GitAutoPilotError {
+    fn drop(&mut self) {
+        error!("{}", self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_categories() {
+        let auth = GitAutoPilotError::Git2Error(git2::Error::new(
+            git2::ErrorCode::Auth,
+            git2::ErrorClass::Http,
+            "authentication required",
+        ));
+        assert_eq!(auth.category(), ErrorCategory::Auth);
+
+        let git = GitAutoPilotError::Git2Error(git2::Error::from_str("not found"));
+        assert_eq!(git.category(), ErrorCategory::Git);
+        assert_eq!(git.category().exit_code(), 6);
+
+        assert_eq!(
+            GitAutoPilotError::HomeDirError.category(),
+            ErrorCategory::Config
+        );
+    }
+
+    #[test]
+    fn test_push_errors() {
+        let rejected = GitAutoPilotError::from_push_error(git2::Error::new(
+            git2::ErrorCode::NotFastForward,
+            git2::ErrorClass::Reference,
+            "refs/heads/main: fetch first",
+        ));
+        assert!(matches!(rejected, GitAutoPilotError::PushRejected(_)));
+        assert_eq!(rejected.category(), ErrorCategory::Git);
+        assert!(rejected.is_remote_error());
+
+        let auth = GitAutoPilotError::from_push_error(git2::Error::new(
+            git2::ErrorCode::Auth,
+            git2::ErrorClass::Http,
+            "authentication required",
+        ));
+        assert!(matches!(auth, GitAutoPilotError::AuthFailed(_)));
+        assert_eq!(auth.category(), ErrorCategory::Auth);
+
+        let other = GitAutoPilotError::from_push_error(git2::Error::from_str("timed out"));
+        assert!(matches!(other, GitAutoPilotError::Git2Error(_)));
+    }
+
+    #[test]
+    fn test_storage_errors() {
+        let full = GitAutoPilotError::Git2Error(git2::Error::new(
+            git2::ErrorCode::GenericError,
+            git2::ErrorClass::Os,
+            "failed to write object: No space left on device",
+        ));
+        assert!(full.is_storage_error());
+
+        let read_only =
+            GitAutoPilotError::IOError(io::Error::from(io::ErrorKind::ReadOnlyFilesystem));
+        assert!(read_only.is_storage_error());
+
+        let locked = GitAutoPilotError::Git2Error(git2::Error::from_str("index is locked"));
+        assert!(!locked.is_storage_error());
+        assert!(!read_only.is_device_gone());
+
+        let unplugged = GitAutoPilotError::Git2Error(git2::Error::new(
+            git2::ErrorCode::GenericError,
+            git2::ErrorClass::Os,
+            "failed to read index: Input/output error",
+        ));
+        assert!(unplugged.is_storage_error());
+        assert!(unplugged.is_device_gone());
+    }
+}
@@ -0,0 +1,148 @@
+//! # Forge Module
+//!
+//! Before the first push to a repository the daemon checks that its `origin`
+//! remote exists. If it does not and `forge` settings are configured, the
+//! repository is created through the GitHub or GitLab API (after asking,
+//! unless `auto_create` is set) and set as `origin`, so new projects are
+//! pushed without any manual setup. Creating repositories requires the
+//! `forge` feature.
+
+use std::io::{IsTerminal, Write};
+use tracing::debug;
+
+use crate::config::{ForgeKind, ForgeSettings};
+use crate::error::GitAutoPilotError;
+use crate::redact;
+
+/// Timeout of requests to the forge API
+#[cfg(feature = "forge")]
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Asks the user whether a missing remote repository should be created
+///
+/// # Arguments
+/// - `name`: Name of the repository to create.
+/// - `settings`: The forge the repository would be created on.
+///
+/// # Returns
+/// The answer, or `None` if not running in a terminal.
+pub fn prompt_create(name: &str, settings: &ForgeSettings) -> Option<bool> {
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        debug!("Not running in a terminal, cannot ask whether to create the remote");
+        return None;
+    }
+
+    eprint!(
+        "The remote repository of {} does not exist. Create it on {}? [y/N]: ",
+        name,
+        api_url(settings)
+    );
+    std::io::stderr().flush().ok()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).ok()?;
+    Some(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Returns the API base URL of a forge
+fn api_url(settings: &ForgeSettings) -> &str {
+    settings
+        .api_url
+        .as_deref()
+        .unwrap_or(match settings.kind {
+            ForgeKind::Github => "https://api.github.com",
+            ForgeKind::Gitlab => "https://gitlab.com/api/v4",
+        })
+        .trim_end_matches('/')
+}
+
+/// Creates a repository on the forge
+///
+/// # Arguments
+/// - `settings`: The forge to create the repository on.
+/// - `name`: Name of the repository.
+///
+/// # Returns
+/// The HTTPS clone URL of the created repository.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the token is not set, the request fails
+/// or the binary was built without the `forge` feature.
+pub fn create_repository(
+    settings: &ForgeSettings,
+    name: &str,
+) -> Result<String, GitAutoPilotError> {
+    let token = std::env::var(&settings.token_env).map_err(|_| {
+        GitAutoPilotError::ForgeError(format!("token variable {} is not set", settings.token_env))
+    })?;
+    redact::register_secret(&token);
+    debug!("Creating repository {} on {}", name, api_url(settings));
+    request_create(settings, &token, name)
+}
+
+/// Sends the request creating a repository
+#[cfg(feature = "forge")]
+fn request_create(
+    settings: &ForgeSettings,
+    token: &str,
+    name: &str,
+) -> Result<String, GitAutoPilotError> {
+    let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+    let api = api_url(settings);
+    let forge_error = |err: ureq::Error| GitAutoPilotError::ForgeError(err.to_string());
+
+    let (response, url_key): (serde_json::Value, &str) = match settings.kind {
+        ForgeKind::Github => {
+            let url = match settings.owner.as_deref() {
+                Some(owner) => format!("{}/orgs/{}/repos", api, owner),
+                None => format!("{}/user/repos", api),
+            };
+            let response = agent
+                .post(&url)
+                .set("Authorization", &format!("Bearer {}", token))
+                .set("Accept", "application/vnd.github+json")
+                .set("User-Agent", "git-auto-pilot")
+                .send_json(serde_json::json!({ "name": name, "private": settings.private }))
+                .map_err(forge_error)?;
+            (response.into_json()?, "clone_url")
+        }
+        ForgeKind::Gitlab => {
+            let mut body = serde_json::json!({
+                "name": name,
+                "path": name,
+                "visibility": if settings.private { "private" } else { "public" },
+            });
+            if let Some(owner) = settings.owner.as_deref() {
+                let namespace: serde_json::Value = agent
+                    .get(&format!("{}/namespaces/{}", api, owner.replace('/', "%2F")))
+                    .set("PRIVATE-TOKEN", token)
+                    .call()
+                    .map_err(forge_error)?
+                    .into_json()?;
+                body["namespace_id"] = namespace["id"].clone();
+            }
+            let response = agent
+                .post(&format!("{}/projects", api))
+                .set("PRIVATE-TOKEN", token)
+                .send_json(body)
+                .map_err(forge_error)?;
+            (response.into_json()?, "http_url_to_repo")
+        }
+    };
+
+    response[url_key]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| GitAutoPilotError::ForgeError(format!("response has no {}", url_key)))
+}
+
+/// Sends the request creating a repository
+#[cfg(not(feature = "forge"))]
+fn request_create(
+    _settings: &ForgeSettings,
+    _token: &str,
+    _name: &str,
+) -> Result<String, GitAutoPilotError> {
+    Err(GitAutoPilotError::ForgeError(
+        "creating repositories requires the forge feature".to_string(),
+    ))
+}
@@ -0,0 +1,852 @@
+//! # Message Generator Module
+//!
+//! Commit messages are produced by a `MessageGenerator` selected with the
+//! `generator` configuration setting. New strategies are added by implementing
+//! the trait and registering a constructor in `GENERATORS`.
+//!
+//! ## Built-in generators
+//! - `template`: Renders the configured `message`/`description` templates (default)
+//! - `conventional`: Produces Conventional Commits summaries (`feat(src): add main.rs`)
+//! - `script`: Runs `generator_script`, passing the change as JSON on stdin
+//! - `llm`: Asks an OpenAI-compatible endpoint (requires the `llm` feature)
+
+use git2::Status;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Debug;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tracing::{debug, trace};
+
+use crate::config::{
+    Config, ConfigError, ConventionMode, Message, RepeatAnnotation, SYSTEM_VARIABLES,
+};
+use crate::error::GitAutoPilotError;
+use crate::helper;
+
+#[cfg(feature = "llm")]
+mod llm;
+
+/// Name of the generator used when none is configured
+pub const DEFAULT_GENERATOR: &str = "template";
+
+/// Maximum time a `script` generator may take
+const SCRIPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Kind of change a commit message is generated for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeKind {
+    /// A file was created
+    Create,
+
+    /// A file was modified
+    Modify,
+
+    /// A file was removed
+    Remove,
+
+    /// A file was renamed
+    Rename,
+
+    /// A directory was renamed
+    RenameDir,
+
+    /// A new project was scaffolded
+    Scaffold,
+}
+
+impl ChangeKind {
+    /// All kinds of changes
+    pub const ALL: [ChangeKind; 6] = [
+        ChangeKind::Create,
+        ChangeKind::Modify,
+        ChangeKind::Remove,
+        ChangeKind::Rename,
+        ChangeKind::RenameDir,
+        ChangeKind::Scaffold,
+    ];
+
+    /// Returns the kind of change of a file from its (possibly combined) status
+    ///
+    /// A status may combine index and working tree flags (e.g.
+    /// `WT_MODIFIED | INDEX_MODIFIED`). The working tree decides: a file missing
+    /// from it was removed, otherwise a rename wins over a creation, and a
+    /// file that is neither is modified.
+    pub fn from_status(status: Status) -> ChangeKind {
+        if status.contains(Status::WT_DELETED) {
+            ChangeKind::Remove
+        } else if status.intersects(Status::WT_RENAMED | Status::INDEX_RENAMED) {
+            ChangeKind::Rename
+        } else if status.contains(Status::INDEX_DELETED) {
+            // Removed from the index, a file still in the working tree was re-added
+            if status.contains(Status::WT_NEW) {
+                ChangeKind::Modify
+            } else {
+                ChangeKind::Remove
+            }
+        } else if status.intersects(Status::WT_NEW | Status::INDEX_NEW) {
+            ChangeKind::Create
+        } else {
+            ChangeKind::Modify
+        }
+    }
+
+    /// Returns the configured summary and description templates for this kind
+    ///
+    /// For changes of a single file, the last `file_templates` rule matching
+    /// the file (`FILE_NAME_SHORT`) takes precedence.
+    fn templates<'a>(
+        self,
+        config: &'a Config,
+        values: &HashMap<String, String>,
+    ) -> (&'a Message, &'a Message) {
+        let (message, description) = self.kind_templates(config);
+        if matches!(self, ChangeKind::RenameDir | ChangeKind::Scaffold) {
+            return (message, description);
+        }
+        let Some(file) = values.get("FILE_NAME_SHORT") else {
+            return (message, description);
+        };
+        match config
+            .file_templates
+            .iter()
+            .rev()
+            .find(|rule| helper::matches_path_pattern(file, &rule.pattern))
+        {
+            Some(rule) => {
+                trace!("Using the templates of {} for {}", rule.pattern, file);
+                (
+                    &rule.message,
+                    rule.description.as_ref().unwrap_or(description),
+                )
+            }
+            None => (message, description),
+        }
+    }
+
+    /// Returns the `message`/`description` templates of this kind
+    fn kind_templates(self, config: &Config) -> (&Message, &Message) {
+        match self {
+            ChangeKind::Create => (&config.message.create, &config.description.create),
+            ChangeKind::Modify => (&config.message.modify, &config.description.modify),
+            ChangeKind::Remove => (&config.message.remove, &config.description.remove),
+            ChangeKind::Rename => (&config.message.rename, &config.description.rename),
+            ChangeKind::RenameDir => (&config.message.rename_dir, &config.description.rename_dir),
+            ChangeKind::Scaffold => (&config.message.scaffold, &config.description.scaffold),
+        }
+    }
+}
+
+/// A change a commit message is generated for
+#[derive(Debug, Serialize)]
+pub struct Change<'a> {
+    /// Kind of the change
+    pub kind: ChangeKind,
+
+    /// Template variables describing the change (file names, line counts, ...)
+    pub values: &'a HashMap<String, String>,
+
+    /// The staged diff, only provided to generators that ask for it
+    pub diff: Option<&'a str>,
+}
+
+/// Strategy producing the commit summary and description for a change
+pub trait MessageGenerator: Debug + Send + Sync {
+    /// Generates the commit summary and description
+    ///
+    /// # Arguments
+    /// - `config` - The current configuration.
+    /// - `change` - The change to describe.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if no message could be generated; the caller
+    /// then falls back to the templates.
+    fn generate(
+        &self,
+        config: &Config,
+        change: &Change,
+    ) -> Result<(String, String), GitAutoPilotError>;
+
+    /// Whether the generator needs the staged diff in `Change::diff`
+    fn needs_diff(&self) -> bool {
+        false
+    }
+
+    /// Whether the generator renders the configured message templates
+    fn uses_templates(&self) -> bool {
+        true
+    }
+}
+
+/// Constructor of a registered generator
+type GeneratorFactory = fn() -> Box<dyn MessageGenerator>;
+
+/// Registered generators, keyed by their `generator` configuration name
+const GENERATORS: &[(&str, GeneratorFactory)] = &[
+    ("template", || Box::new(TemplateGenerator)),
+    ("conventional", || Box::new(ConventionalGenerator)),
+    ("script", || Box::new(ScriptGenerator)),
+    #[cfg(feature = "llm")]
+    ("llm", || Box::new(llm::LlmGenerator)),
+];
+
+/// Creates the generator selected by the configuration
+///
+/// # Errors
+/// Returns a `ConfigError` if no generator is registered under the configured name.
+pub fn from_config(config: &Config) -> Result<Box<dyn MessageGenerator>, ConfigError> {
+    GENERATORS
+        .iter()
+        .find(|(name, _)| *name == config.generator)
+        .map(|(_, factory)| factory())
+        .ok_or_else(|| {
+            ConfigError::FileError(format!(
+                "Unknown message generator '{}' (available: {})",
+                config.generator,
+                GENERATORS
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })
+}
+
+/// Creates the default (`template`) generator
+pub fn default_generator() -> Box<dyn MessageGenerator> {
+    Box::new(TemplateGenerator)
+}
+
+/// Returns the variable names of the `{{NAME}}` placeholders in a text
+fn placeholders(text: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        rest = &rest[start + 2..];
+        let Some(end) = rest.find("}}") else {
+            break;
+        };
+        let name = rest[..end].trim();
+        if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            names.push(name);
+        }
+        rest = &rest[end + 2..];
+    }
+    names
+}
+
+/// Returns the placeholders of the templates of a change kind that have no value
+///
+/// # Arguments
+/// - `config` - The configuration holding the templates.
+/// - `kind` - The kind of change being committed.
+/// - `values` - The template variables of the change.
+pub fn unresolved_variables(
+    config: &Config,
+    kind: ChangeKind,
+    values: &HashMap<String, String>,
+) -> BTreeSet<String> {
+    let (message, description) = kind.templates(config, values);
+    [message, description]
+        .iter()
+        .flat_map(|template| [&template.prefix, &template.comment, &template.suffix])
+        .flat_map(|text| placeholders(text))
+        .filter(|name| !values.contains_key(*name))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns the placeholders merging a commit convention left in a message
+///
+/// Placeholders the generated message already had were checked with its
+/// templates and are not returned again.
+///
+/// # Arguments
+/// - `generated` - The summary and description before the merge.
+/// - `merged` - The summary and description returned by `apply_convention`.
+pub fn unresolved_convention_variables(
+    generated: [&str; 2],
+    merged: [&str; 2],
+) -> BTreeSet<String> {
+    let known: BTreeSet<&str> = generated
+        .iter()
+        .flat_map(|text| placeholders(text))
+        .collect();
+    merged
+        .iter()
+        .flat_map(|text| placeholders(text))
+        .filter(|name| !known.contains(name))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns the placeholders in any message template that no variable defines
+///
+/// Checks against the built-in variables and the custom `variables`, without
+/// a concrete change.
+pub fn undefined_variables(config: &Config) -> BTreeSet<String> {
+    let mut known: HashMap<String, String> = SYSTEM_VARIABLES
+        .iter()
+        .map(|(key, _)| (key.to_string(), String::new()))
+        .collect();
+    if let serde_json::Value::Object(variables) = &config.variables {
+        known.extend(variables.keys().map(|key| (key.clone(), String::new())));
+    }
+
+    let file_templates = config
+        .file_templates
+        .iter()
+        .flat_map(|rule| std::iter::once(&rule.message).chain(rule.description.as_ref()));
+    ChangeKind::ALL
+        .iter()
+        .flat_map(|kind| unresolved_variables(config, *kind, &known))
+        .chain(
+            file_templates
+                .flat_map(|template| [&template.prefix, &template.comment, &template.suffix])
+                .flat_map(|text| placeholders(text))
+                .filter(|name| !known.contains_key(*name))
+                .map(str::to_string),
+        )
+        .collect()
+}
+
+/// Renders a message template, substituting the template variables
+fn render(template: &Message, values: &HashMap<String, String>) -> String {
+    format!(
+        "{}{}{}",
+        byteutils::string::replace_multiple_placeholders(&template.prefix, values),
+        byteutils::string::replace_multiple_placeholders(&template.comment, values),
+        byteutils::string::replace_multiple_placeholders(&template.suffix, values)
+    )
+}
+
+/// Renders the configured `message` and `description` templates
+#[derive(Debug)]
+pub struct TemplateGenerator;
+
+impl MessageGenerator for TemplateGenerator {
+    fn generate(
+        &self,
+        config: &Config,
+        change: &Change,
+    ) -> Result<(String, String), GitAutoPilotError> {
+        let (message, description) = change.kind.templates(config, change.values);
+        Ok((
+            render(message, change.values),
+            render(description, change.values),
+        ))
+    }
+}
+
+/// Produces Conventional Commits summaries, scoped by the package of the
+/// changed file in a monorepo (see `PACKAGE`), else by its top-level directory
+///
+/// The description is rendered from the configured description templates.
+#[derive(Debug)]
+pub struct ConventionalGenerator;
+
+impl MessageGenerator for ConventionalGenerator {
+    fn generate(
+        &self,
+        config: &Config,
+        change: &Change,
+    ) -> Result<(String, String), GitAutoPilotError> {
+        let value = |key: &str| change.values.get(key).cloned().unwrap_or_default();
+        let file = value("FILE_NAME_SHORT");
+
+        let (commit_type, subject) = match change.kind {
+            ChangeKind::Create => ("feat", format!("add {}", file)),
+            ChangeKind::Modify => ("chore", format!("update {}", file)),
+            ChangeKind::Remove => ("chore", format!("remove {}", file)),
+            ChangeKind::Rename => (
+                "refactor",
+                format!("rename {} to {}", value("FILE_OLD_NAME"), file),
+            ),
+            ChangeKind::RenameDir => (
+                "refactor",
+                format!("rename {} to {}", value("OLD_DIR"), value("NEW_DIR")),
+            ),
+            ChangeKind::Scaffold => (
+                "feat",
+                format!("scaffold {} ({} files)", value("DIR"), value("FILE_COUNT")),
+            ),
+        };
+        let package = value("PACKAGE");
+        let scope = if package.is_empty() {
+            file.split_once('/')
+                .map(|(dir, _)| dir)
+                .filter(|dir| !dir.is_empty())
+        } else {
+            Some(package.as_str())
+        };
+        let summary = match scope {
+            Some(scope) => format!("{}({}): {}", commit_type, scope, subject),
+            None => format!("{}: {}", commit_type, subject),
+        };
+
+        let (_, description) = change.kind.templates(config, change.values);
+        Ok((summary, render(description, change.values)))
+    }
+}
+
+/// Runs the `generator_script` command, passing the change as JSON on stdin
+///
+/// The first line of the output is the summary, anything after the following
+/// blank line is the description.
+#[derive(Debug)]
+pub struct ScriptGenerator;
+
+impl MessageGenerator for ScriptGenerator {
+    fn generate(
+        &self,
+        config: &Config,
+        change: &Change,
+    ) -> Result<(String, String), GitAutoPilotError> {
+        let script = config.generator_script.as_deref().ok_or_else(|| {
+            GitAutoPilotError::MessageGeneratorError(
+                "generator_script is not configured".to_string(),
+            )
+        })?;
+        let input = serde_json::to_vec(change).map_err(ConfigError::from)?;
+
+        trace!("Running message generator script: {}", script);
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(&input)?;
+        }
+
+        let started = Instant::now();
+        while child.try_wait()?.is_none() {
+            if started.elapsed() > SCRIPT_TIMEOUT {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(GitAutoPilotError::MessageGeneratorError(format!(
+                    "script timed out after {:?}",
+                    SCRIPT_TIMEOUT
+                )));
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(GitAutoPilotError::MessageGeneratorError(format!(
+                "script failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        debug!("Message generator script output: {:?}", stdout);
+        parse_message(&stdout).ok_or_else(|| {
+            GitAutoPilotError::MessageGeneratorError("script produced no summary".to_string())
+        })
+    }
+
+    fn uses_templates(&self) -> bool {
+        false
+    }
+}
+
+/// Splits generated text into the summary line and the description
+///
+/// # Returns
+/// Returns `None` if the text has no summary.
+pub fn parse_message(text: &str) -> Option<(String, String)> {
+    let text = text.trim();
+    let (summary, description) = text.split_once('\n').unwrap_or((text, ""));
+    let summary = summary.trim();
+    if summary.is_empty() {
+        return None;
+    }
+    Some((summary.to_string(), description.trim().to_string()))
+}
+
+/// Marks the summary of a repeated commit of the same file on the same day
+///
+/// # Arguments
+/// - `summary`: The generated summary.
+/// - `annotation`: How repeats are marked.
+/// - `values`: Variables of the change, holding its `SEQ`.
+///
+/// # Returns
+/// The summary, with the counter appended from the second commit of a day on.
+pub fn annotate_repeat(
+    summary: String,
+    annotation: RepeatAnnotation,
+    values: &HashMap<String, String>,
+) -> String {
+    let Some(seq) = values
+        .get("SEQ")
+        .and_then(|seq| seq.parse::<u64>().ok())
+        .filter(|seq| *seq > 1)
+    else {
+        return summary;
+    };
+    match annotation {
+        RepeatAnnotation::Off => summary,
+        RepeatAnnotation::Counter => format!("{} #{}", summary, seq),
+        RepeatAnnotation::Today => format!("{} (x{} today)", summary, seq),
+    }
+}
+
+/// Extracts the convention from a commit template or convention file
+///
+/// # Arguments
+/// - `text`: Contents of the file.
+/// - `markdown`: Whether the file is Markdown, whose first fenced code block
+///   holds the convention.
+///
+/// # Returns
+/// The convention without comment lines, `None` if nothing is left.
+pub fn parse_convention(text: &str, markdown: bool) -> Option<String> {
+    let text = if markdown {
+        let mut blocks = text.split("```").skip(1);
+        // The info string (e.g. `text`) ends the opening fence line
+        let block = blocks.next()?;
+        block.split_once('\n').map_or("", |(_, code)| code)
+    } else {
+        text
+    };
+    let convention = text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let convention = convention.trim();
+    (!convention.is_empty()).then(|| convention.to_string())
+}
+
+/// Merges a commit convention into a generated message
+///
+/// # Arguments
+/// - `summary`: The generated summary.
+/// - `description`: The generated description.
+/// - `convention`: The convention, see `parse_convention`.
+/// - `mode`: How the convention is merged.
+/// - `values`: Variables of the change, rendered in the convention.
+///
+/// # Returns
+/// The summary and description following the convention.
+pub fn apply_convention(
+    summary: String,
+    description: String,
+    convention: &str,
+    mode: ConventionMode,
+    values: &HashMap<String, String>,
+) -> (String, String) {
+    let mut values = values.clone();
+    values.insert("SUMMARY".to_string(), summary.clone());
+    values.insert("DESCRIPTION".to_string(), description.clone());
+    let (first_line, rest) = convention.split_once('\n').unwrap_or((convention, ""));
+
+    let summary = if first_line.contains("{{SUMMARY}}") {
+        byteutils::string::replace_multiple_placeholders(first_line, &values)
+    } else {
+        let prefix = byteutils::string::replace_multiple_placeholders(first_line, &values);
+        let prefix = prefix.trim();
+        if prefix.is_empty() || summary.starts_with(prefix) {
+            summary
+        } else {
+            format!("{} {}", prefix, summary)
+        }
+    };
+    let description = match mode {
+        ConventionMode::Prefix => description,
+        ConventionMode::Scaffold => {
+            let rest = rest.trim_start_matches('\n');
+            if rest.contains("{{DESCRIPTION}}") {
+                byteutils::string::replace_multiple_placeholders(rest, &values)
+            } else {
+                let rest = byteutils::string::replace_multiple_placeholders(rest, &values);
+                [description.as_str(), rest.as_str()]
+                    .into_iter()
+                    .filter(|part| !part.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n\n")
+            }
+        }
+    };
+    (summary, description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FileTemplate;
+
+    #[test]
+    fn test_applies_commit_conventions() {
+        let values = HashMap::from([("PACKAGE".to_string(), "api".to_string())]);
+        let markdown = "# Conventions\n\nUse:\n\n```text\nchore({{PACKAGE}}):\n\nRefs: none\n```\n";
+        let convention = parse_convention(markdown, true).unwrap();
+        assert_eq!(convention, "chore({{PACKAGE}}):\n\nRefs: none");
+        assert_eq!(parse_convention("# Only comments\n", false), None);
+
+        let apply = |mode| {
+            apply_convention(
+                "Update main.rs".to_string(),
+                "1 insertion".to_string(),
+                &convention,
+                mode,
+                &values,
+            )
+        };
+        assert_eq!(
+            apply(ConventionMode::Prefix),
+            (
+                "chore(api): Update main.rs".to_string(),
+                "1 insertion".to_string()
+            )
+        );
+        assert_eq!(
+            apply(ConventionMode::Scaffold),
+            (
+                "chore(api): Update main.rs".to_string(),
+                "1 insertion\n\nRefs: none".to_string()
+            )
+        );
+
+        let (summary, _) = apply_convention(
+            "Update main.rs".to_string(),
+            String::new(),
+            "[autopilot] {{SUMMARY}}",
+            ConventionMode::Prefix,
+            &values,
+        );
+        assert_eq!(summary, "[autopilot] Update main.rs");
+    }
+
+    #[test]
+    fn test_annotates_repeated_commits() {
+        let values = HashMap::from([("SEQ".to_string(), "5".to_string())]);
+        assert_eq!(
+            annotate_repeat(
+                "Update notes.md".to_string(),
+                RepeatAnnotation::Today,
+                &values
+            ),
+            "Update notes.md (x5 today)"
+        );
+        assert_eq!(
+            annotate_repeat(
+                "Update notes.md".to_string(),
+                RepeatAnnotation::Counter,
+                &values
+            ),
+            "Update notes.md #5"
+        );
+        assert_eq!(
+            annotate_repeat(
+                "Update notes.md".to_string(),
+                RepeatAnnotation::Off,
+                &values
+            ),
+            "Update notes.md"
+        );
+
+        let first = HashMap::from([("SEQ".to_string(), "1".to_string())]);
+        assert_eq!(
+            annotate_repeat(
+                "Update notes.md".to_string(),
+                RepeatAnnotation::Today,
+                &first
+            ),
+            "Update notes.md"
+        );
+    }
+
+    #[test]
+    fn test_change_kind_from_combined_status() {
+        assert_eq!(
+            ChangeKind::from_status(Status::WT_MODIFIED | Status::INDEX_MODIFIED),
+            ChangeKind::Modify
+        );
+        assert_eq!(
+            ChangeKind::from_status(Status::INDEX_NEW | Status::WT_MODIFIED),
+            ChangeKind::Create
+        );
+        assert_eq!(
+            ChangeKind::from_status(Status::INDEX_NEW | Status::WT_DELETED),
+            ChangeKind::Remove
+        );
+        assert_eq!(
+            ChangeKind::from_status(Status::INDEX_RENAMED | Status::WT_MODIFIED),
+            ChangeKind::Rename
+        );
+        assert_eq!(
+            ChangeKind::from_status(Status::INDEX_DELETED | Status::WT_NEW),
+            ChangeKind::Modify
+        );
+        assert_eq!(
+            ChangeKind::from_status(Status::INDEX_DELETED),
+            ChangeKind::Remove
+        );
+        assert_eq!(
+            ChangeKind::from_status(Status::WT_TYPECHANGE),
+            ChangeKind::Modify
+        );
+    }
+
+    fn values() -> HashMap<String, String> {
+        HashMap::from([
+            ("FILE_NAME_SHORT".to_string(), "src/main.rs".to_string()),
+            ("FILE_OLD_NAME".to_string(), "src/old.rs".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_conventional_generator() {
+        let config = Config::default();
+        let values = values();
+
+        let (summary, _) = ConventionalGenerator
+            .generate(
+                &config,
+                &Change {
+                    kind: ChangeKind::Create,
+                    values: &values,
+                    diff: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(summary, "feat(src): add src/main.rs");
+
+        let (summary, _) = ConventionalGenerator
+            .generate(
+                &config,
+                &Change {
+                    kind: ChangeKind::Rename,
+                    values: &values,
+                    diff: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(summary, "refactor(src): rename src/old.rs to src/main.rs");
+
+        let mut values = values;
+        values.insert("PACKAGE".to_string(), "api".to_string());
+        let (summary, _) = ConventionalGenerator
+            .generate(
+                &config,
+                &Change {
+                    kind: ChangeKind::Create,
+                    values: &values,
+                    diff: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(summary, "feat(api): add src/main.rs");
+    }
+
+    #[test]
+    fn test_script_generator_reads_change_from_stdin() {
+        let config = Config {
+            generator: "script".to_string(),
+            generator_script: Some(
+                r#"grep -q '"kind":"modify"' && printf 'Scripted summary\n\nScripted body\n'"#
+                    .to_string(),
+            ),
+            ..Default::default()
+        };
+        let values = values();
+
+        let generator = from_config(&config).unwrap();
+        let (summary, description) = generator
+            .generate(
+                &config,
+                &Change {
+                    kind: ChangeKind::Modify,
+                    values: &values,
+                    diff: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(summary, "Scripted summary");
+        assert_eq!(description, "Scripted body");
+    }
+
+    #[test]
+    fn test_detects_unresolved_variables() {
+        let mut config = Config::default();
+        config.message.modify.comment = "Update {{FILE_NAME_SHORT}} on {{BRANHC}}".to_string();
+        let values = values();
+
+        let unresolved = unresolved_variables(&config, ChangeKind::Modify, &values);
+        assert!(unresolved.contains("BRANHC"));
+        assert!(!unresolved.contains("FILE_NAME_SHORT"));
+
+        assert_eq!(
+            undefined_variables(&config).into_iter().collect::<Vec<_>>(),
+            vec!["BRANHC".to_string()]
+        );
+        assert!(undefined_variables(&Config::default()).is_empty());
+    }
+
+    #[test]
+    fn test_file_templates_take_precedence() {
+        let rule = |pattern: &str, comment: &str| FileTemplate {
+            pattern: pattern.to_string(),
+            message: Message {
+                comment: comment.to_string(),
+                ..Default::default()
+            },
+            description: None,
+        };
+        let config = Config {
+            file_templates: vec![
+                rule("*.md", "docs: update {{FILE_NAME_SHORT}}"),
+                rule("*.rs", "code: update {{FILE_NAME_SHORT}}"),
+                rule("src/bin/*.rs", "bin: update {{FILE_NAME_SHORT}}"),
+            ],
+            ..Default::default()
+        };
+        let mut values = values();
+        let summary = |kind: ChangeKind, values: &HashMap<String, String>| {
+            TemplateGenerator
+                .generate(
+                    &config,
+                    &Change {
+                        kind,
+                        values,
+                        diff: None,
+                    },
+                )
+                .unwrap()
+                .0
+        };
+
+        assert_eq!(
+            summary(ChangeKind::Modify, &values),
+            "code: update src/main.rs"
+        );
+        values.insert("FILE_NAME_SHORT".to_string(), "src/bin/cli.rs".to_string());
+        assert_eq!(
+            summary(ChangeKind::Create, &values),
+            "bin: update src/bin/cli.rs"
+        );
+        values.insert("FILE_NAME_SHORT".to_string(), "notes.txt".to_string());
+        assert_eq!(
+            summary(ChangeKind::Modify, &values),
+            render(&config.message.modify, &values)
+        );
+    }
+
+    #[test]
+    fn test_unknown_generator_is_rejected() {
+        let config = Config {
+            generator: "unknown".to_string(),
+            ..Default::default()
+        };
+        assert!(from_config(&config).is_err());
+    }
+}
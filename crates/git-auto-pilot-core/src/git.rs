@@ -0,0 +1,3569 @@
+//! # Git Module
+//!
+//! Git operations of the auto-commit engine: analyzing the changes of a
+//! repository, staging, committing (optionally signed), tagging, pushing and
+//! repository maintenance.
+
+use git2::{
+    build::CheckoutBuilder, BranchType, Commit, Delta, DiffFindOptions, DiffOptions,
+    Error as GitError, IndexAddOption, ObjectType, Oid, Repository, Signature, Status,
+    StatusOptions, Time,
+};
+use secrecy::{ExposeSecret, SecretString};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    ffi::OsStr,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::OnceLock,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, error, info, instrument, trace, warn};
+
+/// Detailed information about changes in a file
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileChangeStats {
+    /// Number of lines added in the file
+    pub lines_added: usize,
+    /// Number of lines deleted from the file
+    pub lines_deleted: usize,
+    /// Number of lines deleted from the file
+    pub lines_modified: usize,
+    /// Overall status of the file change (e.g., added, deleted, modified)
+    pub status: Status,
+    /// Original name of the file if renamed
+    pub old_name: Option<String>,
+}
+
+/// Sparse-checkout patterns of a repository (`git sparse-checkout`)
+///
+/// libgit2 does not support sparse checkouts: files outside the sparse cone
+/// are missing from the working tree and would be reported (and committed) as
+/// deleted, and staging patterns would pull them back into the index. Status
+/// scans and staging are therefore restricted to the paths `contains` accepts.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SparseCheckout {
+    /// Whether the patterns use cone mode (`core.sparseCheckoutCone`)
+    cone: bool,
+    /// Patterns read from `info/sparse-checkout`, in order
+    patterns: Vec<String>,
+}
+
+impl SparseCheckout {
+    /// Reads the sparse-checkout configuration of a repository
+    ///
+    /// # Returns
+    /// `None` if `core.sparseCheckout` is not enabled.
+    ///
+    /// # Errors
+    /// Returns a `GitError` if the configuration or pattern file cannot be read.
+    pub fn from_repo(repo: &Repository) -> Result<Option<Self>, GitError> {
+        let config = repo.config()?.snapshot()?;
+        if !config.get_bool("core.sparseCheckout").unwrap_or(false) {
+            return Ok(None);
+        }
+
+        let contents = match std::fs::read_to_string(repo.path().join("info/sparse-checkout")) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(GitError::from_str(&format!(
+                    "cannot read sparse-checkout patterns: {}",
+                    err
+                )))
+            }
+        };
+        Ok(Some(SparseCheckout {
+            cone: config.get_bool("core.sparseCheckoutCone").unwrap_or(false),
+            patterns: contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string)
+                .collect(),
+        }))
+    }
+
+    /// Checks whether a repository-relative path lies inside the sparse checkout
+    ///
+    /// In cone mode files at the root, in directories included recursively
+    /// (`/dir/`) and directly in their parent directories are inside. Otherwise
+    /// the last pattern matching the path decides, `!` excluding it.
+    pub fn contains(&self, path: &str) -> bool {
+        if !self.cone {
+            return self.patterns.iter().fold(false, |included, pattern| {
+                match pattern.strip_prefix('!') {
+                    Some(excluded) if crate::helper::matches_path_pattern(path, excluded) => false,
+                    None if crate::helper::matches_path_pattern(path, pattern) => true,
+                    _ => included,
+                }
+            });
+        }
+
+        let Some((parent, _)) = path.rsplit_once('/') else {
+            return true;
+        };
+        // `!/dir/*/` marks a parent directory, only its direct files are included
+        let parents: HashSet<&str> = self
+            .patterns
+            .iter()
+            .filter_map(|pattern| pattern.strip_prefix("!/")?.strip_suffix("/*/"))
+            .collect();
+        parents.contains(parent)
+            || self
+                .patterns
+                .iter()
+                .filter(|pattern| !pattern.starts_with('!') && pattern.as_str() != "/*")
+                .map(|pattern| pattern.trim_matches('/'))
+                .filter(|dir| !parents.contains(dir))
+                .any(|dir| {
+                    path.strip_prefix(dir)
+                        .is_some_and(|rest| rest.starts_with('/'))
+                })
+    }
+}
+
+/// A directory rename grouping all files moved from one directory to another
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DirectoryRename {
+    /// Original path of the directory, relative to the repository root
+    pub old_dir: String,
+    /// New path of the directory, relative to the repository root
+    pub new_dir: String,
+    /// Renamed files as `(old path, new path)` pairs
+    pub files: Vec<(String, String)>,
+    /// Combined line statistics of all renamed files
+    pub stats: FileChangeStats,
+}
+
+/// Renamed files grouped by `(old directory, new directory)`, each file as
+/// `(old path, new path, statistics)`
+type RenameGroups<'a> = BTreeMap<(String, String), Vec<(String, String, &'a FileChangeStats)>>;
+
+/// A burst of new files in a previously unknown directory (e.g. `cargo new`)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Scaffold {
+    /// The new directory, relative to the repository root
+    pub dir: String,
+    /// New files in the directory
+    pub files: Vec<String>,
+    /// Combined line statistics of all new files
+    pub stats: FileChangeStats,
+}
+
+/// Network settings applied to every remote operation
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RemoteSettings {
+    /// HTTP(S) proxy URL, libgit2 auto-detects the proxy from the git config when unset
+    pub proxy: Option<String>,
+    /// Custom CA bundle (file or directory); libgit2 gets it process-wide from
+    /// `set_ca_bundle`, the git CLI per command
+    pub ca_bundle: Option<PathBuf>,
+    /// Accept any server certificate (explicit opt-in for testing only)
+    pub insecure_skip_tls_verify: bool,
+    /// Maximum duration of a push or fetch, `None` for no limit
+    pub timeout: Option<Duration>,
+}
+
+impl RemoteSettings {
+    /// Builds the libgit2 proxy options for these settings
+    fn proxy_options(&self) -> git2::ProxyOptions<'_> {
+        let mut proxy_options = git2::ProxyOptions::new();
+        match self.proxy.as_deref() {
+            Some(proxy) => {
+                trace!("Using proxy {}", proxy);
+                proxy_options.url(proxy);
+            }
+            None => {
+                proxy_options.auto();
+            }
+        }
+        proxy_options
+    }
+
+    /// Configuration handed to network commands of the git CLI (`-c key=value`)
+    ///
+    /// The CLI does not see the settings libgit2 was given, so the proxy and
+    /// the certificate settings are passed explicitly. With a `timeout`,
+    /// transfers slower than one byte per second for that long abort.
+    fn cli_config(&self) -> Vec<String> {
+        let mut config = Vec::new();
+        if let Some(proxy) = self.proxy.as_deref() {
+            config.push(format!("http.proxy={}", proxy));
+        }
+        if let Some(ca_bundle) = self.ca_bundle.as_deref() {
+            let key = if ca_bundle.is_dir() {
+                "http.sslCAPath"
+            } else {
+                "http.sslCAInfo"
+            };
+            config.push(format!("{}={}", key, ca_bundle.display()));
+        }
+        if self.insecure_skip_tls_verify {
+            config.push("http.sslVerify=false".to_string());
+        }
+        if let Some(timeout) = self.timeout {
+            config.push("http.lowSpeedLimit=1".to_string());
+            config.push(format!("http.lowSpeedTime={}", timeout.as_secs().max(1)));
+        }
+        config
+    }
+
+    /// Installs the certificate check callback for these settings
+    ///
+    /// Certificates are validated by libgit2 (using the configured CA bundle)
+    /// unless `insecure_skip_tls_verify` is set.
+    fn certificate_check(&self, callbacks: &mut git2::RemoteCallbacks<'_>) {
+        let insecure = self.insecure_skip_tls_verify;
+        callbacks.certificate_check(move |_cert, host| {
+            if insecure {
+                warn!("Skipping TLS certificate verification for {}", host);
+                Ok(git2::CertificateCheckStatus::CertificateOk)
+            } else {
+                Ok(git2::CertificateCheckStatus::CertificatePassthrough)
+            }
+        });
+    }
+
+    /// Installs progress callbacks cancelling the operation once `timeout` is exceeded
+    ///
+    /// Fetches are cancelled while the pack is received. Pushes can only be
+    /// cancelled before their pack is sent and while the server reports its
+    /// progress: libgit2 offers no way to cancel the pack upload itself, which
+    /// is only cut off once the connection stalls for the socket timeout set
+    /// with `set_network_timeout`.
+    fn watchdog(&self, callbacks: &mut git2::RemoteCallbacks<'_>) {
+        let Some(timeout) = self.timeout else {
+            return;
+        };
+        let deadline = Instant::now() + timeout;
+        callbacks.transfer_progress(move |_| Instant::now() < deadline);
+        callbacks.sideband_progress(move |_| Instant::now() < deadline);
+        callbacks.push_negotiation(move |_| {
+            if Instant::now() < deadline {
+                Ok(())
+            } else {
+                Err(GitError::from_str("push timed out"))
+            }
+        });
+    }
+
+    /// Replaces the error of an operation cancelled by the watchdog with a timeout error
+    ///
+    /// # Arguments
+    /// * `err` - The error of the operation.
+    /// * `started` - When the operation started.
+    fn timeout_error(&self, err: GitError, started: Instant) -> GitError {
+        match self.timeout {
+            Some(timeout) if started.elapsed() >= timeout => GitError::new(
+                git2::ErrorCode::GenericError,
+                git2::ErrorClass::Net,
+                format!(
+                    "operation timed out after {}s ({})",
+                    timeout.as_secs(),
+                    err.message()
+                ),
+            ),
+            _ => err,
+        }
+    }
+}
+
+/// Socket timeout set by `set_network_timeout`
+static NETWORK_TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+/// Sets the socket timeouts of all network operations
+///
+/// Connections that cannot be established, or stop transferring data, for
+/// longer than `timeout` fail instead of blocking forever. libgit2 keeps the
+/// timeouts process-wide: only the first call takes effect, changing them
+/// later would race the operations reading them.
+///
+/// # Errors
+/// Returns a `GitError` if libgit2 rejects the timeout.
+pub fn set_network_timeout(timeout: Duration) -> Result<(), GitError> {
+    let current = *NETWORK_TIMEOUT.get_or_init(|| timeout);
+    if current != timeout {
+        warn!(
+            "Keeping the network timeout of {:?}, a new one applies after a restart",
+            current
+        );
+        return Ok(());
+    }
+    let millis = i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX);
+    // SAFETY: the timeouts are set to the same value by every call, so
+    // concurrent network operations never read a changing value
+    unsafe {
+        git2::opts::set_server_connect_timeout_in_milliseconds(millis)?;
+        git2::opts::set_server_timeout_in_milliseconds(millis)?;
+    }
+    debug!("Network timeout set to {:?}", timeout);
+    Ok(())
+}
+
+/// Whether a failed network operation may succeed when retried
+///
+/// True for timeouts, refused or lost connections (reported by the transport
+/// as network, OS, TLS or HTTP errors), false for e.g. rejected credentials,
+/// certificates or pushes.
+pub fn is_transient_error(err: &GitError) -> bool {
+    use git2::{ErrorClass, ErrorCode};
+
+    if matches!(err.code(), ErrorCode::Auth | ErrorCode::Certificate) {
+        return false;
+    }
+    matches!(
+        err.class(),
+        ErrorClass::Net | ErrorClass::Os | ErrorClass::Ssl | ErrorClass::Http
+    ) || err.message().to_lowercase().contains("timed out")
+}
+
+/// Direction of a remote operation, selecting the URL rewrites that apply
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RemoteDirection {
+    /// Fetching from the remote
+    Fetch,
+    /// Pushing to the remote
+    Push,
+}
+
+/// Name and email identifying the author or committer of a commit
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CommitAuthor {
+    /// Author name
+    pub name: String,
+    /// Author email
+    pub email: String,
+}
+
+/// Resolves the identity commits are made with
+///
+/// `user.name` and `user.email` are read through the repository's own config
+/// hierarchy (repository, global and system configuration), so repositories
+/// with a local identity keep it. Values missing there are taken from
+/// `fallback`.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `fallback` - Identity used for values the repository does not configure
+///
+/// # Errors
+/// Returns a `GitError` if the configuration cannot be read or no name or
+/// email is available.
+pub fn identity(
+    repo: &Repository,
+    fallback: Option<&CommitAuthor>,
+) -> Result<CommitAuthor, GitError> {
+    let config = repo.config()?.snapshot()?;
+    let resolve = |key: &str, fallback: Option<&String>| {
+        config
+            .get_string(key)
+            .ok()
+            .or_else(|| fallback.cloned())
+            .filter(|value| !value.is_empty())
+    };
+
+    let name = resolve("user.name", fallback.map(|fallback| &fallback.name));
+    let email = resolve("user.email", fallback.map(|fallback| &fallback.email));
+    match (name, email) {
+        (Some(name), Some(email)) => Ok(CommitAuthor { name, email }),
+        _ => Err(GitError::from_str(
+            "user.name and user.email are neither set in git nor in the configuration",
+        )),
+    }
+}
+
+/// Resolves the file of the `commit.template` git setting
+///
+/// A leading `~` is expanded and relative paths are taken relative to the
+/// working tree, as the daemon does not run from it.
+///
+/// # Returns
+/// The path of the template, `None` if no template is configured.
+pub fn commit_template_path(repo: &Repository) -> Option<PathBuf> {
+    let config = repo
+        .config()
+        .and_then(|mut config| config.snapshot())
+        .ok()?;
+    let path = config.get_path("commit.template").ok()?;
+    Some(match repo.workdir() {
+        Some(workdir) if path.is_relative() => workdir.join(path),
+        _ => path,
+    })
+}
+
+/// External program producing commit signatures
+///
+/// The commit buffer is written to the program's stdin and the detached
+/// signature is read from its stdout, e.g. `gpg -bsau <key>` or
+/// `ssh-keygen -Y sign -n git -f <key>`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CommitSigner {
+    /// Program to run
+    pub program: String,
+    /// Arguments passed to the program
+    pub args: Vec<String>,
+}
+
+impl CommitSigner {
+    /// Signs a commit buffer
+    ///
+    /// # Errors
+    /// Returns a `GitError` if the program cannot be run, fails, or prints no signature.
+    fn sign(&self, buffer: &str) -> Result<String, GitError> {
+        let to_error = |err: std::io::Error| {
+            GitError::from_str(&format!("failed to run {}: {}", self.program, err))
+        };
+
+        trace!("Signing commit with {} {:?}", self.program, self.args);
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(to_error)?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(buffer.as_bytes()).map_err(to_error)?;
+        }
+        let output = child.wait_with_output().map_err(to_error)?;
+
+        let signature = String::from_utf8_lossy(&output.stdout).into_owned();
+        if !output.status.success() || signature.trim().is_empty() {
+            return Err(GitError::from_str(&format!(
+                "{} failed to sign the commit ({}): {}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(signature)
+    }
+}
+
+/// Makes libgit2 trust the certificates of a custom CA bundle
+///
+/// The setting is process-wide and applies to every following remote operation.
+///
+/// # Arguments
+///
+/// * `path` - A PEM bundle file, or a directory of hashed certificates.
+///
+/// # Errors
+///
+/// Returns a `GitError` if libgit2 rejects the location.
+pub fn set_ca_bundle(path: &Path) -> Result<(), GitError> {
+    // SAFETY: called once during startup, before any remote operation runs
+    // concurrently and reads the certificate locations
+    unsafe {
+        if path.is_dir() {
+            git2::opts::set_ssl_cert_dir(path)?;
+        } else {
+            git2::opts::set_ssl_cert_file(path)?;
+        }
+    }
+    info!("Using CA certificates from {}", path.display());
+    Ok(())
+}
+
+/// Gets the name of the currently checked-out branch.
+/// If no branch is found (e.g., in a detached HEAD state), defaults to "master".
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the `git2::Repository` object.
+///
+/// # Returns
+///
+/// * `Ok(String)` - The name of the current branch.
+/// * `Err(GitError)` - In case of any error accessing the repository.
+pub fn get_current_branch(repo: &Repository) -> Result<String, GitError> {
+    let head = repo.head()?;
+    let head_name = head
+        .name()
+        .ok_or_else(|| GitError::from_str("Failed to get HEAD name"))?;
+
+    // Keep the full branch name, which may itself contain slashes (e.g. `autopilot/2024-06-01`)
+    let branch_name = head_name.strip_prefix("refs/heads/").unwrap_or(head_name);
+
+    Ok(branch_name.to_string())
+}
+
+/// Switches the repository to the given local branch, creating it from HEAD if needed.
+///
+/// A newly created branch points at the current HEAD commit, so switching to it
+/// keeps all uncommitted working tree changes untouched.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the `git2::Repository` object.
+/// * `branch_name` - The name of the local branch to switch to.
+///
+/// # Returns
+///
+/// * `Ok(())` - On success.
+/// * `Err(GitError)` - If the branch cannot be created or checked out.
+pub fn switch_to_branch(repo: &Repository, branch_name: &str) -> Result<(), GitError> {
+    let ref_name = format!("refs/heads/{}", branch_name);
+
+    if repo
+        .head()
+        .ok()
+        .and_then(|head| head.name().map(str::to_string))
+        == Some(ref_name.clone())
+    {
+        trace!("Already on branch {}", branch_name);
+        return Ok(());
+    }
+
+    match repo.find_branch(branch_name, BranchType::Local) {
+        Ok(branch) => {
+            debug!("Checking out existing branch {}", branch_name);
+            let target = branch.get().peel(ObjectType::Commit)?;
+            repo.checkout_tree(&target, Some(CheckoutBuilder::new().safe()))?;
+        }
+        Err(_) => match repo.head().and_then(|head| head.peel_to_commit()) {
+            Ok(head_commit) => {
+                info!("Creating branch {}", branch_name);
+                repo.branch(branch_name, &head_commit, false)?;
+            }
+            // Unborn HEAD: the branch is created by the first commit
+            Err(_) => debug!("Repository has no commits, switching unborn HEAD"),
+        },
+    }
+
+    repo.set_head(&ref_name)?;
+    info!("Switched to branch {}", branch_name);
+    Ok(())
+}
+
+/// Updates a Git repository located at a given path.
+/// Optionally forces a reset to the remote repository if `force_update` is `true`.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the `git2::Repository` object.
+/// * `force_update` - A boolean flag indicating whether to discard local changes and force an update.
+///
+/// # Returns
+///
+/// * `Ok(())` - On success.
+/// * `Err(GitError)` - In case of any error accessing or modifying the repository.
+pub fn update_repo(repo: &Repository, force_update: bool) -> Result<(), GitError> {
+    // Get the current branch name
+    let branch_name = get_current_branch(repo)?;
+
+    // Get the directory path for the repository
+    let repo_path = repo.path();
+    let path = repo_path
+        .parent()
+        .ok_or_else(|| GitError::from_str("Failed to determine repository path"))?;
+
+    if force_update {
+        // Force reset to the remote branch (discard local changes)
+        let ref_name = format!("refs/remotes/origin/{}", branch_name);
+        let oid = repo.refname_to_id(&ref_name)?;
+        let object = repo.find_object(oid, None)?;
+        repo.reset(&object, git2::ResetType::Hard, None)?;
+    }
+
+    // Pull from the origin repository (using Git CLI)
+    let output = Command::new("git")
+        .current_dir(path)
+        .arg("pull")
+        .output()
+        .map_err(|e| GitError::from_str(&format!("Failed to execute git pull: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(GitError::from_str(&format!(
+            "Git pull failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
+
+/// Files in the git directory indicating a manual git operation in progress
+///
+/// `index.lock` is held while another git process writes the index, and
+/// `MERGE_MSG` is present while a merge is being concluded (its message may
+/// be open in an editor).
+const BUSY_MARKERS: &[&str] = &["index.lock", "MERGE_MSG"];
+
+/// Returns the marker of a manual git operation in progress, if any
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the `git2::Repository` object.
+///
+/// # Returns
+///
+/// * `Option<&'static str>` - The first marker file found in the git directory
+pub fn busy_marker(repo: &Repository) -> Option<&'static str> {
+    BUSY_MARKERS
+        .iter()
+        .copied()
+        .find(|marker| repo.path().join(marker).exists())
+}
+
+/// Comprehensive repository change analysis
+///
+/// The status scan is restricted to the given paths so that a single event
+/// does not walk the whole repository. When the scoped scan finds a new or
+/// deleted file, which may be one half of a rename whose counterpart lies
+/// outside the given paths, the analysis falls back to a full scan. Renames
+/// that only change the case of a path are detected separately, as git does
+/// not report them on case-insensitive file systems.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the `git2::Repository` object.
+/// * `paths` - Paths (absolute or repository-relative) to scope the scan to;
+///   an empty slice scans the whole repository.
+///
+/// # Returns
+///
+/// * `Result<HashMap<String, Vec<FileChangeStats>>, git2::Error>` - Comprehensive changes grouped by file type
+#[instrument(name = "analysis", skip_all, fields(paths = paths.len()))]
+pub fn analyze_repository_changes(
+    repo: &Repository,
+    paths: &[PathBuf],
+) -> Result<HashMap<String, Vec<FileChangeStats>>, git2::Error> {
+    let pathspecs = to_pathspecs(repo, paths);
+    let mut repository_changes = collect_changes(repo, &pathspecs)?;
+
+    let may_be_rename = repository_changes
+        .values()
+        .flatten()
+        .any(|stats| stats.status.intersects(Status::WT_NEW | Status::WT_DELETED));
+    if !pathspecs.is_empty() && may_be_rename {
+        debug!("Falling back to a full status scan for rename detection");
+        repository_changes = collect_changes(repo, &[])?;
+    }
+
+    detect_renames(repo, &mut repository_changes)?;
+    detect_case_renames(repo, &pathspecs, &mut repository_changes)?;
+    debug!("Repository changes found: {}", repository_changes.len());
+
+    Ok(repository_changes)
+}
+
+/// Converts paths into repository-relative pathspecs
+///
+/// Paths outside the repository working directory are dropped.
+fn to_pathspecs(repo: &Repository, paths: &[PathBuf]) -> Vec<String> {
+    let workdir = repo.workdir();
+
+    paths
+        .iter()
+        .filter_map(|path| match (path.is_absolute(), workdir) {
+            (true, Some(workdir)) => path.strip_prefix(workdir).ok(),
+            (true, None) => None,
+            (false, _) => Some(path.as_path()),
+        })
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .filter_map(|relative| relative.to_str().map(str::to_string))
+        .collect()
+}
+
+/// Collects the status and line statistics of changed files
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the `git2::Repository` object.
+/// * `pathspecs` - Repository-relative paths to restrict the scan to, matched literally; empty for a full scan.
+fn collect_changes(
+    repo: &Repository,
+    pathspecs: &[String],
+) -> Result<HashMap<String, Vec<FileChangeStats>>, git2::Error> {
+    let sparse = SparseCheckout::from_repo(repo)?;
+
+    // Create status options
+    let mut status_opts = StatusOptions::new();
+    status_opts.include_untracked(true);
+    status_opts.recurse_untracked_dirs(true);
+    // Event paths are file names, not patterns: `[` or `*` in them must not
+    // match other files
+    status_opts.disable_pathspec_match(true);
+    for pathspec in pathspecs {
+        status_opts.pathspec(pathspec);
+    }
+
+    // Get repository status to capture all changes
+    let statuses = repo.statuses(Some(&mut status_opts))?;
+
+    // Analyze changes for each file
+    let mut repository_changes: HashMap<String, Vec<FileChangeStats>> = HashMap::new();
+
+    for entry in statuses.iter() {
+        let status = entry.status();
+
+        // Skip entries with zero status or ignored files
+        if status.is_empty() || status.is_ignored() {
+            continue;
+        }
+
+        if let Some(path) = entry.path() {
+            if sparse.as_ref().is_some_and(|sparse| !sparse.contains(path)) {
+                trace!("Skipping path outside the sparse checkout: {}", path);
+                continue;
+            }
+            debug!("Processing path: {} - Status: {:?}", path, status);
+
+            // Create diff options restricted to the current file
+            let mut diff_options = DiffOptions::new();
+            diff_options
+                .context_lines(0)
+                .disable_pathspec_match(true)
+                .pathspec(path);
+
+            // Try to get more detailed diff information
+            let file_stats = match repo.diff_index_to_workdir(None, Some(&mut diff_options)) {
+                Ok(diff) => {
+                    let stats = diff.stats().map_err(|e| {
+                        error!("Error retrieving stats: {:?}", e);
+                        e
+                    })?;
+
+                    FileChangeStats {
+                        lines_added: stats.insertions(),
+                        lines_deleted: stats.deletions(),
+                        lines_modified: stats.insertions() + stats.deletions(),
+                        status,
+                        old_name: None,
+                    }
+                }
+                Err(e) => {
+                    debug!("Error getting diff for path {}: {:?}", path, e);
+                    continue;
+                }
+            };
+
+            repository_changes
+                .entry(path.to_string())
+                .or_default()
+                .push(file_stats);
+        }
+    }
+
+    Ok(repository_changes)
+}
+
+/// Helper function to filter files by status
+pub fn filter_files_by_status<F>(
+    repo: &Repository,
+    status_check: F,
+) -> Result<Vec<String>, git2::Error>
+where
+    F: Fn(Status) -> bool, // This allows the closure to capture variables
+{
+    let statuses = repo.statuses(None)?;
+
+    let filtered_files: Vec<String> = statuses
+        .iter()
+        .filter(|entry| status_check(entry.status()))
+        .filter_map(|entry| entry.path().map(|path| path.to_string()))
+        .collect();
+
+    Ok(filtered_files)
+}
+
+/// Get files with specific status
+pub fn get_files_with_status(
+    repo: &Repository,
+    status: Status,
+) -> Result<Vec<String>, git2::Error> {
+    filter_files_by_status(repo, |file_status| file_status == status)
+}
+
+/// Pairs deleted and newly created files into rename operations
+///
+/// Uses libgit2's similarity scoring across the whole change set, so batch
+/// renames (e.g. `mv src/*.txt docs/`) are each reported as a single
+/// `WT_RENAMED` entry keyed by the new path, carrying the old path in `old_name`.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the `git2::Repository` object.
+/// * `changes` - The analyzed repository changes, updated in place.
+fn detect_renames(
+    repo: &Repository,
+    changes: &mut HashMap<String, Vec<FileChangeStats>>,
+) -> Result<(), GitError> {
+    let has_status = |status: Status| {
+        changes
+            .values()
+            .flatten()
+            .any(|stats| stats.status.contains(status))
+    };
+
+    // A rename needs at least one deleted and one new file
+    if !has_status(Status::WT_DELETED) || !has_status(Status::WT_NEW) {
+        return Ok(());
+    }
+
+    trace!("Checking if files are a result of a rename operation");
+
+    let mut diff_options = DiffOptions::new();
+    diff_options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+    let mut diff = repo.diff_index_to_workdir(None, Some(&mut diff_options))?;
+
+    let mut find_options = DiffFindOptions::new();
+    find_options.renames(true).for_untracked(true);
+    diff.find_similar(Some(&mut find_options))?;
+
+    for delta in diff
+        .deltas()
+        .filter(|delta| delta.status() == Delta::Renamed)
+    {
+        let (Some(old_path), Some(new_path)) = (
+            delta.old_file().path().and_then(Path::to_str),
+            delta.new_file().path().and_then(Path::to_str),
+        ) else {
+            continue;
+        };
+
+        let Some(old_stats) = changes
+            .remove(old_path)
+            .and_then(|stats| stats.into_iter().next())
+        else {
+            continue;
+        };
+        changes.remove(new_path);
+
+        debug!("Detected rename: {} -> {}", old_path, new_path);
+        changes.insert(
+            new_path.to_string(),
+            vec![FileChangeStats {
+                status: Status::WT_RENAMED,
+                old_name: Some(old_path.to_string()),
+                ..old_stats
+            }],
+        );
+    }
+
+    Ok(())
+}
+
+/// Detects renames that only change the case of a path (`File.md` -> `file.md`)
+///
+/// On case-insensitive file systems (`core.ignorecase`) git matches the
+/// renamed file to its index entry and reports no change at all, so the index
+/// would keep the old case forever. Each event path without a change is
+/// compared with the index: if an entry differs from it only in case, the
+/// path exists with exactly its own case and the entry no longer does, a
+/// `WT_RENAMED` change from the entry to the path is recorded.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the `git2::Repository` object.
+/// * `pathspecs` - Repository-relative paths the events reported.
+/// * `changes` - The analyzed repository changes, updated in place.
+fn detect_case_renames(
+    repo: &Repository,
+    pathspecs: &[String],
+    changes: &mut HashMap<String, Vec<FileChangeStats>>,
+) -> Result<(), GitError> {
+    let Some(workdir) = repo.workdir() else {
+        return Ok(());
+    };
+    let index = repo.index()?;
+
+    for path in pathspecs {
+        let is_known = changes.contains_key(path)
+            || changes
+                .values()
+                .flatten()
+                .any(|stats| stats.old_name.as_ref() == Some(path));
+        if is_known {
+            continue;
+        }
+
+        let lowercase = path.to_lowercase();
+        let Some(old_path) = index
+            .iter()
+            .filter_map(|entry| String::from_utf8(entry.path).ok())
+            .find(|entry| entry != path && entry.to_lowercase() == lowercase)
+        else {
+            continue;
+        };
+        if !exists_with_exact_case(workdir, path) || exists_with_exact_case(workdir, &old_path) {
+            continue;
+        }
+
+        debug!("Detected case-only rename: {} -> {}", old_path, path);
+        changes.insert(
+            path.clone(),
+            vec![FileChangeStats {
+                lines_added: 0,
+                lines_deleted: 0,
+                lines_modified: 0,
+                status: Status::WT_RENAMED,
+                old_name: Some(old_path),
+            }],
+        );
+    }
+    Ok(())
+}
+
+/// Checks whether a path exists with exactly the given case
+///
+/// Unlike `Path::exists`, which matches any case on case-insensitive file
+/// systems, every component is compared with the names stored on disk.
+fn exists_with_exact_case(workdir: &Path, relative: &str) -> bool {
+    let mut dir = workdir.to_path_buf();
+    for component in Path::new(relative).components() {
+        let name = component.as_os_str();
+        let Ok(mut entries) = std::fs::read_dir(&dir) else {
+            return false;
+        };
+        if !entries.any(|entry| entry.is_ok_and(|entry| entry.file_name() == name)) {
+            return false;
+        }
+        dir.push(name);
+    }
+    true
+}
+
+/// Groups file renames that result from renaming a whole directory
+///
+/// Each rename is mapped to a directory rename by dropping the path components
+/// the old and new paths have in common at the end (e.g. `src/a/x.txt` ->
+/// `lib/a/x.txt` maps `src` -> `lib`). A mapping is only reported when the old
+/// directory no longer exists, so moving some files out of a directory that
+/// stays around is still handled file by file.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the `git2::Repository` object.
+/// * `changes` - The analyzed repository changes.
+///
+/// # Returns
+///
+/// * `Vec<DirectoryRename>` - The detected directory renames, ordered by old directory.
+pub fn detect_directory_renames(
+    repo: &Repository,
+    changes: &HashMap<String, Vec<FileChangeStats>>,
+) -> Vec<DirectoryRename> {
+    let Some(workdir) = repo.workdir() else {
+        return Vec::new();
+    };
+
+    let mut groups = RenameGroups::new();
+    for (new_path, stats) in changes {
+        let Some(stats) = stats.first() else {
+            continue;
+        };
+        let (Status::WT_RENAMED, Some(old_path)) = (stats.status, stats.old_name.as_ref()) else {
+            continue;
+        };
+
+        let old_components: Vec<&str> = old_path.split('/').collect();
+        let new_components: Vec<&str> = new_path.split('/').collect();
+        let common_suffix = old_components
+            .iter()
+            .rev()
+            .zip(new_components.iter().rev())
+            .take_while(|(old, new)| old == new)
+            .count();
+        if common_suffix == 0
+            || common_suffix >= old_components.len()
+            || common_suffix >= new_components.len()
+        {
+            continue;
+        }
+
+        let old_dir = old_components[..old_components.len() - common_suffix].join("/");
+        let new_dir = new_components[..new_components.len() - common_suffix].join("/");
+        groups.entry((old_dir, new_dir)).or_default().push((
+            old_path.clone(),
+            new_path.clone(),
+            stats,
+        ));
+    }
+
+    groups
+        .into_iter()
+        .filter(|((old_dir, _), _)| !workdir.join(old_dir).exists())
+        .map(|((old_dir, new_dir), mut files)| {
+            files.sort_by(|a, b| a.1.cmp(&b.1));
+            let (lines_added, lines_deleted) =
+                files
+                    .iter()
+                    .fold((0, 0), |(added, deleted), (_, _, stats)| {
+                        (added + stats.lines_added, deleted + stats.lines_deleted)
+                    });
+
+            debug!(
+                "Detected directory rename: {} -> {} ({} files)",
+                old_dir,
+                new_dir,
+                files.len()
+            );
+            DirectoryRename {
+                stats: FileChangeStats {
+                    lines_added,
+                    lines_deleted,
+                    lines_modified: lines_added + lines_deleted,
+                    status: Status::WT_RENAMED,
+                    old_name: Some(old_dir.clone()),
+                },
+                files: files
+                    .into_iter()
+                    .map(|(old_path, new_path, _)| (old_path, new_path))
+                    .collect(),
+                old_dir,
+                new_dir,
+            }
+        })
+        .collect()
+}
+
+/// Groups new files into project scaffolds
+///
+/// Each new file is assigned to its top-most parent directory that holds no
+/// tracked file yet. Directories collecting at least `threshold` new files are
+/// reported as scaffolds; files directly in the repository root never are.
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the `git2::Repository` object.
+/// * `changes` - The analyzed repository changes.
+/// * `threshold` - Minimum number of new files forming a scaffold, `0` disables detection.
+///
+/// # Returns
+///
+/// * `Result<Vec<Scaffold>, GitError>` - The detected scaffolds, ordered by directory.
+pub fn detect_scaffolds(
+    repo: &Repository,
+    changes: &HashMap<String, Vec<FileChangeStats>>,
+    threshold: usize,
+) -> Result<Vec<Scaffold>, GitError> {
+    if threshold == 0 {
+        return Ok(Vec::new());
+    }
+
+    // Every directory that already contains a tracked file
+    let mut tracked_dirs: HashSet<String> = HashSet::new();
+    for entry in repo.index()?.iter() {
+        let path = String::from_utf8_lossy(&entry.path).to_string();
+        let mut dir = path.as_str();
+        while let Some((parent, _)) = dir.rsplit_once('/') {
+            if !tracked_dirs.insert(parent.to_string()) {
+                break;
+            }
+            dir = parent;
+        }
+    }
+
+    let mut groups: BTreeMap<String, Vec<(String, &FileChangeStats)>> = BTreeMap::new();
+    for (path, stats) in changes {
+        let Some(stats) = stats.first() else {
+            continue;
+        };
+        if !stats.status.intersects(Status::WT_NEW | Status::INDEX_NEW) {
+            continue;
+        }
+
+        let root = path
+            .match_indices('/')
+            .map(|(index, _)| &path[..index])
+            .find(|dir| !tracked_dirs.contains(*dir));
+        if let Some(root) = root {
+            groups
+                .entry(root.to_string())
+                .or_default()
+                .push((path.clone(), stats));
+        }
+    }
+
+    Ok(groups
+        .into_iter()
+        .filter(|(_, files)| files.len() >= threshold)
+        .map(|(dir, mut files)| {
+            files.sort_by(|a, b| a.0.cmp(&b.0));
+            let lines_added = files.iter().map(|(_, stats)| stats.lines_added).sum();
+
+            debug!("Detected scaffold: {} ({} files)", dir, files.len());
+            Scaffold {
+                dir,
+                files: files.into_iter().map(|(path, _)| path).collect(),
+                stats: FileChangeStats {
+                    lines_added,
+                    lines_deleted: 0,
+                    lines_modified: lines_added,
+                    status: Status::WT_NEW,
+                    old_name: None,
+                },
+            }
+        })
+        .collect())
+}
+
+/// Returns the staged changes as a unified diff
+///
+/// # Arguments
+///
+/// * `repo` - A reference to the `git2::Repository` object.
+///
+/// # Returns
+///
+/// * `Result<String, GitError>` - The patch between HEAD and the index (empty tree for unborn HEAD).
+pub fn staged_diff(repo: &Repository) -> Result<String, GitError> {
+    let head_tree = match repo.head() {
+        Ok(head) => Some(head.peel_to_tree()?),
+        Err(_) => None,
+    };
+    let diff = repo.diff_tree_to_index(head_tree.as_ref(), None, None)?;
+
+    let mut patch = String::new();
+    diff.print(git2::DiffFormat::Patch, |_delta, _hunk, line| {
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            patch.push(line.origin());
+        }
+        patch.push_str(&String::from_utf8_lossy(line.content()));
+        true
+    })?;
+    Ok(patch)
+}
+
+/// Stages files in a Git repository matching a given pattern.
+///
+/// This function is best used when you need to stage multiple files at once
+/// or when using wildcards (e.g., "*.rs", "src/*").
+///
+/// # Arguments
+/// * `repo` - Reference to the Git repository
+/// * `file_pattern` - Pattern to match files (e.g., "*", "*.rs", "src/")
+///
+/// # Errors
+/// Returns `GitError` if:
+/// * Index cannot be accessed
+/// * Pattern is invalid
+/// * Writing to index fails
+///
+/// # Notes
+/// - In sparse checkouts, paths outside the sparse checkout are not staged
+#[instrument(name = "stage", skip(repo))]
+pub fn add_files(repo: &Repository, file_pattern: &str) -> Result<(), GitError> {
+    let mut index = repo.index()?;
+    let sparse = SparseCheckout::from_repo(repo)?;
+    let mut skip_outside_sparse = |path: &Path, _: &[u8]| -> i32 {
+        let outside = sparse
+            .as_ref()
+            .is_some_and(|sparse| path.to_str().is_some_and(|path| !sparse.contains(path)));
+        i32::from(outside)
+    };
+
+    // Use a transaction-like approach for atomic operations
+    index.add_all(
+        [file_pattern].iter(),
+        IndexAddOption::DEFAULT | IndexAddOption::CHECK_PATHSPEC,
+        Some(&mut skip_outside_sparse),
+    )?;
+
+    index.write()?;
+    info!("Added files matching pattern: {}", file_pattern);
+    Ok(())
+}
+
+/// Stages a single file in a Git repository.
+///
+/// This function is optimized for staging individual files and provides more
+/// precise control over what gets staged. Use this when you need to stage
+/// specific files one at a time.
+///
+/// # Arguments
+/// * `repo` - Reference to the Git repository
+/// * `file_path` - Path to the file to stage (relative to repository root)
+///
+/// # Errors
+/// Returns `GitError` if:
+/// * File path is invalid
+/// * File doesn't exist
+/// * Index cannot be accessed
+/// * Writing to index fails
+///
+/// # Notes
+/// - Files tracked with Git LFS are staged through the git CLI, see `is_lfs_tracked`
+#[instrument(name = "stage", skip_all, fields(path = %file_path.as_ref().display(), is_deleted))]
+pub fn stage_file(
+    repo: &Repository,
+    file_path: impl AsRef<Path>,
+    is_deleted: bool,
+) -> Result<(), GitError> {
+    // Get the absolute path of the file
+    let file_path = file_path.as_ref();
+
+    // Get the repository's root path
+    let repo_path = repo.workdir().unwrap_or(repo.path());
+
+    // Convert the file path to a relative path
+    let relative_path = file_path.strip_prefix(repo_path).unwrap_or(file_path);
+
+    if !is_deleted && is_lfs_tracked(repo, relative_path) {
+        return stage_through_filters(repo, relative_path);
+    }
+
+    let mut index = repo.index()?;
+    if is_deleted {
+        // Handle deleted file by removing it from the index
+        debug!("File is removed: {}", relative_path.display());
+        index.remove_path(relative_path)?;
+    } else {
+        trace!("File is either modified or added");
+        index.add_path(relative_path)?;
+    }
+
+    index.write()?;
+    info!("Staged file: {}", relative_path.display());
+    Ok(())
+}
+
+/// Returns the blob ID of a file in the working tree and in `HEAD`
+///
+/// The working tree ID is hashed without writing the blob to the object
+/// database, after the conversions `git add` applies (see `hash_workdir_file`).
+///
+/// # Arguments
+/// * `repo` - Reference to the Git repository
+/// * `file_path` - Path to the file, relative to the repository root
+///
+/// # Returns
+/// The ID of the working tree content (`None` if the file does not exist)
+/// and of the committed content (`None` if `HEAD` does not contain the file).
+pub fn blob_ids(repo: &Repository, file_path: &str) -> (Option<Oid>, Option<Oid>) {
+    let workdir_id = hash_workdir_file(repo, file_path);
+    let head_id = repo
+        .head()
+        .and_then(|head| head.peel_to_tree())
+        .and_then(|tree| tree.get_path(Path::new(file_path)))
+        .ok()
+        .map(|entry| entry.id());
+    (workdir_id, head_id)
+}
+
+/// Hashes a working tree file the way `git add` stores it
+///
+/// `Oid::hash_file` hashes the raw content, which differs from the blob once
+/// clean filters or end-of-line conversion apply; such files are hashed by
+/// `git hash-object`, which runs them.
+///
+/// # Returns
+/// The blob ID, `None` if the file does not exist or cannot be hashed.
+pub fn hash_workdir_file(repo: &Repository, file_path: &str) -> Option<Oid> {
+    let full_path = repo.workdir()?.join(file_path);
+    if !is_converted(repo, Path::new(file_path)) {
+        return Oid::hash_file(ObjectType::Blob, full_path).ok();
+    }
+    let output = git_command(repo)
+        .args(["hash-object", "--path", file_path, "--"])
+        .arg(&full_path)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Oid::from_str(String::from_utf8_lossy(&output.stdout).trim()).ok()
+}
+
+/// Checks whether git converts the content of a file when staging it
+///
+/// # Arguments
+/// * `repo` - Reference to the Git repository
+/// * `path` - Path to the file, relative to the repository root
+fn is_converted(repo: &Repository, path: &Path) -> bool {
+    let by_attribute = ["filter", "text", "eol", "ident", "working-tree-encoding"]
+        .iter()
+        .any(|name| {
+            let value = repo
+                .get_attr(path, name, git2::AttrCheckFlags::FILE_THEN_INDEX)
+                .map(git2::AttrValue::from_string);
+            !matches!(
+                value,
+                Ok(git2::AttrValue::Unspecified | git2::AttrValue::False) | Err(_)
+            )
+        });
+    by_attribute
+        || repo
+            .config()
+            .and_then(|config| config.get_string("core.autocrlf"))
+            .is_ok_and(|autocrlf| {
+                !matches!(
+                    autocrlf.to_ascii_lowercase().as_str(),
+                    "false" | "no" | "off" | "0" | ""
+                )
+            })
+}
+
+/// Stages a file rename in a single index update
+///
+/// Removing the old path before adding the new one keeps case-only renames
+/// intact on case-insensitive file systems, where adding `file.md` would
+/// otherwise update the existing `File.md` entry.
+///
+/// # Arguments
+/// * `repo` - Reference to the Git repository
+/// * `old_path` - Previous path of the file, relative to the repository root
+/// * `new_path` - New path of the file, relative to the repository root
+///
+/// # Errors
+/// Returns `GitError` if the index cannot be accessed or written, or the new
+/// path cannot be added.
+#[instrument(name = "stage_rename", skip(repo))]
+pub fn stage_rename(repo: &Repository, old_path: &str, new_path: &str) -> Result<(), GitError> {
+    let mut index = repo.index()?;
+    index.remove_path(Path::new(old_path))?;
+    if is_lfs_tracked(repo, Path::new(new_path)) {
+        index.write()?;
+        stage_through_filters(repo, Path::new(new_path))?;
+        info!("Staged rename: {} -> {}", old_path, new_path);
+        return Ok(());
+    }
+    index.add_path(Path::new(new_path))?;
+    index.write()?;
+    info!("Staged rename: {} -> {}", old_path, new_path);
+    Ok(())
+}
+
+/// Creates a new commit in the git repository with an optional description.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository where the commit will be created
+/// * `message` - The main commit message (subject line)
+/// * `description` - Optional detailed description of the commit (commit body)
+/// * `time` - Optional timestamp overriding the current time for author and committer
+/// * `signer` - Optional external program signing the commit
+/// * `author` - Optional author replacing the configured git user as author;
+///   the configured user remains the committer
+/// * `fallback` - Identity used when the repository configures none (see `identity`)
+///
+/// # Errors
+/// Returns a `GitError` if:
+/// - No committer identity is available
+/// - Failed to access or write repository index
+/// - Failed to create tree from index
+/// - Failed to sign or create the commit
+///
+/// # Notes
+/// - For initial commits (no previous commits), it handles the case appropriately
+/// - Uses the same signature for author and committer unless `author` is given
+/// - Automatically handles HEAD reference update
+#[instrument(name = "commit", skip_all)]
+pub fn commit(
+    repo: &Repository,
+    message: &str,
+    description: Option<&str>,
+    time: Option<Time>,
+    signer: Option<&CommitSigner>,
+    author: Option<&CommitAuthor>,
+    fallback: Option<&CommitAuthor>,
+) -> Result<(), GitError> {
+    let committer = identity(repo, fallback)?;
+    let signature = match time {
+        Some(time) => Signature::new(&committer.name, &committer.email, &time)?,
+        None => Signature::now(&committer.name, &committer.email)?,
+    };
+    let author_signature = match author {
+        Some(author) => Signature::new(&author.name, &author.email, &signature.when())?,
+        None => signature.clone(),
+    };
+    let mut index = repo.index()?;
+    let tree_id = index.write_tree()?;
+    let tree = repo.find_tree(tree_id)?;
+
+    // Format commit message with description if provided
+    let full_message = if let Some(desc) = description {
+        format!("{}\n\n{}", message, desc)
+    } else {
+        message.to_string()
+    };
+
+    let parent_commit = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit()?),
+        Err(_) => None, // For initial commit
+    };
+    let parents: Vec<&Commit> = parent_commit.iter().collect();
+
+    let commit_id = if let Some(signer) = signer {
+        let buffer = repo.commit_create_buffer(
+            &author_signature,
+            &signature,
+            &full_message,
+            &tree,
+            &parents,
+        )?;
+        let buffer = buffer
+            .as_str()
+            .ok_or_else(|| GitError::from_str("commit buffer is not valid UTF-8"))?;
+        let commit_signature = signer.sign(buffer)?;
+        let commit_id = repo.commit_signed(buffer, &commit_signature, None)?;
+        // Unlike `commit`, `commit_signed` does not move HEAD
+        advance_head(repo, commit_id, message)?;
+        commit_id
+    } else {
+        repo.commit(
+            Some("HEAD"),
+            &author_signature,
+            &signature,
+            &full_message,
+            &tree,
+            &parents,
+        )?
+    };
+
+    info!(
+        "Created commit with id: {}\nMessage: {}\nDescription: {}",
+        commit_id,
+        message,
+        description.unwrap_or("None")
+    );
+    Ok(())
+}
+
+/// Commits the index to a branch other than the checked out one
+///
+/// HEAD and the working tree are left alone. The commit's parent is the tip
+/// of the branch, or HEAD if the branch does not exist yet, so the branch
+/// holds one snapshot of the index per commit.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `branch` - Name of the branch
+/// * `message` - The commit message
+/// * `fallback` - Identity used when the repository configures none (see `identity`)
+///
+/// # Errors
+/// Returns a `GitError` if no identity is available or the index, the
+/// parent or the branch cannot be read or written
+pub fn commit_to_branch(
+    repo: &Repository,
+    branch: &str,
+    message: &str,
+    fallback: Option<&CommitAuthor>,
+) -> Result<Oid, GitError> {
+    let committer = identity(repo, fallback)?;
+    let signature = Signature::now(&committer.name, &committer.email)?;
+    let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+    let ref_name = format!("refs/heads/{}", branch);
+    let parent = match repo.find_reference(&ref_name) {
+        Ok(reference) => Some(reference.peel_to_commit()?),
+        Err(_) => repo.head().and_then(|head| head.peel_to_commit()).ok(),
+    };
+    let parents: Vec<&Commit> = parent.iter().collect();
+
+    let commit_id = repo.commit(
+        Some(&ref_name),
+        &signature,
+        &signature,
+        message,
+        &tree,
+        &parents,
+    )?;
+    info!("Created commit {} on branch {}", commit_id, branch);
+    Ok(commit_id)
+}
+
+/// Resets the index entries of paths to HEAD, unstaging their changes
+///
+/// Changes staged for other paths (e.g. by the user) and the working tree are
+/// left alone.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `paths` - The paths, relative to the repository root
+///
+/// # Errors
+/// Returns a `GitError` if the index or HEAD cannot be read or written
+pub fn unstage_paths(repo: &Repository, paths: &[&str]) -> Result<(), GitError> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    // Without a commit yet, the paths are removed from the index
+    let head = repo.head().and_then(|head| head.peel_to_commit()).ok();
+    repo.reset_default(head.as_ref().map(|head| head.as_object()), paths)
+}
+
+/// Points HEAD (or the branch it refers to) at a new commit
+///
+/// # Errors
+/// Returns a `GitError` if the reference cannot be updated.
+fn advance_head(repo: &Repository, commit_id: Oid, message: &str) -> Result<(), GitError> {
+    let head = repo.find_reference("HEAD")?;
+    let log_message = format!("commit: {}", message);
+    match head.symbolic_target() {
+        // Also covers the unborn branch of an empty repository
+        Some(branch) => {
+            repo.reference(branch, commit_id, true, &log_message)?;
+        }
+        None => repo.set_head_detached(commit_id)?,
+    }
+    Ok(())
+}
+
+/// Resolves the URL of a remote the way `git` does
+///
+/// Applies the `url.<base>.insteadOf` rewrites (and `url.<base>.pushInsteadOf`
+/// when pushing) from the repository, global and system git configuration.
+/// The longest matching prefix wins.
+///
+/// # Arguments
+/// * `repo` - The repository the remote belongs to
+/// * `remote_name` - Name of the remote (e.g. "origin")
+/// * `direction` - Whether the URL is used for fetching or pushing
+///
+/// # Errors
+/// Returns a `GitError` if the configuration cannot be read or the remote has no URL
+pub fn remote_url(
+    repo: &Repository,
+    remote_name: &str,
+    direction: RemoteDirection,
+) -> Result<String, GitError> {
+    let config = repo.config()?.snapshot()?;
+    let url = config.get_string(&format!("remote.{}.url", remote_name))?;
+
+    if direction == RemoteDirection::Push {
+        // Explicit push URLs are only subject to `insteadOf`, like in git
+        if let Ok(push_url) = config.get_string(&format!("remote.{}.pushurl", remote_name)) {
+            return Ok(rewrite_url(&config, &push_url, "insteadof")?.unwrap_or(push_url));
+        }
+        if let Some(rewritten) = rewrite_url(&config, &url, "pushinsteadof")? {
+            return Ok(rewritten);
+        }
+    }
+    Ok(rewrite_url(&config, &url, "insteadof")?.unwrap_or(url))
+}
+
+/// Applies the longest matching `url.<base>.<key>` rewrite to a URL
+///
+/// # Returns
+/// `None` if no rewrite matches
+fn rewrite_url(config: &git2::Config, url: &str, key: &str) -> Result<Option<String>, GitError> {
+    let suffix = format!(".{}", key);
+    let mut best: Option<(usize, String)> = None;
+
+    config
+        .entries(Some(r"^url\..*insteadof$"))?
+        .for_each(|entry| {
+            let (Some(name), Some(prefix)) = (entry.name(), entry.value()) else {
+                return;
+            };
+            let Some(base) = name
+                .strip_prefix("url.")
+                .and_then(|name| name.strip_suffix(&suffix))
+            else {
+                return;
+            };
+            let longer = best.as_ref().is_none_or(|(len, _)| prefix.len() > *len);
+            if url.starts_with(prefix) && longer {
+                best = Some((prefix.len(), format!("{}{}", base, &url[prefix.len()..])));
+            }
+        })?;
+
+    if let Some((_, rewritten)) = &best {
+        trace!("Rewrote {} to {}", url, rewritten);
+    }
+    Ok(best.map(|(_, rewritten)| rewritten))
+}
+
+/// Lists the local branches and, optionally, tags of a repository
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `include_tags` - Whether tags are listed as well
+///
+/// # Returns
+/// The full ref names (e.g. `refs/heads/main`, `refs/tags/v1`), sorted
+///
+/// # Errors
+/// Returns a `GitError` if the refs cannot be read
+pub fn local_refs(repo: &Repository, include_tags: bool) -> Result<Vec<String>, GitError> {
+    let mut refs = Vec::new();
+    for reference in repo.references()? {
+        let reference = reference?;
+        let Some(name) = reference.name() else {
+            continue;
+        };
+        if reference.is_branch() || (include_tags && reference.is_tag()) {
+            refs.push(name.to_string());
+        }
+    }
+    refs.sort();
+    Ok(refs)
+}
+
+/// Checks whether a remote repository exists, like `git ls-remote`
+///
+/// # Parameters
+/// - `repo`: A reference to the local Git repository.
+/// - `git_username`: The username for authentication with the remote repository.
+/// - `git_password`: The password for authentication with the remote repository.
+/// - `remote_name`: The name of the remote repository (e.g., "origin").
+/// - `settings`: Network settings (e.g. proxy) for the connection.
+///
+/// # Returns
+/// - `Result<bool, GitError>`: `false` if the remote is not configured or the
+///   server reports the repository as missing.
+///
+/// # Errors
+/// Returns a `GitError` if the remote cannot be reached for other reasons.
+#[instrument(name = "ls_remote", skip(repo, git_username, git_password, settings))]
+pub fn remote_exists(
+    repo: &Repository,
+    git_username: &str,
+    git_password: &SecretString,
+    remote_name: &str,
+    settings: &RemoteSettings,
+) -> Result<bool, GitError> {
+    let url = match remote_url(repo, remote_name, RemoteDirection::Push) {
+        Ok(url) => url,
+        Err(err) if err.code() == git2::ErrorCode::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    // Local remotes are checked on disk, there is no server to ask
+    if let Some(path) = local_remote_path(&url) {
+        let exists = Repository::open_ext(
+            &path,
+            git2::RepositoryOpenFlags::NO_SEARCH,
+            std::iter::empty::<&OsStr>(),
+        )
+        .is_ok();
+        if !exists {
+            debug!("Remote {} does not exist", url);
+        }
+        return Ok(exists);
+    }
+
+    let mut remote = repo.remote_anonymous(&url)?;
+
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.credentials(|_url, _username_from_url, _allowed_types| {
+        git2::Cred::userpass_plaintext(git_username, git_password.expose_secret())
+    });
+    settings.certificate_check(&mut callbacks);
+    settings.watchdog(&mut callbacks);
+
+    let exists = match remote.connect_auth(
+        git2::Direction::Push,
+        Some(callbacks),
+        Some(settings.proxy_options()),
+    ) {
+        Ok(_) => Ok(true),
+        Err(err) if is_missing_repository(&err) => {
+            debug!("Remote {} does not exist: {}", url, err);
+            Ok(false)
+        }
+        Err(err) => Err(err),
+    };
+    exists
+}
+
+/// The path of a remote on the local file system
+///
+/// # Returns
+/// - `Option<PathBuf>`: The path of `file://` URLs and plain paths, `None`
+///   for network URLs (including scp-like `host:path`).
+fn local_remote_path(url: &str) -> Option<PathBuf> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return Some(PathBuf::from(path));
+    }
+    let path = Path::new(url);
+    if path.is_absolute() || url.starts_with("./") || url.starts_with("../") {
+        Some(path.to_path_buf())
+    } else {
+        None
+    }
+}
+
+/// Messages of servers and transports reporting a missing repository
+///
+/// Kept specific: a host or credential that is "not found" does not mean
+/// the repository is missing.
+const MISSING_REPOSITORY_MESSAGES: &[&str] = &[
+    "unexpected http status code: 404",
+    "repository not found",
+    "could not be found",
+    "could not find repository",
+    "does not appear to be a git repository",
+];
+
+/// Whether a connection error means the remote repository does not exist
+fn is_missing_repository(err: &GitError) -> bool {
+    let message = err.message().to_lowercase();
+    MISSING_REPOSITORY_MESSAGES
+        .iter()
+        .any(|missing| message.contains(missing))
+}
+
+/// Filters out push options git cannot transmit
+///
+/// Options are sent one per line of the protocol, so options with line
+/// breaks or NUL characters are skipped with a warning.
+fn valid_push_options(push_options: &[String]) -> Vec<&str> {
+    push_options
+        .iter()
+        .map(String::as_str)
+        .filter(|option| {
+            let valid = !option.is_empty() && !option.contains(['\n', '\r', '\0']);
+            if !valid {
+                warn!("Skipping invalid push option {:?}", option);
+            }
+            valid
+        })
+        .collect()
+}
+
+/// Push the given refspecs (branches, tags, ...) to the specified remote repository.
+///
+/// # Parameters
+/// - `repo`: A reference to the local Git repository.
+/// - `git_username`: The username for authentication with the remote repository.
+/// - `git_password`: The password for authentication with the remote repository.
+/// - `remote_name`: The name of the remote repository (e.g., "origin").
+/// - `refspecs`: The refspecs to push (e.g., "refs/heads/main", "refs/tags/v1").
+/// - `push_options`: Push options delivered to the remote (e.g. "ci.skip").
+/// - `settings`: Network settings (e.g. proxy) for the connection.
+///
+/// # Returns
+/// - `Result<(), GitError>`: Returns `Ok(())` on success, or an error of type `GitError` on failure.
+#[instrument(name = "push", skip(repo, git_username, git_password, settings))]
+pub fn push_refspecs(
+    repo: &Repository,
+    git_username: &str,
+    git_password: &SecretString,
+    remote_name: &str,
+    refspecs: &[String],
+    push_options: &[String],
+    settings: &RemoteSettings,
+) -> Result<(), GitError> {
+    // Find the specified remote repository
+    let remote = repo.find_remote(remote_name)?;
+    trace!("Found remote: {}", remote_name);
+
+    // The LFS objects must be on the remote before the pointers referencing them
+    push_lfs_objects(
+        repo,
+        (git_username, git_password),
+        remote_name,
+        refspecs,
+        settings,
+    )?;
+
+    // Push to the rewritten URL if libgit2 did not apply the rewrite itself
+    let url = remote_url(repo, remote_name, RemoteDirection::Push)?;
+    let mut remote = if remote.pushurl().or(remote.url()) == Some(url.as_str()) {
+        remote
+    } else {
+        debug!("Pushing to rewritten URL {}", url);
+        repo.remote_anonymous(&url)?
+    };
+
+    // libgit2 reports refs rejected by the remote only through the
+    // callbacks, so the list has to outlive them
+    let rejected = RefCell::new(Vec::new());
+
+    // Set up remote callbacks for authentication
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.credentials(|_url, username_from_url, _allowed_types| {
+        trace!("Using credentials for remote: {:#?}", username_from_url);
+        git2::Cred::userpass_plaintext(git_username, git_password.expose_secret())
+    });
+
+    settings.certificate_check(&mut callbacks);
+    settings.watchdog(&mut callbacks);
+
+    callbacks.push_update_reference(|refname, status| {
+        if let Some(status) = status {
+            rejected
+                .borrow_mut()
+                .push(format!("{}: {}", refname, status));
+        }
+        Ok(())
+    });
+
+    // Set up push options with the callbacks and proxy
+    let mut options = git2::PushOptions::new();
+    options.remote_callbacks(callbacks);
+    options.proxy_options(settings.proxy_options());
+    let push_options = valid_push_options(push_options);
+    if !push_options.is_empty() {
+        trace!("Using push options {:?}", push_options);
+        options.remote_push_options(&push_options);
+    }
+
+    // Attempt to push the refspecs to the remote
+    let started = Instant::now();
+    remote
+        .push(refspecs, Some(&mut options))
+        .map_err(|err| settings.timeout_error(err, started))?;
+    let rejected = rejected.take();
+    if !rejected.is_empty() {
+        return Err(GitError::new(
+            git2::ErrorCode::NotFastForward,
+            git2::ErrorClass::Reference,
+            rejected.join(", "),
+        ));
+    }
+    info!(
+        "Successfully pushed {:?} to remote '{}'",
+        refspecs, remote_name
+    );
+
+    Ok(())
+}
+
+/// Fetches a branch of a remote into its remote-tracking ref
+///
+/// # Arguments
+/// - `repo`: A reference to the local Git repository.
+/// - `git_username`: The username for authentication with the remote repository.
+/// - `git_password`: The password for authentication with the remote repository.
+/// - `remote_name`: The name of the remote repository (e.g., "origin").
+/// - `branch`: The branch to fetch.
+/// - `settings`: Network settings (e.g. proxy) for the connection.
+///
+/// # Returns
+/// The head of the branch on the remote, `None` if the remote has no such branch.
+///
+/// # Errors
+/// Returns a `GitError` if the remote cannot be reached.
+#[instrument(name = "fetch", skip(repo, git_username, git_password, settings))]
+pub fn fetch_branch(
+    repo: &Repository,
+    git_username: &str,
+    git_password: &SecretString,
+    remote_name: &str,
+    branch: &str,
+    settings: &RemoteSettings,
+) -> Result<Option<Oid>, GitError> {
+    let url = remote_url(repo, remote_name, RemoteDirection::Fetch)?;
+    let mut remote = repo.remote_anonymous(&url)?;
+
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.credentials(|_url, _username_from_url, _allowed_types| {
+        git2::Cred::userpass_plaintext(git_username, git_password.expose_secret())
+    });
+    settings.certificate_check(&mut callbacks);
+    settings.watchdog(&mut callbacks);
+
+    let mut options = git2::FetchOptions::new();
+    options.remote_callbacks(callbacks);
+    options.proxy_options(settings.proxy_options());
+
+    let tracking_ref = format!("refs/remotes/{}/{}", remote_name, branch);
+    let refspec = format!("+refs/heads/{}:{}", branch, tracking_ref);
+    let started = Instant::now();
+    match remote.fetch(&[refspec.as_str()], Some(&mut options), None) {
+        Ok(()) => {}
+        // Fetching a branch the remote does not have yet
+        Err(err) if err.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(err) => return Err(settings.timeout_error(err, started)),
+    }
+    trace!("Fetched {} from remote '{}'", branch, remote_name);
+
+    Ok(repo
+        .find_reference(&tracking_ref)
+        .ok()
+        .and_then(|reference| reference.target()))
+}
+
+/// Counts the commits a local branch and another commit have that the other lacks
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `branch` - The local branch
+/// * `upstream` - The commit to compare with, e.g. the branch head on the remote
+///
+/// # Returns
+/// `(ahead, behind)`: the number of commits only on the branch and only on `upstream`.
+///
+/// # Errors
+/// Returns a `GitError` if the branch or the commit cannot be found.
+pub fn ahead_behind(
+    repo: &Repository,
+    branch: &str,
+    upstream: Oid,
+) -> Result<(usize, usize), GitError> {
+    let local = repo
+        .find_branch(branch, BranchType::Local)?
+        .get()
+        .peel_to_commit()?
+        .id();
+    repo.graph_ahead_behind(local, upstream)
+}
+
+/// Finds the commits of a branch that were never pushed to `origin`
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `branch` - The local branch
+///
+/// # Returns
+/// The number of commits missing on `origin/<branch>` and the time the oldest
+/// of them was made, or `None` if all are pushed or the branch was never pushed.
+///
+/// # Errors
+/// Returns a `GitError` if the history cannot be walked.
+pub fn unpushed_commits(
+    repo: &Repository,
+    branch: &str,
+) -> Result<Option<(u64, SystemTime)>, GitError> {
+    let Ok(pushed) = repo.refname_to_id(&format!("refs/remotes/origin/{}", branch)) else {
+        return Ok(None);
+    };
+    let mut walk = repo.revwalk()?;
+    walk.push(repo.refname_to_id(&format!("refs/heads/{}", branch))?)?;
+    walk.hide(pushed)?;
+    let mut unpushed = 0;
+    let mut oldest = i64::MAX;
+    for id in walk {
+        unpushed += 1;
+        oldest = oldest.min(repo.find_commit(id?)?.time().seconds());
+    }
+    Ok((unpushed > 0).then(|| {
+        (
+            unpushed,
+            UNIX_EPOCH + Duration::from_secs(oldest.max(0) as u64),
+        )
+    }))
+}
+
+/// Reads the time of the latest push of a branch from the reflog of its
+/// remote-tracking ref
+///
+/// # Returns
+/// The time of the latest push, `None` if the reflog records none.
+pub fn last_push_time(repo: &Repository, branch: &str) -> Option<SystemTime> {
+    let reflog = repo
+        .reflog(&format!("refs/remotes/origin/{}", branch))
+        .ok()?;
+    let seconds = reflog
+        .iter()
+        .find(|entry| {
+            entry
+                .message()
+                .is_some_and(|message| message.starts_with("update by push"))
+        })?
+        .committer()
+        .when()
+        .seconds();
+    Some(UNIX_EPOCH + Duration::from_secs(seconds.max(0) as u64))
+}
+
+/// Counts the commits between the checked out branch and its upstream
+///
+/// The upstream is the branch's configured upstream, `origin/<branch>`
+/// otherwise. Only the remote-tracking ref is read, so the counts are as
+/// recent as the last fetch or push.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+///
+/// # Returns
+/// The short name of the upstream (e.g. `origin/main`) and the number of
+/// commits only on the branch and only on the upstream, or `None` if `HEAD` is
+/// detached or the branch has no upstream.
+pub fn upstream_ahead_behind(repo: &Repository) -> Option<(String, usize, usize)> {
+    let head = repo.head().ok().filter(|head| head.is_branch())?;
+    let branch_name = head.shorthand()?.to_string();
+    let upstream = repo
+        .find_branch(&branch_name, BranchType::Local)
+        .and_then(|branch| branch.upstream())
+        .map(|upstream| upstream.into_reference())
+        .or_else(|_| repo.find_reference(&format!("refs/remotes/origin/{}", branch_name)))
+        .ok()?;
+    let (ahead, behind) = repo
+        .graph_ahead_behind(head.target()?, upstream.target()?)
+        .ok()?;
+    Some((upstream.shorthand()?.to_string(), ahead, behind))
+}
+
+/// Fast-forwards the checked out branch to a descendant commit
+///
+/// The working tree is updated with a safe checkout: uncommitted changes to
+/// files the new commits do not touch are kept, a conflicting change fails the
+/// fast-forward before anything is written.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `branch` - The branch to fast-forward, which must be checked out
+/// * `target` - The commit to move the branch to
+///
+/// # Errors
+/// Returns a `GitError` if the branch is not checked out, `target` does not
+/// descend from the branch head or the checkout conflicts with local changes.
+pub fn fast_forward(repo: &Repository, branch: &str, target: Oid) -> Result<(), GitError> {
+    if get_current_branch(repo)? != branch {
+        return Err(GitError::from_str(&format!(
+            "Cannot fast-forward {} as it is not checked out",
+            branch
+        )));
+    }
+    let mut reference = repo.find_reference(&format!("refs/heads/{}", branch))?;
+    let head = reference.peel_to_commit()?.id();
+    if !repo.graph_descendant_of(target, head)? {
+        return Err(GitError::from_str(&format!(
+            "Cannot fast-forward {} to {}, the branches diverged",
+            branch, target
+        )));
+    }
+
+    let commit = repo.find_commit(target)?;
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    reference.set_target(target, &format!("fast-forward {} to {}", branch, target))?;
+    Ok(())
+}
+
+/// Verifies the signatures of the commits another commit adds to a branch
+///
+/// Runs `git verify-commit` for every commit reachable from `incoming` but not
+/// from `local`.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `local` - Head of the local branch
+/// * `incoming` - Head of the commits to verify, e.g. the remote branch head
+/// * `keyring` - GnuPG home directory with the trusted keys, the default keyring if `None`
+/// * `allowed_signers` - File listing the trusted signers of SSH signatures
+///
+/// # Returns
+/// The number of verified commits.
+///
+/// # Errors
+/// Returns a `GitError` naming the first commit without a valid signature, or
+/// if git cannot be run.
+pub fn verify_commits(
+    repo: &Repository,
+    local: Oid,
+    incoming: Oid,
+    keyring: Option<&Path>,
+    allowed_signers: Option<&Path>,
+) -> Result<usize, GitError> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(incoming)?;
+    revwalk.hide(local)?;
+
+    let mut verified = 0;
+    for id in revwalk {
+        let id = id?.to_string();
+        let mut command = Command::new("git");
+        command.arg("--git-dir").arg(repo.path());
+        if let Some(allowed_signers) = allowed_signers {
+            command.arg("-c").arg(format!(
+                "gpg.ssh.allowedSignersFile={}",
+                allowed_signers.display()
+            ));
+        }
+        if let Some(keyring) = keyring {
+            command.env("GNUPGHOME", keyring);
+        }
+        let output = command.args(["verify-commit", &id]).output().map_err(|e| {
+            GitError::from_str(&format!("Failed to execute git verify-commit: {}", e))
+        })?;
+        if !output.status.success() {
+            return Err(GitError::from_str(&format!(
+                "Commit {} has no valid signature: {}",
+                id,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        trace!("Verified the signature of {}", id);
+        verified += 1;
+    }
+    Ok(verified)
+}
+
+/// Rebases the checked out branch onto another ref with `git rebase`
+///
+/// Uncommitted changes are stashed during the rebase. A failed rebase (e.g.
+/// because of conflicts) is aborted, leaving the branch as it was.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `branch` - The branch to rebase, which must be checked out
+/// * `upstream` - The ref to rebase onto, e.g. `refs/remotes/origin/main`
+/// * `fallback` - Committer identity used when the repository configures none
+///
+/// # Errors
+/// Returns a `GitError` if the branch is not checked out, no identity is
+/// available or the rebase fails.
+pub fn rebase_onto(
+    repo: &Repository,
+    branch: &str,
+    upstream: &str,
+    fallback: Option<&CommitAuthor>,
+) -> Result<(), GitError> {
+    let head = repo.head()?;
+    if head.shorthand() != Some(branch) {
+        return Err(GitError::from_str(&format!(
+            "Cannot rebase {} as it is not checked out",
+            branch
+        )));
+    }
+    let committer = identity(repo, fallback)?;
+    let result = run_git(
+        repo,
+        &[
+            "-c",
+            &format!("user.name={}", committer.name),
+            "-c",
+            &format!("user.email={}", committer.email),
+            "rebase",
+            "--autostash",
+            "--quiet",
+            upstream,
+        ],
+    );
+    if result.is_err() {
+        if let Err(err) = run_git(repo, &["rebase", "--abort"]) {
+            warn!("Failed to abort the rebase of {}: {}", branch, err);
+        }
+    }
+    result
+}
+
+/// Returns the size of the repository's object database
+///
+/// # Arguments
+/// * `repo` - The repository to measure
+///
+/// # Returns
+/// The combined size in bytes of all loose objects and packs
+///
+/// # Errors
+/// Returns an `io::Error` if the object database cannot be read
+pub fn object_store_size(repo: &Repository) -> std::io::Result<u64> {
+    let mut size = 0;
+    let mut pending = vec![repo.path().join("objects")];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                size += metadata.len();
+            }
+        }
+    }
+    Ok(size)
+}
+
+/// Whether a file is stored with Git LFS (`filter=lfs` in `.gitattributes`)
+///
+/// libgit2 does not run the LFS filter: staging such a file with libgit2
+/// would commit its full content instead of the LFS pointer.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `path` - The file, relative to the repository root
+pub fn is_lfs_tracked(repo: &Repository, path: &Path) -> bool {
+    repo.get_attr(path, "filter", git2::AttrCheckFlags::FILE_THEN_INDEX)
+        .ok()
+        .flatten()
+        == Some("lfs")
+}
+
+/// Stages a file through the git CLI, which runs the clean filters (e.g. of
+/// Git LFS) libgit2 skips
+///
+/// # Errors
+/// Returns a `GitError` if git cannot be run or staging fails
+fn stage_through_filters(repo: &Repository, path: &Path) -> Result<(), GitError> {
+    run_git(
+        repo,
+        &[OsStr::new("add"), OsStr::new("--"), path.as_os_str()],
+    )?;
+    // The index was written by git, reload it for the commit
+    repo.index()?.read(true)?;
+    info!("Staged file through its filters: {}", path.display());
+    Ok(())
+}
+
+/// Tracks a single file with Git LFS
+///
+/// `git lfs track --filename` adds the exact path (not a pattern matching
+/// other files) to `.gitattributes`, which is staged along. The file itself
+/// is then staged by `stage_file`, which runs the LFS filter for it.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `path` - The file, relative to the repository root
+///
+/// # Errors
+/// Returns a `GitError` if git or git-lfs cannot be run or staging fails
+pub fn track_with_lfs(repo: &Repository, path: &str) -> Result<(), GitError> {
+    run_git(repo, &["lfs", "track", "--filename", path])?;
+    stage_through_filters(repo, Path::new(".gitattributes"))
+}
+
+/// Uploads the Git LFS objects referenced by pushed refs to a remote
+///
+/// libgit2 runs no hooks, so the `pre-push` hook of Git LFS never uploads the
+/// objects of a push; the remote would receive pointers to objects it does not
+/// have. Repositories without local LFS objects are skipped.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `credentials` - Username and password for the remote
+/// * `remote_name` - Name of the remote
+/// * `refspecs` - The refspecs about to be pushed
+/// * `settings` - Network settings for the upload
+///
+/// # Errors
+/// Returns a `GitError` if git-lfs cannot be run or the upload fails
+pub fn push_lfs_objects(
+    repo: &Repository,
+    credentials: (&str, &SecretString),
+    remote_name: &str,
+    refspecs: &[String],
+    settings: &RemoteSettings,
+) -> Result<(), GitError> {
+    if !repo.path().join("lfs").join("objects").is_dir() {
+        return Ok(());
+    }
+    // The source of every refspec, deletions (`:refs/heads/x`) have none
+    let refs: Vec<&str> = refspecs
+        .iter()
+        .filter_map(|refspec| refspec.trim_start_matches('+').split(':').next())
+        .filter(|source| !source.is_empty())
+        .collect();
+    if refs.is_empty() {
+        return Ok(());
+    }
+    let mut args = vec!["lfs", "push", remote_name];
+    args.extend(refs);
+    run_git_remote(repo, &args, settings, Some(credentials))?;
+    debug!(
+        "Uploaded the LFS objects of {:?} to {}",
+        refspecs, remote_name
+    );
+    Ok(())
+}
+
+/// Runs `git gc` on a repository
+///
+/// libgit2 has no garbage collection, so the git CLI is used.
+///
+/// # Errors
+/// Returns a `GitError` if git cannot be run or the collection fails
+pub fn gc(repo: &Repository) -> Result<(), GitError> {
+    run_git(repo, &["gc", "--quiet"])
+}
+
+/// Runs `git prune` on a repository, deleting unreachable loose objects
+///
+/// Only objects older than two weeks (git's `gc.pruneExpire` default) are
+/// deleted: a concurrent commit writes its objects before the ref pointing
+/// at them, pruning those fresh objects would corrupt the commit.
+///
+/// # Errors
+/// Returns a `GitError` if git cannot be run or pruning fails
+pub fn prune(repo: &Repository) -> Result<(), GitError> {
+    run_git(repo, &["prune", "--expire", "2.weeks.ago"])
+}
+
+/// Fetches a remote and deletes remote-tracking refs that no longer exist on it
+///
+/// Uses the git CLI, so the user's credential helpers apply.
+///
+/// # Errors
+/// Returns a `GitError` if git cannot be run or the fetch fails
+pub fn fetch_prune(
+    repo: &Repository,
+    remote_name: &str,
+    settings: &RemoteSettings,
+) -> Result<(), GitError> {
+    run_git_remote(
+        repo,
+        &["fetch", "--prune", "--quiet", remote_name],
+        settings,
+        None,
+    )
+}
+
+/// A branch found stale by `stale_branches`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StaleBranch {
+    /// Name of the branch, e.g. `autopilot/2024-06-01`
+    pub name: String,
+
+    /// Tip of the local branch to delete, `None` to keep it (or if there is none)
+    pub local: Option<Oid>,
+
+    /// Tip of the remote branch to delete as of the last fetch, `None` to
+    /// keep it (or if there is none)
+    pub remote: Option<Oid>,
+}
+
+/// Finds the branches below a prefix that are merged or expired
+///
+/// Local branches and branches of the remote (as of the last fetch) are
+/// considered. A branch is merged if a branch outside the prefix merged it
+/// (see `is_merged`) and expired if its tip commit is older than `max_age`.
+/// The checked out branch, the side branches of diverged pushes
+/// (`<prefix>/diverged/*`) and the quarantine branch (`<prefix>/quarantine`)
+/// are never stale. A remote branch whose tip differs from the local one may
+/// hold commits pushed from elsewhere and is kept.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `remote_name` - Name of the remote whose branches are considered
+/// * `prefix` - Prefix of the branches, without the trailing `/`
+/// * `max_age` - Age of the tip commit after which a branch expires
+/// * `now` - The current time
+///
+/// # Returns
+/// The stale branches, sorted by name
+///
+/// # Errors
+/// Returns a `GitError` if the branches cannot be read
+pub fn stale_branches(
+    repo: &Repository,
+    remote_name: &str,
+    prefix: &str,
+    max_age: Duration,
+    now: SystemTime,
+) -> Result<Vec<StaleBranch>, GitError> {
+    let remote_prefix = format!("{}/", remote_name);
+    let branch_prefix = format!("{}/", prefix);
+    let diverged_prefix = format!("{}/diverged/", prefix);
+    let quarantine = format!("{}/quarantine", prefix);
+    let checked_out = get_current_branch(repo).ok();
+
+    let mut locals: BTreeMap<String, Oid> = BTreeMap::new();
+    let mut remotes: BTreeMap<String, Oid> = BTreeMap::new();
+    let mut others = Vec::new();
+    for branch in repo.branches(None)? {
+        let (branch, branch_type) = branch?;
+        let (Some(name), Some(tip)) = (branch.name()?, branch.get().target()) else {
+            continue;
+        };
+        let name = match branch_type {
+            BranchType::Local => name,
+            BranchType::Remote => match name.strip_prefix(&remote_prefix) {
+                Some(name) => name,
+                None => continue,
+            },
+        };
+        if !name.starts_with(&branch_prefix) {
+            others.push(tip);
+        } else if checked_out.as_deref() != Some(name)
+            && !name.starts_with(&diverged_prefix)
+            && name != quarantine
+        {
+            match branch_type {
+                BranchType::Local => locals.insert(name.to_string(), tip),
+                BranchType::Remote => remotes.insert(name.to_string(), tip),
+            };
+        }
+    }
+
+    let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+    let max_age = i64::try_from(max_age.as_secs()).unwrap_or(i64::MAX);
+    let is_stale = |name: &str, tip: Oid| -> Result<bool, GitError> {
+        let age = now.saturating_sub(repo.find_commit(tip)?.time().seconds());
+        let expired = age > max_age;
+        let mut merged = false;
+        for &other in &others {
+            if is_merged(repo, other, tip)? {
+                merged = true;
+                break;
+            }
+        }
+        if expired || merged {
+            debug!(
+                "Branch {} at {} is stale (merged: {}, expired: {})",
+                name, tip, merged, expired
+            );
+        }
+        Ok(expired || merged)
+    };
+
+    let names: BTreeSet<&String> = locals.keys().chain(remotes.keys()).collect();
+    let mut stale = Vec::new();
+    for name in names {
+        let local_tip = locals.get(name).copied();
+        let remote_tip = remotes.get(name).copied();
+        let local = match local_tip {
+            Some(tip) if is_stale(name, tip)? => Some(tip),
+            _ => None,
+        };
+        let remote = match remote_tip {
+            Some(tip) if local_tip.is_none_or(|local_tip| local_tip == tip) => {
+                if local == Some(tip) || is_stale(name, tip)? {
+                    Some(tip)
+                } else {
+                    None
+                }
+            }
+            Some(tip) => {
+                debug!(
+                    "Keeping {}/{}, its tip {} differs from the local branch",
+                    remote_name, name, tip
+                );
+                None
+            }
+            None => None,
+        };
+        if local.is_some() || remote.is_some() {
+            stale.push(StaleBranch {
+                name: name.clone(),
+                local,
+                remote,
+            });
+        }
+    }
+    Ok(stale)
+}
+
+/// Whether a branch at `tip` was merged into the branch at `other`
+///
+/// The tip must be reachable from `other` without being on its first-parent
+/// line: a branch created at a commit of `other` has no commits of its own
+/// that could have been merged. Fast-forward merges therefore do not count;
+/// such branches are deleted once they expire.
+fn is_merged(repo: &Repository, other: Oid, tip: Oid) -> Result<bool, GitError> {
+    if other == tip || !repo.graph_descendant_of(other, tip)? {
+        return Ok(false);
+    }
+    // Walks the first-parent line of `other` down to the parents of `tip`
+    let mut walk = repo.revwalk()?;
+    walk.simplify_first_parent()?;
+    walk.push(other)?;
+    for parent in repo.find_commit(tip)?.parent_ids() {
+        walk.hide(parent)?;
+    }
+    for commit in walk {
+        if commit? == tip {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Deletes stale branches locally and on a remote
+///
+/// A local branch is only deleted if it still points at the tip found
+/// stale. The remote branches are deleted through the git CLI with a lease on
+/// the tip found stale, so commits pushed since the last fetch are never
+/// lost.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `remote_name` - Name of the remote, `None` to delete local branches only
+/// * `branches` - The branches, as found by `stale_branches`
+/// * `settings` - Network settings for the remote deletion
+/// * `credentials` - Username and password for the remote, the user's
+///   credential helpers apply without
+///
+/// # Errors
+/// Returns a `GitError` if a branch cannot be deleted
+pub fn delete_branches(
+    repo: &Repository,
+    remote_name: Option<&str>,
+    branches: &[StaleBranch],
+    settings: &RemoteSettings,
+    credentials: Option<(&str, &SecretString)>,
+) -> Result<(), GitError> {
+    for branch in branches {
+        let Some(tip) = branch.local else {
+            continue;
+        };
+        if let Ok(mut local) = repo.find_branch(&branch.name, BranchType::Local) {
+            if local.get().target() == Some(tip) {
+                local.delete()?;
+                info!("Deleted branch {}", branch.name);
+            }
+        }
+    }
+    let Some(remote_name) = remote_name else {
+        return Ok(());
+    };
+    let leases: Vec<String> = branches
+        .iter()
+        .filter_map(|branch| {
+            branch
+                .remote
+                .map(|tip| format!("--force-with-lease=refs/heads/{}:{}", branch.name, tip))
+        })
+        .collect();
+    if leases.is_empty() {
+        return Ok(());
+    }
+    let mut args = vec!["push", "--quiet", "--delete"];
+    args.extend(leases.iter().map(String::as_str));
+    args.push(remote_name);
+    args.extend(
+        branches
+            .iter()
+            .filter(|branch| branch.remote.is_some())
+            .map(|branch| branch.name.as_str()),
+    );
+    run_git_remote(repo, &args, settings, credentials)?;
+    info!("Deleted {} branches on {}", leases.len(), remote_name);
+    Ok(())
+}
+
+/// Writes a `git bundle` with all refs of a repository
+///
+/// The bundle is written to a `.bundle.partial` file first and renamed into
+/// place, so an interrupted run never leaves a truncated bundle behind; the
+/// partial file of a failed run is removed.
+///
+/// # Errors
+/// Returns a `GitError` if the repository has no commits, git cannot be run or
+/// the bundle cannot be written
+pub fn create_bundle(repo: &Repository, path: &Path) -> Result<(), GitError> {
+    let partial = path.with_extension("bundle.partial");
+    if let Err(err) = run_git(
+        repo,
+        &[
+            OsStr::new("bundle"),
+            OsStr::new("create"),
+            partial.as_os_str(),
+            OsStr::new("--all"),
+        ],
+    ) {
+        let _ = std::fs::remove_file(&partial);
+        return Err(err);
+    }
+    std::fs::rename(&partial, path).map_err(|e| {
+        GitError::from_str(&format!(
+            "Failed to move bundle to {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// Computes a fingerprint of all refs and their targets
+///
+/// Two fingerprints are equal if no ref was created, deleted or moved in between.
+///
+/// # Errors
+/// Returns a `GitError` if the refs cannot be read
+pub fn refs_fingerprint(repo: &Repository) -> Result<u64, GitError> {
+    let mut refs = BTreeMap::new();
+    for reference in repo.references()? {
+        let reference = reference?;
+        refs.insert(
+            String::from_utf8_lossy(reference.name_bytes()).to_string(),
+            reference.target(),
+        );
+    }
+    let mut hasher = DefaultHasher::new();
+    refs.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// Builds a git CLI command running in a repository
+fn git_command(repo: &Repository) -> Command {
+    let mut command = Command::new("git");
+    command.arg("--git-dir").arg(repo.path());
+    if let Some(workdir) = repo.workdir() {
+        command.current_dir(workdir);
+    }
+    command
+}
+
+/// Runs a git CLI command in a repository
+fn run_git<S: AsRef<OsStr>>(repo: &Repository, args: &[S]) -> Result<(), GitError> {
+    run_git_command(git_command(repo), args)
+}
+
+/// Credential helper answering the credential requests of git CLI commands
+/// (and Git LFS) from the environment, so the password never shows up in the
+/// arguments of a process
+const CLI_CREDENTIAL_HELPER: &str = r#"credential.helper=!f() { test "$1" = get && printf 'username=%s\npassword=%s\n' "$GIT_AUTO_PILOT_USERNAME" "$GIT_AUTO_PILOT_PASSWORD"; }; f"#;
+
+/// Runs a git CLI command reaching a remote
+///
+/// The network settings are applied and git never prompts for credentials,
+/// which would block the daemon with nobody to answer. Without `credentials`
+/// (or with an empty username) the user's credential helpers apply.
+fn run_git_remote<S: AsRef<OsStr>>(
+    repo: &Repository,
+    args: &[S],
+    settings: &RemoteSettings,
+    credentials: Option<(&str, &SecretString)>,
+) -> Result<(), GitError> {
+    let mut command = git_command(repo);
+    command.env("GIT_TERMINAL_PROMPT", "0");
+    for config in settings.cli_config() {
+        command.arg("-c").arg(config);
+    }
+    if let Some((username, password)) = credentials.filter(|(username, _)| !username.is_empty()) {
+        command
+            .env("GIT_AUTO_PILOT_USERNAME", username)
+            .env("GIT_AUTO_PILOT_PASSWORD", password.expose_secret())
+            // An empty helper resets the configured ones, so the configured
+            // credentials are used like for libgit2 operations
+            .args(["-c", "credential.helper=", "-c", CLI_CREDENTIAL_HELPER]);
+    }
+    run_git_command(command, args)
+}
+
+/// Runs a prepared git CLI command with its subcommand and arguments
+fn run_git_command<S: AsRef<OsStr>>(mut command: Command, args: &[S]) -> Result<(), GitError> {
+    let args: Vec<&OsStr> = args.iter().map(AsRef::as_ref).collect();
+    trace!("Running git {:?}", args);
+    let output = command.args(&args).output().map_err(|e| {
+        GitError::from_str(&format!(
+            "Failed to execute git {}: {}",
+            args[0].to_string_lossy(),
+            e
+        ))
+    })?;
+
+    if !output.status.success() {
+        return Err(GitError::from_str(&format!(
+            "Git {} failed: {}",
+            args[0].to_string_lossy(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Creates an annotated tag pointing at the current HEAD commit.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `name` - Name of the tag
+/// * `message` - Message of the annotated tag
+/// * `fallback` - Tagger identity used when the repository configures none
+///
+/// # Errors
+/// Returns a `GitError` if no tagger identity is available, HEAD cannot be
+/// resolved, the tag already exists, or the tag cannot be written.
+#[instrument(name = "tag", skip(repo, message, fallback))]
+pub fn create_tag(
+    repo: &Repository,
+    name: &str,
+    message: &str,
+    fallback: Option<&CommitAuthor>,
+) -> Result<(), GitError> {
+    let tagger = identity(repo, fallback)?;
+    let signature = Signature::now(&tagger.name, &tagger.email)?;
+    let head = repo.head()?.peel(ObjectType::Commit)?;
+
+    let tag_id = repo.tag(name, &head, &signature, message, false)?;
+    info!("Created tag '{}' with id: {}", name, tag_id);
+    Ok(())
+}
+
+/// Notes ref holding the autopilot metadata of commits
+pub const NOTES_REF: &str = "refs/notes/git-auto-pilot";
+
+/// Attaches a note to the current HEAD commit in `NOTES_REF`.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `note` - Content of the note
+/// * `fallback` - Identity used when the repository configures none
+///
+/// # Errors
+/// Returns a `GitError` if no identity is available, HEAD cannot be resolved
+/// or the note cannot be written.
+#[instrument(name = "note", skip_all)]
+pub fn add_note(
+    repo: &Repository,
+    note: &str,
+    fallback: Option<&CommitAuthor>,
+) -> Result<(), GitError> {
+    let author = identity(repo, fallback)?;
+    let signature = Signature::now(&author.name, &author.email)?;
+    let head = repo.head()?.peel_to_commit()?;
+
+    let note_id = repo.note(
+        &signature,
+        &signature,
+        Some(NOTES_REF),
+        head.id(),
+        note,
+        true,
+    )?;
+    debug!("Attached note {} to {}", note_id, head.id());
+    Ok(())
+}
+
+/// Pushes `NOTES_REF` to a remote, merging the notes pushed from other
+/// clones first
+///
+/// The notes of the remote are fetched to `refs/notes/remotes/<remote>/…` and
+/// merged with the `cat_sort_uniq` strategy, which concatenates the notes of
+/// a commit annotated on both sides, so the push never discards notes.
+///
+/// # Arguments
+/// * `repo` - Reference to the git Repository
+/// * `remote_name` - Name of the remote
+/// * `settings` - Network settings for the connection
+/// * `credentials` - Username and password, the user's credential helpers
+///   apply without them
+/// * `fallback` - Identity of the merge when the repository configures none
+///
+/// # Errors
+/// Returns a `GitError` if the notes cannot be fetched, merged or pushed
+#[instrument(name = "push_notes", skip(repo, settings, credentials, fallback))]
+pub fn push_notes(
+    repo: &Repository,
+    remote_name: &str,
+    settings: &RemoteSettings,
+    credentials: Option<(&str, &SecretString)>,
+    fallback: Option<&CommitAuthor>,
+) -> Result<(), GitError> {
+    let remote_notes = NOTES_REF.replacen(
+        "refs/notes/",
+        &format!("refs/notes/remotes/{}/", remote_name),
+        1,
+    );
+    let fetched = run_git_remote(
+        repo,
+        &[
+            "fetch",
+            "--quiet",
+            "--no-tags",
+            remote_name,
+            &format!("+{}:{}", NOTES_REF, remote_notes),
+        ],
+        settings,
+        credentials,
+    );
+    match fetched {
+        Ok(()) => {
+            let author = identity(repo, fallback)?;
+            let mut command = git_command(repo);
+            command
+                .env("GIT_AUTHOR_NAME", &author.name)
+                .env("GIT_AUTHOR_EMAIL", &author.email)
+                .env("GIT_COMMITTER_NAME", &author.name)
+                .env("GIT_COMMITTER_EMAIL", &author.email);
+            run_git_command(
+                command,
+                &[
+                    "notes",
+                    "--ref",
+                    NOTES_REF,
+                    "merge",
+                    "--quiet",
+                    "--strategy=cat_sort_uniq",
+                    &remote_notes,
+                ],
+            )?;
+        }
+        // The first push of the notes
+        Err(err) if err.message().contains("couldn't find remote ref") => {}
+        Err(err) => return Err(err),
+    }
+    run_git_remote(
+        repo,
+        &[
+            "push",
+            "--quiet",
+            remote_name,
+            &format!("{}:{}", NOTES_REF, NOTES_REF),
+        ],
+        settings,
+        credentials,
+    )?;
+    info!("Pushed the notes to {}", remote_name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Creates a repository with the given files committed on the initial commit
+    fn init_repo_with_files(files: &[(&str, &str)]) -> (tempfile::TempDir, Repository) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        {
+            let mut config = repo.config().unwrap();
+            config.set_str("user.name", "Test").unwrap();
+            config.set_str("user.email", "test@example.com").unwrap();
+        }
+
+        for (name, content) in files {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, content).unwrap();
+            stage_file(&repo, name, false).unwrap();
+        }
+        commit(&repo, "initial", None, None, None, None, None).unwrap();
+
+        (dir, repo)
+    }
+
+    #[test]
+    fn test_detects_batch_renames() {
+        let (dir, repo) = init_repo_with_files(&[
+            ("src/a.txt", "first file\nwith some content\n"),
+            ("src/b.txt", "second file\nwith other content\n"),
+        ]);
+
+        fs::create_dir_all(dir.path().join("docs")).unwrap();
+        fs::rename(dir.path().join("src/a.txt"), dir.path().join("docs/a.txt")).unwrap();
+        fs::rename(dir.path().join("src/b.txt"), dir.path().join("docs/b.txt")).unwrap();
+
+        let changes = analyze_repository_changes(&repo, &[]).unwrap();
+        assert_eq!(changes.len(), 2);
+        for (new_path, old_path) in [("docs/a.txt", "src/a.txt"), ("docs/b.txt", "src/b.txt")] {
+            let stats = &changes[new_path][0];
+            assert_eq!(stats.status, Status::WT_RENAMED);
+            assert_eq!(stats.old_name.as_deref(), Some(old_path));
+        }
+    }
+
+    #[test]
+    fn test_detects_manual_git_operations() {
+        let (_dir, repo) = init_repo_with_files(&[("a.txt", "content\n")]);
+        assert_eq!(busy_marker(&repo), None);
+
+        fs::write(repo.path().join("MERGE_MSG"), "Merge branch 'feature'\n").unwrap();
+        assert_eq!(busy_marker(&repo), Some("MERGE_MSG"));
+
+        fs::remove_file(repo.path().join("MERGE_MSG")).unwrap();
+        fs::write(repo.path().join("index.lock"), "").unwrap();
+        assert_eq!(busy_marker(&repo), Some("index.lock"));
+        fs::remove_file(repo.path().join("index.lock")).unwrap();
+        assert_eq!(busy_marker(&repo), None);
+    }
+
+    #[test]
+    fn test_sparse_checkout_cone() {
+        let sparse = SparseCheckout {
+            cone: true,
+            patterns: ["/*", "!/*/", "/docs/", "/src/", "!/src/*/", "/src/core/"]
+                .map(String::from)
+                .to_vec(),
+        };
+
+        assert!(sparse.contains("README.md"));
+        assert!(sparse.contains("docs/guide/intro.md"));
+        assert!(sparse.contains("src/lib.rs"));
+        assert!(sparse.contains("src/core/engine.rs"));
+        assert!(!sparse.contains("src/cli/main.rs"));
+        assert!(!sparse.contains("tests/it.rs"));
+        assert!(!sparse.contains("docsite/index.md"));
+    }
+
+    #[test]
+    fn test_analysis_ignores_paths_outside_sparse_checkout() {
+        let (dir, repo) = init_repo_with_files(&[
+            ("README.md", "readme\n"),
+            ("docs/a.md", "docs\n"),
+            ("src/b.rs", "fn b() {}\n"),
+        ]);
+        {
+            let mut config = repo.config().unwrap();
+            config.set_bool("core.sparseCheckout", true).unwrap();
+            config.set_bool("core.sparseCheckoutCone", true).unwrap();
+        }
+        fs::create_dir_all(repo.path().join("info")).unwrap();
+        fs::write(
+            repo.path().join("info/sparse-checkout"),
+            "/*\n!/*/\n/docs/\n",
+        )
+        .unwrap();
+        // Files outside the cone are missing from the working tree
+        fs::remove_dir_all(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("docs/a.md"), "docs\nmore\n").unwrap();
+
+        let changes = analyze_repository_changes(&repo, &[]).unwrap();
+        assert!(changes.contains_key("docs/a.md"));
+        assert!(!changes.contains_key("src/b.rs"));
+    }
+
+    #[test]
+    fn test_detects_case_only_rename() {
+        let (dir, repo) = init_repo_with_files(&[("File.md", "notes\n")]);
+        fs::rename(dir.path().join("File.md"), dir.path().join("file.md")).unwrap();
+
+        // Case-insensitive file systems report no status change at all
+        let mut changes = HashMap::new();
+        detect_case_renames(&repo, &["file.md".to_string()], &mut changes).unwrap();
+        let stats = &changes["file.md"][0];
+        assert_eq!(stats.status, Status::WT_RENAMED);
+        assert_eq!(stats.old_name.as_deref(), Some("File.md"));
+
+        stage_rename(&repo, "File.md", "file.md").unwrap();
+        let index = repo.index().unwrap();
+        assert!(index.get_path(Path::new("file.md"), 0).is_some());
+        assert!(index.get_path(Path::new("File.md"), 0).is_none());
+    }
+
+    #[test]
+    fn test_detects_directory_rename() {
+        let (dir, repo) = init_repo_with_files(&[
+            ("src/a.txt", "first file\nwith some content\n"),
+            ("src/nested/b.txt", "second file\nwith other content\n"),
+            ("keep/c.txt", "third file\nstays in place\n"),
+        ]);
+
+        fs::rename(dir.path().join("src"), dir.path().join("lib")).unwrap();
+        fs::create_dir_all(dir.path().join("docs")).unwrap();
+        fs::rename(dir.path().join("keep/c.txt"), dir.path().join("docs/c.txt")).unwrap();
+
+        let changes = analyze_repository_changes(&repo, &[]).unwrap();
+        let renames = detect_directory_renames(&repo, &changes);
+
+        // `keep` still exists, so moving `c.txt` out of it is a plain file rename
+        assert_eq!(renames.len(), 1);
+        assert_eq!(renames[0].old_dir, "src");
+        assert_eq!(renames[0].new_dir, "lib");
+        assert_eq!(
+            renames[0].files,
+            vec![
+                ("src/a.txt".to_string(), "lib/a.txt".to_string()),
+                (
+                    "src/nested/b.txt".to_string(),
+                    "lib/nested/b.txt".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_detects_scaffold_in_new_directory() {
+        let (dir, repo) = init_repo_with_files(&[("src/main.rs", "fn main() {}\n")]);
+
+        for name in ["app/Cargo.toml", "app/src/main.rs", "app/.gitignore"] {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "generated\n").unwrap();
+        }
+        fs::write(dir.path().join("src/lib.rs"), "pub fn lib() {}\n").unwrap();
+        fs::write(dir.path().join("notes.txt"), "todo\n").unwrap();
+
+        let changes = analyze_repository_changes(&repo, &[]).unwrap();
+        let scaffolds = detect_scaffolds(&repo, &changes, 3).unwrap();
+
+        // `src` is already tracked and `notes.txt` lives in the root
+        assert_eq!(scaffolds.len(), 1);
+        assert_eq!(scaffolds[0].dir, "app");
+        assert_eq!(
+            scaffolds[0].files,
+            vec!["app/.gitignore", "app/Cargo.toml", "app/src/main.rs"]
+        );
+        assert!(detect_scaffolds(&repo, &changes, 4).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_unrelated_delete_and_create_are_not_renames() {
+        let (dir, repo) = init_repo_with_files(&[("old.txt", "completely different\n")]);
+
+        fs::remove_file(dir.path().join("old.txt")).unwrap();
+        fs::write(dir.path().join("new.txt"), "nothing in common here\n").unwrap();
+
+        let changes = analyze_repository_changes(&repo, &[]).unwrap();
+        assert_eq!(changes["old.txt"][0].status, Status::WT_DELETED);
+        assert_eq!(changes["new.txt"][0].status, Status::WT_NEW);
+    }
+
+    #[test]
+    fn test_analysis_scoped_to_event_paths() {
+        let (dir, repo) = init_repo_with_files(&[("a.txt", "a\n"), ("b.txt", "b\n")]);
+
+        fs::write(dir.path().join("a.txt"), "a\nmore\n").unwrap();
+        fs::write(dir.path().join("b.txt"), "b\nmore\nand more\n").unwrap();
+
+        let changes = analyze_repository_changes(&repo, &[dir.path().join("a.txt")]).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes["a.txt"][0].status, Status::WT_MODIFIED);
+        assert_eq!(changes["a.txt"][0].lines_added, 1);
+    }
+
+    #[test]
+    fn test_analysis_takes_event_paths_literally() {
+        let (dir, repo) = init_repo_with_files(&[("a.txt", "a\n"), ("[ab].txt", "ab\n")]);
+
+        fs::write(dir.path().join("a.txt"), "a\nmore\nand more\n").unwrap();
+        fs::write(dir.path().join("[ab].txt"), "ab\nmore\n").unwrap();
+
+        let changes = analyze_repository_changes(&repo, &[dir.path().join("[ab].txt")]).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes["[ab].txt"][0].lines_added, 1);
+    }
+
+    #[test]
+    fn test_remote_url_applies_instead_of_rewrites() {
+        let (_dir, repo) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        repo.remote("origin", "gh:myorg/notes.git").unwrap();
+        {
+            let mut config = repo.config().unwrap();
+            config
+                .set_str("url.https://github.com/.insteadOf", "gh:")
+                .unwrap();
+            config
+                .set_str("url.https://github.com/myorg/.insteadOf", "gh:myorg/")
+                .unwrap();
+            config
+                .set_str("url.git@github.com:.pushInsteadOf", "https://github.com/")
+                .unwrap();
+        }
+
+        assert_eq!(
+            remote_url(&repo, "origin", RemoteDirection::Fetch).unwrap(),
+            "https://github.com/myorg/notes.git"
+        );
+        // `pushInsteadOf` matches the configured URL, not the fetch rewrite
+        assert_eq!(
+            remote_url(&repo, "origin", RemoteDirection::Push).unwrap(),
+            "https://github.com/myorg/notes.git"
+        );
+
+        repo.remote_set_url("origin", "https://github.com/myorg/notes.git")
+            .unwrap();
+        assert_eq!(
+            remote_url(&repo, "origin", RemoteDirection::Push).unwrap(),
+            "git@github.com:myorg/notes.git"
+        );
+    }
+
+    #[test]
+    fn test_commit_signed_with_external_program() {
+        let (dir, repo) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        fs::write(dir.path().join("notes.md"), "todo\ndone\n").unwrap();
+        stage_file(&repo, "notes.md", false).unwrap();
+
+        let signer = CommitSigner {
+            program: "sh".to_string(),
+            args: vec![
+                "-c".to_string(),
+                "grep -q '^tree ' && printf 'FAKE SIGNATURE'".to_string(),
+            ],
+        };
+        commit(&repo, "signed", None, None, Some(&signer), None, None).unwrap();
+
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.summary(), Some("signed"));
+        assert_eq!(head.parent_count(), 1);
+        let (signature, _) = repo.extract_signature(&head.id(), None).unwrap();
+        assert_eq!(signature.as_str(), Some("FAKE SIGNATURE"));
+    }
+
+    #[test]
+    fn test_commit_with_mapped_author() {
+        let (dir, repo) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        fs::write(dir.path().join("notes.md"), "todo\ndone\n").unwrap();
+        stage_file(&repo, "notes.md", false).unwrap();
+
+        let author = CommitAuthor {
+            name: "Docs Bot".to_string(),
+            email: "docs-bot@example.com".to_string(),
+        };
+        commit(&repo, "update notes", None, None, None, Some(&author), None).unwrap();
+
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.author().name(), Some("Docs Bot"));
+        assert_eq!(head.author().email(), Some("docs-bot@example.com"));
+        assert_eq!(head.committer().name(), Some("Test"));
+    }
+
+    #[test]
+    fn test_repository_identity_overrides_fallback() {
+        let (_dir, repo) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        let fallback = CommitAuthor {
+            name: "Configured".to_string(),
+            email: "configured@example.com".to_string(),
+        };
+
+        let resolved = identity(&repo, Some(&fallback)).unwrap();
+        assert_eq!(resolved.name, "Test");
+        assert_eq!(resolved.email, "test@example.com");
+    }
+
+    #[test]
+    fn test_object_store_size_grows_with_commits() {
+        let (dir, repo) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        let before = object_store_size(&repo).unwrap();
+        assert!(before > 0);
+
+        fs::write(dir.path().join("data.bin"), vec![7u8; 64 * 1024]).unwrap();
+        stage_file(&repo, "data.bin", false).unwrap();
+        commit(&repo, "add data", None, None, None, None, None).unwrap();
+
+        assert!(object_store_size(&repo).unwrap() > before);
+    }
+
+    #[test]
+    fn test_lists_local_refs() {
+        let (_dir, repo) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.branch("feature", &head, false).unwrap();
+        repo.tag_lightweight("v1", head.as_object(), false).unwrap();
+        let current = format!("refs/heads/{}", get_current_branch(&repo).unwrap());
+
+        let branches = local_refs(&repo, false).unwrap();
+        assert!(branches.contains(&"refs/heads/feature".to_string()));
+        assert!(branches.contains(&current));
+        assert!(!branches.contains(&"refs/tags/v1".to_string()));
+
+        assert!(local_refs(&repo, true)
+            .unwrap()
+            .contains(&"refs/tags/v1".to_string()));
+    }
+
+    #[test]
+    fn test_create_bundle_with_all_refs() {
+        let (dir, repo) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        let fingerprint = refs_fingerprint(&repo).unwrap();
+        let backups = tempfile::tempdir().unwrap();
+        let bundle = backups.path().join("snapshot.bundle");
+
+        create_bundle(&repo, &bundle).unwrap();
+        assert!(bundle.exists());
+        assert!(!backups.path().join("snapshot.bundle.partial").exists());
+        assert_eq!(refs_fingerprint(&repo).unwrap(), fingerprint);
+
+        fs::write(dir.path().join("notes.md"), "todo\ndone\n").unwrap();
+        stage_file(&repo, "notes.md", false).unwrap();
+        commit(&repo, "update notes", None, None, None, None, None).unwrap();
+        assert_ne!(refs_fingerprint(&repo).unwrap(), fingerprint);
+    }
+
+    #[test]
+    fn test_switch_to_new_branch_keeps_working_tree_changes() {
+        let (dir, repo) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        fs::write(dir.path().join("notes.md"), "todo\ndone\n").unwrap();
+
+        switch_to_branch(&repo, "autopilot/2024-06-01").unwrap();
+
+        assert_eq!(get_current_branch(&repo).unwrap(), "autopilot/2024-06-01");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("notes.md")).unwrap(),
+            "todo\ndone\n"
+        );
+    }
+
+    #[test]
+    fn test_detects_diverged_remote_branch() {
+        let (remote_dir, remote) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        let local_dir = tempfile::tempdir().unwrap();
+        let local =
+            Repository::clone(remote_dir.path().to_str().unwrap(), local_dir.path()).unwrap();
+        {
+            let mut config = local.config().unwrap();
+            config.set_str("user.name", "Test").unwrap();
+            config.set_str("user.email", "test@example.com").unwrap();
+        }
+        let branch = get_current_branch(&local).unwrap();
+        let settings = RemoteSettings::default();
+
+        let head = fetch_branch(
+            &local,
+            "",
+            &SecretString::default(),
+            "origin",
+            &branch,
+            &settings,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(ahead_behind(&local, &branch, head).unwrap(), (0, 0));
+
+        fs::write(remote_dir.path().join("remote.md"), "remote\n").unwrap();
+        stage_file(&remote, "remote.md", false).unwrap();
+        commit(&remote, "remote change", None, None, None, None, None).unwrap();
+        fs::write(local_dir.path().join("local.md"), "local\n").unwrap();
+        stage_file(&local, "local.md", false).unwrap();
+        commit(&local, "local change", None, None, None, None, None).unwrap();
+
+        let head = fetch_branch(
+            &local,
+            "",
+            &SecretString::default(),
+            "origin",
+            &branch,
+            &settings,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(ahead_behind(&local, &branch, head).unwrap(), (1, 1));
+        assert_eq!(
+            upstream_ahead_behind(&local),
+            Some((format!("origin/{}", branch), 1, 1))
+        );
+        assert_eq!(
+            fetch_branch(
+                &local,
+                "",
+                &SecretString::default(),
+                "origin",
+                "missing",
+                &settings
+            )
+            .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_fast_forwards_to_remote_commits() {
+        let (remote_dir, remote) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        let local_dir = tempfile::tempdir().unwrap();
+        let local =
+            Repository::clone(remote_dir.path().to_str().unwrap(), local_dir.path()).unwrap();
+        let branch = get_current_branch(&local).unwrap();
+
+        fs::write(remote_dir.path().join("remote.md"), "remote\n").unwrap();
+        stage_file(&remote, "remote.md", false).unwrap();
+        commit(&remote, "remote change", None, None, None, None, None).unwrap();
+        let remote_head = remote.head().unwrap().target();
+        fs::write(local_dir.path().join("notes.md"), "todo\ndone\n").unwrap();
+
+        let head = fetch_branch(
+            &local,
+            "",
+            &SecretString::default(),
+            "origin",
+            &branch,
+            &RemoteSettings::default(),
+        )
+        .unwrap()
+        .unwrap();
+        fast_forward(&local, &branch, head).unwrap();
+
+        assert_eq!(local.head().unwrap().target(), remote_head);
+        assert!(local_dir.path().join("remote.md").exists());
+        assert_eq!(
+            fs::read_to_string(local_dir.path().join("notes.md")).unwrap(),
+            "todo\ndone\n"
+        );
+    }
+
+    #[test]
+    fn test_rejects_unsigned_incoming_commits() {
+        let (remote_dir, remote) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        let local_dir = tempfile::tempdir().unwrap();
+        let local =
+            Repository::clone(remote_dir.path().to_str().unwrap(), local_dir.path()).unwrap();
+        let local_head = local.head().unwrap().target().unwrap();
+        assert_eq!(
+            verify_commits(&local, local_head, local_head, None, None).unwrap(),
+            0
+        );
+
+        fs::write(remote_dir.path().join("remote.md"), "remote\n").unwrap();
+        stage_file(&remote, "remote.md", false).unwrap();
+        commit(&remote, "remote change", None, None, None, None, None).unwrap();
+        let branch = get_current_branch(&local).unwrap();
+        let incoming = fetch_branch(
+            &local,
+            "",
+            &SecretString::default(),
+            "origin",
+            &branch,
+            &RemoteSettings::default(),
+        )
+        .unwrap()
+        .unwrap();
+
+        let err = verify_commits(&local, local_head, incoming, None, None).unwrap_err();
+        assert!(err.message().contains(&incoming.to_string()));
+    }
+
+    #[test]
+    fn test_skips_invalid_push_options() {
+        let options =
+            ["ci.skip", "", "merge_request.title=a\nb", "ci.variable=A=1"].map(String::from);
+        assert_eq!(valid_push_options(&options), ["ci.skip", "ci.variable=A=1"]);
+    }
+
+    #[test]
+    fn test_cli_config_applies_network_settings() {
+        assert!(RemoteSettings::default().cli_config().is_empty());
+
+        let settings = RemoteSettings {
+            proxy: Some("http://proxy.internal:3128".to_string()),
+            ca_bundle: Some(PathBuf::from("/etc/ssl/corp-ca.pem")),
+            timeout: Some(Duration::from_secs(30)),
+            ..RemoteSettings::default()
+        };
+        assert_eq!(
+            settings.cli_config(),
+            [
+                "http.proxy=http://proxy.internal:3128",
+                "http.sslCAInfo=/etc/ssl/corp-ca.pem",
+                "http.lowSpeedLimit=1",
+                "http.lowSpeedTime=30"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_attaches_note_to_head() {
+        let (_dir, repo) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        add_note(&repo, "{\"kind\":\"create\"}", None).unwrap();
+
+        let head = repo.head().unwrap().target().unwrap();
+        let note = repo.find_note(Some(NOTES_REF), head).unwrap();
+        assert_eq!(note.message(), Some("{\"kind\":\"create\"}"));
+    }
+
+    #[test]
+    fn test_lfs_tracking_follows_gitattributes() {
+        let (_dir, repo) = init_repo_with_files(&[(
+            ".gitattributes",
+            "assets/cover.psd filter=lfs diff=lfs merge=lfs -text\n",
+        )]);
+
+        assert!(is_lfs_tracked(&repo, Path::new("assets/cover.psd")));
+        assert!(!is_lfs_tracked(&repo, Path::new("assets/other.psd")));
+        assert!(!is_lfs_tracked(&repo, Path::new("README.md")));
+    }
+
+    #[test]
+    fn test_push_notes_merges_notes_of_other_clones() {
+        let remote_dir = tempfile::tempdir().unwrap();
+        Repository::init_bare(remote_dir.path()).unwrap();
+        let remote_url = remote_dir.path().to_str().unwrap();
+        let settings = RemoteSettings::default();
+
+        let (_first_dir, first) = init_repo_with_files(&[("a.md", "a\n")]);
+        let (_second_dir, second) = init_repo_with_files(&[("b.md", "b\n")]);
+        for repo in [&first, &second] {
+            repo.remote("origin", remote_url).unwrap();
+            add_note(repo, "{\"version\":1}", None).unwrap();
+            push_notes(repo, "origin", &settings, None, None).unwrap();
+        }
+
+        let remote = Repository::open_bare(remote_dir.path()).unwrap();
+        for repo in [&first, &second] {
+            let head = repo.head().unwrap().peel_to_commit().unwrap().id();
+            assert!(remote.find_note(Some(NOTES_REF), head).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_missing_repository_errors() {
+        let error = |class, message| GitError::new(git2::ErrorCode::GenericError, class, message);
+
+        assert!(is_missing_repository(&error(
+            git2::ErrorClass::Http,
+            "unexpected http status code: 404"
+        )));
+        assert!(is_missing_repository(&error(
+            git2::ErrorClass::Ssh,
+            "ERROR: Repository not found."
+        )));
+        assert!(!is_missing_repository(&error(
+            git2::ErrorClass::Net,
+            "failed to resolve address for git.example.com: Name or service not found"
+        )));
+        assert!(!is_missing_repository(&error(
+            git2::ErrorClass::Http,
+            "unexpected http status code: 401"
+        )));
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        let error = |code, class, message| GitError::new(code, class, message);
+
+        assert!(is_transient_error(&error(
+            git2::ErrorCode::GenericError,
+            git2::ErrorClass::Os,
+            "failed to connect to 127.0.0.1: Connection refused"
+        )));
+        assert!(is_transient_error(&error(
+            git2::ErrorCode::GenericError,
+            git2::ErrorClass::Net,
+            "early EOF"
+        )));
+        assert!(!is_transient_error(&error(
+            git2::ErrorCode::Auth,
+            git2::ErrorClass::Http,
+            "authentication required but no callback set"
+        )));
+        assert!(!is_transient_error(&error(
+            git2::ErrorCode::NotFastForward,
+            git2::ErrorClass::Reference,
+            "cannot push non-fastforwardable reference"
+        )));
+    }
+
+    #[test]
+    fn test_local_remote_paths() {
+        assert_eq!(
+            local_remote_path("file:///srv/git/repo.git"),
+            Some(PathBuf::from("/srv/git/repo.git"))
+        );
+        assert_eq!(
+            local_remote_path("../repo.git"),
+            Some(PathBuf::from("../repo.git"))
+        );
+        assert_eq!(local_remote_path("git@example.com:repo.git"), None);
+        assert_eq!(local_remote_path("https://example.com/repo.git"), None);
+    }
+
+    #[test]
+    fn test_blob_ids_apply_eol_conversion() {
+        let (dir, repo) = init_repo_with_files(&[
+            (".gitattributes", "*.txt text\n"),
+            ("notes.txt", "todo\n"),
+            ("raw.bin", "todo\n"),
+        ]);
+
+        // Saved by an editor using Windows line endings: staged as before
+        fs::write(dir.path().join("notes.txt"), "todo\r\n").unwrap();
+        let (content_id, head_id) = blob_ids(&repo, "notes.txt");
+        assert!(content_id.is_some());
+        assert_eq!(content_id, head_id);
+
+        fs::write(dir.path().join("raw.bin"), "todo\r\n").unwrap();
+        let (content_id, head_id) = blob_ids(&repo, "raw.bin");
+        assert_ne!(content_id, head_id);
+    }
+
+    #[test]
+    fn test_lfs_push_skipped_without_lfs_objects() {
+        let (_dir, repo) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        let password = SecretString::from("secret".to_string());
+
+        // No remote exists: running git-lfs would fail
+        push_lfs_objects(
+            &repo,
+            ("user", &password),
+            "origin",
+            &["refs/heads/main:refs/heads/main".to_string()],
+            &RemoteSettings::default(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_finds_merged_and_expired_branches() {
+        let (_dir, repo) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let main = get_current_branch(&repo).unwrap();
+        let signature = repo.signature().unwrap();
+        let tree = head.tree().unwrap();
+        let commit_on = |reference: &str, message: &str, parents: &[&Commit]| {
+            let oid = repo
+                .commit(
+                    Some(reference),
+                    &signature,
+                    &signature,
+                    message,
+                    &tree,
+                    parents,
+                )
+                .unwrap();
+            repo.find_commit(oid).unwrap()
+        };
+        let merged = commit_on("refs/heads/autopilot/merged", "merged", &[&head]);
+        commit_on("refs/heads/autopilot/active", "unmerged", &[&head]);
+        for name in [
+            "autopilot/fresh",
+            "autopilot/quarantine",
+            "autopilot/diverged/main",
+        ] {
+            repo.branch(name, &head, false).unwrap();
+        }
+        commit_on(&format!("refs/heads/{}", main), "merge", &[&head, &merged]);
+
+        let names = |stale: Vec<StaleBranch>| -> Vec<String> {
+            stale.into_iter().map(|branch| branch.name).collect()
+        };
+        let day = Duration::from_secs(24 * 60 * 60);
+        let now = SystemTime::now();
+        let stale = stale_branches(&repo, "origin", "autopilot", day, now).unwrap();
+        assert_eq!(names(stale), ["autopilot/merged"]);
+        let stale = stale_branches(&repo, "origin", "autopilot", day, now + 2 * day).unwrap();
+        assert_eq!(
+            names(stale.clone()),
+            ["autopilot/active", "autopilot/fresh", "autopilot/merged"]
+        );
+        let retention = Duration::from_secs(u64::MAX);
+        let stale = stale_branches(&repo, "origin", "autopilot", retention, now).unwrap();
+        assert_eq!(names(stale), ["autopilot/merged"]);
+
+        let stale = stale_branches(&repo, "origin", "autopilot", day, now + 2 * day).unwrap();
+        delete_branches(&repo, None, &stale, &RemoteSettings::default(), None).unwrap();
+        assert!(repo
+            .find_branch("autopilot/active", BranchType::Local)
+            .is_err());
+        assert!(repo
+            .find_branch("autopilot/quarantine", BranchType::Local)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_keeps_remote_branch_with_other_tip() {
+        let (_dir, repo) = init_repo_with_files(&[("notes.md", "todo\n")]);
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let signature = repo.signature().unwrap();
+        repo.branch("autopilot/old", &head, false).unwrap();
+        // Someone else pushed to the branch since
+        let pushed = repo
+            .commit(
+                Some("refs/remotes/origin/autopilot/old"),
+                &signature,
+                &signature,
+                "pushed elsewhere",
+                &head.tree().unwrap(),
+                &[&head],
+            )
+            .unwrap();
+
+        let day = Duration::from_secs(24 * 60 * 60);
+        let later = SystemTime::now() + 2 * day;
+        let stale = stale_branches(&repo, "origin", "autopilot", day, later).unwrap();
+        assert_eq!(
+            stale,
+            [StaleBranch {
+                name: "autopilot/old".to_string(),
+                local: Some(head.id()),
+                remote: None,
+            }]
+        );
+
+        repo.find_branch("autopilot/old", BranchType::Local)
+            .unwrap()
+            .delete()
+            .unwrap();
+        let stale = stale_branches(&repo, "origin", "autopilot", day, later).unwrap();
+        assert_eq!(
+            stale,
+            [StaleBranch {
+                name: "autopilot/old".to_string(),
+                local: None,
+                remote: Some(pushed),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_commits_to_other_branch_and_unstages() {
+        let (dir, repo) = init_repo_with_files(&[("notes.md", "todo\n"), ("user.md", "")]);
+        let head = repo.head().unwrap().target().unwrap();
+        fs::write(dir.path().join("user.md"), "staged by the user\n").unwrap();
+        stage_file(&repo, "user.md", false).unwrap();
+        fs::write(dir.path().join("notes.md"), "todo\nbroken\n").unwrap();
+        stage_file(&repo, "notes.md", false).unwrap();
+
+        let first = commit_to_branch(&repo, "autopilot/quarantine", "broken", None).unwrap();
+        let second = commit_to_branch(&repo, "autopilot/quarantine", "again", None).unwrap();
+        assert_eq!(repo.find_commit(first).unwrap().parent_id(0).unwrap(), head);
+        assert_eq!(
+            repo.find_commit(second).unwrap().parent_id(0).unwrap(),
+            first
+        );
+        assert_eq!(repo.head().unwrap().target(), Some(head));
+
+        unstage_paths(&repo, &["notes.md"]).unwrap();
+        let status = repo.status_file(Path::new("notes.md")).unwrap();
+        assert_eq!(status, Status::WT_MODIFIED);
+        let status = repo.status_file(Path::new("user.md")).unwrap();
+        assert_eq!(status, Status::INDEX_MODIFIED);
+    }
+}
@@ -0,0 +1,297 @@
+//! # Health Module
+//!
+//! Serves liveness and readiness probes over HTTP for running the daemon in
+//! containers (Docker `HEALTHCHECK`, Kubernetes probes):
+//! - `/healthz` answers `200` while the event loop keeps running and `503`
+//!   once it has stalled for `STALL_TIMEOUT`
+//! - `/readyz` answers `200` once every repository is watched and onboarded
+//!   and the event loop is running, `503` before
+//!
+//! Both report the health of every repository as JSON. The server is enabled
+//! with `health_port` (or `--health-port`) and listens on `health_address`,
+//! the loopback interface by default.
+//!
+//! A `POST` to `/hooks/follow` (e.g. a push webhook of the Git host) makes the
+//! repositories in follow mode poll their remote on the next tick instead of
+//! waiting for `follow.interval_secs`.
+
+use serde_json::json;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, trace};
+
+use crate::state::{RepoState, StatusSnapshot};
+
+/// Time without a heartbeat after which the event loop counts as stalled
+const STALL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Maximum size of a request read from a client
+const MAX_REQUEST_BYTES: usize = 4096;
+
+/// Time a client may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Health of the daemon as reported by the probes
+#[derive(Debug, Default)]
+pub struct Health {
+    inner: Mutex<HealthState>,
+}
+
+#[derive(Debug, Default)]
+struct HealthState {
+    /// Whether every repository is watched and onboarded
+    ready: bool,
+
+    /// Last time the event loop was seen running
+    heartbeat: Option<Instant>,
+
+    /// Latest published status
+    snapshot: Option<StatusSnapshot>,
+
+    /// Whether the follow webhook was called since the last poll
+    follow_requested: bool,
+}
+
+impl Health {
+    /// Marks the initial setup of the watcher as done
+    pub fn set_ready(&self) {
+        let mut state = self.inner.lock().unwrap_or_else(|p| p.into_inner());
+        state.ready = true;
+        state.heartbeat = Some(Instant::now());
+    }
+
+    /// Records that the event loop is running
+    pub fn beat(&self) {
+        self.inner
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .heartbeat = Some(Instant::now());
+    }
+
+    /// Stores the latest status reported by the probes
+    pub fn update(&self, snapshot: StatusSnapshot) {
+        self.inner
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .snapshot = Some(snapshot);
+    }
+
+    /// Takes the pending request of the follow webhook
+    ///
+    /// # Returns
+    /// Whether the webhook was called since the last call.
+    pub fn take_follow_request(&self) -> bool {
+        std::mem::take(
+            &mut self
+                .inner
+                .lock()
+                .unwrap_or_else(|p| p.into_inner())
+                .follow_requested,
+        )
+    }
+
+    /// Answers a request
+    ///
+    /// # Arguments
+    /// - `method`: The request method.
+    /// - `path`: The requested path, without the query.
+    ///
+    /// # Returns
+    /// The HTTP status code and the JSON body.
+    fn handle(&self, method: Option<&str>, path: &str) -> (u16, String) {
+        match (method, path) {
+            (Some("POST"), "/hooks/follow") => {
+                self.inner
+                    .lock()
+                    .unwrap_or_else(|p| p.into_inner())
+                    .follow_requested = true;
+                (202, json!({ "status": "accepted" }).to_string())
+            }
+            (Some("GET") | Some("HEAD"), _) => self.respond(path),
+            _ => (405, json!({ "error": "method not allowed" }).to_string()),
+        }
+    }
+
+    /// Answers a probe
+    ///
+    /// # Arguments
+    /// - `path`: The requested path.
+    ///
+    /// # Returns
+    /// The HTTP status code and the JSON body.
+    fn respond(&self, path: &str) -> (u16, String) {
+        let state = self.inner.lock().unwrap_or_else(|p| p.into_inner());
+        let running = state
+            .heartbeat
+            .is_some_and(|heartbeat| heartbeat.elapsed() < STALL_TIMEOUT);
+        let healthy = match path {
+            "/healthz" => running || !state.ready,
+            "/readyz" => running && state.ready,
+            _ => return (404, json!({ "error": "not found" }).to_string()),
+        };
+
+        let repos: Vec<_> = state
+            .snapshot
+            .iter()
+            .flat_map(|snapshot| &snapshot.repos)
+            .map(|repo| {
+                json!({
+                    "path": repo.path,
+                    "health": repo_health(&repo.state),
+                    "pending_changes": repo.state.pending_changes,
+                    "unpushed_commits": repo.state.unpushed_commits,
+                    "seconds_since_push": repo.state.last_successful_push.map(|at| {
+                        at.elapsed().unwrap_or_default().as_secs()
+                    }),
+                    "push_overdue": repo.state.push_overdue_since.is_some(),
+                })
+            })
+            .collect();
+        let body = json!({
+            "status": if healthy { "ok" } else { "unavailable" },
+            "ready": state.ready,
+            "watcher": if running { "running" } else { "stalled" },
+            "repos": repos,
+        });
+        (if healthy { 200 } else { 503 }, body.to_string())
+    }
+}
+
+/// Summarizes the health of a repository
+///
+/// # Returns
+/// `suspended` if its file system failed, `paused` if automation is paused,
+/// `degraded` if paths are quarantined, the last push failed or commits wait
+/// too long for a push, else `ok`.
+fn repo_health(state: &RepoState) -> &'static str {
+    if state.storage_error.is_some() {
+        "suspended"
+    } else if state.paused {
+        "paused"
+    } else if !state.quarantined.is_empty()
+        || state.push_overdue_since.is_some()
+        || state
+            .last_push
+            .as_ref()
+            .is_some_and(|push| push.error.is_some())
+    {
+        "degraded"
+    } else {
+        "ok"
+    }
+}
+
+/// Serves the probes until cancelled
+///
+/// # Arguments
+/// - `listener`: The bound listener.
+/// - `health`: The health to report.
+/// - `cancellation`: Token stopping the server.
+pub async fn serve(
+    listener: TcpListener,
+    health: std::sync::Arc<Health>,
+    cancellation: CancellationToken,
+) {
+    loop {
+        let stream = tokio::select! {
+            _ = cancellation.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    debug!("Failed to accept health probe: {}", err);
+                    continue;
+                }
+            },
+        };
+        let health = health.clone();
+        tokio::spawn(async move {
+            if let Err(err) = answer(stream, &health).await {
+                debug!("Failed to answer health probe: {}", err);
+            }
+        });
+    }
+}
+
+/// Reads a single request from a client and answers it
+///
+/// Clients not sending their request within `READ_TIMEOUT` are dropped, so
+/// idle connections do not pile up.
+async fn answer(mut stream: TcpStream, health: &Health) -> std::io::Result<()> {
+    let mut request = vec![0; MAX_REQUEST_BYTES];
+    let read = tokio::time::timeout(READ_TIMEOUT, stream.read(&mut request))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "request timed out"))??;
+    let request = String::from_utf8_lossy(&request[..read]);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let (method, path) = (request_line.next(), request_line.next().unwrap_or("/"));
+    trace!("Health probe {:?} {}", method, path);
+
+    let (status, body) = health.handle(method, path.split('?').next().unwrap_or(path));
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Service Unavailable",
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        if method == Some("HEAD") { "" } else { body.as_str() }
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::StateStore;
+    use std::path::Path;
+
+    #[test]
+    fn test_probes_follow_watcher_state() {
+        let health = Health::default();
+        assert_eq!(health.respond("/healthz").0, 200);
+        assert_eq!(health.respond("/readyz").0, 503);
+        assert_eq!(health.respond("/metrics").0, 404);
+
+        let store = StateStore::default();
+        store.with_repo(Path::new("/work/notes"), |state| state.paused = true);
+        health.update(store.snapshot());
+        health.set_ready();
+
+        let (status, body) = health.respond("/readyz");
+        assert_eq!(status, 200);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["watcher"], "running");
+        assert_eq!(body["repos"][0]["health"], "paused");
+
+        store.with_repo(Path::new("/work/notes"), |state| {
+            state.paused = false;
+            state.push_overdue_since = Some(std::time::SystemTime::now());
+        });
+        health.update(store.snapshot());
+        let body: serde_json::Value = serde_json::from_str(&health.respond("/healthz").1).unwrap();
+        assert_eq!(body["repos"][0]["health"], "degraded");
+        assert_eq!(body["repos"][0]["push_overdue"], true);
+        assert!(body["repos"][0]["seconds_since_push"].is_null());
+    }
+
+    #[test]
+    fn test_follow_webhook_requests_one_poll() {
+        let health = Health::default();
+        assert!(!health.take_follow_request());
+        assert_eq!(health.handle(Some("GET"), "/hooks/follow").0, 404);
+        assert_eq!(health.handle(Some("POST"), "/healthz").0, 405);
+
+        assert_eq!(health.handle(Some("POST"), "/hooks/follow").0, 202);
+        assert!(health.take_follow_request());
+        assert!(!health.take_follow_request());
+    }
+}
@@ -0,0 +1,157 @@
+//! # IPC Module
+//!
+//! File-based communication between the running daemon and client commands
+//! (`status`, `tui`). The daemon publishes a `status.json` snapshot in the state
+//! directory and periodically consumes the commands clients append to
+//! `control.jsonl`.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::config::ConfigError;
+use crate::error::GitAutoPilotError;
+use crate::redact;
+use crate::state::StatusSnapshot;
+
+/// Name of the status snapshot file in the state directory
+const STATUS_FILE: &str = "status.json";
+
+/// Name of the control command queue in the state directory
+const CONTROL_FILE: &str = "control.jsonl";
+
+/// Action requested by a client for a repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ControlAction {
+    /// Stop automation for the repository
+    Pause,
+
+    /// Resume automation for the repository
+    Resume,
+
+    /// Commit and push all pending changes of the repository now
+    ForceSync,
+}
+
+/// A command queued by a client for the daemon
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlCommand {
+    /// Path of the repository the command applies to
+    pub repo: PathBuf,
+
+    /// The requested action
+    pub action: ControlAction,
+}
+
+/// Publishes a status snapshot for client commands
+///
+/// The snapshot is written to a temporary file first and renamed into place,
+/// so readers never observe a partially written file.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the snapshot cannot be serialized or written.
+pub fn write_snapshot(state_dir: &str, snapshot: &StatusSnapshot) -> Result<(), GitAutoPilotError> {
+    let path = Path::new(state_dir).join(STATUS_FILE);
+    let temp_path = path.with_extension("json.tmp");
+
+    // Errors recorded in the snapshot may carry credentials, e.g. in a remote URL
+    let contents = serde_json::to_string_pretty(snapshot).map_err(ConfigError::from)?;
+    fs::write(&temp_path, redact::redact(&contents).as_bytes())?;
+    fs::rename(&temp_path, &path)?;
+    Ok(())
+}
+
+/// Reads the status snapshot published by the daemon
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if no snapshot exists or it cannot be parsed.
+pub fn read_snapshot(state_dir: &str) -> Result<StatusSnapshot, GitAutoPilotError> {
+    let path = Path::new(state_dir).join(STATUS_FILE);
+    let contents = fs::read_to_string(&path).map_err(|e| {
+        GitAutoPilotError::ConfigError(ConfigError::FileError(format!(
+            "No daemon status found at {} ({}), is the daemon running?",
+            path.display(),
+            e
+        )))
+    })?;
+
+    Ok(serde_json::from_str(&contents).map_err(ConfigError::from)?)
+}
+
+/// Queues a command for the daemon
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the command cannot be written.
+pub fn queue_command(state_dir: &str, command: &ControlCommand) -> Result<(), GitAutoPilotError> {
+    let line = serde_json::to_string(command).map_err(ConfigError::from)?;
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(Path::new(state_dir).join(CONTROL_FILE))?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+/// Takes all commands queued by clients, removing them from the queue
+///
+/// The queue file is renamed before being read so that commands appended
+/// concurrently end up in a fresh queue instead of being lost.
+///
+/// # Returns
+/// Returns the queued commands in order; malformed lines are skipped.
+pub fn take_commands(state_dir: &str) -> Vec<ControlCommand> {
+    let path = Path::new(state_dir).join(CONTROL_FILE);
+    if !path.exists() {
+        return Vec::new();
+    }
+
+    let processing_path = path.with_extension("jsonl.processing");
+    if let Err(err) = fs::rename(&path, &processing_path) {
+        warn!("Failed to take control commands: {}", err);
+        return Vec::new();
+    }
+
+    let contents = fs::read_to_string(&processing_path).unwrap_or_default();
+    let _ = fs::remove_file(&processing_path);
+
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(command) => Some(command),
+            Err(err) => {
+                debug!("Skipping malformed control command {:?}: {}", line, err);
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_commands_drains_queue() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().to_str().unwrap();
+
+        let pause = ControlCommand {
+            repo: PathBuf::from("/repo/a"),
+            action: ControlAction::Pause,
+        };
+        let sync = ControlCommand {
+            repo: PathBuf::from("/repo/b"),
+            action: ControlAction::ForceSync,
+        };
+        queue_command(state_dir, &pause).unwrap();
+        queue_command(state_dir, &sync).unwrap();
+
+        assert_eq!(take_commands(state_dir), vec![pause, sync]);
+        assert!(take_commands(state_dir).is_empty());
+    }
+}
@@ -0,0 +1,237 @@
+//! # Jobs Module
+//!
+//! Runs slow repository work (maintenance, fetches, verification) on the
+//! blocking thread pool of the runtime, so the watch loop keeps handling
+//! events meanwhile. A job hands back a result the watch loop collects on its
+//! next tick. At most one job of a kind runs per repository.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{error, trace};
+
+use crate::state;
+
+/// Identifies a job: the repository and the kind of work
+type JobKey = (PathBuf, &'static str);
+
+/// Background jobs whose results are collected by the watch loop
+pub struct Jobs<T> {
+    /// Jobs started and not collected yet
+    running: Mutex<HashSet<JobKey>>,
+
+    /// Cloned into every job to hand back its result, `None` if it panicked
+    sender: Mutex<mpsc::Sender<(JobKey, Option<T>)>>,
+
+    /// Results of the finished jobs
+    finished: Mutex<mpsc::Receiver<(JobKey, Option<T>)>>,
+}
+
+impl<T> Default for Jobs<T> {
+    fn default() -> Self {
+        let (sender, finished) = mpsc::channel();
+        Jobs {
+            running: Mutex::default(),
+            sender: Mutex::new(sender),
+            finished: Mutex::new(finished),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Jobs<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jobs")
+            .field("running", &self.running)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Send + 'static> Jobs<T> {
+    /// Starts a job unless one of the same kind runs for the repository
+    ///
+    /// Outside a Tokio runtime (one-off commands, tests) the job runs right
+    /// away; its result is still collected by `finished`.
+    ///
+    /// # Arguments
+    /// - `repo` - The repository the job works on, see `state::repo_key`.
+    /// - `kind` - The kind of work, e.g. `maintenance`.
+    /// - `job` - The work, only using data it owns.
+    ///
+    /// # Returns
+    /// Returns `false` if a job of that kind was already running.
+    pub fn start(
+        &self,
+        repo: &Path,
+        kind: &'static str,
+        job: impl FnOnce() -> T + Send + 'static,
+    ) -> bool {
+        let key = (state::repo_key(repo), kind);
+        if !self
+            .running
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(key.clone())
+        {
+            trace!("Job {} is already running for {}", kind, repo.display());
+            return false;
+        }
+        let sender = self
+            .sender
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        let run = move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(job)).ok();
+            // The receiver lives as long as the instance
+            let _ = sender.send((key, result));
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => drop(runtime.spawn_blocking(run)),
+            Err(_) => run(),
+        }
+        true
+    }
+
+    /// Whether a job of a kind runs for a repository
+    pub fn is_running(&self, repo: &Path, kind: &'static str) -> bool {
+        self.running
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .contains(&(state::repo_key(repo), kind))
+    }
+
+    /// Collects the results of the jobs finished since the last call
+    ///
+    /// A job only counts as running until its result is collected, so the
+    /// result is applied before a job of the same kind can start again.
+    pub fn finished(&self) -> Vec<T> {
+        let finished = self
+            .finished
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut running = self
+            .running
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut results = Vec::new();
+        for job in finished.try_iter() {
+            Self::collect(&mut running, job, &mut results);
+        }
+        results
+    }
+
+    /// Waits for the running jobs to finish and collects their results
+    ///
+    /// Unlike `finished` this blocks, for shutdown and tests that must see
+    /// the results of the jobs they started.
+    ///
+    /// # Arguments
+    /// - `timeout` - How long to wait at most; jobs still running then keep
+    ///   running and are collected by a later call.
+    pub fn wait_finished(&self, timeout: Duration) -> Vec<T> {
+        let deadline = Instant::now() + timeout;
+        let finished = self
+            .finished
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut results = Vec::new();
+        loop {
+            let mut running = self
+                .running
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            for job in finished.try_iter() {
+                Self::collect(&mut running, job, &mut results);
+            }
+            if running.is_empty() {
+                break;
+            }
+            // Jobs need the lock of `running` to start, not to finish
+            drop(running);
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match finished.recv_timeout(remaining) {
+                Ok(job) => Self::collect(
+                    &mut self
+                        .running
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner()),
+                    job,
+                    &mut results,
+                ),
+                Err(_) => {
+                    trace!("Jobs still running after {:?}", timeout);
+                    break;
+                }
+            }
+        }
+        results
+    }
+
+    /// Marks a finished job as not running and keeps its result
+    fn collect(
+        running: &mut HashSet<JobKey>,
+        ((repo, kind), result): (JobKey, Option<T>),
+        results: &mut Vec<T>,
+    ) {
+        running.remove(&(repo.clone(), kind));
+        match result {
+            Some(result) => results.push(result),
+            None => error!("Job {} panicked for {}", kind, repo.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_job_per_kind_and_repository() {
+        let jobs = Jobs::default();
+        let repo = Path::new("/srv/repo");
+
+        assert!(jobs.start(repo, "maintenance", || 1));
+        assert!(jobs.is_running(repo, "maintenance"));
+        assert!(!jobs.start(repo, "maintenance", || 2));
+        assert!(jobs.start(repo, "fetch", || 3));
+        assert!(jobs.start(Path::new("/srv/other"), "maintenance", || 4));
+
+        let mut results = jobs.finished();
+        results.sort();
+        assert_eq!(results, [1, 3, 4]);
+        assert!(!jobs.is_running(repo, "maintenance"));
+        assert!(jobs.start(repo, "maintenance", || 5));
+    }
+
+    #[test]
+    fn test_wait_finished_blocks_for_running_jobs() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let _guard = runtime.enter();
+        let jobs = Jobs::default();
+        let repo = Path::new("/srv/repo");
+
+        jobs.start(repo, "fetch", || {
+            std::thread::sleep(Duration::from_millis(50));
+            1
+        });
+
+        assert_eq!(jobs.wait_finished(Duration::from_secs(10)), [1]);
+        assert!(!jobs.is_running(repo, "fetch"));
+        assert!(jobs.wait_finished(Duration::ZERO).is_empty());
+    }
+
+    #[test]
+    fn test_panicked_job_stops_running() {
+        let jobs: Jobs<()> = Jobs::default();
+        let repo = Path::new("/srv/repo");
+
+        jobs.start(repo, "maintenance", || panic!("gc crashed"));
+
+        assert!(jobs.finished().is_empty());
+        assert!(!jobs.is_running(repo, "maintenance"));
+    }
+}
@@ -0,0 +1,239 @@
+//! # Journal Module
+//!
+//! With `event_journal` enabled, every file system event the daemon handles
+//! is appended to `<state dir>/journal/<date>.jsonl`, one JSON object per
+//! line, together with the status and the blob ID of its paths at that time.
+//! The journal of the last `JOURNAL_KEEP_FILES` days is kept.
+//!
+//! `git-auto-pilot replay` reads a journal and runs its events through the
+//! processing pipeline again in dry-run mode, so misbehavior such as a wrong
+//! template or a missed rename can be reproduced. The events are replayed
+//! against the current state of the repository, so replaying on a copy in
+//! the state of the time of the events reproduces them exactly; paths whose
+//! content changed since are pointed out. Decisions
+//! taken while processing events (see `JournalEntry::decision`) are listed
+//! but not replayed.
+
+use git2::Repository;
+use notify::event::{
+    AccessKind, CreateKind, DataChange, MetadataKind, ModifyKind, RemoveKind, RenameMode,
+};
+use notify::{Event, EventKind};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::config::ConfigError;
+use crate::error::GitAutoPilotError;
+use crate::{git, helper};
+
+/// Name of the journal directory inside the state directory
+pub const JOURNAL_DIR_NAME: &str = "journal";
+
+/// Number of daily journal files kept
+const JOURNAL_KEEP_FILES: usize = 7;
+
+/// A recorded file system event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Time the event was received
+    pub at: SystemTime,
+
+    /// The repository the event belongs to
+    pub repo: PathBuf,
+
+    /// Kind of the event, as `helper::event_kind_name` reports it
+    pub kind: String,
+
+    /// The paths of the event
+    pub paths: Vec<PathBuf>,
+
+    /// Decision taken while processing an event (e.g. how a large file was
+    /// handled); such entries are notes rather than events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decision: Option<String>,
+
+    /// State of the paths inside the repository when the event was received
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<RecordedFile>,
+}
+
+/// State of a path of an event when it was recorded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedFile {
+    /// The path, relative to the repository root
+    pub path: String,
+
+    /// Its status, as `helper::status_to_string` reports it
+    pub status: String,
+
+    /// ID of the blob its content hashes to, `None` if it did not exist
+    pub blob: Option<String>,
+}
+
+impl RecordedFile {
+    /// Reads the current state of a path
+    ///
+    /// # Arguments
+    /// - `repo`: The repository.
+    /// - `path`: The path, relative to the repository root.
+    pub fn read(repo: &Repository, path: &str) -> Self {
+        RecordedFile {
+            path: path.to_string(),
+            status: repo
+                .status_file(Path::new(path))
+                .map(helper::status_to_string)
+                .unwrap_or_else(|_| "UNKNOWN".to_string()),
+            blob: git::hash_workdir_file(repo, path).map(|id| id.to_string()),
+        }
+    }
+}
+
+impl JournalEntry {
+    /// Records an event received for a repository
+    pub fn new(repo: &Path, event: &Event, at: SystemTime) -> Self {
+        JournalEntry {
+            at,
+            repo: repo.to_path_buf(),
+            kind: helper::event_kind_name(&event.kind).to_string(),
+            paths: event.paths.clone(),
+            decision: None,
+            files: Vec::new(),
+        }
+    }
+
+    /// Adds the current state of the paths inside the repository
+    pub fn with_files(mut self, repo: &Repository) -> Self {
+        let Some(workdir) = repo.workdir() else {
+            return self;
+        };
+        self.files = self
+            .paths
+            .iter()
+            .filter_map(|path| path.strip_prefix(workdir).ok())
+            .filter(|path| !path.as_os_str().is_empty())
+            .map(|path| RecordedFile::read(repo, &path.to_string_lossy()))
+            .collect();
+        self
+    }
+
+    /// Compares the recorded state of the paths with their current state
+    ///
+    /// # Returns
+    /// The recorded and the current state of every path that changed since.
+    pub fn changed_files(&self, repo: &Repository) -> Vec<(&RecordedFile, RecordedFile)> {
+        self.files
+            .iter()
+            .map(|recorded| (recorded, RecordedFile::read(repo, &recorded.path)))
+            .filter(|(recorded, current)| *recorded != current)
+            .collect()
+    }
+
+    /// Records a decision taken for a path while processing an event
+    pub fn decision(repo: &Path, kind: &str, path: &Path, decision: String) -> Self {
+        JournalEntry {
+            at: SystemTime::now(),
+            repo: repo.to_path_buf(),
+            kind: kind.to_string(),
+            paths: vec![path.to_path_buf()],
+            decision: Some(decision),
+            files: Vec::new(),
+        }
+    }
+
+    /// Rebuilds the recorded event
+    ///
+    /// Details the kind name does not carry (e.g. whether a file or a folder
+    /// was created) are replayed as `Any`.
+    pub fn event(&self) -> Event {
+        let kind = match self.kind.as_str() {
+            "create" => EventKind::Create(CreateKind::Any),
+            "data-modify" => EventKind::Modify(ModifyKind::Data(DataChange::Any)),
+            "metadata-modify" => EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any)),
+            "rename-from" => EventKind::Modify(ModifyKind::Name(RenameMode::From)),
+            "rename-to" => EventKind::Modify(ModifyKind::Name(RenameMode::To)),
+            "rename" => EventKind::Modify(ModifyKind::Name(RenameMode::Any)),
+            "modify" => EventKind::Modify(ModifyKind::Any),
+            "remove" => EventKind::Remove(RemoveKind::Any),
+            "access" => EventKind::Access(AccessKind::Any),
+            _ => EventKind::Other,
+        };
+        let mut event = Event::new(kind);
+        event.paths = self.paths.clone();
+        event
+    }
+}
+
+/// Appends an event to today's journal file
+///
+/// # Arguments
+/// - `journal_dir`: The journal directory.
+/// - `entry`: The event to record.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the journal cannot be written.
+pub fn append(journal_dir: &Path, entry: &JournalEntry) -> Result<(), GitAutoPilotError> {
+    fs::create_dir_all(journal_dir)?;
+    // RFC 3339 timestamps start with the `YYYY-MM-DD` date
+    let date = humantime::format_rfc3339_seconds(entry.at).to_string();
+    let path = journal_dir.join(format!("{}.jsonl", &date[..10]));
+    let is_new = !path.exists();
+
+    let mut line = serde_json::to_string(entry).map_err(ConfigError::from)?;
+    line.push('\n');
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?
+        .write_all(line.as_bytes())?;
+    if is_new {
+        helper::rotate_files(journal_dir, "jsonl", JOURNAL_KEEP_FILES)?;
+    }
+    Ok(())
+}
+
+/// Reads the events of a journal file
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the file cannot be read or a line is not
+/// a journal entry.
+pub fn read(path: &Path) -> Result<Vec<JournalEntry>, GitAutoPilotError> {
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str(line)
+                .map_err(|err| GitAutoPilotError::from(ConfigError::from(err)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorded_events_replay_alike() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut event = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::From)));
+        event.paths = vec![PathBuf::from("/work/notes/old.md")];
+        let entry = JournalEntry::new(Path::new("/work/notes"), &event, SystemTime::now());
+
+        append(dir.path(), &entry).unwrap();
+        append(dir.path(), &entry).unwrap();
+        let journal = fs::read_dir(dir.path())
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let entries = read(&journal).unwrap();
+        assert_eq!(entries, [entry.clone(), entry]);
+
+        let replayed = entries[0].event();
+        assert_eq!(replayed.kind, event.kind);
+        assert_eq!(replayed.paths, event.paths);
+    }
+}
@@ -0,0 +1,1742 @@
+//! # git-auto-pilot-core
+//!
+//! The auto-commit engine behind the `git-auto-pilot` binary. It watches git
+//! repositories and commits (and pushes) every change with a message rendered
+//! from the configured templates or message generator.
+//!
+//! Embedders create a [`GitAutoPilot`] and run [`GitAutoPilot::watch_with_cancellation`]:
+//!
+//! ```ignore
+//! let git_auto_pilot = GitAutoPilot::new(0, false, None, None)?;
+//! let cancellation = CancellationToken::new();
+//! git_auto_pilot.watch_with_cancellation(cancellation).await?;
+//! ```
+//!
+//! The building blocks are public as well: [`config`] (configuration file and
+//! its schema), [`credentials`] (credential providers), [`git`] (staging,
+//! committing, pushing), [`generator`] (commit message generators), [`state`]
+//! (per-repository runtime state) and [`helper`] (event and path handling);
+//! daemon plumbing such as the instance lock, control queue and dashboard
+//! stays private.
+//!
+//! Public items follow semantic versioning: while the crate is at `0.x`, a
+//! breaking change bumps the minor version, so `0.y.z` releases with the same
+//! `y` are compatible with each other.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use config::ConfigError;
+pub use error::{ErrorCategory, GitAutoPilotError};
+use generator::{ChangeKind, MessageGenerator};
+use git::FileChangeStats;
+use git2::{Repository, Status};
+pub use paths::set_dot_dir;
+use pipeline::Trigger;
+use secrecy::SecretString;
+use serde::Deserialize;
+use serde::Serialize;
+pub use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, trace, warn};
+pub use watcher::WatchExit;
+
+mod analysis_cache;
+pub mod backend;
+pub mod config;
+mod content_hash;
+pub mod credentials;
+pub mod error;
+mod forge;
+pub mod generator;
+pub mod git;
+mod health;
+pub mod helper;
+mod ipc;
+mod jobs;
+mod journal;
+mod limiter;
+mod lock;
+mod logger;
+mod onboarding;
+mod paths;
+mod pipeline;
+mod push;
+mod queue;
+pub mod redact;
+pub mod state;
+mod stats;
+mod template_test;
+mod trash;
+mod tui;
+mod verify;
+mod watcher;
+
+/// Represents the Git Auto Pilot configuration and file management
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitAutoPilot {
+    /// Configuration settings for the Git Auto Pilot
+    pub config: config::Config,
+
+    /// Location of the dot directory
+    pub dot_dir_location: String,
+
+    /// Location of the configuration file
+    pub dot_file_location: String,
+
+    /// Location of the state directory (instance lock, status and control files)
+    pub state_dir_location: String,
+
+    /// Location of the cache directory
+    pub cache_dir_location: String,
+
+    /// Time this instance was created, identifying the current session
+    #[serde(skip, default = "SystemTime::now")]
+    pub session_started: SystemTime,
+
+    /// Runtime state tracked per repository
+    #[serde(skip)]
+    state: state::StateStore,
+
+    /// Credentials entered interactively during this session
+    #[serde(skip)]
+    prompted_credentials: OnceLock<(String, SecretString)>,
+
+    /// Effective configuration of repositories belonging to a group
+    #[serde(skip)]
+    repo_configs: HashMap<PathBuf, config::Config>,
+
+    /// Single files watched in repositories not watched as a whole, by repository
+    #[serde(skip)]
+    watched_files: HashMap<PathBuf, Vec<PathBuf>>,
+
+    /// Subset of the configured repositories watched by this instance, all if empty
+    #[serde(skip)]
+    repo_filter: Vec<PathBuf>,
+
+    /// Generator producing the commit messages
+    #[serde(skip, default = "generator::default_generator")]
+    message_generator: Box<dyn generator::MessageGenerator>,
+
+    /// Backend reading the status of repositories and making unsigned commits
+    #[serde(skip, default = "backend::default_backend")]
+    backend: Box<dyn backend::VcsBackend>,
+
+    /// Limits concurrent pushes per host and globally
+    #[serde(skip)]
+    push_limiter: limiter::PushLimiter,
+
+    /// Content hashes of files in repositories with `content_hashing` enabled
+    #[serde(skip)]
+    content_hashes: content_hash::ContentHashCache,
+
+    /// Content files had before their latest commit, to hold back quick reverts
+    #[serde(skip)]
+    reverted_contents: content_hash::RevertMemory,
+
+    /// Recent change analyses, reused by bursts of events
+    #[serde(skip)]
+    analysis_cache: analysis_cache::AnalysisCache,
+
+    /// Slow repository work running off the watch loop
+    #[serde(skip)]
+    jobs: jobs::Jobs<JobDone>,
+
+    /// Number of commits made during this session
+    #[serde(skip)]
+    session_commits: AtomicU64,
+
+    /// Whether commits are pushed, off for one-off commits without `--push`
+    #[serde(skip)]
+    push_enabled: bool,
+
+    /// Prints the commits that would be made instead of making them (`replay`)
+    #[serde(skip)]
+    dry_run: bool,
+
+    /// Whether the watch loop runs and collects the results of background jobs
+    #[serde(skip)]
+    watching: bool,
+
+    /// Health reported by the probes of the health endpoint
+    #[serde(skip)]
+    health: Arc<health::Health>,
+
+    /// Lock preventing other daemon instances from running, held until dropped
+    #[serde(skip)]
+    _instance_lock: Option<lock::InstanceLock>,
+}
+
+/// Environment variable pointing at an explicit configuration file
+pub const CONFIG_ENV_VAR: &str = "GIT_AUTO_PILOT_CONFIG";
+
+/// Environment variable pointing at a single directory holding the
+/// configuration, state and cache, for environments without a home directory
+pub const DOT_DIR_ENV_VAR: &str = "GIT_AUTO_PILOT_DOT_DIR";
+
+/// Result of a background job, applied by the watch loop once collected
+type JobDone = Box<dyn FnOnce(&GitAutoPilot) + Send>;
+
+/// Branch cleanup of a maintenance run, resolved on the watch loop so the
+/// job does not need the instance
+#[derive(Debug)]
+struct BranchCleanup {
+    /// Prefix of the autopilot branches, without the trailing `/`
+    prefix: String,
+
+    /// Age of the last commit after which an unmerged branch is deleted
+    max_age: Duration,
+
+    /// Branches kept: the one commits go to and the one waiting to be pushed
+    keep: Vec<String>,
+
+    /// Refs that are never deleted
+    protected_refs: Vec<String>,
+
+    /// Whether the branches are deleted on `origin` as well
+    remote: bool,
+
+    /// Network settings for the remote deletion
+    remote_settings: git::RemoteSettings,
+
+    /// Credentials for the remote deletion, the user's credential helpers
+    /// apply without
+    credentials: Option<(String, SecretString)>,
+}
+
+impl BranchCleanup {
+    /// Deletes the merged and expired branches below the prefix
+    ///
+    /// # Errors
+    /// Returns a `git2::Error` if the branches cannot be read or deleted.
+    fn run(&self, repo: &Repository) -> Result<(), git2::Error> {
+        let stale: Vec<git::StaleBranch> = git::stale_branches(
+            repo,
+            "origin",
+            &self.prefix,
+            self.max_age,
+            SystemTime::now(),
+        )?
+        .into_iter()
+        .filter(|branch| {
+            !self.keep.contains(&branch.name)
+                && !helper::is_ref_protected(
+                    &format!("refs/heads/{}", branch.name),
+                    &self.protected_refs,
+                )
+        })
+        .collect();
+        if stale.is_empty() {
+            return Ok(());
+        }
+
+        info!(
+            "Deleting {} stale branches of {}: {}",
+            stale.len(),
+            repo.workdir().unwrap_or(repo.path()).display(),
+            stale
+                .iter()
+                .map(|branch| branch.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        git::delete_branches(
+            repo,
+            self.remote.then_some("origin"),
+            &stale,
+            &self.remote_settings,
+            self.credentials
+                .as_ref()
+                .map(|(username, password)| (username.as_str(), password)),
+        )
+    }
+}
+
+impl GitAutoPilot {
+    /// Creates a new GitAutoPilot instance
+    ///
+    /// Acquires the instance lock in the state directory, so only one daemon runs
+    /// at a time unless `force` is set.
+    ///
+    /// # Arguments
+    /// - `verbosity` - Number of `-v` flags given.
+    /// - `force` - Start even if another instance holds the lock.
+    /// - `config_path` - Explicit configuration file, overriding `GIT_AUTO_PILOT_CONFIG`
+    ///   and the default `config.json` in the dot directory.
+    /// - `log_target` - Where to log, overriding the configured `log_target`.
+    ///
+    /// # Returns
+    /// A new GitAutoPilot instance with configuration and file paths
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if initialization fails, the log target
+    /// cannot be opened or another instance is running
+    pub fn new(
+        verbosity: u64,
+        force: bool,
+        config_path: Option<PathBuf>,
+        log_target: Option<config::LogTarget>,
+    ) -> Result<Self, GitAutoPilotError> {
+        let _ = logger::setup_logging(verbosity).or_else(|err| {
+            error!("Logging initialize failed: {}", err);
+            Ok::<(), ConfigError>(())
+        });
+
+        // Determine the XDG directories
+        let dirs = paths::Dirs::from_env()?;
+        let dot_dir = dirs.config.display().to_string();
+        let state_dir = dirs.state.display().to_string();
+
+        // Ensure dot and state directories exist
+        ensure_dot_dir_exists(&dot_dir)?;
+        ensure_dot_dir_exists(&state_dir)?;
+
+        // Make sure no other instance is watching the same repositories
+        let instance_lock = lock::InstanceLock::acquire(&state_dir, force)?;
+
+        // Move files left by older versions, only once no other instance uses them
+        if let Some(home) = helper::find_home_dir() {
+            dirs.migrate_legacy_files(&home)?;
+        }
+
+        // Construct dot file path
+        let dot_file = resolve_config_path(&dot_dir, config_path);
+        debug!("Using configuration file {}", dot_file);
+
+        // Load or create configuration
+        let mut config = load_or_create_config(&dot_file)?;
+
+        // Log to the configured target from now on
+        logger::set_target(log_target.as_ref().unwrap_or(&config.log_target))?;
+
+        // check and populate git credentials
+        helper::populate_git_credentials(&mut config)?;
+
+        // Trust the custom CA bundle for all remote operations
+        if let Some(ca_bundle) = config.ca_bundle.as_ref() {
+            git::set_ca_bundle(ca_bundle)?;
+        }
+        if config.insecure_skip_tls_verify {
+            warn!("TLS certificate verification is disabled (insecure_skip_tls_verify)");
+        }
+        if config.network_timeout_secs > 0 {
+            git::set_network_timeout(Duration::from_secs(config.network_timeout_secs))?;
+        }
+
+        let message_generator = generator::from_config(&config)?;
+        let push_limiter = limiter::PushLimiter::new(config.max_concurrent_pushes);
+
+        let mut git_auto_pilot = GitAutoPilot {
+            config,
+            dot_dir_location: dot_dir,
+            state_dir_location: state_dir,
+            cache_dir_location: dirs.cache.display().to_string(),
+            dot_file_location: dot_file,
+            session_started: SystemTime::now(),
+            state: state::StateStore::default(),
+            prompted_credentials: OnceLock::new(),
+            repo_configs: HashMap::new(),
+            watched_files: HashMap::new(),
+            repo_filter: Vec::new(),
+            message_generator,
+            backend: backend::default_backend(),
+            push_limiter,
+            content_hashes: content_hash::ContentHashCache::default(),
+            reverted_contents: content_hash::RevertMemory::default(),
+            analysis_cache: analysis_cache::AnalysisCache::default(),
+            jobs: jobs::Jobs::default(),
+            session_commits: AtomicU64::new(0),
+            push_enabled: true,
+            dry_run: false,
+            watching: false,
+            health: Arc::default(),
+            _instance_lock: instance_lock,
+        };
+
+        // Validate the repository groups early
+        Self::resolve_repos(&mut git_auto_pilot)?;
+
+        info!("GitAutoPilot instance created successfully");
+        Ok(git_auto_pilot)
+    }
+
+    /// Serves the health probes on a port, overriding `health_port`
+    ///
+    /// # Arguments
+    /// - `port` - The TCP port to listen on, bound to `health_address`
+    ///   (loopback unless configured otherwise).
+    pub fn set_health_port(&mut self, port: u16) {
+        self.config.health_port = Some(port);
+    }
+
+    /// Watches only a subset of the configured repositories
+    ///
+    /// The subset also applies to repositories matched by glob entries later.
+    ///
+    /// # Arguments
+    /// - `repos` - Paths (or globs) of configured repositories, e.g. from `--repo`.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if a path is not a configured repository.
+    pub fn set_repo_filter(&mut self, repos: &[PathBuf]) -> Result<(), GitAutoPilotError> {
+        let filter = helper::expand_repo_entries(repos);
+        if filter.is_empty() {
+            let requested: Vec<_> = repos
+                .iter()
+                .map(|repo| repo.display().to_string())
+                .collect();
+            return Err(ConfigError::UnknownRepository(requested.join(", ")).into());
+        }
+        self.repo_filter.clear();
+        let configured = Self::resolve_repos(self)?;
+        if let Some(unknown) = filter.iter().find(|repo| !configured.contains(repo)) {
+            return Err(ConfigError::UnknownRepository(unknown.display().to_string()).into());
+        }
+        info!(
+            "Watching {} of {} configured repositories",
+            filter.len(),
+            configured.len()
+        );
+        self.repo_filter = filter;
+        Ok(())
+    }
+
+    /// Creates an instance for one-off commands next to a running daemon
+    ///
+    /// Loads the configuration and resolves the repositories like `new`, but
+    /// neither takes the instance lock nor sets up logging or the remote
+    /// settings. Pushing is disabled.
+    ///
+    /// # Arguments
+    /// - `config_path` - Explicit configuration file, as for `new`.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the configuration cannot be loaded or
+    /// the repository groups are invalid.
+    fn detached(config_path: Option<PathBuf>) -> Result<Self, GitAutoPilotError> {
+        let dirs = paths::Dirs::from_env()?;
+        let dot_file = resolve_config_path(&dirs.config.display().to_string(), config_path);
+        let config = load_or_create_config(&dot_file)?;
+        Self::detached_with(config, &dirs, dot_file)
+    }
+
+    /// Creates an instance for one-off commands from a loaded configuration
+    ///
+    /// # Arguments
+    /// - `config` - The configuration.
+    /// - `dirs` - The directories of the instance.
+    /// - `dot_file` - The configuration file the configuration was loaded from.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the repository groups are invalid.
+    fn detached_with(
+        config: config::Config,
+        dirs: &paths::Dirs,
+        dot_file: String,
+    ) -> Result<Self, GitAutoPilotError> {
+        let mut git_auto_pilot = GitAutoPilot {
+            config,
+            dot_dir_location: dirs.config.display().to_string(),
+            state_dir_location: dirs.state.display().to_string(),
+            cache_dir_location: dirs.cache.display().to_string(),
+            dot_file_location: dot_file,
+            session_started: SystemTime::now(),
+            state: state::StateStore::default(),
+            prompted_credentials: OnceLock::new(),
+            repo_configs: HashMap::new(),
+            watched_files: HashMap::new(),
+            repo_filter: Vec::new(),
+            message_generator: generator::default_generator(),
+            backend: backend::default_backend(),
+            push_limiter: limiter::PushLimiter::default(),
+            content_hashes: content_hash::ContentHashCache::default(),
+            reverted_contents: content_hash::RevertMemory::default(),
+            analysis_cache: analysis_cache::AnalysisCache::default(),
+            jobs: jobs::Jobs::default(),
+            session_commits: AtomicU64::new(0),
+            push_enabled: false,
+            dry_run: false,
+            watching: false,
+            health: Arc::default(),
+            _instance_lock: None,
+        };
+        Self::resolve_repos(&mut git_auto_pilot)?;
+        Ok(git_auto_pilot)
+    }
+
+    /// Appends an entry to the event journal, if `event_journal` is enabled
+    ///
+    /// Nothing is recorded while replaying a journal.
+    ///
+    /// # Arguments
+    /// - `entry` - Builds the entry, only called if it is recorded.
+    fn record_in_journal(&self, entry: impl FnOnce() -> journal::JournalEntry) {
+        if !self.config.event_journal || self.dry_run {
+            return;
+        }
+        let journal_dir = Path::new(&self.state_dir_location).join(journal::JOURNAL_DIR_NAME);
+        if let Err(err) = journal::append(&journal_dir, &entry()) {
+            warn!("Failed to record event in the journal: {}", err);
+        }
+    }
+
+    /// Starts the maintenance of every repository that is due
+    ///
+    /// The tasks run as a background job, so a long `gc` does not hold up
+    /// the watch loop; the job takes the operation lock of the repository.
+    /// Failed tasks do not stop the remaining ones; their errors are reported
+    /// in the published status.
+    fn run_due_maintenance(&self) {
+        for repo_status in self.state.snapshot().repos {
+            let Ok(repo) = Repository::open(&repo_status.path) else {
+                continue;
+            };
+            let Some(settings) = Self::repo_config(self, &repo).maintenance.clone() else {
+                continue;
+            };
+            let last_run = repo_status
+                .state
+                .last_maintenance
+                .as_ref()
+                .map_or(self.session_started, |record| record.at);
+            if last_run.elapsed().unwrap_or_default() < Duration::from_secs(settings.interval_secs)
+                || self.jobs.is_running(&repo_status.path, "maintenance")
+            {
+                continue;
+            }
+
+            let branch_cleanup = Self::branch_cleanup(self, &repo);
+            let remote_settings = Self::remote_settings(self);
+            let operation_lock = self.state.operation_lock(&repo_status.path);
+            let path = repo_status.path.clone();
+            info!("Running maintenance for {}", path.display());
+            self.jobs.start(&repo_status.path, "maintenance", move || {
+                let errors = {
+                    let _operation_guard = operation_lock
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+                    Self::run_maintenance(&path, &settings, &remote_settings, branch_cleanup)
+                };
+                let job_done: JobDone = Box::new(move |git_auto_pilot: &GitAutoPilot| {
+                    git_auto_pilot.state.with_repo(&path, |state| {
+                        state.last_maintenance = Some(state::MaintenanceRecord {
+                            at: SystemTime::now(),
+                            error: (!errors.is_empty()).then(|| errors.join("; ")),
+                        });
+                        // Measure the disk usage again from the compacted size
+                        state.disk_usage = None;
+                    });
+                    Self::publish_status(git_auto_pilot);
+                });
+                job_done
+            });
+        }
+    }
+
+    /// Runs the enabled maintenance tasks of a repository
+    ///
+    /// # Returns
+    /// The errors of the failed tasks.
+    fn run_maintenance(
+        path: &Path,
+        settings: &config::MaintenanceSettings,
+        remote_settings: &git::RemoteSettings,
+        branch_cleanup: Option<BranchCleanup>,
+    ) -> Vec<String> {
+        let repo = match Repository::open(path) {
+            Ok(repo) => repo,
+            Err(err) => return vec![format!("open: {}", err.message())],
+        };
+        let fetch_prune = |repo: &Repository| git::fetch_prune(repo, "origin", remote_settings);
+        let branch_cleanup = |repo: &Repository| match &branch_cleanup {
+            Some(branch_cleanup) => branch_cleanup.run(repo),
+            None => Ok(()),
+        };
+        type Task<'a> = &'a dyn Fn(&Repository) -> Result<(), git2::Error>;
+        let tasks: [(bool, &str, Task); 4] = [
+            (settings.fetch_prune, "fetch --prune", &fetch_prune),
+            (
+                settings.branch_cleanup.is_some(),
+                "branch cleanup",
+                &branch_cleanup,
+            ),
+            (settings.prune, "prune", &git::prune),
+            (settings.gc, "gc", &git::gc),
+        ];
+        let mut errors = Vec::new();
+        for (_, name, task) in tasks.iter().filter(|(enabled, _, _)| *enabled) {
+            match task(&repo) {
+                Ok(()) => debug!("Maintenance task {} succeeded", name),
+                Err(err) => {
+                    error!(
+                        "Maintenance task {} failed for {}: {}",
+                        name,
+                        path.display(),
+                        err
+                    );
+                    errors.push(format!("{}: {}", name, err.message()));
+                }
+            }
+        }
+        errors
+    }
+
+    /// Resolves the branch cleanup of a repository for a maintenance run
+    ///
+    /// The branch commits are currently made on and the branch with commits
+    /// waiting to be pushed are kept. Remote branches are only deleted where
+    /// pushing is enabled and allowed.
+    ///
+    /// # Returns
+    /// `None` if branch cleanup is not enabled.
+    fn branch_cleanup(&self, repo: &Repository) -> Option<BranchCleanup> {
+        let config = Self::repo_config(self, repo);
+        let settings = config.maintenance.as_ref()?.branch_cleanup.as_ref()?;
+        let unpushed = repo.workdir().and_then(|workdir| {
+            self.state
+                .with_repo(workdir, |state| state.unpushed_branch.clone())
+        });
+        let keep = Self::autopilot_branch_name(self, repo)
+            .into_iter()
+            .chain(unpushed)
+            .collect();
+        let remote = settings.remote
+            && self.push_enabled
+            && config.push_enabled
+            && helper::ensure_push_allowed(repo, "origin", &config.allowed_push_hosts).is_ok();
+        Some(BranchCleanup {
+            prefix: config.branch_prefix.clone(),
+            max_age: Duration::from_secs(settings.retention_days.saturating_mul(24 * 60 * 60)),
+            keep,
+            protected_refs: config.protected_refs.clone(),
+            remote,
+            remote_settings: Self::remote_settings(self),
+            credentials: remote.then(|| Self::known_credentials(self)).flatten(),
+        })
+    }
+
+    /// Starts writing a bundle backup of every repository whose
+    /// `bundle_backup.interval_secs` passed since its last bundle
+    ///
+    /// A repository without commits is skipped, as is one whose refs did not
+    /// change since its last successful bundle (checked again on every tick
+    /// until they do). Bundles are written by a job, without the operation
+    /// lock as they only read the repository, so commits continue while a
+    /// large bundle is written. Once written, the bundles of the repository
+    /// are rotated down to `bundle_backup.keep`.
+    fn write_due_bundles(&self) {
+        for repo_status in self.state.snapshot().repos {
+            let Ok(repo) = Repository::open(&repo_status.path) else {
+                continue;
+            };
+            let Some(settings) = Self::repo_config(self, &repo).bundle_backup.clone() else {
+                continue;
+            };
+            let last_bundle = repo_status.state.last_bundle.as_ref();
+            let last_run = last_bundle.map_or(self.session_started, |record| record.at);
+            if last_run.elapsed().unwrap_or_default() < Duration::from_secs(settings.interval_secs)
+                || self.jobs.is_running(&repo_status.path, "bundle")
+            {
+                continue;
+            }
+            if repo.head().is_err() {
+                trace!(
+                    "Skipping bundle of {}, no commits yet",
+                    repo_status.path.display()
+                );
+                continue;
+            }
+
+            let fingerprint = git::refs_fingerprint(&repo).unwrap_or_default();
+            if last_bundle.is_some_and(|record| {
+                record.error.is_none() && record.refs_fingerprint == fingerprint
+            }) {
+                trace!(
+                    "Skipping bundle of {}, no refs changed",
+                    repo_status.path.display()
+                );
+                continue;
+            }
+
+            let path = repo_status.path.clone();
+            self.jobs.start(&repo_status.path, "bundle", move || {
+                let record = Self::write_bundle(&path, &settings, fingerprint);
+                let job_done: JobDone = Box::new(move |git_auto_pilot: &GitAutoPilot| {
+                    git_auto_pilot
+                        .state
+                        .with_repo(&path, |state| state.last_bundle = Some(record));
+                    Self::publish_status(git_auto_pilot);
+                });
+                job_done
+            });
+        }
+    }
+
+    /// Writes a bundle backup of a repository and rotates its bundles
+    ///
+    /// Partial bundles left behind by an interrupted run are removed first.
+    ///
+    /// # Arguments
+    /// - `path` - The repository.
+    /// - `settings` - The `bundle_backup` settings of the repository.
+    /// - `fingerprint` - The fingerprint of its refs, see `git::refs_fingerprint`.
+    ///
+    /// # Returns
+    /// The record of the bundle, holding the error if it could not be written.
+    fn write_bundle(
+        path: &Path,
+        settings: &config::BundleBackupSettings,
+        fingerprint: u64,
+    ) -> state::BundleRecord {
+        // One directory per repository, named after its path
+        let bundle_dir =
+            helper::expand_tilde(Path::new(&settings.dir)).join(helper::path_dir_name(path));
+        let bundle_path = bundle_dir.join(format!(
+            "{}.bundle",
+            helper::file_timestamp(SystemTime::now())
+        ));
+
+        let result = fs::create_dir_all(&bundle_dir)
+            .map_err(|e| e.to_string())
+            .and_then(|()| {
+                if let Err(err) = helper::rotate_files(&bundle_dir, "partial", 0) {
+                    warn!(
+                        "Failed to remove partial bundles in {}: {}",
+                        bundle_dir.display(),
+                        err
+                    );
+                }
+                let repo = Repository::open(path).map_err(|e| e.message().to_string())?;
+                git::create_bundle(&repo, &bundle_path).map_err(|e| e.message().to_string())
+            });
+        match result {
+            Ok(()) => {
+                info!("Wrote bundle backup {}", bundle_path.display());
+                if let Err(err) = helper::rotate_files(&bundle_dir, "bundle", settings.keep) {
+                    warn!(
+                        "Failed to rotate bundles in {}: {}",
+                        bundle_dir.display(),
+                        err
+                    );
+                }
+                state::BundleRecord {
+                    at: SystemTime::now(),
+                    path: Some(bundle_path),
+                    refs_fingerprint: fingerprint,
+                    error: None,
+                }
+            }
+            Err(err) => {
+                error!(
+                    "Failed to write bundle backup of {}: {}",
+                    path.display(),
+                    err
+                );
+                state::BundleRecord {
+                    at: SystemTime::now(),
+                    path: None,
+                    refs_fingerprint: fingerprint,
+                    error: Some(err),
+                }
+            }
+        }
+    }
+
+    /// Returns the effective configuration of a repository
+    ///
+    /// # Returns
+    /// The resolved group configuration if the repository belongs to a group,
+    /// otherwise the top-level configuration.
+    fn repo_config(&self, repo: &Repository) -> &config::Config {
+        let path = repo.workdir().unwrap_or(repo.path());
+        self.repo_configs
+            .get(&state::repo_key(path))
+            .unwrap_or(&self.config)
+    }
+}
+
+/// Prints the state of the running daemon
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the daemon status cannot be read
+pub fn print_status() -> Result<(), GitAutoPilotError> {
+    let snapshot = ipc::read_snapshot(&get_state_dir_path()?)?;
+
+    println!(
+        "git-auto-pilot (pid {}) - updated {}",
+        snapshot.pid,
+        helper::format_age(snapshot.updated_at)
+    );
+    for repo in &snapshot.repos {
+        let state = &repo.state;
+        println!();
+        println!(
+            "{} [{}]",
+            repo.path.display(),
+            if state.paused {
+                "paused"
+            } else if state.storage_error.is_some() {
+                "suspended"
+            } else {
+                "active"
+            }
+        );
+        if let Some(remote_pause) = &state.remote_pause {
+            println!(
+                "  remote pause:    since {} by {}: {}",
+                humantime::format_rfc3339_seconds(remote_pause.at),
+                &remote_pause.commit[..remote_pause.commit.len().min(7)],
+                remote_pause.summary
+            );
+        }
+        if let Some(storage_error) = &state.storage_error {
+            println!(
+                "  storage error:   since {}: {}",
+                humantime::format_rfc3339_seconds(storage_error.since),
+                storage_error.error
+            );
+        }
+        println!("  pending changes: {}", state.pending_changes);
+        if let Some(event) = &state.last_event {
+            println!(
+                "  last event:      {} {} ({})",
+                event.kind,
+                event.path.display(),
+                helper::format_age(event.at)
+            );
+        }
+        if let Some(push) = &state.last_push {
+            match &push.error {
+                None => println!(
+                    "  last push:       ok ({}){}",
+                    helper::format_age(push.at),
+                    push.commit_url
+                        .as_deref()
+                        .or(push.commit_sha.as_deref())
+                        .map(|commit| format!(" {}", commit))
+                        .unwrap_or_default()
+                ),
+                Some(err) => println!(
+                    "  last push:       failed ({}): {}",
+                    helper::format_age(push.at),
+                    err
+                ),
+            }
+        }
+        for (path, quarantine) in &state.quarantined {
+            println!(
+                "  quarantined:     {} (until {}): {}",
+                path,
+                humantime::format_rfc3339_seconds(quarantine.until),
+                quarantine.error
+            );
+        }
+        if let Some(follow) = &state.last_follow {
+            match &follow.error {
+                None => println!(
+                    "  follow:          ok ({}), {} commits pulled",
+                    helper::format_age(follow.at),
+                    follow.pulled
+                ),
+                Some(err) => println!(
+                    "  follow:          failed ({}): {}",
+                    helper::format_age(follow.at),
+                    err
+                ),
+            }
+        }
+        if let Some(verify) = &state.last_verify {
+            match &verify.error {
+                None => println!("  verify:          ok ({})", helper::format_age(verify.at)),
+                Some(err) => println!(
+                    "  verify:          failed ({}): {}",
+                    helper::format_age(verify.at),
+                    err
+                ),
+            }
+        }
+        if let Some(maintenance) = &state.last_maintenance {
+            match &maintenance.error {
+                None => println!(
+                    "  maintenance:     ok ({})",
+                    helper::format_age(maintenance.at)
+                ),
+                Some(err) => println!(
+                    "  maintenance:     failed ({}): {}",
+                    helper::format_age(maintenance.at),
+                    err
+                ),
+            }
+        }
+        if let Some(bundle) = &state.last_bundle {
+            match (&bundle.error, &bundle.path) {
+                (None, Some(path)) => println!(
+                    "  last backup:     ok ({}): {}",
+                    helper::format_age(bundle.at),
+                    path.display()
+                ),
+                (error, _) => println!(
+                    "  last backup:     failed ({}): {}",
+                    helper::format_age(bundle.at),
+                    error.as_deref().unwrap_or_default()
+                ),
+            }
+        }
+        if let Some(disk_usage) = &state.disk_usage {
+            println!(
+                "  disk usage:      {} (+{} since baseline){}",
+                helper::format_size(disk_usage.current_bytes),
+                helper::format_size(
+                    disk_usage
+                        .current_bytes
+                        .saturating_sub(disk_usage.baseline_bytes)
+                ),
+                if disk_usage.over_threshold {
+                    ", run `git gc`"
+                } else {
+                    ""
+                }
+            );
+        }
+        if !state.sensitive_paths.is_empty() {
+            println!(
+                "  sensitive paths: {}{} withheld from commits: {}",
+                if state.sensitive_paths_truncated {
+                    "more than "
+                } else {
+                    ""
+                },
+                state.sensitive_paths.len(),
+                state
+                    .sensitive_paths
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        if !state.unresolved_variables.is_empty() {
+            println!(
+                "  template errors: {} messages, unresolved {}",
+                state.template_failures,
+                state
+                    .unresolved_variables
+                    .iter()
+                    .map(String::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        if state.unpushed_commits > 0 {
+            println!("  unpushed:        {} commits", state.unpushed_commits);
+        }
+        if let Some(pushed_at) = state.last_successful_push {
+            println!("  pushed:          {}", helper::format_age(pushed_at));
+        }
+        if let Some(since) = state.push_overdue_since {
+            println!(
+                "  push overdue:    commits waiting since {}",
+                helper::format_age(since)
+            );
+        }
+        if let Some(upstream) = &state.upstream {
+            println!(
+                "  upstream:        {}, {} ahead, {} behind ({}){}",
+                upstream.upstream,
+                upstream.ahead,
+                upstream.behind,
+                helper::format_age(upstream.at),
+                if upstream.behind > 0 {
+                    ", pull needed before pushing"
+                } else {
+                    ""
+                }
+            );
+        }
+        if let Some(commit) = state.recent_commits.front() {
+            println!(
+                "  last commit:     {} ({})",
+                commit.message,
+                helper::format_age(commit.at)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Opens the live terminal dashboard of the running daemon
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the terminal cannot be used
+pub fn run_tui() -> Result<(), GitAutoPilotError> {
+    tui::run(&get_state_dir_path()?)
+}
+
+/// Checks the configuration for problems
+///
+/// Lists template placeholders that no variable defines, for the top-level
+/// configuration and every group, and the variables the running daemon could
+/// not resolve while rendering commit messages.
+///
+/// # Arguments
+/// * `config_path` - Explicit configuration file, as for `GitAutoPilot::new`.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the configuration cannot be loaded
+pub fn run_doctor(config_path: Option<PathBuf>) -> Result<(), GitAutoPilotError> {
+    let dirs = paths::Dirs::from_env()?;
+    let dot_file = resolve_config_path(&dirs.config.display().to_string(), config_path);
+    let config = load_or_create_config(&dot_file)?;
+    println!("configuration: {}", dot_file);
+
+    let mut problems = 0;
+    let mut report_undefined = |scope: &str, config: &config::Config| {
+        let undefined = generator::undefined_variables(config);
+        if !undefined.is_empty() {
+            problems += 1;
+            println!(
+                "  {}: undefined template variables: {}",
+                scope,
+                undefined.into_iter().collect::<Vec<_>>().join(", ")
+            );
+        }
+    };
+    report_undefined("top level", &config);
+    for name in config.groups.keys() {
+        report_undefined(&format!("group {}", name), &config.for_group(name)?);
+    }
+
+    if let Ok(snapshot) = ipc::read_snapshot(&dirs.state.display().to_string()) {
+        for repo in &snapshot.repos {
+            if !repo.state.unresolved_variables.is_empty() {
+                problems += 1;
+                println!(
+                    "  {}: unresolved while rendering: {}",
+                    repo.path.display(),
+                    repo.state
+                        .unresolved_variables
+                        .iter()
+                        .map(String::as_str)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+        }
+    }
+
+    if problems == 0 {
+        println!("  no problems found");
+    }
+    Ok(())
+}
+
+/// Prints the commit message the templates render for a change
+///
+/// Uses the real change statistics of `file` if it has uncommitted changes,
+/// otherwise sample values, so templates can be tried out without committing.
+///
+/// # Arguments
+/// * `config_path` - Explicit configuration file, as for `GitAutoPilot::new`.
+/// * `repo_path` - The repository (or a path inside it) to render for.
+/// * `file` - File the change is rendered for, relative to the repository root.
+/// * `status` - Overrides the status of the change: `new`, `modified`,
+///   `deleted` or `renamed`.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the configuration cannot be loaded, the
+/// repository cannot be opened or the templates cannot be rendered.
+pub fn run_preview(
+    config_path: Option<PathBuf>,
+    repo_path: &Path,
+    file: Option<&str>,
+    status: Option<&str>,
+) -> Result<(), GitAutoPilotError> {
+    // Renders like the daemon, but without taking the instance lock
+    let preview = GitAutoPilot::detached(config_path)?;
+
+    let repo = Repository::discover(repo_path)?;
+    let workdir = repo.workdir().unwrap_or(repo.path()).to_path_buf();
+    let short_file_name = file.unwrap_or("example.txt").to_string();
+    let full_file_name = workdir.join(&short_file_name);
+
+    let changes = git::analyze_repository_changes(&repo, std::slice::from_ref(&full_file_name))?;
+    let mut stats = match changes
+        .get(&short_file_name)
+        .and_then(|stats| stats.first())
+    {
+        Some(stats) => stats.clone(),
+        None => {
+            if file.is_some() {
+                println!(
+                    "# {} has no uncommitted changes, using sample values",
+                    short_file_name
+                );
+            }
+            FileChangeStats {
+                lines_added: 12,
+                lines_deleted: 3,
+                lines_modified: 15,
+                status: Status::WT_MODIFIED,
+                old_name: Some(format!("old-{}", short_file_name)),
+            }
+        }
+    };
+    if let Some(status) = status {
+        stats.status = match status {
+            "new" => Status::WT_NEW,
+            "modified" => Status::WT_MODIFIED,
+            "deleted" => Status::WT_DELETED,
+            "renamed" => Status::WT_RENAMED,
+            other => {
+                return Err(GitAutoPilotError::MessageGeneratorError(format!(
+                    "unknown status '{}' (expected new, modified, deleted or renamed)",
+                    other
+                )))
+            }
+        };
+    }
+    let kind = ChangeKind::from_status(stats.status);
+
+    let branch = git::get_current_branch(&repo).unwrap_or("master".to_string());
+    let dynamic_values = GitAutoPilot::prepare_dynamic_values(
+        &preview,
+        &repo,
+        &branch,
+        short_file_name.clone(),
+        full_file_name.display().to_string(),
+        &stats,
+        &Trigger::preview(kind),
+    );
+    let config = GitAutoPilot::repo_config(&preview, &repo);
+    let change = generator::Change {
+        kind,
+        values: &dynamic_values,
+        diff: None,
+    };
+    let (message, description) = generator::TemplateGenerator.generate(config, &change)?;
+
+    println!("{}", message);
+    if !description.is_empty() {
+        println!();
+        println!("{}", description);
+    }
+    let unresolved = generator::unresolved_variables(config, kind, &dynamic_values);
+    if !unresolved.is_empty() {
+        println!();
+        println!(
+            "# unresolved template variables: {}",
+            unresolved.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Renders the configured templates for a matrix of sample changes and
+/// compares them with golden files
+///
+/// The top-level templates are compared with `<golden dir>/<scenario>.txt`,
+/// those of a repository group with `<golden dir>/groups/<group>/<scenario>.txt`.
+///
+/// # Arguments
+/// * `config_path` - Explicit configuration file, as for `GitAutoPilot::new`.
+/// * `golden_dir` - Directory of the golden files (default: `golden` in the
+///   dot directory).
+/// * `update` - Writes the current output as the new golden files.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the configuration cannot be loaded, the
+/// golden files cannot be written or an output differs from its golden file.
+pub fn run_test_templates(
+    config_path: Option<PathBuf>,
+    golden_dir: Option<PathBuf>,
+    update: bool,
+) -> Result<(), GitAutoPilotError> {
+    let dirs = paths::Dirs::from_env()?;
+    let dot_file = resolve_config_path(&dirs.config.display().to_string(), config_path);
+    let config = load_or_create_config(&dot_file)?;
+    let golden_dir = golden_dir.unwrap_or_else(|| dirs.config.join("golden"));
+    println!("configuration: {}", dot_file);
+    println!("golden files:  {}", golden_dir.display());
+
+    let mut scopes = vec![("top level".to_string(), config.clone(), golden_dir.clone())];
+    for name in config.groups.keys() {
+        scopes.push((
+            format!("group {}", name),
+            config.for_group(name)?,
+            golden_dir.join("groups").join(name),
+        ));
+    }
+    let scratch_dir = dirs.cache.join("template-test");
+
+    let mut failures = 0;
+    for (scope, config, dir) in &scopes {
+        println!("{}:", scope);
+        // The commit convention file is read from the first repository of the scope
+        let conventions_from = config
+            .repos
+            .first()
+            .and_then(|entry| helper::expand_repo_entries(&[entry.path().to_path_buf()]).pop());
+        let outcomes = template_test::check(
+            config,
+            dir,
+            &scratch_dir,
+            conventions_from.as_deref(),
+            update,
+        )?;
+        for (scenario, outcome) in outcomes {
+            match outcome {
+                template_test::Outcome::Match => println!("  ok       {}", scenario),
+                template_test::Outcome::Updated => println!("  updated  {}", scenario),
+                template_test::Outcome::Missing => {
+                    failures += 1;
+                    println!("  missing  {} (run with --update to create)", scenario);
+                }
+                template_test::Outcome::Mismatch { expected, actual } => {
+                    failures += 1;
+                    println!("  changed  {}", scenario);
+                    for line in expected.lines() {
+                        println!("    - {}", line);
+                    }
+                    for line in actual.lines() {
+                        println!("    + {}", line);
+                    }
+                }
+            }
+        }
+    }
+
+    if failures > 0 {
+        return Err(GitAutoPilotError::MessageGeneratorError(format!(
+            "{} template outputs differ from the golden files",
+            failures
+        )));
+    }
+    Ok(())
+}
+
+/// Commits the changes of the given paths once, rendering the messages with
+/// the configured templates like the daemon does
+///
+/// Every changed file below the paths is committed on its own. The commits
+/// are only pushed with `push`, right away regardless of push batching.
+///
+/// # Arguments
+/// * `verbosity` - Number of `-v` flags given.
+/// * `config_path` - Explicit configuration file, as for `GitAutoPilot::new`.
+/// * `paths` - Files or directories to commit, possibly in different repositories.
+/// * `push` - Push the commits afterwards.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the configuration cannot be loaded, a
+/// path is not inside a repository or committing or pushing fails.
+pub fn run_commit(
+    verbosity: u64,
+    config_path: Option<PathBuf>,
+    paths: &[PathBuf],
+    push: bool,
+) -> Result<(), GitAutoPilotError> {
+    let _ = logger::setup_logging(verbosity);
+    let mut one_off = GitAutoPilot::detached(config_path)?;
+    one_off.message_generator = generator::from_config(&one_off.config)?;
+    if push {
+        helper::populate_git_credentials(&mut one_off.config)?;
+        if let Some(ca_bundle) = one_off.config.ca_bundle.as_ref() {
+            git::set_ca_bundle(ca_bundle)?;
+        }
+        if one_off.config.network_timeout_secs > 0 {
+            git::set_network_timeout(Duration::from_secs(one_off.config.network_timeout_secs))?;
+        }
+        one_off.push_enabled = true;
+    }
+    commit_paths(&one_off, paths, push)
+}
+
+/// Commits every changed file below the given paths, see `run_commit`
+///
+/// # Arguments
+/// * `one_off` - The instance committing the changes.
+/// * `paths` - Files or directories to commit, relative to the current directory.
+/// * `push` - Push the commits afterwards.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if a path is not inside a repository or
+/// committing or pushing fails.
+fn commit_paths(
+    one_off: &GitAutoPilot,
+    paths: &[PathBuf],
+    push: bool,
+) -> Result<(), GitAutoPilotError> {
+    // Group the paths by repository, keeping their order
+    let current_dir = std::env::current_dir()?;
+    let mut repos: Vec<(PathBuf, Vec<PathBuf>)> = Vec::new();
+    for path in paths {
+        let path = current_dir.join(path);
+        // A removed file is looked up from its parent directory
+        let existing = path.ancestors().find(|ancestor| ancestor.exists());
+        let repo = Repository::discover(existing.unwrap_or(&path))?;
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| {
+                git2::Error::from_str(&format!("{} is in a bare repository", path.display()))
+            })?
+            .to_path_buf();
+        match repos.iter_mut().find(|(known, _)| *known == workdir) {
+            Some((_, repo_paths)) => repo_paths.push(path),
+            None => repos.push((workdir, vec![path])),
+        }
+    }
+
+    let trigger = Trigger::one_off();
+    for (workdir, repo_paths) in &repos {
+        let repo = Repository::open(workdir)?;
+        let mut committed = 0;
+        let mut changes: Vec<_> = git::analyze_repository_changes(&repo, repo_paths)?
+            .into_iter()
+            .collect();
+        changes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (changed_path, stats) in changes {
+            let full_file_name = workdir.join(&changed_path);
+            let old_path = stats
+                .first()
+                .and_then(|stats| stats.old_name.as_ref())
+                .map(|old_name| workdir.join(old_name));
+            let requested = repo_paths.iter().any(|path| {
+                full_file_name.starts_with(path)
+                    || old_path.as_ref().is_some_and(|old| old.starts_with(path))
+            });
+            let Some(file_changes) = stats.first().filter(|_| requested) else {
+                continue;
+            };
+            GitAutoPilot::take_action(
+                one_off,
+                &repo,
+                file_changes,
+                &changed_path,
+                full_file_name.to_str().unwrap_or(&changed_path),
+                &trigger,
+            )?;
+            committed += 1;
+        }
+        if push {
+            GitAutoPilot::push_pending(one_off, workdir, true)?;
+        }
+        println!("{}: committed {} changes", workdir.display(), committed);
+    }
+    Ok(())
+}
+
+/// Runs the events of a journal through the processing pipeline again in
+/// dry-run mode, printing the commits that would be made
+///
+/// Nothing is staged, committed or pushed and the autopilot branch is not
+/// switched to. The events are replayed against the current state of the
+/// repositories.
+///
+/// # Arguments
+/// * `verbosity` - Number of `-v` flags given.
+/// * `config_path` - Explicit configuration file, as for `GitAutoPilot::new`.
+/// * `journal_path` - Journal file to replay (see `event_journal`).
+/// * `repo` - Only replays the events of this repository.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the configuration or the journal cannot
+/// be loaded or `repo` is not a repository.
+pub fn run_replay(
+    verbosity: u64,
+    config_path: Option<PathBuf>,
+    journal_path: &Path,
+    repo: Option<&Path>,
+) -> Result<(), GitAutoPilotError> {
+    let _ = logger::setup_logging(verbosity);
+    let mut replay = GitAutoPilot::detached(config_path)?;
+    replay.message_generator = generator::from_config(&replay.config)?;
+    replay.dry_run = true;
+
+    let only_repo = repo
+        .map(|repo| -> Result<PathBuf, GitAutoPilotError> {
+            let repo = Repository::discover(repo)?;
+            let workdir = repo.workdir().ok_or_else(|| {
+                git2::Error::from_str(&format!("{} is a bare repository", repo.path().display()))
+            })?;
+            Ok(state::repo_key(workdir))
+        })
+        .transpose()?;
+
+    let entries = journal::read(journal_path)?;
+    let mut replayed = 0;
+    for entry in &entries {
+        if only_repo
+            .as_ref()
+            .is_some_and(|only_repo| *only_repo != state::repo_key(&entry.repo))
+        {
+            continue;
+        }
+        if let Some(decision) = &entry.decision {
+            println!(
+                "{} {}: {} (recorded)",
+                humantime::format_rfc3339_seconds(entry.at),
+                entry.kind,
+                decision
+            );
+            continue;
+        }
+        println!(
+            "{} {} {}",
+            humantime::format_rfc3339_seconds(entry.at),
+            entry.kind,
+            entry
+                .paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        if let Ok(repo) = Repository::open(&entry.repo) {
+            for (recorded, current) in entry.changed_files(&repo) {
+                println!(
+                    "    {} changed since: {} {} then, {} {} now",
+                    recorded.path,
+                    recorded.status,
+                    recorded.blob.as_deref().unwrap_or("-"),
+                    current.status,
+                    current.blob.as_deref().unwrap_or("-")
+                );
+            }
+        }
+        if let Err(err) = GitAutoPilot::handle_event(&replay, &entry.event(), &entry.repo, entry.at)
+        {
+            println!("    failed: {}", err);
+        }
+        replayed += 1;
+    }
+    println!("replayed {} of {} events", replayed, entries.len());
+    Ok(())
+}
+
+/// Determines the path for the state directory the daemon publishes to
+///
+/// # Returns
+/// A `String` representing the full path to the state directory
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if home directory cannot be determined
+fn get_state_dir_path() -> Result<String, GitAutoPilotError> {
+    Ok(paths::Dirs::from_env()?.state.display().to_string())
+}
+
+/// Determines the configuration file to use
+///
+/// # Arguments
+/// * `dot_dir` - Path to the dot directory
+/// * `explicit` - Path given on the command line
+///
+/// # Returns
+/// The command line path if given, otherwise the path in `GIT_AUTO_PILOT_CONFIG`,
+/// otherwise `config.json` in the dot directory
+fn resolve_config_path(dot_dir: &str, explicit: Option<PathBuf>) -> String {
+    explicit
+        .or_else(|| {
+            std::env::var_os(CONFIG_ENV_VAR)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        })
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| format!("{}/config.json", dot_dir))
+}
+
+/// Ensures the dot directory exists, creating it if necessary
+///
+/// # Arguments
+/// * `dot_dir` - Path to the dot directory
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if directory creation fails
+fn ensure_dot_dir_exists(dot_dir: &str) -> Result<(), GitAutoPilotError> {
+    trace!("Checking if dot directory exists");
+
+    if !Path::new(dot_dir).exists() {
+        debug!("Dot directory does not exist, creating: {}", dot_dir);
+
+        fs::create_dir_all(dot_dir)
+            .map_err(|e| GitAutoPilotError::DirCreationError(format!("{}: {}", dot_dir, e)))?;
+
+        debug!("Dot directory created successfully");
+    }
+
+    Ok(())
+}
+
+/// Loads existing configuration or creates a default one, reading the
+/// templates stored in external files and applying the `GIT_AUTO_PILOT_*`
+/// environment overrides
+///
+/// # Arguments
+/// * `dot_file` - Path to the configuration file
+///
+/// # Returns
+/// A `Config` instance, either loaded from file or default
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if file operations fail or a template file
+/// cannot be read
+fn load_or_create_config(dot_file: &str) -> Result<config::Config, GitAutoPilotError> {
+    let mut config = read_or_create_config(dot_file)?;
+    let base_dir = Path::new(dot_file).parent().unwrap_or(Path::new(""));
+    config.load_template_files(base_dir)?;
+    config.apply_env_overrides(
+        std::env::vars_os().filter_map(|(name, value)| {
+            Some((name.into_string().ok()?, value.into_string().ok()?))
+        }),
+    )?;
+    Ok(config)
+}
+
+/// Reads existing configuration or creates a default one
+///
+/// Configurations written with an older schema are migrated to the current
+/// version, keeping a backup of the original file next to it.
+///
+/// # Arguments
+/// * `dot_file` - Path to the configuration file
+///
+/// # Returns
+/// A `Config` instance, either loaded from file or default
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if file operations fail
+fn read_or_create_config(dot_file: &str) -> Result<config::Config, GitAutoPilotError> {
+    trace!("Checking configuration file existence");
+
+    let config_path = PathBuf::from(dot_file);
+
+    if !config_path.exists() {
+        debug!(
+            "Configuration file does not exist, creating default: {}",
+            dot_file
+        );
+
+        if let Some(parent) = config_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| {
+                GitAutoPilotError::DirCreationError(format!("{}: {}", parent.display(), e))
+            })?;
+        }
+
+        let default_config = config::Config::default();
+        config::Config::save_to_file(&default_config, &config_path)
+            .map_err(|e| GitAutoPilotError::ConfigError(ConfigError::FileError(e.to_string())))?;
+
+        debug!("Default configuration file created");
+        Ok(default_config)
+    } else {
+        debug!("Configuration file exists, loading: {}", dot_file);
+
+        let mut raw = config::read_json(&config_path)?;
+
+        // Layered configurations are migrated in memory only, so the including
+        // file never absorbs the settings of the files it includes
+        if config::resolve_includes(&mut raw, &config_path)? {
+            config::migrate(&mut raw)?;
+            return Ok(serde_json::from_value(raw).map_err(ConfigError::from)?);
+        }
+
+        let Some(old_version) = config::migrate(&mut raw)? else {
+            return Ok(serde_json::from_value(raw).map_err(ConfigError::from)?);
+        };
+
+        // Keep the original file around before overwriting it with the new schema
+        let backup_path = PathBuf::from(format!("{}.v{}.bak", dot_file, old_version));
+        info!(
+            "Migrating configuration from version {} to {}, backup saved to {}",
+            old_version,
+            config::CONFIG_VERSION,
+            backup_path.display()
+        );
+        fs::copy(&config_path, &backup_path)
+            .map_err(|e| GitAutoPilotError::ConfigError(ConfigError::FileError(e.to_string())))?;
+
+        let migrated: config::Config = serde_json::from_value(raw).map_err(ConfigError::from)?;
+        migrated.save_to_file(&config_path)?;
+
+        debug!("Configuration migrated successfully");
+        Ok(migrated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::{Event, EventKind};
+
+    /// Creates a repository with a committed `README.md` and a detached
+    /// instance watching it, with its directories next to the repository
+    ///
+    /// # Returns
+    /// The temporary directory (removed once dropped), the repository and the instance.
+    pub(crate) fn test_instance(
+        mut config: config::Config,
+    ) -> (tempfile::TempDir, Repository, GitAutoPilot) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo_path = dir.path().join("repo");
+        let repo = Repository::init(&repo_path).unwrap();
+        {
+            let mut repo_config = repo.config().unwrap();
+            repo_config.set_str("user.name", "Test").unwrap();
+            repo_config
+                .set_str("user.email", "test@example.com")
+                .unwrap();
+        }
+        fs::write(repo_path.join("README.md"), "readme\n").unwrap();
+        git::stage_file(&repo, "README.md", false).unwrap();
+        git::commit(&repo, "initial", None, None, None, None, None).unwrap();
+
+        config.repos = vec![repo_path.into()];
+        let dirs = paths::Dirs::in_dir(dir.path().join("dot"));
+        fs::create_dir_all(&dirs.state).unwrap();
+        let dot_file = dirs.config.join("config.json").display().to_string();
+        let git_auto_pilot = GitAutoPilot::detached_with(config, &dirs, dot_file).unwrap();
+        (dir, repo, git_auto_pilot)
+    }
+
+    /// Paths committed in `HEAD`
+    pub(crate) fn committed_paths(repo: &Repository) -> Vec<String> {
+        let mut paths = Vec::new();
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                paths.push(format!("{}{}", root, entry.name().unwrap()));
+            }
+            git2::TreeWalkResult::Ok
+        })
+        .unwrap();
+        paths
+    }
+
+    /// Line statistics of a new file
+    pub(crate) fn new_file_stats() -> FileChangeStats {
+        FileChangeStats {
+            lines_added: 1,
+            lines_deleted: 0,
+            lines_modified: 1,
+            status: Status::WT_NEW,
+            old_name: None,
+        }
+    }
+
+    /// Writes a new file and runs it through the commit pipeline
+    pub(crate) fn commit_new_file(git_auto_pilot: &GitAutoPilot, repo: &Repository, name: &str) {
+        let full_file_name = repo.workdir().unwrap().join(name);
+        fs::write(&full_file_name, "content\n").unwrap();
+        GitAutoPilot::take_action(
+            git_auto_pilot,
+            repo,
+            &new_file_stats(),
+            name,
+            full_file_name.to_str().unwrap(),
+            &Trigger::catch_up(),
+        )
+        .unwrap();
+    }
+
+    /// Summary of the commit `HEAD` points at
+    pub(crate) fn head_summary(repo: &Repository) -> String {
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        head.summary().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_replay_in_dry_run_writes_nothing() {
+        let (dir, repo, mut git_auto_pilot) = test_instance(config::Config {
+            event_journal: true,
+            ..config::Config::default()
+        });
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        fs::write(workdir.join("notes.md"), "content\n").unwrap();
+        let mut event = Event::new(EventKind::Create(notify::event::CreateKind::File));
+        event.paths = vec![workdir.join("notes.md")];
+        let entry =
+            journal::JournalEntry::new(&workdir, &event, SystemTime::now()).with_files(&repo);
+        assert_eq!(entry.files[0].status, "WT_NEW");
+        assert!(entry.changed_files(&repo).is_empty());
+
+        git_auto_pilot.dry_run = true;
+        GitAutoPilot::record_in_journal(&git_auto_pilot, || entry.clone());
+        GitAutoPilot::handle_event(&git_auto_pilot, &entry.event(), &workdir, entry.at).unwrap();
+
+        assert_eq!(head_summary(&repo), "initial");
+        assert_eq!(
+            repo.status_file(Path::new("notes.md")).unwrap(),
+            Status::WT_NEW
+        );
+        assert!(!dir
+            .path()
+            .join("dot")
+            .join("state")
+            .join(journal::JOURNAL_DIR_NAME)
+            .exists());
+    }
+
+    #[test]
+    fn test_commit_paths_commits_each_requested_file() {
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config::default());
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        fs::create_dir(workdir.join("docs")).unwrap();
+        fs::write(workdir.join("docs/guide.md"), "guide\n").unwrap();
+        fs::write(workdir.join("docs/intro.md"), "intro\n").unwrap();
+        fs::write(workdir.join("notes.md"), "notes\n").unwrap();
+
+        commit_paths(&git_auto_pilot, &[workdir.join("docs")], false).unwrap();
+
+        assert_eq!(
+            committed_paths(&repo),
+            ["README.md", "docs/guide.md", "docs/intro.md"]
+        );
+        let mut revwalk = repo.revwalk().unwrap();
+        revwalk.push_head().unwrap();
+        assert_eq!(revwalk.count(), 3);
+        // Changes outside the requested paths are left alone
+        let statuses = repo.statuses(None).unwrap();
+        assert_eq!(statuses.iter().next().unwrap().path(), Some("notes.md"));
+        assert_eq!(statuses.len(), 1);
+    }
+
+    #[test]
+    fn test_bundle_job_replaces_partial_bundles() {
+        let backups = tempfile::tempdir().unwrap();
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config {
+            bundle_backup: Some(config::BundleBackupSettings {
+                dir: backups.path().display().to_string(),
+                interval_secs: 0,
+                keep: 5,
+            }),
+            ..config::Config::default()
+        });
+        git_auto_pilot
+            .state
+            .with_repo(repo.workdir().unwrap(), |_| ());
+        let repo_path = git_auto_pilot.state.snapshot().repos[0].path.clone();
+        // Left behind by an interrupted run
+        let bundle_dir = backups.path().join(helper::path_dir_name(&repo_path));
+        fs::create_dir_all(&bundle_dir).unwrap();
+        fs::write(bundle_dir.join("old.bundle.partial"), "trunc").unwrap();
+
+        GitAutoPilot::write_due_bundles(&git_auto_pilot);
+        for job_done in git_auto_pilot.jobs.wait_finished(Duration::from_secs(30)) {
+            job_done(&git_auto_pilot);
+        }
+
+        let files: Vec<PathBuf> = fs::read_dir(&bundle_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].extension().is_some_and(|ext| ext == "bundle"));
+        let record = git_auto_pilot
+            .state
+            .with_repo(&repo_path, |state| state.last_bundle.clone())
+            .unwrap();
+        assert_eq!((record.path, record.error), (Some(files[0].clone()), None));
+    }
+}
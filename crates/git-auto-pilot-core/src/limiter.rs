@@ -0,0 +1,181 @@
+//! # Push Limiter Module
+//!
+//! Limits how many pushes run at the same time, so that pushing many
+//! repositories at once does not trip the rate limits of a remote host.
+//! Pushes to the same host are serialized, pushes to different hosts may run
+//! concurrently up to a global limit.
+//!
+//! Pushes are blocking and wait for each other with blocking locks. Inside a
+//! multi-threaded Tokio runtime a waiting worker first hands its other tasks
+//! to the remaining workers (`block_in_place`), so a push waiting in the watch
+//! loop does not stall them.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tracing::trace;
+
+/// Concurrency limiter for pushes, grouped by remote host
+#[derive(Debug, Default)]
+pub struct PushLimiter {
+    /// Maximum number of concurrent pushes across all hosts, `0` for no limit
+    max_concurrent: usize,
+
+    /// Number of pushes currently running
+    active: Mutex<usize>,
+
+    /// Signalled whenever a push finishes
+    released: Condvar,
+
+    /// Locks serializing pushes per host
+    hosts: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+}
+
+impl PushLimiter {
+    /// Creates a limiter allowing up to `max_concurrent` pushes at once
+    ///
+    /// # Arguments
+    /// - `max_concurrent` - The global limit, `0` disables it.
+    pub fn new(max_concurrent: usize) -> Self {
+        PushLimiter {
+            max_concurrent,
+            ..Default::default()
+        }
+    }
+
+    /// Runs a push once the host is free and a global slot is available
+    ///
+    /// The host and the slot are freed when the push returns or panics.
+    ///
+    /// # Arguments
+    /// - `host` - The remote host the push goes to.
+    /// - `push` - The push to run.
+    ///
+    /// # Returns
+    /// Returns the value produced by `push`.
+    pub fn run<T>(&self, host: &str, push: impl FnOnce() -> T) -> T {
+        let host_lock = self
+            .hosts
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(host.to_string())
+            .or_default()
+            .clone();
+        let _host_guard = blocking(|| {
+            host_lock
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+        });
+
+        let _slot = blocking(|| self.acquire_slot());
+        trace!("Acquired push slot for {}", host);
+        push()
+    }
+
+    /// Blocks until fewer than `max_concurrent` pushes are running
+    fn acquire_slot(&self) -> PushSlot<'_> {
+        let mut active = self
+            .active
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        while self.max_concurrent > 0 && *active >= self.max_concurrent {
+            active = self
+                .released
+                .wait(active)
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        *active += 1;
+        PushSlot(self)
+    }
+}
+
+/// A global slot taken by `acquire_slot`, freed when dropped
+struct PushSlot<'a>(&'a PushLimiter);
+
+impl Drop for PushSlot<'_> {
+    fn drop(&mut self) {
+        let mut active = self
+            .0
+            .active
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *active -= 1;
+        self.0.released.notify_one();
+    }
+}
+
+/// Runs a wait that may block, moving the other tasks of the current
+/// multi-threaded Tokio worker to other workers first
+fn blocking<T>(wait: impl FnOnce() -> T) -> T {
+    match Handle::try_current() {
+        Ok(runtime) if runtime.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(wait)
+        }
+        _ => wait(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_limits_concurrent_pushes() {
+        let limiter = PushLimiter::new(2);
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let same_host_running = AtomicUsize::new(0);
+
+        thread::scope(|scope| {
+            for index in 0..6 {
+                // Even pushes share a host, odd pushes each go to their own host
+                let host = if index % 2 == 0 {
+                    "github.com".to_string()
+                } else {
+                    format!("host-{}.internal", index)
+                };
+                let (limiter, running, peak, same_host_running) =
+                    (&limiter, &running, &peak, &same_host_running);
+                scope.spawn(move || {
+                    limiter.run(&host, || {
+                        let shared_host = host == "github.com";
+                        if shared_host {
+                            assert_eq!(same_host_running.fetch_add(1, Ordering::SeqCst), 0);
+                        }
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        thread::sleep(Duration::from_millis(20));
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        if shared_host {
+                            same_host_running.fetch_sub(1, Ordering::SeqCst);
+                        }
+                    });
+                });
+            }
+        });
+
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn test_panicked_push_frees_its_slot() {
+        let limiter = PushLimiter::new(1);
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            limiter.run("github.com", || panic!("push crashed"))
+        }));
+        assert!(panicked.is_err());
+
+        assert_eq!(limiter.run("github.com", || 1), 1);
+        assert_eq!(
+            *limiter
+                .active
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+            0
+        );
+    }
+}
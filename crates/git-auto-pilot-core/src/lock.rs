@@ -0,0 +1,106 @@
+//! # Instance Lock Module
+//!
+//! Prevents two daemons from watching the same repositories at once, which
+//! would double-commit and race on the git index. The daemon holds an
+//! exclusive `flock` on a file in the state directory for as long as it runs.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, Write};
+use std::path::Path;
+use tracing::{debug, warn};
+
+use crate::error::GitAutoPilotError;
+
+/// Name of the instance lock file in the state directory
+const LOCK_FILE: &str = "daemon.lock";
+
+/// Exclusive lock held by the running daemon, released when dropped
+#[derive(Debug)]
+pub struct InstanceLock {
+    /// The locked file; the lock is tied to this open file
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Acquires the instance lock in the state directory
+    ///
+    /// The process id of the lock holder is written to the lock file so that
+    /// a second instance can report who holds it.
+    ///
+    /// # Arguments
+    /// - `state_dir` - The state directory the lock file lives in.
+    /// - `force` - Start anyway if another instance holds the lock.
+    ///
+    /// # Returns
+    /// Returns `None` if the lock is held elsewhere and `force` was given.
+    ///
+    /// # Errors
+    /// Returns `GitAutoPilotError::InstanceLockedError` if another instance holds
+    /// the lock, or a `GitAutoPilotError` if the lock file cannot be used.
+    pub fn acquire(state_dir: &str, force: bool) -> Result<Option<Self>, GitAutoPilotError> {
+        let path = Path::new(state_dir).join(LOCK_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut holder = String::new();
+                let _ = file.read_to_string(&mut holder);
+                let holder = match holder.trim() {
+                    "" => "unknown pid".to_string(),
+                    pid => format!("pid {}", pid),
+                };
+
+                if force {
+                    warn!(
+                        "Another instance ({}) holds {}, continuing because of --force",
+                        holder,
+                        path.display()
+                    );
+                    return Ok(None);
+                }
+                return Err(GitAutoPilotError::InstanceLockedError(format!(
+                    "{} holds {}, stop it or pass --force",
+                    holder,
+                    path.display()
+                )));
+            }
+            Err(TryLockError::Error(err)) => return Err(err.into()),
+        }
+
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+        file.flush()?;
+        debug!("Acquired instance lock {}", path.display());
+
+        Ok(Some(InstanceLock { _file: file }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_acquire_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().to_str().unwrap();
+
+        let lock = InstanceLock::acquire(state_dir, false).unwrap();
+        assert!(lock.is_some());
+        assert!(matches!(
+            InstanceLock::acquire(state_dir, false),
+            Err(GitAutoPilotError::InstanceLockedError(_))
+        ));
+        assert!(InstanceLock::acquire(state_dir, true).unwrap().is_none());
+
+        drop(lock);
+        assert!(InstanceLock::acquire(state_dir, false).unwrap().is_some());
+    }
+}
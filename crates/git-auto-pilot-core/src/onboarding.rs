@@ -0,0 +1,175 @@
+//! # Onboarding Module
+//!
+//! A repository added to the autopilot may already contain hundreds of
+//! uncommitted changes. The first time the daemon sees a repository it handles
+//! this backlog according to the `backlog_policy` setting, asking the user at
+//! startup when so configured. Onboarded repositories are recorded in `onboarded.json` in
+//! the state directory, so the backlog is only handled once.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::config::{BacklogPolicy, ConfigError};
+use crate::error::GitAutoPilotError;
+
+/// Name of the file listing onboarded repositories in the state directory
+const ONBOARDED_FILE: &str = "onboarded.json";
+
+/// Summary of the commit taking the whole backlog
+pub const SNAPSHOT_MESSAGE: &str = "Initial autopilot snapshot";
+
+/// Directory name used for files at the repository root
+pub const ROOT_DIR: &str = ".";
+
+/// Reads the set of onboarded repositories, empty if none were recorded yet
+fn read_onboarded(state_dir: &str) -> BTreeSet<PathBuf> {
+    fs::read_to_string(Path::new(state_dir).join(ONBOARDED_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Checks whether the backlog of a repository was already handled
+///
+/// # Arguments
+/// - `state_dir`: The state directory.
+/// - `repo`: Key of the repository (see `state::repo_key`).
+pub fn is_onboarded(state_dir: &str, repo: &Path) -> bool {
+    read_onboarded(state_dir).contains(repo)
+}
+
+/// Records that the backlog of a repository was handled
+///
+/// # Arguments
+/// - `state_dir`: The state directory.
+/// - `repo`: Key of the repository (see `state::repo_key`).
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the list cannot be written.
+pub fn mark_onboarded(state_dir: &str, repo: &Path) -> Result<(), GitAutoPilotError> {
+    let mut onboarded = read_onboarded(state_dir);
+    if !onboarded.insert(repo.to_path_buf()) {
+        return Ok(());
+    }
+
+    let path = Path::new(state_dir).join(ONBOARDED_FILE);
+    let temp_path = path.with_extension("json.tmp");
+    let contents = serde_json::to_string_pretty(&onboarded).map_err(ConfigError::from)?;
+    fs::write(&temp_path, contents)?;
+    fs::rename(&temp_path, &path)?;
+    Ok(())
+}
+
+/// Asks the user how to handle the backlog of a repository
+///
+/// # Arguments
+/// - `repo`: Path of the repository.
+/// - `backlog`: Number of uncommitted changes.
+///
+/// # Returns
+/// The chosen policy, or `None` if not running in a terminal.
+pub fn prompt_policy(repo: &Path, backlog: usize) -> Option<BacklogPolicy> {
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        debug!("Not running in a terminal, cannot ask for the backlog policy");
+        return None;
+    }
+
+    eprintln!(
+        "{} has {} uncommitted changes. Commit them as",
+        repo.display(),
+        backlog
+    );
+    eprint!(
+        "  [s]ingle snapshot, one commit per [d]irectory or [p]ackage or [i]gnore them? [s/d/p/I]: "
+    );
+    std::io::stderr().flush().ok()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).ok()?;
+
+    Some(match answer.trim().to_lowercase().as_str() {
+        "s" | "snapshot" => BacklogPolicy::Snapshot,
+        "d" | "directory" => BacklogPolicy::PerDirectory,
+        "p" | "package" => BacklogPolicy::PerPackage,
+        _ => BacklogPolicy::Ignore,
+    })
+}
+
+/// Groups changed paths by their top-level directory
+///
+/// Files at the repository root are grouped under `.`.
+///
+/// # Arguments
+/// - `paths`: Paths relative to the repository root.
+pub fn group_by_directory<'a>(
+    paths: impl IntoIterator<Item = &'a String>,
+) -> BTreeMap<String, Vec<&'a String>> {
+    let mut groups: BTreeMap<String, Vec<&String>> = BTreeMap::new();
+    for path in paths {
+        let dir = match path.split_once('/') {
+            Some((dir, _)) => dir,
+            None => ROOT_DIR,
+        };
+        groups.entry(dir.to_string()).or_default().push(path);
+    }
+    groups
+}
+
+/// Groups changed paths by their package (see `helper::package_name`)
+///
+/// Files outside any package are grouped under `.`.
+///
+/// # Arguments
+/// - `workdir`: The working directory of the repository.
+/// - `paths`: Paths relative to the repository root.
+pub fn group_by_package<'a>(
+    workdir: &Path,
+    paths: impl IntoIterator<Item = &'a String>,
+) -> BTreeMap<String, Vec<&'a String>> {
+    let mut groups: BTreeMap<String, Vec<&String>> = BTreeMap::new();
+    for path in paths {
+        let package =
+            crate::helper::package_name(workdir, path).unwrap_or_else(|| ROOT_DIR.to_string());
+        groups.entry(package).or_default().push(path);
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_groups_backlog_by_directory() {
+        let paths = [
+            "README.md".to_string(),
+            "src/main.rs".to_string(),
+            "src/lib/mod.rs".to_string(),
+            "docs/intro.md".to_string(),
+        ];
+
+        let groups = group_by_directory(&paths);
+
+        assert_eq!(
+            groups.keys().map(String::as_str).collect::<Vec<_>>(),
+            vec![".", "docs", "src"]
+        );
+        assert_eq!(groups["src"].len(), 2);
+        assert_eq!(groups["."], vec![&paths[0]]);
+    }
+
+    #[test]
+    fn test_marks_repositories_onboarded() {
+        let state_dir = tempfile::tempdir().unwrap();
+        let state_dir = state_dir.path().to_str().unwrap();
+        let repo = Path::new("/home/user/notes");
+
+        assert!(!is_onboarded(state_dir, repo));
+        mark_onboarded(state_dir, repo).unwrap();
+        mark_onboarded(state_dir, Path::new("/home/user/code")).unwrap();
+        assert!(is_onboarded(state_dir, repo));
+        assert!(!is_onboarded(state_dir, Path::new("/home/user/other")));
+    }
+}
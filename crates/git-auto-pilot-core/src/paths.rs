@@ -0,0 +1,225 @@
+//! # Paths Module
+//!
+//! Resolves the directories used by the daemon following the XDG base
+//! directory specification:
+//! - configuration in `$XDG_CONFIG_HOME/git-auto-pilot` (`~/.config/git-auto-pilot`)
+//! - runtime state (instance lock, status snapshot, control queue) in
+//!   `$XDG_STATE_HOME/git-auto-pilot` (`~/.local/state/git-auto-pilot`)
+//! - caches in `$XDG_CACHE_HOME/git-auto-pilot` (`~/.cache/git-auto-pilot`)
+//!
+//! Older versions kept everything in `~/.config/git-auto-pilot`;
+//! `migrate_legacy_files` moves those files to their new locations.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::{debug, info, warn};
+
+use crate::error::GitAutoPilotError;
+use crate::helper;
+use crate::DOT_DIR_ENV_VAR;
+
+/// Name of the application directory inside every base directory
+const APP_DIR: &str = "git-auto-pilot";
+
+/// Directory all files lived in before XDG support, relative to the home directory
+const LEGACY_DIR: &str = ".config/git-auto-pilot";
+
+/// Files that belong in the state directory
+const STATE_FILES: &[&str] = &["status.json", "control.jsonl"];
+
+/// Dot directory set by `set_dot_dir`
+static DOT_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Places all directories in a single dot directory for this process, taking
+/// precedence over `GIT_AUTO_PILOT_DOT_DIR` (`--dot-dir`)
+///
+/// Only the first call takes effect.
+pub fn set_dot_dir(dot_dir: PathBuf) {
+    if DOT_DIR.set(dot_dir).is_err() {
+        warn!("Keeping the dot directory set before");
+    }
+}
+
+/// Directories used by the daemon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dirs {
+    /// Holds the configuration file and its migration backups
+    pub config: PathBuf,
+
+    /// Holds the instance lock, status snapshot and control queue
+    pub state: PathBuf,
+
+    /// Holds caches that can be safely deleted
+    pub cache: PathBuf,
+}
+
+impl Dirs {
+    /// Resolves the directories from the XDG environment variables, or below
+    /// the dot directory set by `set_dot_dir` or in `GIT_AUTO_PILOT_DOT_DIR`
+    ///
+    /// # Errors
+    /// Returns `GitAutoPilotError::HomeDirError` if the home directory cannot
+    /// be determined and no dot directory is set
+    pub fn from_env() -> Result<Self, GitAutoPilotError> {
+        if let Some(dot_dir) = DOT_DIR.get() {
+            return Ok(Self::in_dir(dot_dir.clone()));
+        }
+        if let Some(dot_dir) = std::env::var_os(DOT_DIR_ENV_VAR).filter(|dir| !dir.is_empty()) {
+            return Ok(Self::in_dir(PathBuf::from(dot_dir)));
+        }
+        Ok(Self::resolve(&helper::home_dir()?, |name| {
+            std::env::var_os(name)
+        }))
+    }
+
+    /// Places all directories in a single dot directory: the configuration
+    /// directly in it, the state and cache in subdirectories
+    pub fn in_dir(dot_dir: PathBuf) -> Self {
+        Dirs {
+            state: dot_dir.join("state"),
+            cache: dot_dir.join("cache"),
+            config: dot_dir,
+        }
+    }
+
+    /// Resolves the directories relative to `home`, reading variables through `var`
+    fn resolve(home: &Path, var: impl Fn(&str) -> Option<OsString>) -> Self {
+        let base = |name: &str, fallback: &str| {
+            var(name)
+                .map(PathBuf::from)
+                // The specification requires absolute paths, relative ones are ignored
+                .filter(|path| path.is_absolute())
+                .unwrap_or_else(|| home.join(fallback))
+                .join(APP_DIR)
+        };
+
+        Dirs {
+            config: base("XDG_CONFIG_HOME", ".config"),
+            state: base("XDG_STATE_HOME", ".local/state"),
+            cache: base("XDG_CACHE_HOME", ".cache"),
+        }
+    }
+
+    /// Moves files left in the legacy directory to their new locations
+    ///
+    /// Files already present at the new location are left untouched.
+    ///
+    /// # Arguments
+    /// - `home` - The home directory containing the legacy directory.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if a directory cannot be created or a file cannot be moved.
+    pub fn migrate_legacy_files(&self, home: &Path) -> Result<(), GitAutoPilotError> {
+        let legacy = home.join(LEGACY_DIR);
+        let Ok(entries) = fs::read_dir(&legacy) else {
+            return Ok(());
+        };
+
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(name_str) = name.to_str() else {
+                continue;
+            };
+            let target_dir = if STATE_FILES.contains(&name_str) {
+                &self.state
+            } else if name_str.starts_with("config.json") {
+                &self.config
+            } else {
+                continue;
+            };
+
+            let target = target_dir.join(&name);
+            if target == entry.path() {
+                continue;
+            }
+            if target.exists() {
+                warn!(
+                    "Not migrating {}, {} already exists",
+                    entry.path().display(),
+                    target.display()
+                );
+                continue;
+            }
+
+            fs::create_dir_all(target_dir).map_err(|e| {
+                GitAutoPilotError::DirCreationError(format!("{}: {}", target_dir.display(), e))
+            })?;
+            move_file(&entry.path(), &target)?;
+            info!("Moved {} to {}", entry.path().display(), target.display());
+        }
+        Ok(())
+    }
+}
+
+/// Moves a file, copying it when source and target are on different file systems
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if let Err(err) = fs::rename(from, to) {
+        debug!("Rename failed ({}), copying instead", err);
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_honors_xdg_variables() {
+        let home = Path::new("/home/user");
+
+        let dirs = Dirs::resolve(home, |_| None);
+        assert_eq!(dirs.config, home.join(".config/git-auto-pilot"));
+        assert_eq!(dirs.state, home.join(".local/state/git-auto-pilot"));
+        assert_eq!(dirs.cache, home.join(".cache/git-auto-pilot"));
+
+        let dirs = Dirs::resolve(home, |name| match name {
+            "XDG_CONFIG_HOME" => Some("/xdg/config".into()),
+            "XDG_STATE_HOME" => Some("relative/state".into()),
+            _ => None,
+        });
+        assert_eq!(dirs.config, Path::new("/xdg/config/git-auto-pilot"));
+        assert_eq!(dirs.state, home.join(".local/state/git-auto-pilot"));
+    }
+
+    #[test]
+    fn test_in_dir_keeps_everything_below_dot_dir() {
+        let dirs = Dirs::in_dir(PathBuf::from("/data"));
+        assert_eq!(dirs.config, Path::new("/data"));
+        assert_eq!(dirs.state, Path::new("/data/state"));
+        assert_eq!(dirs.cache, Path::new("/data/cache"));
+    }
+
+    #[test]
+    fn test_migrate_legacy_files() {
+        let home = tempfile::tempdir().unwrap();
+        let legacy = home.path().join(LEGACY_DIR);
+        fs::create_dir_all(&legacy).unwrap();
+        for name in [
+            "config.json",
+            "config.json.v1.bak",
+            "status.json",
+            "daemon.lock",
+        ] {
+            fs::write(legacy.join(name), name).unwrap();
+        }
+
+        let dirs = Dirs::resolve(home.path(), |name| match name {
+            "XDG_CONFIG_HOME" => Some(home.path().join("xdg-config").into()),
+            _ => None,
+        });
+        dirs.migrate_legacy_files(home.path()).unwrap();
+
+        assert!(dirs.config.join("config.json").exists());
+        assert!(dirs.config.join("config.json.v1.bak").exists());
+        assert!(dirs.state.join("status.json").exists());
+        assert!(!legacy.join("config.json").exists());
+        assert!(!legacy.join("status.json").exists());
+        // The lock may still be held by a running old version
+        assert!(legacy.join("daemon.lock").exists());
+    }
+}
@@ -0,0 +1,2440 @@
+//! # Pipeline Module
+//!
+//! The commit pipeline: from an analyzed change to a commit. Changes are
+//! checked against the sensitive path and skip rules, staged, verified,
+//! committed with a rendered message, recorded and handed to the push.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use git2::{Repository, Time};
+use notify::{Event, EventKind};
+use tracing::{debug, error, info, trace, warn};
+
+use crate::config::{
+    BacklogPolicy, BranchStrategy, CommitTime, ConfigError, LargeFileAction, Priority,
+    SigningFormat, UnknownVariables, VerifyFailure, SYSTEM_VARIABLES,
+};
+use crate::generator::{self, ChangeKind, MessageGenerator};
+use crate::git::{self, FileChangeStats};
+use crate::{
+    helper, journal, onboarding, state, stats, trash, verify, GitAutoPilot, GitAutoPilotError,
+    JobDone,
+};
+
+/// The event a commit is made for
+#[derive(Debug, Clone)]
+pub(crate) struct Trigger {
+    /// The time the event was received
+    pub(crate) received_at: SystemTime,
+
+    /// Granular kind of the event, exposed as `{{EVENT_KIND}}`
+    pub(crate) kind: &'static str,
+
+    /// Where the event came from, exposed as `{{EVENT_SOURCE}}`
+    pub(crate) source: String,
+}
+
+impl Trigger {
+    /// Describes a file system event received from the watcher
+    pub(crate) fn from_event(event: &Event, received_at: SystemTime) -> Self {
+        Trigger {
+            received_at,
+            kind: helper::event_kind_name(&event.kind),
+            source: helper::event_source(event),
+        }
+    }
+
+    /// Describes a force-sync requested by a client
+    pub(crate) fn force_sync() -> Self {
+        Trigger {
+            received_at: SystemTime::now(),
+            kind: "sync",
+            source: "force-sync".to_string(),
+        }
+    }
+
+    /// Describes the startup catch-up on changes made while the daemon was down
+    pub(crate) fn catch_up() -> Self {
+        Trigger {
+            received_at: SystemTime::now(),
+            kind: "sync",
+            source: "catch-up".to_string(),
+        }
+    }
+
+    /// Describes the commit of the changes found in a newly added repository
+    fn onboarding() -> Self {
+        Trigger {
+            received_at: SystemTime::now(),
+            kind: "sync",
+            source: "onboarding".to_string(),
+        }
+    }
+
+    /// Describes a one-off commit requested on the command line
+    pub(crate) fn one_off() -> Self {
+        Trigger {
+            received_at: SystemTime::now(),
+            kind: "sync",
+            source: "commit".to_string(),
+        }
+    }
+
+    /// Describes the event a previewed commit message is rendered for
+    pub(crate) fn preview(kind: ChangeKind) -> Self {
+        Trigger {
+            received_at: SystemTime::now(),
+            kind: match kind {
+                ChangeKind::Create => "create",
+                ChangeKind::Remove => "remove",
+                ChangeKind::Rename | ChangeKind::RenameDir => "rename",
+                ChangeKind::Modify | ChangeKind::Scaffold => "data-modify",
+            },
+            source: "preview".to_string(),
+        }
+    }
+}
+
+/// How the index is updated for an autopilot commit
+#[derive(Debug)]
+pub(crate) enum StagePlan<'a> {
+    /// Stages a new or modified file, refusing sensitive paths
+    Add(&'a str),
+
+    /// Tracks a new large file with Git LFS and stages its pointer, refusing
+    /// sensitive paths
+    AddLfs(&'a str),
+
+    /// Keeps the committed version in the trash and stages the deletion
+    Remove(&'a str),
+
+    /// Stages a rename in a single index update, refusing sensitive paths
+    ///
+    /// Without the previous path only the new path is staged.
+    Rename { old: Option<&'a str>, new: &'a str },
+
+    /// Stages the rename of every file of a directory, refusing sensitive
+    /// new paths
+    RenameDir {
+        dir: &'a str,
+        files: &'a [(String, String)],
+    },
+
+    /// Stages the given files of a new directory one by one, leaving out
+    /// sensitive ones; `lfs_files` are large files tracked with Git LFS
+    AddDir {
+        dir: &'a str,
+        files: &'a [String],
+        lfs_files: &'a [String],
+    },
+}
+
+impl StagePlan<'_> {
+    /// Every path the plan stages, relative to the repository root
+    fn paths(&self) -> Vec<&str> {
+        match self {
+            StagePlan::Add(path) | StagePlan::Remove(path) => vec![*path],
+            StagePlan::AddLfs(path) => vec![*path, ".gitattributes"],
+            StagePlan::Rename { old, new } => old.iter().copied().chain([*new]).collect(),
+            StagePlan::RenameDir { files, .. } => files
+                .iter()
+                .flat_map(|(old, new)| [old.as_str(), new.as_str()])
+                .collect(),
+            StagePlan::AddDir {
+                files, lfs_files, ..
+            } => {
+                let mut paths: Vec<&str> = files
+                    .iter()
+                    .chain(lfs_files.iter())
+                    .map(String::as_str)
+                    .collect();
+                if !lfs_files.is_empty() {
+                    paths.push(".gitattributes");
+                }
+                paths
+            }
+        }
+    }
+}
+
+/// When an autopilot commit is pushed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PushPolicy {
+    /// Follows the configured push batching
+    Batched,
+
+    /// Pushes everything pending right away, bypassing push batching
+    Immediate,
+}
+
+/// A single autopilot commit, run by `GitAutoPilot::run_pipeline`
+///
+/// Every commit made for a change goes through the same steps: stage
+/// according to the `StagePlan`, generate the message for the `ChangeKind`,
+/// run `verify_command`, commit, record the commit, push according to the
+/// `PushPolicy` and apply the tag rule. New kinds of changes only describe
+/// themselves here.
+#[derive(Debug)]
+pub(crate) struct CommitPipeline<'a> {
+    /// Kind of the change, selecting the message templates
+    kind: ChangeKind,
+
+    /// How the index is updated
+    stage: StagePlan<'a>,
+
+    /// Values of the template variables
+    values: HashMap<String, String>,
+
+    /// When the push happens
+    push: PushPolicy,
+
+    /// Committed content the commit replaces, remembered so a change
+    /// reverting it is held back within the revert window
+    replaces: Option<git2::Oid>,
+}
+
+impl CommitPipeline<'_> {
+    /// Path the commit is counted for in the statistics
+    ///
+    /// The new path for renames and the directory for directory changes.
+    fn path(&self) -> &str {
+        match &self.stage {
+            StagePlan::Add(path)
+            | StagePlan::AddLfs(path)
+            | StagePlan::Remove(path)
+            | StagePlan::Rename { new: path, .. } => path,
+            StagePlan::RenameDir { dir, .. } | StagePlan::AddDir { dir, .. } => dir,
+        }
+    }
+
+    /// Path the author rules are matched against
+    ///
+    /// Directories end with a `/`, so the rules of the directory apply.
+    fn author_path(&self) -> String {
+        match &self.stage {
+            StagePlan::RenameDir { dir, .. } | StagePlan::AddDir { dir, .. } => {
+                format!("{}/", dir)
+            }
+            _ => self.path().to_string(),
+        }
+    }
+}
+
+impl GitAutoPilot {
+    /// Whether a changed file opted out of auto-commits with the skip marker
+    ///
+    /// # Arguments
+    /// - `repo` - The repository the file belongs to.
+    /// - `path` - The file, relative to the repository root.
+    pub(crate) fn has_skip_marker(&self, repo: &Repository, path: &str) -> bool {
+        let Some(workdir) = repo.workdir() else {
+            return false;
+        };
+        let skipped = helper::has_skip_marker(
+            &workdir.join(path),
+            Self::repo_config(self, repo).skip_marker_lines,
+        );
+        if skipped {
+            debug!("Skipping {}, it carries the skip marker", path);
+        }
+        skipped
+    }
+
+    /// Returns the `sensitive_paths` pattern a path matches, if any
+    fn sensitive_pattern<'a>(&'a self, repo: &Repository, path: &str) -> Option<&'a str> {
+        Self::repo_config(self, repo)
+            .sensitive_paths
+            .iter()
+            .find(|pattern| helper::matches_path_pattern(path, pattern))
+            .map(String::as_str)
+    }
+
+    /// Whether a changed path matches `sensitive_paths` and must not be committed
+    ///
+    /// Hits are logged as warnings and listed in the status.
+    ///
+    /// # Arguments
+    /// - `repo` - The repository the path belongs to.
+    /// - `path` - The path, relative to the repository root.
+    pub(crate) fn is_sensitive(&self, repo: &Repository, path: &str) -> bool {
+        let Some(pattern) = Self::sensitive_pattern(self, repo, path) else {
+            return false;
+        };
+        warn!(
+            "NOT committing {}: it matches the sensitive path pattern {}",
+            path, pattern
+        );
+        if let Some(workdir) = repo.workdir() {
+            self.state
+                .with_repo(workdir, |state| state.record_sensitive_path(path));
+        }
+        true
+    }
+
+    /// Refuses to stage a path matching `sensitive_paths`
+    ///
+    /// The final guard before staging, in case a sensitive path slipped
+    /// through the filtering of changes.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the path is sensitive.
+    fn ensure_not_sensitive(&self, repo: &Repository, path: &str) -> Result<(), GitAutoPilotError> {
+        match Self::sensitive_pattern(self, repo, path) {
+            Some(pattern) => {
+                error!(
+                    "Refusing to stage {}: it matches the sensitive path pattern {}",
+                    path, pattern
+                );
+                Err(GitAutoPilotError::SensitivePathError(path.to_string()))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Copies the committed version of a deleted file to the trash, if enabled
+    ///
+    /// # Arguments
+    /// - `repo` - The repository the file is deleted from.
+    /// - `path` - The file, relative to the repository root.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the copy cannot be written, so the
+    /// deletion is not committed without it.
+    fn keep_in_trash(&self, repo: &Repository, path: &str) -> Result<(), GitAutoPilotError> {
+        let Some(settings) = Self::repo_config(self, repo).trash.as_ref() else {
+            return Ok(());
+        };
+        trash::save(
+            &Path::new(&self.dot_dir_location).join(trash::TRASH_DIR_NAME),
+            repo,
+            path,
+            Duration::from_secs(settings.retention_days * 24 * 60 * 60),
+        )?;
+        Ok(())
+    }
+
+    /// Handles the uncommitted changes of a repository seen for the first time
+    ///
+    /// Depending on `backlog_policy` the backlog is committed as one snapshot,
+    /// as one snapshot per top-level directory, or left alone.
+    ///
+    /// # Arguments
+    /// - `repo_path` - The repository.
+    /// - `interactive` - Whether the user may be asked for the policy; only
+    ///   at startup, never while events are handled.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the repository cannot be analyzed or
+    /// committing the backlog fails.
+    pub(crate) fn onboard_repo(
+        &self,
+        repo_path: &Path,
+        interactive: bool,
+    ) -> Result<(), GitAutoPilotError> {
+        let key = state::repo_key(repo_path);
+        if onboarding::is_onboarded(&self.state_dir_location, &key) {
+            return Ok(());
+        }
+
+        let repo = Repository::open(repo_path)?;
+        let mut backlog = git::analyze_repository_changes(&repo, &[])?;
+        backlog.retain(|path, _| {
+            Self::is_watched(self, repo_path, &[repo_path.join(path)])
+                && !Self::has_skip_marker(self, &repo, path)
+                && !Self::is_sensitive(self, &repo, path)
+        });
+        if !backlog.is_empty() {
+            let policy = match Self::repo_config(self, &repo).backlog_policy {
+                BacklogPolicy::Ask => match interactive
+                    .then(|| onboarding::prompt_policy(repo_path, backlog.len()))
+                    .flatten()
+                {
+                    Some(policy) => policy,
+                    None => {
+                        warn!(
+                            "{} has {} uncommitted changes, set backlog_policy to handle them",
+                            repo_path.display(),
+                            backlog.len()
+                        );
+                        return Ok(());
+                    }
+                },
+                policy => policy,
+            };
+
+            let operation_lock = self.state.operation_lock(repo_path);
+            let _operation_guard = operation_lock
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            match policy {
+                BacklogPolicy::Snapshot => {
+                    Self::commit_backlog(
+                        self,
+                        &repo,
+                        &backlog,
+                        backlog.keys(),
+                        onboarding::SNAPSHOT_MESSAGE,
+                    )?;
+                }
+                BacklogPolicy::PerDirectory => {
+                    for (dir, files) in onboarding::group_by_directory(backlog.keys()) {
+                        Self::commit_backlog(
+                            self,
+                            &repo,
+                            &backlog,
+                            files,
+                            &format!("Autopilot snapshot of {}", dir),
+                        )?;
+                    }
+                }
+                BacklogPolicy::PerPackage => {
+                    let workdir = repo.workdir().unwrap_or(repo_path);
+                    for (package, files) in onboarding::group_by_package(workdir, backlog.keys()) {
+                        Self::commit_backlog(
+                            self,
+                            &repo,
+                            &backlog,
+                            files,
+                            &format!("Autopilot snapshot of {}", package),
+                        )?;
+                    }
+                }
+                BacklogPolicy::Ask | BacklogPolicy::Ignore => info!(
+                    "Ignoring {} pre-existing changes in {}",
+                    backlog.len(),
+                    repo_path.display()
+                ),
+            }
+        }
+        onboarding::mark_onboarded(&self.state_dir_location, &key)
+    }
+
+    /// Commits part of the backlog of a newly added repository as one commit
+    ///
+    /// # Arguments
+    /// - `repo` - The repository.
+    /// - `backlog` - All uncommitted changes of the repository.
+    /// - `files` - The changed paths to commit.
+    /// - `message` - The commit summary.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if staging, committing or pushing fails.
+    fn commit_backlog<'a>(
+        &self,
+        repo: &Repository,
+        backlog: &HashMap<String, Vec<FileChangeStats>>,
+        files: impl IntoIterator<Item = &'a String>,
+        message: &str,
+    ) -> Result<(), GitAutoPilotError> {
+        Self::commit_group(
+            self,
+            repo,
+            backlog,
+            files,
+            message,
+            |file_count| {
+                format!(
+                    "Committed {} files present before the autopilot was enabled",
+                    file_count
+                )
+            },
+            &Trigger::onboarding(),
+        )
+    }
+
+    /// Commits a group of changed files as one commit
+    ///
+    /// # Arguments
+    /// - `repo` - The repository.
+    /// - `changes` - The uncommitted changes of the repository.
+    /// - `files` - The changed paths to commit.
+    /// - `message` - The commit summary.
+    /// - `description` - Builds the description from the number of files committed.
+    /// - `trigger` - What the commit is made for.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if staging, committing or pushing fails.
+    pub(crate) fn commit_group<'a>(
+        &self,
+        repo: &Repository,
+        changes: &HashMap<String, Vec<FileChangeStats>>,
+        files: impl IntoIterator<Item = &'a String>,
+        message: &str,
+        description: impl FnOnce(usize) -> String,
+        trigger: &Trigger,
+    ) -> Result<(), GitAutoPilotError> {
+        let Some(workdir) = repo.workdir() else {
+            return Ok(());
+        };
+        let repo_branch = Self::commit_branch(self, repo)?;
+
+        let mut file_count = 0;
+        for file in files {
+            let Some(stats) = changes.get(file).and_then(|stats| stats.first()) else {
+                continue;
+            };
+            if let Some(old_name) = stats
+                .old_name
+                .as_ref()
+                .filter(|_| ChangeKind::from_status(stats.status) == ChangeKind::Rename)
+            {
+                git::stage_file(repo, old_name, true)?;
+            }
+            let removed = ChangeKind::from_status(stats.status) == ChangeKind::Remove;
+            if removed {
+                Self::keep_in_trash(self, repo, file)?;
+            } else {
+                Self::ensure_not_sensitive(self, repo, file)?;
+                if ChangeKind::from_status(stats.status) == ChangeKind::Create {
+                    let full_file_name = workdir.join(file).display().to_string();
+                    match Self::large_file_action(self, repo, file, &full_file_name) {
+                        Some(LargeFileAction::Skip) => continue,
+                        Some(LargeFileAction::Lfs) => git::track_with_lfs(repo, file)?,
+                        None => {}
+                    }
+                }
+            }
+            git::stage_file(repo, file, removed)?;
+            file_count += 1;
+        }
+        if file_count == 0 {
+            return Ok(());
+        }
+
+        let description = description(file_count);
+        let fallback = Self::fallback_identity(self);
+        match Self::commit_signer(self, repo) {
+            Some(signer) => git::commit(
+                repo,
+                message,
+                Some(&description),
+                None,
+                Some(&signer),
+                None,
+                fallback.as_ref(),
+            )?,
+            // Signing is left to libgit2, other backends only make plain commits
+            None => {
+                self.backend.commit(
+                    workdir,
+                    &format!("{}\n\n{}", message, description),
+                    None,
+                    None,
+                    fallback.as_ref(),
+                )?;
+            }
+        }
+        Self::write_note(
+            self,
+            repo,
+            trigger,
+            serde_json::json!({
+                "change": "snapshot",
+                "stats": { "files": file_count },
+            }),
+        );
+        Self::record_commit(self, repo, message, None);
+        Self::push_commit(self, repo, &repo_branch)
+    }
+
+    /// Handles a single file system event by analyzing changes in the corresponding Git repository.
+    ///
+    /// # Arguments
+    /// - `event` - The file system event to be handled.
+    /// - `repo` - The path to the Git repository related to the event.
+    /// - `received_at` - The time the event was received from the watcher.
+    ///
+    /// # Behavior
+    /// - Analyzes repository changes for specified file paths.
+    /// - Logs detailed information about the changes.
+    pub(crate) fn handle_event(
+        &self,
+        event: &Event,
+        repo: &Path,
+        received_at: SystemTime,
+    ) -> Result<(), GitAutoPilotError> {
+        let repo_path = repo;
+        let paused = self.state.with_repo(repo, |state| {
+            state.last_event = Some(state::EventRecord {
+                at: received_at,
+                kind: format!("{:?}", event.kind),
+                path: event.paths.first().cloned().unwrap_or_default(),
+            });
+            state.paused
+        });
+        if paused {
+            debug!("Automation paused for repository: {}", repo.display());
+            return Ok(());
+        }
+        if self
+            .state
+            .with_repo(repo, |state| state.storage_error.is_some())
+        {
+            debug!(
+                "Automation suspended until storage recovers for repository: {}",
+                repo.display()
+            );
+            return Ok(());
+        }
+
+        let repo_config = self
+            .repo_configs
+            .get(&state::repo_key(repo))
+            .unwrap_or(&self.config);
+        let write_settle_ms = repo_config.write_settle_ms;
+        if write_settle_ms > 0
+            && matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+            && Self::event_priority(self, repo, &event.paths) < Priority::High
+        {
+            if let Some(remaining) =
+                helper::write_settle_remaining(&event.paths, Duration::from_millis(write_settle_ms))
+            {
+                // Checked again once settled, a newer write postpones it again
+                debug!(
+                    "Deferring event for files still being written: {:?}",
+                    event.paths
+                );
+                let retry_at = SystemTime::now() + remaining;
+                self.state.with_repo(repo, |state| {
+                    for path in &event.paths {
+                        state.retry_at.insert(path.clone(), retry_at);
+                    }
+                });
+                return Ok(());
+            }
+        }
+
+        // NOTE: staging while the user runs git would fail with "index is locked",
+        // committing during a build would capture half-written files
+        if matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) {
+            let marker = Repository::open(repo)
+                .ok()
+                .and_then(|git_repo| git::busy_marker(&git_repo));
+            if let Some(marker) = marker {
+                debug!("Deferring event because of {}: {:?}", marker, event.paths);
+                let retry_at = SystemTime::now() + Duration::from_millis(repo_config.busy_wait_ms);
+                self.state.with_repo(repo, |state| {
+                    for path in &event.paths {
+                        state.retry_at.insert(path.clone(), retry_at);
+                    }
+                });
+                return Ok(());
+            }
+            if Self::build_in_progress(self, repo) {
+                debug!(
+                    "Deferring event because of a running build: {:?}",
+                    event.paths
+                );
+                self.state.with_repo(repo, |state| {
+                    state.deferred_paths.extend(event.paths.iter().cloned())
+                });
+                return Ok(());
+            }
+        }
+
+        if repo_config.content_hashing
+            && matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            )
+            && self
+                .content_hashes
+                .changed_paths(&state::repo_key(repo), &event.paths)
+                .is_empty()
+        {
+            debug!("Skipping event, content unchanged: {:?}", event.paths);
+            return Ok(());
+        }
+
+        let operation_lock = self.state.operation_lock(repo);
+        let _operation_guard = operation_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let trigger = Trigger::from_event(event, received_at);
+        match event.kind {
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) => {
+                // NOTE: the status is analyzed once for all paths of the event
+                let repo = match Repository::open(repo) {
+                    Ok(repo) => repo,
+                    Err(e) => {
+                        error!("Failed to open repository: {}", e);
+                        return Ok(());
+                    }
+                };
+                if helper::is_automation_disabled(&repo) {
+                    debug!("Automation disabled for repository: {:?}", repo.workdir());
+                    return Ok(());
+                }
+                let mut git_changes = self.analysis_cache.analyze(
+                    &state::repo_key(repo_path),
+                    &repo,
+                    &event.paths,
+                )?;
+                self.state.with_repo(repo_path, |state| {
+                    state.pending_changes = git_changes.len();
+                });
+                git_changes.retain(|path, _| {
+                    !Self::has_skip_marker(self, &repo, path)
+                        && !Self::is_sensitive(self, &repo, path)
+                });
+                if git_changes.is_empty() {
+                    trace!("No git changes found");
+                    return Ok(());
+                }
+                // NOTE: a renamed directory is committed at once instead of file by file
+                for directory_rename in git::detect_directory_renames(&repo, &git_changes) {
+                    for (_, new_path) in &directory_rename.files {
+                        git_changes.remove(new_path);
+                    }
+                    let _take_git_action =
+                        Self::guarded_action(self, &repo, &directory_rename.new_dir, || {
+                            Self::take_directory_rename_action(
+                                self,
+                                &repo,
+                                &directory_rename,
+                                &trigger,
+                            )
+                        });
+                }
+                // NOTE: a burst of new files in a new directory is committed as one scaffold
+                for scaffold in git::detect_scaffolds(
+                    &repo,
+                    &git_changes,
+                    Self::repo_config(self, &repo).scaffold_threshold,
+                )? {
+                    for scaffold_file in &scaffold.files {
+                        git_changes.remove(scaffold_file);
+                    }
+                    let _take_git_action = Self::guarded_action(self, &repo, &scaffold.dir, || {
+                        Self::take_scaffold_action(self, &repo, &scaffold, &trigger)
+                    });
+                }
+                debug!("git_changes={:#?}", git_changes);
+
+                // Both paths of a rename event map to the same change
+                let mut handled = HashSet::new();
+                for path in &event.paths {
+                    trace!("Path  - {}", &path.display());
+                    let Some(file_name) = repo
+                        .workdir()
+                        .and_then(|workdir| path.strip_prefix(workdir).ok())
+                        .and_then(Path::to_str)
+                        .filter(|file_name| !file_name.is_empty())
+                        .map(str::to_string)
+                    else {
+                        continue;
+                    };
+                    // NOTE: in case of rename operation, the event may carry the old path
+                    let matched = git_changes.get_key_value(&file_name).or_else(|| {
+                        git_changes.iter().find(|(_, stats)| {
+                            stats.first().and_then(|stats| stats.old_name.as_deref())
+                                == Some(file_name.as_str())
+                        })
+                    });
+                    let Some((changed_path, stats)) = matched else {
+                        continue;
+                    };
+                    if !handled.insert(changed_path.clone()) {
+                        continue;
+                    }
+                    if let Some(file_changes) = stats.first() {
+                        match ChangeKind::from_status(file_changes.status) {
+                            ChangeKind::Rename => {
+                                trace!("Rename operation found");
+                                let full_file_name = repo
+                                    .workdir()
+                                    .map(|workdir| workdir.join(changed_path))
+                                    .unwrap_or_else(|| PathBuf::from(changed_path));
+                                let _take_git_action =
+                                    Self::guarded_action(self, &repo, changed_path, || {
+                                        Self::take_action(
+                                            self,
+                                            &repo,
+                                            file_changes,
+                                            changed_path,
+                                            full_file_name.to_str().unwrap_or(changed_path),
+                                            &trigger,
+                                        )
+                                    });
+                            }
+                            _ => {
+                                let _take_git_action =
+                                    Self::guarded_action(self, &repo, &file_name, || {
+                                        Self::take_action(
+                                            self,
+                                            &repo,
+                                            file_changes,
+                                            &file_name,
+                                            path.to_str().unwrap_or(&file_name),
+                                            &trigger,
+                                        )
+                                    });
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Runs the action for a changed path unless the path is quarantined
+    ///
+    /// Paths whose action fails `quarantine_after_failures` times in a row are
+    /// skipped for `quarantine_secs`, so a single broken file is not retried
+    /// (and logged) on every event. Failures reaching the remote do not count.
+    ///
+    /// # Arguments
+    /// - `repo` - The repository the path belongs to.
+    /// - `path` - The path, relative to the repository root.
+    /// - `action` - The action committing the change of the path.
+    ///
+    /// # Errors
+    /// Returns the error of the action.
+    fn guarded_action(
+        &self,
+        repo: &Repository,
+        path: &str,
+        action: impl FnOnce() -> Result<(), GitAutoPilotError>,
+    ) -> Result<(), GitAutoPilotError> {
+        let Some(workdir) = repo.workdir() else {
+            return action();
+        };
+        if self
+            .state
+            .with_repo(workdir, |state| state.is_quarantined(path))
+        {
+            debug!("Skipping quarantined path {}", path);
+            return Ok(());
+        }
+
+        let result = action();
+        match &result {
+            Ok(()) => self
+                .state
+                .with_repo(workdir, |state| state.record_success(path)),
+            Err(err) if err.is_remote_error() => {}
+            Err(err) if err.is_storage_error() || !workdir.exists() => {
+                Self::pause_on_storage_error(self, workdir, err);
+            }
+            Err(err) => {
+                let config = Self::repo_config(self, repo);
+                let quarantined = self.state.with_repo(workdir, |state| {
+                    state.record_failure(
+                        path,
+                        &err.to_string(),
+                        config.quarantine_after_failures,
+                        Duration::from_secs(config.quarantine_secs),
+                    )
+                });
+                if quarantined {
+                    warn!(
+                        "Quarantined {} for {}s after {} consecutive failures",
+                        path, config.quarantine_secs, config.quarantine_after_failures
+                    );
+                }
+            }
+        }
+        result
+    }
+
+    /// Decides how a new file above `large_files.threshold_mb` is committed
+    ///
+    /// The decision is logged and recorded in the event journal.
+    ///
+    /// # Returns
+    /// `None` if the file is not large (or no threshold is configured), else
+    /// the configured action.
+    fn large_file_action(
+        &self,
+        repo: &Repository,
+        short_file_name: &str,
+        full_file_name: &str,
+    ) -> Option<LargeFileAction> {
+        let settings = Self::repo_config(self, repo).large_files.as_ref()?;
+        let size = fs::metadata(full_file_name).ok()?.len();
+        if size <= settings.threshold_mb * 1024 * 1024 {
+            return None;
+        }
+
+        let decision = match settings.action {
+            LargeFileAction::Skip => format!(
+                "skipped {} ({} > {} MiB)",
+                short_file_name,
+                helper::format_size(size),
+                settings.threshold_mb
+            ),
+            LargeFileAction::Lfs => format!(
+                "tracking {} ({}) with Git LFS",
+                short_file_name,
+                helper::format_size(size)
+            ),
+        };
+        warn!("Large file: {}", decision);
+        if let Some(workdir) = repo.workdir() {
+            Self::record_in_journal(self, || {
+                journal::JournalEntry::decision(
+                    workdir,
+                    "large-file",
+                    Path::new(full_file_name),
+                    decision,
+                )
+            });
+        }
+        Some(settings.action)
+    }
+
+    pub(crate) fn take_action(
+        &self,
+        repo: &Repository,
+        file_change_stats: &FileChangeStats,
+        short_file_name: &str,
+        full_file_name: &str,
+        trigger: &Trigger,
+    ) -> Result<(), GitAutoPilotError> {
+        debug!("full_file_name={:#?}", full_file_name);
+        debug!("short_file_name={:#?}", short_file_name);
+        trace!("{:#?} staging", full_file_name);
+        let repo_branch = Self::commit_branch(self, repo)?;
+        let kind = ChangeKind::from_status(file_change_stats.status);
+        let mut replaces = None;
+        let stage = match kind {
+            ChangeKind::Create => {
+                match Self::large_file_action(self, repo, short_file_name, full_file_name) {
+                    None => StagePlan::Add(short_file_name),
+                    Some(LargeFileAction::Skip) => return Ok(()),
+                    Some(LargeFileAction::Lfs) => StagePlan::AddLfs(short_file_name),
+                }
+            }
+            ChangeKind::Rename => StagePlan::Rename {
+                old: file_change_stats.old_name.as_deref(),
+                new: short_file_name,
+            },
+            ChangeKind::Remove => StagePlan::Remove(short_file_name),
+            // NOTE: else modified
+            _ => {
+                // The status compares the index stat data first, so a file
+                // touched without a change is skipped without hashing it
+                if let Some(workdir) = repo.workdir() {
+                    let path = PathBuf::from(short_file_name);
+                    if self
+                        .backend
+                        .changed_paths(workdir, std::slice::from_ref(&path))?
+                        .is_empty()
+                    {
+                        debug!("Skipping {}, it has no changes", short_file_name);
+                        return Ok(());
+                    }
+                }
+                let (content_id, head_id) = git::blob_ids(repo, short_file_name);
+                if content_id.is_some() && content_id == head_id {
+                    debug!("Skipping {}, content matches HEAD", short_file_name);
+                    return Ok(());
+                }
+                let repo_key = state::repo_key(repo.workdir().unwrap_or(repo.path()));
+                let window = Duration::from_secs(Self::repo_config(self, repo).revert_window_secs);
+                if content_id.is_some_and(|content_id| {
+                    self.reverted_contents.is_revert(
+                        &repo_key,
+                        short_file_name,
+                        content_id,
+                        SystemTime::now(),
+                        window,
+                    )
+                }) {
+                    debug!(
+                        "Holding back {}, it reverts the latest commit within {:?}",
+                        short_file_name, window
+                    );
+                    // Checked again once the window has closed
+                    self.state.with_repo(&repo_key, |state| {
+                        state
+                            .retry_at
+                            .insert(repo_key.join(short_file_name), SystemTime::now() + window);
+                    });
+                    return Ok(());
+                }
+                replaces = head_id;
+                StagePlan::Add(short_file_name)
+            }
+        };
+
+        let values = Self::prepare_dynamic_values(
+            self,
+            repo,
+            &repo_branch,
+            short_file_name.to_string(),
+            full_file_name.to_string(),
+            file_change_stats,
+            trigger,
+        );
+        // High priority changes are pushed right away, bypassing push batching
+        let push = match repo.workdir() {
+            Some(workdir)
+                if Self::event_priority(self, workdir, &[workdir.join(short_file_name)])
+                    == Priority::High =>
+            {
+                PushPolicy::Immediate
+            }
+            _ => PushPolicy::Batched,
+        };
+        let pipeline = CommitPipeline {
+            kind,
+            stage,
+            values,
+            push,
+            replaces,
+        };
+        Self::run_pipeline(self, repo, &repo_branch, pipeline, trigger)
+    }
+
+    /// Runs a commit pipeline: stages, commits, records, pushes and tags
+    ///
+    /// # Arguments
+    /// - `repo` - The repository the change was made in.
+    /// - `branch` - The branch the commit is made on, see `commit_branch`.
+    /// - `pipeline` - The commit to make.
+    /// - `trigger` - The event the commit is made for.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if a path is sensitive or staging,
+    /// committing, pushing or tagging fails.
+    fn run_pipeline(
+        &self,
+        repo: &Repository,
+        branch: &str,
+        pipeline: CommitPipeline,
+        trigger: &Trigger,
+    ) -> Result<(), GitAutoPilotError> {
+        if self.dry_run {
+            // Nothing is staged, so the message is rendered without a diff
+            let (message, description) =
+                Self::commit_summary(self, repo, pipeline.kind, &pipeline.values)?;
+            println!(
+                "would commit {} on {}: {}",
+                pipeline.path(),
+                branch,
+                message
+            );
+            for line in description.lines() {
+                println!("    {}", line);
+            }
+            return Ok(());
+        }
+        let commit_time = Self::resolve_commit_time(self, repo, trigger.received_at);
+        let signer = Self::commit_signer(self, repo);
+        let author = Self::commit_author(self, repo, &pipeline.author_path());
+
+        let Some(failure) = Self::verify_working_tree(self, repo, &pipeline.stage) else {
+            // Retried once the verification running in the background finished
+            return Ok(());
+        };
+        let verify_failure = Self::repo_config(self, repo).verify_failure;
+        match (&failure, verify_failure) {
+            (None, _) => Self::retry_unverified(self, repo),
+            (Some(failure), VerifyFailure::Skip) => {
+                warn!("Not committing {}: {}", pipeline.path(), failure);
+                if let Some(workdir) = repo.workdir() {
+                    let paths = pipeline.stage.paths();
+                    self.state.with_repo(workdir, |state| {
+                        state
+                            .unverified_paths
+                            .extend(paths.iter().map(|path| workdir.join(path)))
+                    });
+                }
+                return Ok(());
+            }
+            (Some(_), _) => {}
+        }
+
+        Self::stage(self, repo, &pipeline.stage)?;
+        let (mut message, description) =
+            Self::commit_summary(self, repo, pipeline.kind, &pipeline.values)?;
+        if let Some(failure) = failure {
+            match verify_failure {
+                // Nothing was staged
+                VerifyFailure::Skip => {}
+                VerifyFailure::Quarantine => {
+                    let branch =
+                        format!("{}/quarantine", Self::repo_config(self, repo).branch_prefix);
+                    warn!("Committing {} to {}: {}", pipeline.path(), branch, failure);
+                    git::commit_to_branch(
+                        repo,
+                        &branch,
+                        &format!("{}\n\n{}\n\n{}", message, description, failure),
+                        Self::fallback_identity(self).as_ref(),
+                    )?;
+                    git::unstage_paths(repo, &pipeline.stage.paths())?;
+                    return Ok(());
+                }
+                VerifyFailure::Mark => {
+                    warn!("Committing {} marked: {}", pipeline.path(), failure);
+                    message = format!("[verify-failed] {}", message);
+                }
+            }
+        }
+        let fallback = Self::fallback_identity(self);
+        match (signer, repo.workdir()) {
+            // Signing is left to libgit2, other backends only make plain commits
+            (None, Some(workdir)) => {
+                self.backend.commit(
+                    workdir,
+                    &format!("{}\n\n{}", message, description),
+                    commit_time,
+                    author.as_ref(),
+                    fallback.as_ref(),
+                )?;
+            }
+            (signer, _) => git::commit(
+                repo,
+                &message,
+                Some(&description),
+                commit_time,
+                signer.as_ref(),
+                author.as_ref(),
+                fallback.as_ref(),
+            )?,
+        }
+        Self::attach_note(self, repo, &pipeline, trigger);
+        if let Some(workdir) = repo.workdir() {
+            if Self::repo_config(self, repo).content_hashing {
+                self.content_hashes.record_committed(
+                    &state::repo_key(workdir),
+                    pipeline
+                        .stage
+                        .paths()
+                        .into_iter()
+                        .map(|path| workdir.join(path)),
+                );
+            }
+        }
+        if let Some(replaced) = pipeline.replaces {
+            self.reverted_contents.record(
+                &state::repo_key(repo.workdir().unwrap_or(repo.path())),
+                pipeline.path(),
+                replaced,
+                SystemTime::now(),
+            );
+        }
+        Self::record_commit(self, repo, &message, Some(pipeline.path()));
+
+        Self::push_commit(self, repo, branch)?;
+        if pipeline.push == PushPolicy::Immediate {
+            if let Some(workdir) = repo.workdir() {
+                Self::push_pending(self, workdir, true)?;
+            }
+        }
+        Self::apply_tag_rule(self, repo, branch)
+    }
+
+    /// Attaches the metadata of a new commit as a git note, if configured
+    ///
+    /// A failure only logs a warning, the commit itself is already made.
+    fn attach_note(&self, repo: &Repository, pipeline: &CommitPipeline, trigger: &Trigger) {
+        let count = |key: &str| {
+            pipeline
+                .values
+                .get(key)
+                .and_then(|value| value.parse::<u64>().ok())
+        };
+        Self::write_note(
+            self,
+            repo,
+            trigger,
+            serde_json::json!({
+                "change": pipeline.kind,
+                "path": pipeline.path(),
+                "stats": {
+                    "insertions": count("INSERTIONS"),
+                    "deletions": count("DELETIONS"),
+                    "files": count("FILE_COUNT").unwrap_or(1),
+                },
+            }),
+        );
+    }
+
+    /// Attaches a metadata note to the `HEAD` commit if `notes` are enabled
+    ///
+    /// # Arguments
+    /// - `repo` - The repository the commit was made in.
+    /// - `trigger` - The event the commit was made for.
+    /// - `commit` - What the commit changed: `change`, `path` and `stats`.
+    fn write_note(&self, repo: &Repository, trigger: &Trigger, commit: serde_json::Value) {
+        if Self::repo_config(self, repo).notes.is_none() {
+            return;
+        }
+        let mut metadata = serde_json::json!({
+            "version": 1,
+            "event": {
+                "kind": trigger.kind,
+                "source": trigger.source,
+                "received_at": humantime::format_rfc3339_seconds(trigger.received_at).to_string(),
+            },
+            "committed_at": humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        });
+        if let (serde_json::Value::Object(metadata), serde_json::Value::Object(commit)) =
+            (&mut metadata, commit)
+        {
+            metadata.extend(commit);
+        }
+        if let Err(err) = git::add_note(
+            repo,
+            &metadata.to_string(),
+            Self::fallback_identity(self).as_ref(),
+        ) {
+            warn!("Failed to attach the metadata note: {}", err);
+        }
+    }
+
+    /// Runs the `verify_command` of a repository on its working tree
+    ///
+    /// In the watch loop the command runs as a background job: the paths of
+    /// the change are deferred until it finished, and its outcome is reused
+    /// until the next event changes the working tree. Elsewhere (one-off
+    /// commits) the command runs right away.
+    ///
+    /// # Returns
+    /// The description of the failure (`Some(None)` if the working tree
+    /// passed or no command is configured), `None` if the change was deferred.
+    fn verify_working_tree(&self, repo: &Repository, stage: &StagePlan) -> Option<Option<String>> {
+        let config = Self::repo_config(self, repo);
+        let (Some(command), Some(workdir)) = (config.verify_command.clone(), repo.workdir()) else {
+            return Some(None);
+        };
+        let timeout = Duration::from_secs(config.verify_timeout_secs);
+        if !self.watching {
+            let failure = verify::run(workdir, &command, timeout).err();
+            Self::record_verification(self, workdir, None, failure.clone());
+            return Some(failure);
+        }
+
+        let verification = self.state.with_repo(workdir, |state| {
+            let generation = state.tree_generation;
+            match &state.verification {
+                Some(verification) if verification.generation == generation => {
+                    Ok(verification.failure.clone())
+                }
+                _ => {
+                    state
+                        .deferred_paths
+                        .extend(stage.paths().into_iter().map(|path| workdir.join(path)));
+                    Err(generation)
+                }
+            }
+        });
+        let generation = match verification {
+            Ok(failure) => return Some(failure),
+            Err(generation) => generation,
+        };
+        let job_workdir = workdir.to_path_buf();
+        let started = self.jobs.start(workdir, "verify", move || {
+            let failure = verify::run(&job_workdir, &command, timeout).err();
+            let job_done: JobDone = Box::new(move |git_auto_pilot: &GitAutoPilot| {
+                Self::record_verification(git_auto_pilot, &job_workdir, Some(generation), failure);
+            });
+            job_done
+        });
+        if started {
+            debug!("Verifying {} in the background", workdir.display());
+        }
+        None
+    }
+
+    /// Records the outcome of `verify_command`
+    ///
+    /// # Arguments
+    /// - `workdir` - The working tree the command ran in.
+    /// - `generation` - The `tree_generation` the command ran on, `None` if
+    ///   the outcome is not reused.
+    /// - `failure` - The description of the failure, `None` if it passed.
+    fn record_verification(
+        &self,
+        workdir: &Path,
+        generation: Option<u64>,
+        failure: Option<String>,
+    ) {
+        self.state.with_repo(workdir, |state| {
+            state.last_verify = Some(state::VerifyRecord {
+                at: SystemTime::now(),
+                error: failure.clone(),
+            });
+            if let Some(generation) = generation {
+                state.verification = Some(state::TreeVerification {
+                    generation,
+                    failure,
+                });
+            }
+        });
+    }
+
+    /// Defers the changes left uncommitted by failed verifications, once a
+    /// later change passes
+    fn retry_unverified(&self, repo: &Repository) {
+        let Some(workdir) = repo.workdir() else {
+            return;
+        };
+        self.state.with_repo(workdir, |state| {
+            let unverified = std::mem::take(&mut state.unverified_paths);
+            state.deferred_paths.extend(unverified);
+        });
+    }
+
+    /// Updates the index according to a stage plan
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if a staged path is sensitive or the
+    /// index cannot be updated.
+    fn stage(&self, repo: &Repository, plan: &StagePlan) -> Result<(), GitAutoPilotError> {
+        match plan {
+            StagePlan::Add(path) => {
+                Self::ensure_not_sensitive(self, repo, path)?;
+                git::stage_file(repo, path, false)?;
+            }
+            StagePlan::AddLfs(path) => {
+                Self::ensure_not_sensitive(self, repo, path)?;
+                git::track_with_lfs(repo, path)?;
+                git::stage_file(repo, path, false)?;
+            }
+            StagePlan::Remove(path) => {
+                Self::keep_in_trash(self, repo, path)?;
+                git::stage_file(repo, path, true)?;
+            }
+            StagePlan::Rename { old, new } => {
+                Self::ensure_not_sensitive(self, repo, new)?;
+                match old {
+                    Some(old) => git::stage_rename(repo, old, new)?,
+                    None => git::stage_file(repo, new, false)?,
+                }
+            }
+            StagePlan::RenameDir { files, .. } => {
+                for (_, new_path) in files.iter() {
+                    Self::ensure_not_sensitive(self, repo, new_path)?;
+                }
+                for (old_path, new_path) in files.iter() {
+                    git::stage_rename(repo, old_path, new_path)?;
+                }
+            }
+            StagePlan::AddDir {
+                files, lfs_files, ..
+            } => {
+                // Staged file by file: the files were filtered (skip markers,
+                // sensitive paths, large files), staging the directory would
+                // take them all
+                for file in files.iter() {
+                    if !Self::is_sensitive(self, repo, file) {
+                        git::stage_file(repo, file, false)?;
+                    }
+                }
+                for file in lfs_files.iter() {
+                    if !Self::is_sensitive(self, repo, file) {
+                        git::track_with_lfs(repo, file)?;
+                        git::stage_file(repo, file, false)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Switches to the autopilot branch, if one is configured
+    ///
+    /// In dry-run mode the branch is only named.
+    ///
+    /// # Returns
+    /// The name of the branch commits are made on.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the branch cannot be checked out.
+    fn commit_branch(&self, repo: &Repository) -> Result<String, GitAutoPilotError> {
+        if let Some(branch_name) = Self::autopilot_branch_name(self, repo) {
+            if self.dry_run {
+                return Ok(branch_name);
+            }
+            git::switch_to_branch(repo, &branch_name)?;
+        }
+        Ok(git::get_current_branch(repo).unwrap_or("master".to_string()))
+    }
+
+    /// Commits all files of a renamed directory as a single rename commit
+    ///
+    /// # Arguments
+    /// - `repo` - The repository the directory was renamed in.
+    /// - `directory_rename` - The detected directory rename.
+    /// - `trigger` - The event the commit is made for.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if staging, committing or pushing fails.
+    fn take_directory_rename_action(
+        &self,
+        repo: &Repository,
+        directory_rename: &git::DirectoryRename,
+        trigger: &Trigger,
+    ) -> Result<(), GitAutoPilotError> {
+        let repo_branch = Self::commit_branch(self, repo)?;
+        let full_dir_name = repo
+            .workdir()
+            .map(|workdir| workdir.join(&directory_rename.new_dir))
+            .unwrap_or_else(|| PathBuf::from(&directory_rename.new_dir));
+        let mut dynamic_values = Self::prepare_dynamic_values(
+            self,
+            repo,
+            &repo_branch,
+            directory_rename.new_dir.clone(),
+            full_dir_name.display().to_string(),
+            &directory_rename.stats,
+            trigger,
+        );
+        dynamic_values.insert("OLD_DIR".to_string(), directory_rename.old_dir.clone());
+        dynamic_values.insert("NEW_DIR".to_string(), directory_rename.new_dir.clone());
+        dynamic_values.insert(
+            "FILE_COUNT".to_string(),
+            directory_rename.files.len().to_string(),
+        );
+        let pipeline = CommitPipeline {
+            kind: ChangeKind::RenameDir,
+            stage: StagePlan::RenameDir {
+                dir: &directory_rename.new_dir,
+                files: &directory_rename.files,
+            },
+            values: dynamic_values,
+            push: PushPolicy::Batched,
+            replaces: None,
+        };
+        Self::run_pipeline(self, repo, &repo_branch, pipeline, trigger)
+    }
+
+    /// Commits all files of a new project scaffold as a single commit
+    ///
+    /// # Arguments
+    /// - `repo` - The repository the scaffold appeared in.
+    /// - `scaffold` - The detected scaffold.
+    /// - `trigger` - The event the commit is made for.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if staging, committing or pushing fails.
+    fn take_scaffold_action(
+        &self,
+        repo: &Repository,
+        scaffold: &git::Scaffold,
+        trigger: &Trigger,
+    ) -> Result<(), GitAutoPilotError> {
+        let Some(workdir) = repo.workdir() else {
+            return Ok(());
+        };
+        // NOTE: large files follow the large file policy like single files do
+        let mut files = Vec::new();
+        let mut lfs_files = Vec::new();
+        for file in &scaffold.files {
+            let full_file_name = workdir.join(file).display().to_string();
+            match Self::large_file_action(self, repo, file, &full_file_name) {
+                Some(LargeFileAction::Skip) => {}
+                Some(LargeFileAction::Lfs) => lfs_files.push(file.clone()),
+                None => files.push(file.clone()),
+            }
+        }
+        if files.is_empty() && lfs_files.is_empty() {
+            return Ok(());
+        }
+
+        let repo_branch = Self::commit_branch(self, repo)?;
+        let mut dynamic_values = Self::prepare_dynamic_values(
+            self,
+            repo,
+            &repo_branch,
+            scaffold.dir.clone(),
+            workdir.join(&scaffold.dir).display().to_string(),
+            &scaffold.stats,
+            trigger,
+        );
+        dynamic_values.insert("DIR".to_string(), scaffold.dir.clone());
+        dynamic_values.insert(
+            "FILE_COUNT".to_string(),
+            (files.len() + lfs_files.len()).to_string(),
+        );
+        let pipeline = CommitPipeline {
+            kind: ChangeKind::Scaffold,
+            stage: StagePlan::AddDir {
+                dir: &scaffold.dir,
+                files: &files,
+                lfs_files: &lfs_files,
+            },
+            values: dynamic_values,
+            push: PushPolicy::Batched,
+            replaces: None,
+        };
+        Self::run_pipeline(self, repo, &repo_branch, pipeline, trigger)
+    }
+
+    /// Generates the commit summary and description for a staged change
+    ///
+    /// Uses the configured message generator and falls back to the templates
+    /// if it fails. The commit convention of the repository is merged in.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the templates or the commit convention
+    /// reference unknown variables and `unknown_variables` is `fail`.
+    pub(crate) fn commit_summary(
+        &self,
+        repo: &Repository,
+        kind: ChangeKind,
+        dynamic_values: &HashMap<String, String>,
+    ) -> Result<(String, String), GitAutoPilotError> {
+        if self.message_generator.uses_templates() {
+            Self::check_template_variables(self, repo, kind, dynamic_values)?;
+        }
+        let diff = self
+            .message_generator
+            .needs_diff()
+            .then(|| git::staged_diff(repo))
+            .transpose()
+            .unwrap_or_else(|err| {
+                warn!("Failed to compute staged diff: {}", err);
+                None
+            });
+        let change = generator::Change {
+            kind,
+            values: dynamic_values,
+            diff: diff.as_deref(),
+        };
+        let config = Self::repo_config(self, repo);
+        let (message, description) =
+            self.message_generator
+                .generate(config, &change)
+                .or_else(|err| {
+                    warn!("Falling back to message templates: {}", err);
+                    if !self.message_generator.uses_templates() {
+                        Self::check_template_variables(self, repo, kind, dynamic_values)?;
+                    }
+                    generator::TemplateGenerator.generate(config, &change)
+                })?;
+        let message = generator::annotate_repeat(message, config.repeat_annotation, dynamic_values);
+        let (Some(conventions), Some(convention)) = (
+            config.commit_conventions.as_ref(),
+            Self::commit_convention(self, repo),
+        ) else {
+            return Ok((message, description));
+        };
+
+        let (merged_message, merged_description) = generator::apply_convention(
+            message.clone(),
+            description.clone(),
+            &convention,
+            conventions.mode,
+            dynamic_values,
+        );
+        let unresolved = generator::unresolved_convention_variables(
+            [message.as_str(), description.as_str()],
+            [merged_message.as_str(), merged_description.as_str()],
+        );
+        Self::report_unresolved_variables(self, repo, unresolved)?;
+        Ok((merged_message, merged_description))
+    }
+
+    /// Reads the commit convention of a repository, see `commit_conventions`
+    ///
+    /// # Returns
+    /// The convention, `None` if none is configured or it cannot be read.
+    fn commit_convention(&self, repo: &Repository) -> Option<String> {
+        let conventions = Self::repo_config(self, repo).commit_conventions.as_ref()?;
+        let path = conventions
+            .commit_template
+            .then(|| git::commit_template_path(repo))
+            .flatten()
+            .or_else(|| {
+                let workdir = repo.workdir()?;
+                conventions.file.as_ref().map(|file| workdir.join(file))
+            })?;
+        let text = fs::read_to_string(&path)
+            .map_err(|err| {
+                warn!(
+                    "Failed to read commit convention {}: {}",
+                    path.display(),
+                    err
+                )
+            })
+            .ok()?;
+        let markdown = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("md"));
+        generator::parse_convention(&text, markdown)
+    }
+
+    /// Checks the templates of a change for placeholders without a value
+    ///
+    /// Unresolved variables are recorded for the status and `doctor` output.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if variables are unresolved and
+    /// `unknown_variables` is `fail`.
+    fn check_template_variables(
+        &self,
+        repo: &Repository,
+        kind: ChangeKind,
+        dynamic_values: &HashMap<String, String>,
+    ) -> Result<(), GitAutoPilotError> {
+        let config = Self::repo_config(self, repo);
+        let unresolved = generator::unresolved_variables(config, kind, dynamic_values);
+        Self::report_unresolved_variables(self, repo, unresolved)
+    }
+
+    /// Records placeholders without a value and applies `unknown_variables`
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if any placeholder is unresolved and
+    /// `unknown_variables` is `fail`.
+    fn report_unresolved_variables(
+        &self,
+        repo: &Repository,
+        unresolved: BTreeSet<String>,
+    ) -> Result<(), GitAutoPilotError> {
+        if unresolved.is_empty() {
+            return Ok(());
+        }
+
+        let config = Self::repo_config(self, repo);
+
+        if let Some(workdir) = repo.workdir() {
+            self.state.with_repo(workdir, |state| {
+                state.template_failures += 1;
+                state
+                    .unresolved_variables
+                    .extend(unresolved.iter().cloned());
+            });
+        }
+        let names = unresolved.into_iter().collect::<Vec<_>>().join(", ");
+        match config.unknown_variables {
+            UnknownVariables::Ignore => {
+                debug!("Unresolved template variables: {}", names);
+                Ok(())
+            }
+            UnknownVariables::Warn => {
+                warn!(
+                    "Unresolved template variables left in the commit message: {}",
+                    names
+                );
+                Ok(())
+            }
+            UnknownVariables::Fail => Err(GitAutoPilotError::ConfigError(
+                ConfigError::TemplateError(format!("unresolved template variables: {}", names)),
+            )),
+        }
+    }
+
+    /// Commits every pending change of a repository, grouping directory
+    /// renames and project scaffolds like the watcher does
+    ///
+    /// Failures of single paths are logged and do not stop the others.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the repository cannot be analyzed.
+    pub(crate) fn commit_pending_changes(
+        &self,
+        repo: &Repository,
+        repo_path: &Path,
+        trigger: &Trigger,
+    ) -> Result<(), GitAutoPilotError> {
+        let mut git_changes = git::analyze_repository_changes(repo, &[])?;
+        let workdir = repo.workdir().unwrap_or(repo_path).to_path_buf();
+        git_changes.retain(|path, _| {
+            let full_path = workdir.join(path);
+            !Self::is_in_ignored_dir(self, &full_path)
+                && Self::is_watched(self, repo_path, &[full_path])
+                && !Self::has_skip_marker(self, repo, path)
+                && !Self::is_sensitive(self, repo, path)
+        });
+
+        for directory_rename in git::detect_directory_renames(repo, &git_changes) {
+            for (_, new_path) in &directory_rename.files {
+                git_changes.remove(new_path);
+            }
+            if let Err(err) = Self::guarded_action(self, repo, &directory_rename.new_dir, || {
+                Self::take_directory_rename_action(self, repo, &directory_rename, trigger)
+            }) {
+                error!("Failed to sync {}: {}", directory_rename.new_dir, err);
+            }
+        }
+        for scaffold in git::detect_scaffolds(
+            repo,
+            &git_changes,
+            Self::repo_config(self, repo).scaffold_threshold,
+        )? {
+            for scaffold_file in &scaffold.files {
+                git_changes.remove(scaffold_file);
+            }
+            if let Err(err) = Self::guarded_action(self, repo, &scaffold.dir, || {
+                Self::take_scaffold_action(self, repo, &scaffold, trigger)
+            }) {
+                error!("Failed to sync {}: {}", scaffold.dir, err);
+            }
+        }
+
+        for (changed_path, stats) in &git_changes {
+            let Some(file_changes) = stats.first() else {
+                continue;
+            };
+            let full_file_name = workdir.join(changed_path);
+            if let Err(err) = Self::guarded_action(self, repo, changed_path, || {
+                Self::take_action(
+                    self,
+                    repo,
+                    file_changes,
+                    changed_path,
+                    full_file_name.to_str().unwrap_or(changed_path),
+                    trigger,
+                )
+            }) {
+                error!("Failed to sync {}: {}", changed_path, err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates and pushes a milestone tag when the configured tag rule fires
+    ///
+    /// While the commit is waiting in a push batch (or for a retry), the tag
+    /// is pushed along with it instead.
+    ///
+    /// # Arguments
+    /// - `repo` - The repository a commit was just made in.
+    /// - `branch` - The branch the commit was made on.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the tag cannot be created or pushed.
+    fn apply_tag_rule(&self, repo: &Repository, branch: &str) -> Result<(), GitAutoPilotError> {
+        let Some(rule) = Self::repo_config(self, repo).tagging.as_ref() else {
+            return Ok(());
+        };
+        let workdir = repo.workdir().unwrap_or(repo.path()).to_path_buf();
+        let now = SystemTime::now();
+
+        let commit_count = self.state.with_repo(&workdir, |state| {
+            state.commits_since_tag += 1;
+            let last_tag_at = *state.last_tag_at.get_or_insert(now);
+
+            let count_reached = rule
+                .every_n_commits
+                .is_some_and(|every| every > 0 && state.commits_since_tag >= every);
+            let interval_elapsed = rule.interval_secs.is_some_and(|interval| {
+                now.duration_since(last_tag_at)
+                    .is_ok_and(|elapsed| elapsed.as_secs() >= interval)
+            });
+
+            if !count_reached && !interval_elapsed {
+                return None;
+            }
+            let count = state.commits_since_tag;
+            state.commits_since_tag = 0;
+            state.last_tag_at = Some(now);
+            Some(count)
+        });
+        let Some(commit_count) = commit_count else {
+            return Ok(());
+        };
+
+        // RFC 3339 timestamps look like `YYYY-MM-DDTHH:MM:SSZ`
+        let timestamp = humantime::format_rfc3339_seconds(now).to_string();
+        let mut values: HashMap<String, String> = HashMap::new();
+        values.insert("BRANCH".to_string(), branch.to_string());
+        values.insert("DATE".to_string(), timestamp[..10].to_string());
+        values.insert("TIME".to_string(), timestamp[11..19].replace(':', ""));
+        values.insert("COMMIT_COUNT".to_string(), commit_count.to_string());
+
+        let name = byteutils::string::replace_multiple_placeholders(&rule.name, &values);
+        let message = byteutils::string::replace_multiple_placeholders(&rule.message, &values);
+        git::create_tag(
+            repo,
+            &name,
+            &message,
+            Self::fallback_identity(self).as_ref(),
+        )?;
+        if !self.push_enabled || !Self::repo_config(self, repo).push_enabled {
+            return Ok(());
+        }
+
+        // The tag must not reach the remote before the commit it points to
+        let deferred = self.state.with_repo(&workdir, |state| {
+            let waiting = state.unpushed_commits > 0;
+            if waiting {
+                state.unpushed_tags.push(name.clone());
+            }
+            waiting
+        });
+        if deferred {
+            debug!(
+                "Deferring push of tag {} until its commits are pushed",
+                name
+            );
+            return Ok(());
+        }
+
+        let (username, password) = Self::push_credentials(self)?;
+        helper::ensure_push_allowed(
+            repo,
+            "origin",
+            &Self::repo_config(self, repo).allowed_push_hosts,
+        )?;
+        self.push_limiter
+            .run(&helper::remote_host(repo, "origin"), || {
+                git::push_refspecs(
+                    repo,
+                    &username,
+                    &password,
+                    "origin",
+                    &[format!("refs/tags/{}", name)],
+                    &Self::repo_config(self, repo).push_options,
+                    &Self::remote_settings(self),
+                )
+            })
+            .map_err(GitAutoPilotError::from_push_error)?;
+        Ok(())
+    }
+
+    /// Determines the branch auto-commits should go to based on the `branch_strategy` setting
+    ///
+    /// # Returns
+    /// `None` when committing on the current branch, otherwise the branch name.
+    pub(crate) fn autopilot_branch_name(&self, repo: &Repository) -> Option<String> {
+        let config = Self::repo_config(self, repo);
+        match config.branch_strategy {
+            BranchStrategy::Current => None,
+            BranchStrategy::PerDay => {
+                // RFC 3339 timestamps start with the `YYYY-MM-DD` date
+                let now = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+                Some(format!("{}/{}", config.branch_prefix, &now[..10]))
+            }
+            BranchStrategy::PerSession => {
+                let started = humantime::format_rfc3339_seconds(self.session_started)
+                    .to_string()
+                    .replace(['-', ':'], "");
+                Some(format!("{}/session-{}", config.branch_prefix, started))
+            }
+        }
+    }
+
+    /// Resolves the timestamp to use for a commit based on the `commit_time` setting
+    ///
+    /// # Returns
+    /// `None` when the current time should be used, otherwise the explicit commit time.
+    fn resolve_commit_time(&self, repo: &Repository, received_at: SystemTime) -> Option<Time> {
+        let seconds_since_epoch = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs() as i64)
+                .unwrap_or_default()
+        };
+
+        let config = Self::repo_config(self, repo);
+        match config.commit_time {
+            CommitTime::Now => None,
+            CommitTime::Event => {
+                // Keep the local timezone git would otherwise use
+                let offset = repo
+                    .signature()
+                    .map(|signature| signature.when().offset_minutes())
+                    .unwrap_or_default();
+                Some(Time::new(seconds_since_epoch(received_at), offset))
+            }
+            CommitTime::FixedOffset => Some(Time::new(
+                seconds_since_epoch(SystemTime::now()),
+                config.commit_time_offset_minutes,
+            )),
+        }
+    }
+
+    pub(crate) fn prepare_dynamic_values(
+        &self,
+        repo: &Repository,
+        branch: &str,
+        short_file_name: String,
+        full_file_name: String,
+        file_change_stats: &FileChangeStats,
+        trigger: &Trigger,
+    ) -> HashMap<String, String> {
+        let mut dynamic_values: HashMap<String, String> = HashMap::new();
+        dynamic_values.insert("BRANCH".to_string(), branch.to_owned());
+        dynamic_values.insert(
+            "STATUS".to_string(),
+            helper::status_to_string(file_change_stats.status),
+        );
+        dynamic_values.insert("FILE_NAME_SHORT".to_string(), short_file_name.to_owned());
+        let package = repo
+            .workdir()
+            .and_then(|workdir| helper::package_name(workdir, &short_file_name));
+        dynamic_values.insert("PACKAGE".to_string(), package.unwrap_or_default());
+        dynamic_values.insert("FILE_NAME_FULL".to_string(), full_file_name.to_owned());
+        match ChangeKind::from_status(file_change_stats.status) {
+            ChangeKind::Rename => {
+                dynamic_values.insert(
+                    "FILE_OLD_NAME".to_string(),
+                    file_change_stats
+                        .old_name
+                        .clone()
+                        .unwrap_or_else(|| short_file_name.clone()),
+                );
+            }
+            _ => {
+                dynamic_values.insert("FILE_OLD_NAME".to_string(), short_file_name.clone());
+            }
+        }
+        dynamic_values.insert(
+            "DELETIONS".to_string(),
+            file_change_stats.lines_deleted.to_string(),
+        );
+        dynamic_values.insert(
+            "LINES_MODIFIED".to_string(),
+            file_change_stats.lines_modified.to_string(),
+        );
+        dynamic_values.insert(
+            "INSERTIONS".to_string(),
+            file_change_stats.lines_added.to_string(),
+        );
+        dynamic_values.insert("EVENT_KIND".to_string(), trigger.kind.to_string());
+        dynamic_values.insert("EVENT_SOURCE".to_string(), trigger.source.clone());
+        let file_path = Path::new(&full_file_name);
+        dynamic_values.insert(
+            "FILE_SIZE".to_string(),
+            helper::format_file_size(fs::metadata(file_path).map_or(0, |metadata| metadata.len())),
+        );
+        dynamic_values.insert(
+            "FILE_MIME".to_string(),
+            helper::detect_mime_type(file_path).to_string(),
+        );
+        if let Some(workdir) = repo.workdir() {
+            // Count the commit being made, so the first commit of a day reads 1
+            let today = Self::stats_day(repo);
+            let mut repo_stats =
+                stats::repo_stats(&self.state_dir_location, &state::repo_key(workdir));
+            repo_stats.record_commit(today, Some(&short_file_name));
+            dynamic_values.insert(
+                "TODAY_COMMITS".to_string(),
+                repo_stats.today_commits(today).to_string(),
+            );
+            dynamic_values.insert(
+                "TOTAL_COMMITS".to_string(),
+                repo_stats.total_commits.to_string(),
+            );
+            dynamic_values.insert(
+                "STREAK".to_string(),
+                repo_stats.current_streak(today).to_string(),
+            );
+            dynamic_values.insert(
+                "SEQ".to_string(),
+                repo_stats.file_commits(today, &short_file_name).to_string(),
+            );
+        }
+
+        // Insert system variables into the HashMap
+        for &(key, value) in SYSTEM_VARIABLES {
+            dynamic_values.insert(
+                key.to_string(),
+                byteutils::string::replace_multiple_placeholders(
+                    &format!("{{{{{}}}}}", value),
+                    &dynamic_values,
+                ),
+            );
+        }
+
+        if let serde_json::Value::Object(config_map) = &Self::repo_config(self, repo).variables {
+            for (key, value) in config_map {
+                if let serde_json::Value::String(ref val) = value {
+                    if !dynamic_values.contains_key(key) {
+                        dynamic_values.insert(key.to_string(), val.to_string());
+                    }
+                }
+            }
+        }
+        trace!("dynamic_values={:#?}", dynamic_values);
+        dynamic_values
+    }
+
+    /// Remembers a commit made in a repository for the status output and
+    /// counts it in the persistent statistics
+    ///
+    /// # Arguments
+    /// - `repo` - The repository the commit was made in.
+    /// - `message` - Summary of the commit.
+    /// - `file` - The file (or directory) the commit is about, counted for `SEQ`.
+    fn record_commit(&self, repo: &Repository, message: &str, file: Option<&str>) {
+        if let Some(workdir) = repo.workdir() {
+            self.analysis_cache.invalidate(&state::repo_key(workdir));
+            self.state
+                .with_repo(workdir, |state| state.record_commit(message));
+            Self::refresh_upstream(self, repo);
+            self.session_commits.fetch_add(1, Ordering::Relaxed);
+            let day = Self::stats_day(repo);
+            if let Err(err) = stats::record_commit(
+                &self.state_dir_location,
+                &state::repo_key(workdir),
+                day,
+                file,
+            ) {
+                error!("Failed to record statistics of {:?}: {}", workdir, err);
+            }
+        }
+    }
+
+    /// Returns the current local day of a repository for its statistics
+    pub(crate) fn stats_day(repo: &Repository) -> i64 {
+        let offset = repo
+            .signature()
+            .map(|signature| signature.when().offset_minutes())
+            .unwrap_or_default();
+        stats::local_day(SystemTime::now(), offset)
+    }
+
+    /// Resolves the author of a commit changing a path from the `authors` rules
+    ///
+    /// # Arguments
+    /// - `repo` - The repository the commit is made in.
+    /// - `path` - The changed path relative to the repository root; directories
+    ///   end with `/`.
+    ///
+    /// # Returns
+    /// `None` if no rule matches and the configured git user is the author.
+    fn commit_author(&self, repo: &Repository, path: &str) -> Option<git::CommitAuthor> {
+        let rule = Self::repo_config(self, repo)
+            .authors
+            .iter()
+            .rev()
+            .find(|rule| helper::matches_path_pattern(path, &rule.pattern))?;
+        trace!("Authoring {} as {} <{}>", path, rule.name, rule.email);
+        Some(git::CommitAuthor {
+            name: rule.name.clone(),
+            email: rule.email.clone(),
+        })
+    }
+
+    /// Builds the external commit signer configured for a repository
+    ///
+    /// # Returns
+    /// `None` if commits of the repository are not signed.
+    fn commit_signer(&self, repo: &Repository) -> Option<git::CommitSigner> {
+        let signing = Self::repo_config(self, repo).signing.as_ref()?;
+        let (program, args) = match signing.format {
+            SigningFormat::Gpg => ("gpg", vec!["--status-fd=2", "-bsau", signing.key.as_str()]),
+            SigningFormat::Ssh => (
+                "ssh-keygen",
+                vec!["-Y", "sign", "-n", "git", "-f", signing.key.as_str()],
+            ),
+        };
+        Some(git::CommitSigner {
+            program: signing
+                .program
+                .clone()
+                .unwrap_or_else(|| program.to_string()),
+            args: args.into_iter().map(String::from).collect(),
+        })
+    }
+
+    /// Returns the configured git credentials as identity for repositories without one
+    ///
+    /// The repository's own `user.name` and `user.email` take precedence, see
+    /// `git::identity`.
+    pub(crate) fn fallback_identity(&self) -> Option<git::CommitAuthor> {
+        self.config
+            .git_credentials
+            .as_ref()
+            .map(|cred| git::CommitAuthor {
+                name: cred.username.clone(),
+                email: cred.email.clone(),
+            })
+    }
+
+    /// Resolves the processing priority of an event from `priority` and
+    /// `priority_rules`
+    ///
+    /// # Arguments
+    /// - `repo` - Path of the repository the event belongs to.
+    /// - `paths` - Paths of the event.
+    ///
+    /// # Returns
+    /// The highest priority of the paths.
+    pub(crate) fn event_priority(&self, repo: &Path, paths: &[PathBuf]) -> Priority {
+        let config = self
+            .repo_configs
+            .get(&state::repo_key(repo))
+            .unwrap_or(&self.config);
+        paths
+            .iter()
+            .map(|path| {
+                let relative = path.strip_prefix(repo).unwrap_or(path).to_string_lossy();
+                config
+                    .priority_rules
+                    .iter()
+                    .rev()
+                    .find(|rule| helper::matches_path_pattern(&relative, &rule.pattern))
+                    .map_or(config.priority, |rule| rule.priority)
+            })
+            .max()
+            .unwrap_or(config.priority)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use crate::tests::{
+        commit_new_file, committed_paths, head_summary, new_file_stats, test_instance,
+    };
+    use git2::Status;
+
+    /// Configuration running a verify command with the given failure handling
+    fn verify_config(command: &str, verify_failure: VerifyFailure) -> config::Config {
+        config::Config {
+            verify_command: Some(command.to_string()),
+            verify_failure,
+            ..config::Config::default()
+        }
+    }
+
+    #[test]
+    fn test_verify_failure_skip_retries_once_a_change_passes() {
+        let (_dir, repo, git_auto_pilot) =
+            test_instance(verify_config("test ! -e broken", VerifyFailure::Skip));
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        fs::write(workdir.join("README.md"), "staged by the user\n").unwrap();
+        git::stage_file(&repo, "README.md", false).unwrap();
+        fs::write(workdir.join("broken"), "").unwrap();
+
+        commit_new_file(&git_auto_pilot, &repo, "notes.md");
+        assert_eq!(head_summary(&repo), "initial");
+        assert_eq!(
+            repo.status_file(Path::new("notes.md")).unwrap(),
+            Status::WT_NEW
+        );
+        assert_eq!(
+            repo.status_file(Path::new("README.md")).unwrap(),
+            Status::INDEX_MODIFIED
+        );
+        let unverified = git_auto_pilot
+            .state
+            .with_repo(&workdir, |state| state.unverified_paths.clone());
+        assert_eq!(unverified, BTreeSet::from([workdir.join("notes.md")]));
+
+        fs::remove_file(workdir.join("broken")).unwrap();
+        commit_new_file(&git_auto_pilot, &repo, "todo.md");
+        assert_ne!(head_summary(&repo), "initial");
+        let (unverified, deferred) = git_auto_pilot.state.with_repo(&workdir, |state| {
+            (state.unverified_paths.clone(), state.deferred_paths.clone())
+        });
+        assert!(unverified.is_empty());
+        assert!(deferred.contains(&workdir.join("notes.md")));
+    }
+
+    #[test]
+    fn test_verify_failure_quarantine_commits_to_side_branch() {
+        let (_dir, repo, git_auto_pilot) =
+            test_instance(verify_config("exit 1", VerifyFailure::Quarantine));
+
+        commit_new_file(&git_auto_pilot, &repo, "notes.md");
+
+        assert_eq!(head_summary(&repo), "initial");
+        assert_eq!(
+            repo.status_file(Path::new("notes.md")).unwrap(),
+            Status::WT_NEW
+        );
+        let quarantine = repo
+            .find_branch("autopilot/quarantine", git2::BranchType::Local)
+            .unwrap();
+        let tree = quarantine.get().peel_to_tree().unwrap();
+        assert!(tree.get_name("notes.md").is_some());
+    }
+
+    #[test]
+    fn test_verify_failure_mark_commits_with_marker() {
+        let (_dir, repo, git_auto_pilot) =
+            test_instance(verify_config("exit 1", VerifyFailure::Mark));
+
+        commit_new_file(&git_auto_pilot, &repo, "notes.md");
+
+        assert!(head_summary(&repo).starts_with("[verify-failed] "));
+        assert_eq!(committed_paths(&repo), ["README.md", "notes.md"]);
+    }
+
+    #[test]
+    fn test_unknown_convention_variables_fail_the_commit() {
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config {
+            commit_conventions: Some(config::CommitConventions {
+                commit_template: false,
+                file: Some(PathBuf::from(".gitmessage")),
+                mode: config::ConventionMode::Prefix,
+            }),
+            unknown_variables: UnknownVariables::Fail,
+            ..config::Config::default()
+        });
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        fs::write(workdir.join(".gitmessage"), "[{{TICKET}}]\n").unwrap();
+        fs::write(workdir.join("notes.md"), "content\n").unwrap();
+
+        let result = GitAutoPilot::take_action(
+            &git_auto_pilot,
+            &repo,
+            &new_file_stats(),
+            "notes.md",
+            workdir.join("notes.md").to_str().unwrap(),
+            &Trigger::catch_up(),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(head_summary(&repo), "initial");
+    }
+
+    #[test]
+    fn test_held_back_revert_is_checked_again() {
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config::default());
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        let readme = workdir.join("README.md");
+        let modified = FileChangeStats {
+            status: Status::WT_MODIFIED,
+            ..new_file_stats()
+        };
+        let change = |content: &str| {
+            fs::write(&readme, content).unwrap();
+            GitAutoPilot::take_action(
+                &git_auto_pilot,
+                &repo,
+                &modified,
+                "README.md",
+                readme.to_str().unwrap(),
+                &Trigger::catch_up(),
+            )
+            .unwrap();
+        };
+
+        change("updated\n");
+        let updated = head_summary(&repo);
+        assert_ne!(updated, "initial");
+
+        // Flipping back right away is held back and scheduled for later
+        change("readme\n");
+        assert_eq!(head_summary(&repo), updated);
+        let key = state::repo_key(&workdir).join("README.md");
+        let scheduled = git_auto_pilot
+            .state
+            .with_repo(&workdir, |state| state.retry_at.get(&key).copied());
+        assert!(scheduled.is_some_and(|at| at > SystemTime::now()));
+
+        // Once due, the change is replayed (and held back again, the window
+        // of the commit has not closed yet)
+        git_auto_pilot.state.with_repo(&workdir, |state| {
+            state.retry_at.insert(key.clone(), UNIX_EPOCH);
+        });
+        GitAutoPilot::retry_deferred_events(&git_auto_pilot);
+        let rescheduled = git_auto_pilot
+            .state
+            .with_repo(&workdir, |state| state.retry_at.get(&key).copied());
+        assert!(rescheduled.is_some_and(|at| at > UNIX_EPOCH));
+    }
+
+    #[test]
+    fn test_sensitive_paths_are_never_staged() {
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config {
+            sensitive_paths: vec!["**/.env".to_string(), "secrets/**".to_string()],
+            ..config::Config::default()
+        });
+        let workdir = repo.workdir().unwrap().to_path_buf();
+
+        assert!(GitAutoPilot::is_sensitive(
+            &git_auto_pilot,
+            &repo,
+            "api/.env"
+        ));
+        assert!(GitAutoPilot::is_sensitive(
+            &git_auto_pilot,
+            &repo,
+            "secrets/key.pem"
+        ));
+        assert!(!GitAutoPilot::is_sensitive(
+            &git_auto_pilot,
+            &repo,
+            "api/env.rs"
+        ));
+        let listed = git_auto_pilot
+            .state
+            .with_repo(&workdir, |state| state.sensitive_paths.clone());
+        assert_eq!(
+            listed,
+            BTreeSet::from(["api/.env".to_string(), "secrets/key.pem".to_string()])
+        );
+
+        // The final guard refuses to stage what slipped through the filtering
+        fs::write(workdir.join(".env"), "TOKEN=secret\n").unwrap();
+        let err = GitAutoPilot::take_action(
+            &git_auto_pilot,
+            &repo,
+            &new_file_stats(),
+            ".env",
+            workdir.join(".env").to_str().unwrap(),
+            &Trigger::catch_up(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, GitAutoPilotError::SensitivePathError(_)));
+        assert_eq!(repo.status_file(Path::new(".env")).unwrap(), Status::WT_NEW);
+    }
+
+    #[test]
+    fn test_scaffold_stages_only_its_filtered_files() {
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config::default());
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        fs::create_dir_all(workdir.join("api/src")).unwrap();
+        fs::write(workdir.join("api/Cargo.toml"), "[package]\n").unwrap();
+        fs::write(workdir.join("api/src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(workdir.join("api/notes.md"), "git-auto-pilot: skip\n").unwrap();
+
+        let scaffold = git::Scaffold {
+            dir: "api".to_string(),
+            files: vec!["api/Cargo.toml".to_string(), "api/src/main.rs".to_string()],
+            stats: new_file_stats(),
+        };
+        GitAutoPilot::take_scaffold_action(&git_auto_pilot, &repo, &scaffold, &Trigger::catch_up())
+            .unwrap();
+
+        assert_eq!(
+            committed_paths(&repo),
+            ["README.md", "api/Cargo.toml", "api/src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn test_scaffold_leaves_large_files_uncommitted() {
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config {
+            large_files: Some(config::LargeFileSettings {
+                threshold_mb: 0,
+                action: LargeFileAction::Skip,
+            }),
+            ..config::Config::default()
+        });
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        fs::create_dir_all(workdir.join("assets")).unwrap();
+        fs::write(workdir.join("assets/.keep"), "").unwrap();
+        fs::write(workdir.join("assets/cover.psd"), "layers").unwrap();
+
+        let scaffold = git::Scaffold {
+            dir: "assets".to_string(),
+            files: vec!["assets/.keep".to_string(), "assets/cover.psd".to_string()],
+            stats: new_file_stats(),
+        };
+        GitAutoPilot::take_scaffold_action(&git_auto_pilot, &repo, &scaffold, &Trigger::catch_up())
+            .unwrap();
+
+        assert_eq!(committed_paths(&repo), ["README.md", "assets/.keep"]);
+    }
+
+    #[test]
+    fn test_backlog_snapshot_gets_a_note() {
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config {
+            backlog_policy: BacklogPolicy::Snapshot,
+            notes: Some(config::NotesSettings { push: false }),
+            ..config::Config::default()
+        });
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        fs::write(workdir.join("draft.md"), "draft\n").unwrap();
+
+        GitAutoPilot::onboard_repo(&git_auto_pilot, &workdir, false).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.summary(), Some(onboarding::SNAPSHOT_MESSAGE));
+        let note = repo.find_note(Some(git::NOTES_REF), head.id()).unwrap();
+        let metadata: serde_json::Value = serde_json::from_str(note.message().unwrap()).unwrap();
+        assert_eq!(metadata["change"], "snapshot");
+        assert_eq!(metadata["event"]["source"], "onboarding");
+        assert_eq!(metadata["stats"]["files"], 1);
+    }
+
+    #[test]
+    fn test_backlog_policy_ask_never_prompts_while_running() {
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config {
+            backlog_policy: BacklogPolicy::Ask,
+            ..config::Config::default()
+        });
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        fs::write(workdir.join("draft.md"), "draft\n").unwrap();
+
+        GitAutoPilot::onboard_repo(&git_auto_pilot, &workdir, false).unwrap();
+        assert_eq!(head_summary(&repo), "initial");
+        // Asked again on the next start
+        assert!(!onboarding::is_onboarded(
+            &git_auto_pilot.state_dir_location,
+            &state::repo_key(&workdir)
+        ));
+    }
+
+    #[test]
+    fn test_tag_rule_counts_commits_per_working_tree() {
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config {
+            tagging: Some(config::TagRule {
+                every_n_commits: Some(2),
+                interval_secs: None,
+                name: "milestone-{{COMMIT_COUNT}}".to_string(),
+                message: "Milestone".to_string(),
+            }),
+            ..config::Config::default()
+        });
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        let commits_since_tag = || {
+            git_auto_pilot
+                .state
+                .with_repo(&workdir, |state| state.commits_since_tag)
+        };
+
+        commit_new_file(&git_auto_pilot, &repo, "a.md");
+        assert_eq!(commits_since_tag(), 1);
+        assert!(repo.find_reference("refs/tags/milestone-2").is_err());
+
+        commit_new_file(&git_auto_pilot, &repo, "b.md");
+        assert_eq!(commits_since_tag(), 0);
+        assert!(repo.find_reference("refs/tags/milestone-2").is_ok());
+        // No counters under the git directory
+        assert!(!git_auto_pilot
+            .state
+            .snapshot()
+            .repos
+            .iter()
+            .any(|repo_status| repo_status.path == state::repo_key(repo.path())));
+    }
+
+    #[test]
+    fn test_tag_waits_for_its_batched_commits() {
+        let (dir, repo, mut git_auto_pilot) = test_instance(config::Config {
+            git_credentials: Some(config::GitCred {
+                username: "Test".to_string(),
+                email: "test@example.com".to_string(),
+                login_username: Some("test".to_string()),
+                password: Some("token".into()),
+            }),
+            tagging: Some(config::TagRule {
+                every_n_commits: Some(2),
+                interval_secs: None,
+                name: "milestone-{{COMMIT_COUNT}}".to_string(),
+                message: "Milestone".to_string(),
+            }),
+            push_every_n_commits: Some(3),
+            ..config::Config::default()
+        });
+        git_auto_pilot.push_enabled = true;
+        let remote_path = dir.path().join("remote.git");
+        let remote = Repository::init_bare(&remote_path).unwrap();
+        repo.remote("origin", remote_path.to_str().unwrap())
+            .unwrap();
+
+        commit_new_file(&git_auto_pilot, &repo, "a.md");
+        commit_new_file(&git_auto_pilot, &repo, "b.md");
+        assert!(repo.find_reference("refs/tags/milestone-2").is_ok());
+        assert!(remote.find_reference("refs/tags/milestone-2").is_err());
+
+        GitAutoPilot::push_pending(&git_auto_pilot, repo.workdir().unwrap(), true).unwrap();
+        let branch = git::get_current_branch(&repo).unwrap();
+        assert!(remote
+            .find_reference(&format!("refs/heads/{}", branch))
+            .is_ok());
+        assert!(remote.find_reference("refs/tags/milestone-2").is_ok());
+    }
+}
@@ -0,0 +1,1379 @@
+//! # Push Module
+//!
+//! Pushing autopilot commits (batched, retried and limited per host) and
+//! following `origin`: fetching in the background, fast-forwarding and
+//! applying the remote control markers.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use git2::Repository;
+use secrecy::{ExposeSecret, SecretString};
+use tracing::{debug, error, info, trace, warn};
+
+use crate::config::{self, DivergencePolicy, PushMode};
+use crate::git;
+use crate::{
+    forge, helper, journal, redact, state, verify, GitAutoPilot, GitAutoPilotError, JobDone,
+};
+
+/// Interval between polls of `origin` for a resume marker while a repository
+/// outside follow mode is paused by a remote commit
+const REMOTE_PAUSE_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Time after which `post_push_command` is killed
+const POST_PUSH_TIMEOUT: Duration = Duration::from_secs(60);
+
+impl GitAutoPilot {
+    /// Pushes a branch to `origin`, recording the outcome for the status output
+    ///
+    /// Depending on `push_mode` all other local branches (and tags) are pushed
+    /// along with it. A push that timed out or lost its connection is retried
+    /// up to `network_retries` times: one-off commands wait for the retries,
+    /// the watch loop schedules them (see `flush_pending_pushes`).
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if credentials are missing, the remote is
+    /// not an allowed push target, or the push fails and is not retried.
+    fn push_branch(&self, repo: &Repository, branch: &str) -> Result<(), GitAutoPilotError> {
+        let config = Self::repo_config(self, repo);
+        let result = Self::push_credentials(self).and_then(|(username, password)| {
+            Self::ensure_remote(self, repo, &username, &password)?;
+            helper::ensure_push_allowed(
+                repo,
+                "origin",
+                &Self::repo_config(self, repo).allowed_push_hosts,
+            )?;
+            let mut refspecs =
+                match Self::resolve_divergence(self, repo, branch, &username, &password)? {
+                    Some(side_branch) => {
+                        vec![format!("refs/heads/{}:refs/heads/{}", branch, side_branch)]
+                    }
+                    None => Self::refspecs_to_push(self, repo, branch)?,
+                };
+            // Tags held back until the commits they point to are pushed
+            let unpushed_tags = repo.workdir().map_or_else(Vec::new, |workdir| {
+                self.state
+                    .with_repo(workdir, |state| state.unpushed_tags.clone())
+            });
+            for tag in unpushed_tags {
+                let refspec = format!("refs/tags/{}", tag);
+                if !refspecs.iter().any(|pushed| pushed.ends_with(&refspec)) {
+                    refspecs.push(refspec);
+                }
+            }
+            let mut attempt = 0;
+            loop {
+                let pushed = self
+                    .push_limiter
+                    .run(&helper::remote_host(repo, "origin"), || {
+                        git::push_refspecs(
+                            repo,
+                            &username,
+                            &password,
+                            "origin",
+                            &refspecs,
+                            &config.push_options,
+                            &Self::remote_settings(self),
+                        )
+                    });
+                match pushed {
+                    Err(err)
+                        if !self.watching
+                            && attempt < config.network_retries
+                            && git::is_transient_error(&err) =>
+                    {
+                        let backoff = Duration::from_secs(config.network_retry_backoff_secs)
+                            * 2u32.saturating_pow(attempt);
+                        attempt += 1;
+                        warn!(
+                            "Push failed ({}), retry {} of {} in {:?}",
+                            err, attempt, config.network_retries, backoff
+                        );
+                        std::thread::sleep(backoff);
+                    }
+                    pushed => {
+                        if pushed.is_ok() {
+                            Self::push_notes(self, repo, &username, &password);
+                        }
+                        return pushed
+                            .map(|()| refspecs)
+                            .map_err(GitAutoPilotError::from_push_error);
+                    }
+                }
+            }
+        });
+
+        // The pushed branch now points to the local head on the remote
+        let commit_sha = result
+            .as_ref()
+            .ok()
+            .and_then(|_| repo.revparse_single(&format!("refs/heads/{}", branch)).ok())
+            .map(|object| object.id().to_string());
+        let commit_url = commit_sha.as_deref().and_then(|sha| {
+            let url = git::remote_url(repo, "origin", git::RemoteDirection::Push).ok()?;
+            helper::commit_url(&url, sha)
+        });
+        if let Some(url) = &commit_url {
+            info!("Pushed {} to {}", branch, url);
+        }
+        if let (Ok(refspecs), Some(sha)) = (&result, &commit_sha) {
+            Self::run_post_push(self, repo, branch, refspecs, sha, commit_url.as_deref());
+        }
+
+        Self::refresh_upstream(self, repo);
+        // Also covers checking the remote and fetching it before the push
+        let transient = matches!(
+            &result,
+            Err(GitAutoPilotError::Git2Error(err)) if git::is_transient_error(err)
+        );
+        let mut retry = None;
+        if let Some(workdir) = repo.workdir() {
+            self.state.with_repo(workdir, |state| {
+                state.last_push = Some(state::PushRecord {
+                    at: SystemTime::now(),
+                    error: result.as_ref().err().map(|err| err.to_string()),
+                    refspecs: result.as_ref().map(Vec::clone).unwrap_or_default(),
+                    commit_sha: commit_sha.clone(),
+                    commit_url: commit_url.clone(),
+                });
+                if result.is_ok() {
+                    state.unpushed_commits = 0;
+                    state.unpushed_since = None;
+                    state.unpushed_branch = None;
+                    state.unpushed_tags.clear();
+                    state.last_successful_push = Some(SystemTime::now());
+                    state.push_overdue_since = None;
+                }
+                if transient && self.watching && state.push_retries < config.network_retries {
+                    let backoff = Duration::from_secs(config.network_retry_backoff_secs)
+                        * 2u32.saturating_pow(state.push_retries);
+                    state.push_retries += 1;
+                    state.push_retry_at = Some(SystemTime::now() + backoff);
+                    retry = Some((state.push_retries, backoff));
+                } else {
+                    state.push_retries = 0;
+                    state.push_retry_at = None;
+                }
+            });
+        }
+        match (result, retry) {
+            (Err(err), Some((retry, backoff))) => {
+                warn!(
+                    "Push failed ({}), retry {} of {} in {:?}",
+                    err, retry, config.network_retries, backoff
+                );
+                Ok(())
+            }
+            (result, _) => result.map(|_| ()),
+        }
+    }
+
+    /// Records a successful push in the event journal and runs the
+    /// `post_push_command` with the push variables
+    ///
+    /// In the watch loop the command runs as a background job, elsewhere
+    /// right away. A failure is only logged.
+    ///
+    /// # Arguments
+    /// - `repo` - The repository.
+    /// - `branch` - The pushed branch.
+    /// - `refspecs` - The pushed refspecs.
+    /// - `commit_sha` - The commit the remote branch points to now.
+    /// - `commit_url` - The web page of the commit, if the host is known.
+    fn run_post_push(
+        &self,
+        repo: &Repository,
+        branch: &str,
+        refspecs: &[String],
+        commit_sha: &str,
+        commit_url: Option<&str>,
+    ) {
+        let Some(workdir) = repo.workdir() else {
+            return;
+        };
+        Self::record_in_journal(self, || {
+            journal::JournalEntry::decision(
+                workdir,
+                "push",
+                workdir,
+                format!(
+                    "pushed {} at {}{}",
+                    branch,
+                    commit_sha,
+                    commit_url.map_or(String::new(), |url| format!(" ({})", url))
+                ),
+            )
+        });
+        let Some(command) = Self::repo_config(self, repo).post_push_command.clone() else {
+            return;
+        };
+        let env = [
+            ("COMMIT_SHA", commit_sha.to_string()),
+            ("COMMIT_URL", commit_url.unwrap_or_default().to_string()),
+            ("BRANCH", branch.to_string()),
+            ("REFSPECS", refspecs.join(" ")),
+        ];
+        let job_workdir = workdir.to_path_buf();
+        let hook = move || {
+            if let Err(err) = verify::run_with_env(&job_workdir, &command, &env, POST_PUSH_TIMEOUT)
+            {
+                warn!("post_push_command of {}: {}", job_workdir.display(), err);
+            }
+            let job_done: JobDone = Box::new(|_: &GitAutoPilot| {});
+            job_done
+        };
+        if self.watching {
+            self.jobs.start(workdir, "post-push", hook);
+        } else {
+            hook()(self);
+        }
+    }
+
+    /// Checks whether `origin` got commits on `branch` the local branch lacks
+    /// and applies the `divergence_policy` if so
+    ///
+    /// Skipped in the `mirror` push mode, which overwrites the remote anyway.
+    ///
+    /// # Returns
+    /// The side branch to push the local commits to instead of `branch`, if any.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the remote branch cannot be fetched,
+    /// the rebase fails or the repository was paused (by the policy or by a
+    /// pause marker on the remote branch).
+    fn resolve_divergence(
+        &self,
+        repo: &Repository,
+        branch: &str,
+        username: &str,
+        password: &SecretString,
+    ) -> Result<Option<String>, GitAutoPilotError> {
+        let config = Self::repo_config(self, repo);
+        if config.push_mode == PushMode::Mirror {
+            return Ok(None);
+        }
+        let Some(remote_head) = git::fetch_branch(
+            repo,
+            username,
+            password,
+            "origin",
+            branch,
+            &Self::remote_settings(self),
+        )?
+        else {
+            return Ok(None);
+        };
+        let workdir = repo.workdir().unwrap_or_else(|| repo.path());
+        if Self::apply_remote_control(self, repo, branch, remote_head) {
+            return Err(GitAutoPilotError::RemotePausedError(format!(
+                "origin/{} of {} asks to pause automation; not pushing",
+                branch,
+                workdir.display()
+            )));
+        }
+        let (ahead, behind) = git::ahead_behind(repo, branch, remote_head)?;
+        Self::refresh_upstream(self, repo);
+        if behind == 0 {
+            return Ok(None);
+        }
+
+        warn!(
+            "origin/{} of {} has {} commit(s) the local branch lacks, {} local commit(s) not pushed",
+            branch,
+            workdir.display(),
+            behind,
+            ahead
+        );
+        match config.divergence_policy {
+            DivergencePolicy::Rebase => {
+                Self::verify_pulled_commits(self, repo, branch, remote_head)?;
+                git::rebase_onto(
+                    repo,
+                    branch,
+                    &format!("refs/remotes/origin/{}", branch),
+                    Self::fallback_identity(self).as_ref(),
+                )?;
+                info!("Rebased {} onto origin/{}", branch, branch);
+                Ok(None)
+            }
+            DivergencePolicy::SideBranch => {
+                let side_branch = format!("{}/diverged/{}", config.branch_prefix, branch);
+                info!("Pushing {} to side branch {}", branch, side_branch);
+                Ok(Some(side_branch))
+            }
+            DivergencePolicy::Pause => {
+                self.state.with_repo(workdir, |state| state.paused = true);
+                Self::publish_status(self);
+                Err(GitAutoPilotError::DivergedError(format!(
+                    "origin/{} of {} has {} commit(s) the local branch lacks; \
+                     paused until resumed",
+                    branch,
+                    workdir.display(),
+                    behind
+                )))
+            }
+        }
+    }
+
+    /// Applies the `[autopilot pause]` or `[autopilot resume]` marker in the
+    /// message of the newest commit fetched from `origin`
+    ///
+    /// Every marker commit is applied once, so pausing or resuming by hand
+    /// afterwards holds until another marker commit arrives. A resume marker
+    /// only lifts a pause set by a pause marker. Only commits pushed by others
+    /// count: a commit the local branch already has (e.g. one the autopilot
+    /// pushed itself) is ignored, as is one failing `verify_pulled_commits`.
+    ///
+    /// # Arguments
+    /// - `repo`: The repository.
+    /// - `branch`: The local branch the remote branch belongs to.
+    /// - `remote_head`: Head of the fetched remote branch.
+    ///
+    /// # Returns
+    /// `true` if automation of the repository is paused by a remote commit.
+    fn apply_remote_control(
+        &self,
+        repo: &Repository,
+        branch: &str,
+        remote_head: git2::Oid,
+    ) -> bool {
+        let workdir = repo.workdir().unwrap_or_else(|| repo.path());
+        let id = remote_head.to_string();
+        let (remote_paused, seen) = self.state.with_repo(workdir, |state| {
+            (
+                state.remote_pause.is_some(),
+                state.remote_control_commit.as_deref() == Some(id.as_str()),
+            )
+        });
+        let Ok(commit) = repo.find_commit(remote_head) else {
+            return remote_paused;
+        };
+        let marker = helper::remote_pause_marker(&String::from_utf8_lossy(commit.message_bytes()));
+        let Some(pause) = marker.filter(|_| !seen) else {
+            return remote_paused;
+        };
+        let local_head = repo
+            .find_branch(branch, git2::BranchType::Local)
+            .ok()
+            .and_then(|branch| branch.get().target());
+        if local_head.is_some_and(|local_head| {
+            local_head == remote_head
+                || repo
+                    .graph_descendant_of(local_head, remote_head)
+                    .unwrap_or(false)
+        }) {
+            trace!("Ignoring the marker of {}, the local branch has it", id);
+            return remote_paused;
+        }
+        if let Err(err) = Self::verify_pulled_commits(self, repo, branch, remote_head) {
+            warn!(
+                "Ignoring the marker of unverified remote commit {}: {}",
+                id, err
+            );
+            return remote_paused;
+        }
+        let summary =
+            String::from_utf8_lossy(commit.summary_bytes().unwrap_or_default()).to_string();
+
+        let changed = self.state.with_repo(workdir, |state| {
+            state.remote_control_commit = Some(id.clone());
+            if pause {
+                state.paused = true;
+                state.remote_pause = Some(state::RemotePauseRecord {
+                    at: SystemTime::now(),
+                    commit: id.clone(),
+                    summary: summary.clone(),
+                });
+                true
+            } else if state.remote_pause.take().is_some() {
+                state.paused = false;
+                true
+            } else {
+                false
+            }
+        });
+        if !changed {
+            return remote_paused;
+        }
+        if pause {
+            warn!(
+                "Automation of {} paused by remote commit {}: {}",
+                workdir.display(),
+                id,
+                summary
+            );
+        } else {
+            info!(
+                "Automation of {} resumed by remote commit {}: {}",
+                workdir.display(),
+                id,
+                summary
+            );
+        }
+        Self::publish_status(self);
+        pause
+    }
+
+    /// Verifies the signatures of the remote commits about to be merged into a
+    /// branch, if `verify_pulled_commits` is configured
+    ///
+    /// # Arguments
+    /// - `repo`: The repository.
+    /// - `branch`: The local branch the commits are merged into.
+    /// - `remote_head`: Head of the remote branch.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if a commit has no trusted signature.
+    fn verify_pulled_commits(
+        &self,
+        repo: &Repository,
+        branch: &str,
+        remote_head: git2::Oid,
+    ) -> Result<(), GitAutoPilotError> {
+        let Some(settings) = Self::repo_config(self, repo).verify_pulled_commits.as_ref() else {
+            return Ok(());
+        };
+        let local_head = repo
+            .find_branch(branch, git2::BranchType::Local)?
+            .get()
+            .peel_to_commit()?
+            .id();
+        let keyring = settings
+            .keyring
+            .as_ref()
+            .map(|keyring| helper::expand_tilde(Path::new(keyring)));
+        let allowed_signers = settings
+            .allowed_signers
+            .as_ref()
+            .map(|file| helper::expand_tilde(Path::new(file)));
+        match git::verify_commits(
+            repo,
+            local_head,
+            remote_head,
+            keyring.as_deref(),
+            allowed_signers.as_deref(),
+        ) {
+            Ok(verified) => {
+                debug!("Verified {} commit(s) of origin/{}", verified, branch);
+                Ok(())
+            }
+            Err(err) => {
+                error!(
+                    "Refusing to merge origin/{} of {}: {}",
+                    branch,
+                    repo.workdir().unwrap_or(repo.path()).display(),
+                    err.message()
+                );
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Makes sure the `origin` remote exists before the first push of the session
+    ///
+    /// A missing remote repository is created on the configured forge and set
+    /// as `origin`. Unless `auto_create` is set the user is asked first, only
+    /// by one-off commands: the watch loop never waits for an answer. A
+    /// declined creation is not asked for again during the session. Without
+    /// forge settings the push is attempted as is.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the remote cannot be checked or created.
+    fn ensure_remote(
+        &self,
+        repo: &Repository,
+        username: &str,
+        password: &SecretString,
+    ) -> Result<(), GitAutoPilotError> {
+        let Some(workdir) = repo.workdir() else {
+            return Ok(());
+        };
+        if self.state.with_repo(workdir, |state| {
+            state.remote_verified || state.remote_creation_declined
+        }) {
+            return Ok(());
+        }
+
+        let exists = git::remote_exists(
+            repo,
+            username,
+            password,
+            "origin",
+            &Self::remote_settings(self),
+        )?;
+        if !exists {
+            let Some(forge) = Self::repo_config(self, repo).forge.as_ref() else {
+                debug!("Remote origin of {} does not exist", workdir.display());
+                return Ok(());
+            };
+            let name = workdir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let create = forge.auto_create
+                || (!self.watching && forge::prompt_create(&name, forge) == Some(true));
+            if !create {
+                warn!(
+                    "Remote origin of {} does not exist; set forge.auto_create to create it",
+                    workdir.display()
+                );
+                self.state
+                    .with_repo(workdir, |state| state.remote_creation_declined = true);
+                return Ok(());
+            }
+
+            let url = forge::create_repository(forge, &name)?;
+            match repo.find_remote("origin") {
+                Ok(origin) => {
+                    warn!(
+                        "Pointing origin of {} at the created repository {}, it pointed at {}",
+                        workdir.display(),
+                        url,
+                        origin.url().unwrap_or("an invalid URL")
+                    );
+                    repo.remote_set_url("origin", &url)?;
+                }
+                Err(_) => {
+                    repo.remote("origin", &url)?;
+                }
+            }
+            info!("Created remote repository {} as origin", url);
+        }
+
+        self.state
+            .with_repo(workdir, |state| state.remote_verified = true);
+        Ok(())
+    }
+
+    /// Builds the refspecs pushed for a commit on `branch` according to `push_mode`
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the local refs cannot be listed.
+    fn refspecs_to_push(
+        &self,
+        repo: &Repository,
+        branch: &str,
+    ) -> Result<Vec<String>, GitAutoPilotError> {
+        let config = Self::repo_config(self, repo);
+        let (include_tags, force) = match config.push_mode {
+            PushMode::Branch => (None, ""),
+            PushMode::AllBranches => (Some(false), ""),
+            PushMode::Mirror => (Some(true), "+"),
+        };
+
+        let refspecs = match include_tags {
+            None => vec![format!("refs/heads/{}", branch)],
+            Some(include_tags) => git::local_refs(repo, include_tags)?
+                .into_iter()
+                .filter(|name| {
+                    let protected = helper::is_ref_protected(name, &config.protected_refs);
+                    if protected {
+                        trace!("Not pushing protected ref {}", name);
+                    }
+                    !protected
+                })
+                .map(|name| format!("{}{}", force, name))
+                .collect(),
+        };
+        Ok(refspecs)
+    }
+
+    /// Pushes the metadata notes to `origin` if `notes.push` is set
+    ///
+    /// Pushed apart from the branch and best effort: the notes pushed from
+    /// other clones are merged first (see `git::push_notes`), and a failure is
+    /// only logged as the pushed commits do not depend on their notes.
+    fn push_notes(&self, repo: &Repository, username: &str, password: &SecretString) {
+        let push_notes = Self::repo_config(self, repo)
+            .notes
+            .as_ref()
+            .is_some_and(|notes| notes.push)
+            && repo.find_reference(git::NOTES_REF).is_ok();
+        if !push_notes {
+            return;
+        }
+        let pushed = self
+            .push_limiter
+            .run(&helper::remote_host(repo, "origin"), || {
+                git::push_notes(
+                    repo,
+                    "origin",
+                    &Self::remote_settings(self),
+                    Some((username, password)),
+                    Self::fallback_identity(self).as_ref(),
+                )
+            });
+        if let Err(err) = pushed {
+            warn!("Failed to push the metadata notes: {}", err);
+        }
+    }
+
+    /// Pushes a new commit, or defers the push while the batching limits are not reached
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if a push is due and fails.
+    pub(crate) fn push_commit(
+        &self,
+        repo: &Repository,
+        branch: &str,
+    ) -> Result<(), GitAutoPilotError> {
+        if !self.push_enabled || !Self::repo_config(self, repo).push_enabled {
+            debug!("Not pushing {}, pushing is disabled", branch);
+            return Ok(());
+        }
+        let Some(workdir) = repo.workdir() else {
+            return Self::push_branch(self, repo, branch);
+        };
+
+        // Commits waiting on another branch (e.g. yesterday's) are pushed first
+        let previous_branch = self.state.with_repo(workdir, |state| {
+            state
+                .unpushed_branch
+                .clone()
+                .filter(|previous| previous != branch)
+        });
+        if let Some(previous_branch) = previous_branch {
+            Self::push_branch(self, repo, &previous_branch)?;
+        }
+
+        let config = Self::repo_config(self, repo);
+        let (due, unpushed) = self.state.with_repo(workdir, |state| {
+            state.unpushed_commits += 1;
+            state.unpushed_branch = Some(branch.to_string());
+            let since = *state.unpushed_since.get_or_insert_with(SystemTime::now);
+            // A failed push waits for its retry, see `flush_pending_pushes`
+            let retrying = state.push_retry_at.is_some();
+            (
+                !retrying && config.is_push_due(state.unpushed_commits, since),
+                state.unpushed_commits,
+            )
+        });
+        if !due {
+            debug!(
+                "Deferring push of {} ({} unpushed commits)",
+                branch, unpushed
+            );
+            return Ok(());
+        }
+        Self::push_branch(self, repo, branch)
+    }
+
+    /// Pushes the commits waiting in a batch once a batching limit is reached
+    ///
+    /// The caller must hold the operation lock of the repository.
+    ///
+    /// # Arguments
+    /// - `repo_path`: Path of the repository.
+    /// - `force`: Push regardless of the batching limits.
+    ///
+    /// # Returns
+    /// `true` if a push was attempted.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the repository cannot be opened or the push fails.
+    pub(crate) fn push_pending(
+        &self,
+        repo_path: &Path,
+        force: bool,
+    ) -> Result<bool, GitAutoPilotError> {
+        let pending = self.state.with_repo(repo_path, |state| {
+            Some((
+                state.unpushed_branch.clone()?,
+                state.unpushed_commits,
+                state.unpushed_since?,
+            ))
+        });
+        let Some((branch, unpushed, since)) = pending else {
+            return Ok(false);
+        };
+
+        let repo = Repository::open(repo_path)?;
+        if !force && !Self::repo_config(self, &repo).is_push_due(unpushed, since) {
+            return Ok(false);
+        }
+        info!(
+            "Pushing {} batched commits of {}",
+            unpushed,
+            repo_path.display()
+        );
+        Self::push_branch(self, &repo, &branch)?;
+        Ok(true)
+    }
+
+    /// Alerts about repositories whose commits have waited longer than
+    /// `push_alert_secs` for a successful push
+    ///
+    /// The alert is logged once per repository and kept in its state (see
+    /// `push_overdue_since`) until a push succeeds.
+    pub(crate) fn check_push_lag(&self) {
+        for repo_status in self.state.snapshot().repos {
+            let config = self
+                .repo_configs
+                .get(&state::repo_key(&repo_status.path))
+                .unwrap_or(&self.config);
+            if config.push_alert_secs == 0 || !config.push_enabled || !self.push_enabled {
+                continue;
+            }
+            let threshold = Duration::from_secs(config.push_alert_secs);
+            let overdue = self.state.with_repo(&repo_status.path, |state| {
+                let since = state.unpushed_since.filter(|since| {
+                    state.push_overdue_since.is_none()
+                        && since.elapsed().unwrap_or_default() > threshold
+                })?;
+                state.push_overdue_since = Some(since);
+                Some((state.unpushed_commits, state.last_successful_push))
+            });
+            let Some((unpushed, last_success)) = overdue else {
+                continue;
+            };
+            error!(
+                "{} has {} commits waiting for more than {}, last successful push: {}",
+                repo_status.path.display(),
+                unpushed,
+                humantime::format_duration(threshold),
+                last_success.map_or("never".to_string(), helper::format_age)
+            );
+            Self::publish_status(self);
+        }
+    }
+
+    /// Fast-forwards every repository in follow mode whose poll is due to the
+    /// commits pushed to `origin` by others
+    ///
+    /// A webhook request (see `health`) makes every poll due right away. The
+    /// fetch runs as a background job, its result is applied by
+    /// `finish_follow`. Only credentials known already are used: the watch
+    /// loop never prompts for them.
+    pub(crate) fn follow_remotes(&self) {
+        let requested = self.health.take_follow_request();
+        let mut polled = false;
+        for repo_status in self.state.snapshot().repos {
+            // Repositories paused by a remote commit keep polling for the
+            // commit resuming them
+            let remote_paused = repo_status.state.remote_pause.is_some();
+            if (repo_status.state.paused && !remote_paused)
+                || repo_status.state.storage_error.is_some()
+                || self.jobs.is_running(&repo_status.path, "fetch")
+            {
+                continue;
+            }
+            let Ok(repo) = Repository::open(&repo_status.path) else {
+                continue;
+            };
+            let interval = match Self::repo_config(self, &repo).follow.as_ref() {
+                Some(settings) => Duration::from_secs(settings.interval_secs),
+                None if remote_paused => REMOTE_PAUSE_POLL_INTERVAL,
+                None => continue,
+            };
+            let last_poll = repo_status
+                .state
+                .last_follow
+                .as_ref()
+                .map_or(self.session_started, |record| record.at);
+            if !requested && last_poll.elapsed().unwrap_or_default() < interval {
+                continue;
+            }
+
+            let started = git::get_current_branch(&repo)
+                .map_err(|err| err.to_string())
+                .and_then(|branch| {
+                    let (username, password) = Self::known_credentials(self)
+                        .ok_or("no credentials are known yet to fetch with")?;
+                    Ok((branch, username, password))
+                });
+            let (branch, username, password) = match started {
+                Ok(started) => started,
+                Err(err) => {
+                    warn!(
+                        "Failed to follow origin of {}: {}",
+                        repo_status.path.display(),
+                        err
+                    );
+                    Self::record_follow(self, &repo_status.path, Err(err));
+                    polled = true;
+                    continue;
+                }
+            };
+            let remote_settings = Self::remote_settings(self);
+            let path = repo_status.path.clone();
+            self.jobs.start(&repo_status.path, "fetch", move || {
+                let fetched = Repository::open(&path)
+                    .and_then(|repo| {
+                        git::fetch_branch(
+                            &repo,
+                            &username,
+                            &password,
+                            "origin",
+                            &branch,
+                            &remote_settings,
+                        )
+                    })
+                    .map_err(|err| err.message().to_string());
+                let job_done: JobDone = Box::new(move |git_auto_pilot: &GitAutoPilot| {
+                    Self::finish_follow(git_auto_pilot, &path, &branch, fetched);
+                });
+                job_done
+            });
+        }
+        if polled {
+            Self::publish_status(self);
+        }
+    }
+
+    /// Applies the fetch of a follow poll: the remote control markers and the
+    /// fast-forward
+    ///
+    /// # Arguments
+    /// - `path` - The repository.
+    /// - `branch` - The branch that was fetched.
+    /// - `fetched` - The head of the branch on `origin`, `None` if it has none.
+    fn finish_follow(&self, path: &Path, branch: &str, fetched: Result<Option<git2::Oid>, String>) {
+        let result = fetched.and_then(|remote_head| {
+            let Some(remote_head) = remote_head else {
+                return Ok(0);
+            };
+            let repo = Repository::open(path).map_err(|err| err.message().to_string())?;
+            let operation_lock = self.state.operation_lock(path);
+            let _operation_guard = operation_lock
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            Self::follow_remote(self, &repo, branch, remote_head).map_err(|err| err.to_string())
+        });
+        if let Err(err) = &result {
+            warn!("Failed to follow origin of {}: {}", path.display(), err);
+        }
+        Self::record_follow(self, path, result);
+        if let Ok(repo) = Repository::open(path) {
+            Self::refresh_upstream(self, &repo);
+        }
+        Self::publish_status(self);
+    }
+
+    /// Records the outcome of a follow poll for the status output
+    fn record_follow(&self, path: &Path, result: Result<usize, String>) {
+        self.state.with_repo(path, |state| {
+            state.last_follow = Some(state::FollowRecord {
+                at: SystemTime::now(),
+                pulled: result.as_ref().map_or(0, |pulled| *pulled),
+                error: result.err(),
+            });
+        });
+    }
+
+    /// Fast-forwards a branch to its head on `origin` if only the remote has
+    /// new commits
+    ///
+    /// Nothing is fast-forwarded while a pause marker on the remote branch
+    /// holds, outside follow mode (where the fetch only looks for markers),
+    /// or once another branch was checked out since the fetch.
+    ///
+    /// The caller must hold the operation lock of the repository.
+    ///
+    /// # Arguments
+    /// - `repo` - The repository.
+    /// - `branch` - The fetched branch.
+    /// - `remote_head` - The head of the branch on `origin`.
+    ///
+    /// # Returns
+    /// The number of commits the branch was fast-forwarded by.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the verification or the fast-forward fails.
+    fn follow_remote(
+        &self,
+        repo: &Repository,
+        branch: &str,
+        remote_head: git2::Oid,
+    ) -> Result<usize, GitAutoPilotError> {
+        if Self::apply_remote_control(self, repo, branch, remote_head)
+            || Self::repo_config(self, repo).follow.is_none()
+            || git::get_current_branch(repo)? != branch
+        {
+            return Ok(0);
+        }
+        let (ahead, behind) = git::ahead_behind(repo, branch, remote_head)?;
+        if behind == 0 {
+            return Ok(0);
+        }
+        if ahead > 0 {
+            debug!(
+                "Not following origin/{}, {} local commit(s) are not pushed yet",
+                branch, ahead
+            );
+            return Ok(0);
+        }
+        Self::verify_pulled_commits(self, repo, branch, remote_head)?;
+
+        git::fast_forward(repo, branch, remote_head)?;
+        if let Some(workdir) = repo.workdir() {
+            self.analysis_cache.invalidate(&state::repo_key(workdir));
+        }
+        info!(
+            "Fast-forwarded {} by {} commit(s) from origin/{}",
+            branch, behind, branch
+        );
+        Ok(behind)
+    }
+
+    /// Pushes the batched commits of every repository whose batch is due,
+    /// and retries the failed pushes whose retry is due
+    ///
+    /// # Arguments
+    /// - `force`: Push all waiting commits regardless of the batching limits
+    ///   and retry times.
+    pub(crate) fn flush_pending_pushes(&self, force: bool) {
+        let mut pushed = false;
+        let now = SystemTime::now();
+        for repo_status in self.state.snapshot().repos {
+            if repo_status.state.unpushed_commits == 0 {
+                continue;
+            }
+            let retry_at = repo_status.state.push_retry_at;
+            if !force && retry_at.is_some_and(|at| at > now) {
+                continue;
+            }
+            let operation_lock = self.state.operation_lock(&repo_status.path);
+            let _operation_guard = operation_lock
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+            match Self::push_pending(self, &repo_status.path, force || retry_at.is_some()) {
+                Ok(attempted) => pushed |= attempted,
+                Err(err) => {
+                    pushed = true;
+                    error!(
+                        "Failed to push batched commits of {}: {}",
+                        repo_status.path.display(),
+                        err
+                    );
+                }
+            }
+        }
+        if pushed {
+            Self::publish_status(self);
+        }
+    }
+
+    /// Restores the push bookkeeping of a repository from git at startup
+    ///
+    /// Commits made before a restart and not pushed yet count as waiting
+    /// since they were made, so `push_alert_secs` and the batching limits see
+    /// them. The latest push is read from the reflog of the remote-tracking
+    /// ref.
+    pub(crate) fn seed_push_state(&self, repo: &Repository) {
+        let (Some(workdir), Ok(branch)) = (repo.workdir(), git::get_current_branch(repo)) else {
+            return;
+        };
+        let unpushed = match git::unpushed_commits(repo, &branch) {
+            Ok(unpushed) => unpushed,
+            Err(err) => {
+                warn!(
+                    "Failed to find the unpushed commits of {}: {}",
+                    workdir.display(),
+                    err
+                );
+                None
+            }
+        };
+        let last_push = git::last_push_time(repo, &branch);
+        self.state.with_repo(workdir, |state| {
+            if state.last_successful_push.is_none() {
+                state.last_successful_push = last_push;
+            }
+            if let (Some((commits, since)), None) = (unpushed, &state.unpushed_branch) {
+                state.unpushed_commits = commits;
+                state.unpushed_since = Some(since);
+                state.unpushed_branch = Some(branch.clone());
+            }
+        });
+    }
+
+    /// Records the divergence of the checked out branch from its upstream
+    pub(crate) fn refresh_upstream(&self, repo: &Repository) {
+        let Some(workdir) = repo.workdir() else {
+            return;
+        };
+        let upstream = git::upstream_ahead_behind(repo).map(|(upstream, ahead, behind)| {
+            state::UpstreamRecord {
+                upstream,
+                ahead,
+                behind,
+                at: SystemTime::now(),
+            }
+        });
+        self.state
+            .with_repo(workdir, |state| state.upstream = upstream);
+    }
+
+    /// The configured credentials, or those entered earlier in this session
+    ///
+    /// Never prompts, for work running outside the watch loop.
+    pub(crate) fn known_credentials(&self) -> Option<(String, SecretString)> {
+        let configured = self.config.git_credentials.as_ref().and_then(|cred| {
+            match (cred.login_username.as_deref(), cred.password.as_ref()) {
+                (Some(username), Some(password))
+                    if !username.is_empty() && !password.expose_secret().is_empty() =>
+                {
+                    Some((username.to_string(), password.clone()))
+                }
+                _ => None,
+            }
+        });
+        configured.or_else(|| self.prompted_credentials.get().cloned())
+    }
+
+    /// Resolves the login username and password used for pushing
+    ///
+    /// Uses the configured credentials when available. Otherwise, when running in
+    /// an interactive terminal, the user is prompted once and may choose to save
+    /// the answer to the configuration file.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if no credentials are configured and none
+    /// could be obtained interactively.
+    pub(crate) fn push_credentials(&self) -> Result<(String, SecretString), GitAutoPilotError> {
+        if let Some(credentials) = Self::known_credentials(self) {
+            return Ok(credentials);
+        }
+
+        let Some((username, password, save)) = helper::prompt_for_credentials() else {
+            return Err(GitAutoPilotError::CredentialsMissing(
+                "Git credentials are not set".to_string(),
+            ));
+        };
+        redact::register_secret(password.expose_secret());
+
+        if save {
+            let config_path = PathBuf::from(&self.dot_file_location);
+            let mut saved_config = config::Config::load(&config_path)?;
+            let cred = saved_config
+                .git_credentials
+                .get_or_insert_with(|| config::GitCred {
+                    username: String::new(),
+                    email: String::new(),
+                    login_username: None,
+                    password: None,
+                });
+            cred.login_username = Some(username.clone());
+            cred.password = Some(password.clone());
+            saved_config.save_to_file(&config_path)?;
+            info!("Saved git credentials to {}", self.dot_file_location);
+        }
+
+        Ok(self
+            .prompted_credentials
+            .get_or_init(|| (username, password))
+            .clone())
+    }
+
+    /// Builds the network settings for remote operations from the configuration
+    pub(crate) fn remote_settings(&self) -> git::RemoteSettings {
+        git::RemoteSettings {
+            proxy: helper::resolve_proxy(self.config.proxy.as_deref()),
+            ca_bundle: self.config.ca_bundle.clone(),
+            insecure_skip_tls_verify: self.config.insecure_skip_tls_verify,
+            timeout: (self.config.network_timeout_secs > 0)
+                .then_some(Duration::from_secs(self.config.network_timeout_secs)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_instance;
+    use std::fs;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_remote_control_only_follows_commits_of_others() {
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config::default());
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        let branch = git::get_current_branch(&repo).unwrap();
+        let remote_ref = format!("refs/remotes/origin/{}", branch);
+        let signature = repo.signature().unwrap();
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        let commit_on = |reference: &str, message: &str| {
+            let parent = repo
+                .find_reference(reference)
+                .unwrap()
+                .peel_to_commit()
+                .unwrap();
+            repo.commit(
+                Some(reference),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &[&parent],
+            )
+            .unwrap()
+        };
+
+        // Made locally and pushed by the autopilot
+        let own = commit_on("HEAD", "Pause [autopilot pause]");
+        repo.reference(&remote_ref, own, true, "push").unwrap();
+        assert!(!GitAutoPilot::apply_remote_control(
+            &git_auto_pilot,
+            &repo,
+            &branch,
+            own
+        ));
+
+        let foreign = commit_on(&remote_ref, "Hold on [autopilot pause]");
+        assert!(GitAutoPilot::apply_remote_control(
+            &git_auto_pilot,
+            &repo,
+            &branch,
+            foreign
+        ));
+        assert!(git_auto_pilot
+            .state
+            .with_repo(&workdir, |state| state.paused));
+    }
+
+    #[test]
+    fn test_follow_fetches_in_a_job_and_fast_forwards() {
+        let (dir, repo, git_auto_pilot) = test_instance(config::Config {
+            git_credentials: Some(config::GitCred {
+                username: "Test".to_string(),
+                email: "test@example.com".to_string(),
+                login_username: Some("test".to_string()),
+                password: Some("token".into()),
+            }),
+            follow: Some(config::FollowSettings { interval_secs: 0 }),
+            ..config::Config::default()
+        });
+        let branch = git::get_current_branch(&repo).unwrap();
+        let remote_path = dir.path().join("remote.git");
+        Repository::init_bare(&remote_path).unwrap();
+        let mut origin = repo
+            .remote("origin", remote_path.to_str().unwrap())
+            .unwrap();
+
+        // Someone else pushes a commit on top of the local branch
+        let signature = repo.signature().unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let pushed = repo
+            .commit(
+                Some("refs/heads/elsewhere"),
+                &signature,
+                &signature,
+                "From another machine",
+                &head.tree().unwrap(),
+                &[&head],
+            )
+            .unwrap();
+        origin
+            .push(
+                &[format!("refs/heads/elsewhere:refs/heads/{}", branch)],
+                None,
+            )
+            .unwrap();
+        repo.find_reference("refs/heads/elsewhere")
+            .unwrap()
+            .delete()
+            .unwrap();
+        // Only repositories known to the state are followed
+        git_auto_pilot
+            .state
+            .with_repo(repo.workdir().unwrap(), |_| ());
+
+        GitAutoPilot::follow_remotes(&git_auto_pilot);
+        for job_done in git_auto_pilot.jobs.wait_finished(Duration::from_secs(30)) {
+            job_done(&git_auto_pilot);
+        }
+
+        assert_eq!(repo.head().unwrap().target(), Some(pushed));
+        let record = git_auto_pilot
+            .state
+            .with_repo(repo.workdir().unwrap(), |state| state.last_follow.clone())
+            .unwrap();
+        assert_eq!((record.pulled, record.error), (1, None));
+    }
+
+    #[test]
+    fn test_push_lag_survives_a_restart() {
+        let (dir, repo, mut git_auto_pilot) = test_instance(config::Config {
+            push_alert_secs: 60,
+            ..config::Config::default()
+        });
+        git_auto_pilot.push_enabled = true;
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        let branch = git::get_current_branch(&repo).unwrap();
+        let remote_path = dir.path().join("remote.git");
+        Repository::init_bare(&remote_path).unwrap();
+        let mut origin = repo
+            .remote("origin", remote_path.to_str().unwrap())
+            .unwrap();
+        origin
+            .push(&[format!("refs/heads/{0}:refs/heads/{0}", branch)], None)
+            .unwrap();
+
+        // Committed an hour ago by a previous run that never pushed it
+        let signature = git2::Signature::new(
+            "Test",
+            "test@example.com",
+            &git2::Time::new(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64
+                    - 3600,
+                0,
+            ),
+        )
+        .unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "Before the restart",
+            &head.tree().unwrap(),
+            &[&head],
+        )
+        .unwrap();
+
+        GitAutoPilot::seed_push_state(&git_auto_pilot, &repo);
+        let (unpushed, last_push) = git_auto_pilot.state.with_repo(&workdir, |state| {
+            (state.unpushed_commits, state.last_successful_push)
+        });
+        assert_eq!(unpushed, 1);
+        assert!(last_push.is_some());
+
+        GitAutoPilot::check_push_lag(&git_auto_pilot);
+        assert!(git_auto_pilot
+            .state
+            .with_repo(&workdir, |state| state.push_overdue_since.is_some()));
+    }
+
+    #[test]
+    fn test_failed_push_is_retried_from_the_loop() {
+        let (_dir, repo, mut git_auto_pilot) = test_instance(config::Config {
+            git_credentials: Some(config::GitCred {
+                username: "Test".to_string(),
+                email: "test@example.com".to_string(),
+                login_username: Some("test".to_string()),
+                password: Some("token".into()),
+            }),
+            network_retries: 2,
+            network_retry_backoff_secs: 60,
+            ..config::Config::default()
+        });
+        git_auto_pilot.watching = true;
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        // Nothing listens on the discard port: the connection is refused
+        repo.remote("origin", "http://127.0.0.1:9/notes.git")
+            .unwrap();
+        let branch = git::get_current_branch(&repo).unwrap();
+        git_auto_pilot.state.with_repo(&workdir, |state| {
+            state.unpushed_commits = 1;
+            state.unpushed_branch = Some(branch.clone());
+            state.unpushed_since = Some(SystemTime::now());
+        });
+        let retries = || {
+            git_auto_pilot
+                .state
+                .with_repo(&workdir, |state| (state.push_retries, state.push_retry_at))
+        };
+
+        // The failure schedules a retry instead of waiting for it
+        GitAutoPilot::push_branch(&git_auto_pilot, &repo, &branch).unwrap();
+        let (attempts, retry_at) = retries();
+        assert_eq!(attempts, 1);
+        assert!(retry_at.is_some_and(|at| at > SystemTime::now() + Duration::from_secs(30)));
+
+        // Not retried before it is due
+        GitAutoPilot::flush_pending_pushes(&git_auto_pilot, false);
+        assert_eq!(retries().0, 1);
+
+        let make_due = || {
+            git_auto_pilot
+                .state
+                .with_repo(&workdir, |state| state.push_retry_at = Some(UNIX_EPOCH));
+        };
+        make_due();
+        GitAutoPilot::flush_pending_pushes(&git_auto_pilot, false);
+        assert_eq!(retries().0, 2);
+
+        // Out of retries, the failure is reported and the commits keep waiting
+        make_due();
+        GitAutoPilot::flush_pending_pushes(&git_auto_pilot, false);
+        assert_eq!(retries(), (0, None));
+        assert!(git_auto_pilot.state.with_repo(&workdir, |state| {
+            state.unpushed_commits == 1
+                && state
+                    .last_push
+                    .as_ref()
+                    .is_some_and(|push| push.error.is_some())
+        }));
+    }
+
+    #[test]
+    fn test_post_push_command_gets_the_pushed_commit() {
+        let (dir, repo, git_auto_pilot) = test_instance(config::Config {
+            git_credentials: Some(config::GitCred {
+                username: "Test".to_string(),
+                email: "test@example.com".to_string(),
+                login_username: Some("test".to_string()),
+                password: Some("token".into()),
+            }),
+            post_push_command: Some("echo \"$COMMIT_SHA $BRANCH\" > ../pushed".to_string()),
+            ..config::Config::default()
+        });
+        let branch = git::get_current_branch(&repo).unwrap();
+        let remote_path = dir.path().join("remote.git");
+        Repository::init_bare(&remote_path).unwrap();
+        repo.remote("origin", remote_path.to_str().unwrap())
+            .unwrap();
+
+        GitAutoPilot::push_branch(&git_auto_pilot, &repo, &branch).unwrap();
+
+        let head = repo.head().unwrap().target().unwrap();
+        let pushed = fs::read_to_string(repo.workdir().unwrap().join("../pushed")).unwrap();
+        assert_eq!(pushed.trim_end(), format!("{} {}", head, branch));
+    }
+
+    #[test]
+    fn test_missing_remote_is_not_created_without_consent() {
+        let (dir, repo, mut git_auto_pilot) = test_instance(config::Config {
+            forge: Some(config::ForgeSettings {
+                kind: config::ForgeKind::Github,
+                api_url: Some("http://127.0.0.1:9".to_string()),
+                token_env: "GIT_AUTO_PILOT_TEST_FORGE_TOKEN".to_string(),
+                owner: None,
+                private: true,
+                auto_create: false,
+            }),
+            ..config::Config::default()
+        });
+        git_auto_pilot.watching = true;
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        let missing = dir.path().join("missing.git");
+        repo.remote("origin", missing.to_str().unwrap()).unwrap();
+        let password = SecretString::from(String::new());
+
+        GitAutoPilot::ensure_remote(&git_auto_pilot, &repo, "", &password).unwrap();
+        assert!(git_auto_pilot
+            .state
+            .with_repo(&workdir, |state| state.remote_creation_declined));
+        // The configured URL is left alone
+        assert_eq!(repo.find_remote("origin").unwrap().url(), missing.to_str());
+
+        // An existing remote is verified once
+        let remote_dir = tempfile::tempdir().unwrap();
+        Repository::init_bare(remote_dir.path()).unwrap();
+        repo.remote_set_url("origin", remote_dir.path().to_str().unwrap())
+            .unwrap();
+        git_auto_pilot.state.with_repo(&workdir, |state| {
+            state.remote_creation_declined = false;
+        });
+        GitAutoPilot::ensure_remote(&git_auto_pilot, &repo, "", &password).unwrap();
+        assert!(git_auto_pilot
+            .state
+            .with_repo(&workdir, |state| state.remote_verified
+                && !state.remote_creation_declined));
+    }
+}
@@ -0,0 +1,86 @@
+//! # Queue Module
+//!
+//! Events are not always committed right away: low priority events wait for
+//! their batch and events arriving during a manual git operation or a build
+//! are deferred. When the daemon shuts down with such events still queued,
+//! their paths are written to `queue.json` in the state directory, so they
+//! are replayed at the next start instead of waiting for an unrelated event.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::ConfigError;
+use crate::error::GitAutoPilotError;
+
+/// Name of the file holding the queued events in the state directory
+const QUEUE_FILE: &str = "queue.json";
+
+/// Paths of queued events, by repository key (see `state::repo_key`)
+pub type Queue = BTreeMap<PathBuf, BTreeSet<PathBuf>>;
+
+/// Writes the queued events, removing the file if nothing is queued
+///
+/// # Arguments
+/// - `state_dir`: The state directory.
+/// - `queue`: The queued paths of every repository.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the file cannot be written or removed.
+pub fn save(state_dir: &str, queue: &Queue) -> Result<(), GitAutoPilotError> {
+    let path = Path::new(state_dir).join(QUEUE_FILE);
+    if queue.values().all(BTreeSet::is_empty) {
+        return match fs::remove_file(&path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        };
+    }
+
+    let temp_path = path.with_extension("json.tmp");
+    let contents = serde_json::to_string_pretty(queue).map_err(ConfigError::from)?;
+    fs::write(&temp_path, contents)?;
+    fs::rename(&temp_path, &path)?;
+    Ok(())
+}
+
+/// Reads and removes the events queued by the previous run
+///
+/// # Arguments
+/// - `state_dir`: The state directory.
+///
+/// # Returns
+/// The queued paths of every repository, empty if nothing was queued or the
+/// file cannot be read.
+pub fn take(state_dir: &str) -> Queue {
+    let path = Path::new(state_dir).join(QUEUE_FILE);
+    let queue = fs::read_to_string(&path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+    let _ = fs::remove_file(&path);
+    queue
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queue_survives_restart_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_dir = dir.path().to_str().unwrap();
+        assert!(take(state_dir).is_empty());
+
+        let queue = Queue::from([(
+            PathBuf::from("/work/notes"),
+            BTreeSet::from([PathBuf::from("/work/notes/todo.md")]),
+        )]);
+        save(state_dir, &queue).unwrap();
+        assert_eq!(take(state_dir), queue);
+        assert!(take(state_dir).is_empty());
+
+        save(state_dir, &queue).unwrap();
+        save(state_dir, &Queue::new()).unwrap();
+        assert!(take(state_dir).is_empty());
+    }
+}
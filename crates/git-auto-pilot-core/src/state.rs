@@ -0,0 +1,591 @@
+//! # Runtime State Module
+//!
+//! This module holds the in-memory state the daemon tracks for each watched
+//! repository while it is running (e.g. counters used by milestone tagging,
+//! recent activity shown by the `status` and `tui` commands).
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Maximum number of recent commits remembered per repository
+const RECENT_COMMITS_LIMIT: usize = 10;
+
+/// Maximum number of sensitive paths listed per repository
+const SENSITIVE_PATHS_LIMIT: usize = 20;
+
+/// A file system event handled by the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventRecord {
+    /// Time the event was received
+    pub at: SystemTime,
+
+    /// Kind of the event (e.g. `Modify(Data(Content))`)
+    pub kind: String,
+
+    /// First path carried by the event
+    pub path: PathBuf,
+}
+
+/// A commit created by the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitRecord {
+    /// Time the commit was created
+    pub at: SystemTime,
+
+    /// Commit summary line
+    pub message: String,
+}
+
+/// Outcome of the latest push attempt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushRecord {
+    /// Time of the push attempt
+    pub at: SystemTime,
+
+    /// Error message if the push failed
+    pub error: Option<String>,
+
+    /// Refspecs that were pushed
+    #[serde(default)]
+    pub refspecs: Vec<String>,
+
+    /// SHA of the commit the pushed branch points to on the remote
+    #[serde(default)]
+    pub commit_sha: Option<String>,
+
+    /// Web URL of the pushed commit, built from the remote URL
+    #[serde(default)]
+    pub commit_url: Option<String>,
+}
+
+/// Divergence of the checked out branch from its upstream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamRecord {
+    /// Short name of the upstream, e.g. `origin/main`
+    pub upstream: String,
+
+    /// Number of local commits the upstream lacks
+    pub ahead: usize,
+
+    /// Number of upstream commits the local branch lacks
+    pub behind: usize,
+
+    /// Time the counts were computed
+    pub at: SystemTime,
+}
+
+/// Outcome of the latest poll of the remote branch in follow mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FollowRecord {
+    /// Time of the poll
+    pub at: SystemTime,
+
+    /// Number of commits the branch was fast-forwarded by
+    pub pulled: usize,
+
+    /// Error message if the poll or the fast-forward failed
+    pub error: Option<String>,
+}
+
+/// A pause requested by a commit pulled from the remote
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemotePauseRecord {
+    /// Time the pause was applied
+    pub at: SystemTime,
+
+    /// The commit carrying the pause marker
+    pub commit: String,
+
+    /// Summary line of that commit
+    pub summary: String,
+}
+
+/// Outcome of the latest run of `verify_command`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyRecord {
+    /// Time the command finished
+    pub at: SystemTime,
+
+    /// Description of the failure if the change failed verification
+    pub error: Option<String>,
+}
+
+/// Outcome of `verify_command` for a state of the working tree
+#[derive(Debug, Clone)]
+pub struct TreeVerification {
+    /// The `tree_generation` the command ran on
+    pub generation: u64,
+
+    /// Description of the failure if the working tree failed verification
+    pub failure: Option<String>,
+}
+
+/// Outcome of the latest maintenance run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceRecord {
+    /// Time the run finished
+    pub at: SystemTime,
+
+    /// Errors of the failed tasks, if any
+    pub error: Option<String>,
+}
+
+/// Outcome of the latest bundle backup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleRecord {
+    /// Time the bundle was attempted
+    pub at: SystemTime,
+
+    /// The written bundle, `None` if the attempt failed
+    pub path: Option<PathBuf>,
+
+    /// Fingerprint of the refs contained in the last written bundle
+    pub refs_fingerprint: u64,
+
+    /// Error of the failed attempt
+    pub error: Option<String>,
+}
+
+/// Latest object database measurement of a repository
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskUsageRecord {
+    /// Size of the object database when first measured (or after the last `git gc`), in bytes
+    pub baseline_bytes: u64,
+
+    /// Size of the object database at the last measurement, in bytes
+    pub current_bytes: u64,
+
+    /// Time of the last measurement
+    pub checked_at: SystemTime,
+
+    /// Whether the growth since the baseline exceeds the configured threshold
+    pub over_threshold: bool,
+}
+
+/// A repository paused because its file system is full or read-only, or its
+/// device disappeared
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageErrorRecord {
+    /// Time the error first occurred
+    pub since: SystemTime,
+
+    /// The error that paused the repository
+    pub error: String,
+
+    /// Time the file system was last probed for recovery
+    pub probed_at: SystemTime,
+
+    /// Whether the device holding the repository disappeared (e.g. an
+    /// unplugged drive), so it is watched again once it returns
+    #[serde(default)]
+    pub device_gone: bool,
+}
+
+/// A path skipped after failing repeatedly
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineRecord {
+    /// Time the path is processed again
+    pub until: SystemTime,
+
+    /// Number of consecutive failures that led to the quarantine
+    pub failures: u32,
+
+    /// The last error
+    pub error: String,
+}
+
+/// Runtime state tracked for a single repository
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct RepoState {
+    /// Number of autopilot commits since the last milestone tag
+    #[serde(skip)]
+    pub commits_since_tag: u64,
+
+    /// Time of the last milestone tag (or of the first commit if none was created yet)
+    #[serde(skip)]
+    pub last_tag_at: Option<SystemTime>,
+
+    /// Whether automation is paused for this repository
+    #[serde(default)]
+    pub paused: bool,
+
+    /// Set while automation is suspended because the file system is full or read-only
+    #[serde(default)]
+    pub storage_error: Option<StorageErrorRecord>,
+
+    /// The last event handled for this repository
+    #[serde(default)]
+    pub last_event: Option<EventRecord>,
+
+    /// Number of uncommitted changes found by the last analysis
+    #[serde(default)]
+    pub pending_changes: usize,
+
+    /// The most recent autopilot commits, newest first
+    #[serde(default)]
+    pub recent_commits: VecDeque<CommitRecord>,
+
+    /// The latest push attempt
+    #[serde(default)]
+    pub last_push: Option<PushRecord>,
+
+    /// Divergence from the upstream as of the last commit, fetch or push
+    #[serde(default)]
+    pub upstream: Option<UpstreamRecord>,
+
+    /// The latest poll of the remote branch in follow mode
+    #[serde(default)]
+    pub last_follow: Option<FollowRecord>,
+
+    /// Set while automation is paused by an `[autopilot pause]` commit pulled
+    /// from the remote
+    #[serde(default)]
+    pub remote_pause: Option<RemotePauseRecord>,
+
+    /// The last pulled commit whose pause or resume marker was applied, so a
+    /// manual resume is not undone by the same commit
+    #[serde(default)]
+    pub remote_control_commit: Option<String>,
+
+    /// The latest run of `verify_command`
+    #[serde(default)]
+    pub last_verify: Option<VerifyRecord>,
+
+    /// Number of file system events seen, identifying the state of the
+    /// working tree `verify_command` ran on
+    #[serde(skip)]
+    pub tree_generation: u64,
+
+    /// Outcome of `verify_command` for the latest state of the working tree
+    /// it ran on
+    #[serde(skip)]
+    pub verification: Option<TreeVerification>,
+
+    /// Paths of changes left uncommitted because they failed verification,
+    /// retried once a later change passes
+    #[serde(default)]
+    pub unverified_paths: BTreeSet<PathBuf>,
+
+    /// Number of commit messages rendered with unresolved template variables
+    #[serde(default)]
+    pub template_failures: u64,
+
+    /// Template variables that had no value while rendering commit messages
+    #[serde(default)]
+    pub unresolved_variables: BTreeSet<String>,
+
+    /// Changed paths withheld from commits because they match
+    /// `sensitive_paths`, at most `SENSITIVE_PATHS_LIMIT`
+    #[serde(default)]
+    pub sensitive_paths: BTreeSet<String>,
+
+    /// Whether more sensitive paths were withheld than are listed
+    #[serde(default)]
+    pub sensitive_paths_truncated: bool,
+
+    /// The latest maintenance run
+    #[serde(default)]
+    pub last_maintenance: Option<MaintenanceRecord>,
+
+    /// The latest bundle backup
+    #[serde(default)]
+    pub last_bundle: Option<BundleRecord>,
+
+    /// The latest object database measurement, if disk usage tracking is enabled
+    #[serde(default)]
+    pub disk_usage: Option<DiskUsageRecord>,
+
+    /// Number of commits waiting to be pushed while pushes are batched
+    #[serde(default)]
+    pub unpushed_commits: u64,
+
+    /// Time of the latest successful push
+    #[serde(default)]
+    pub last_successful_push: Option<SystemTime>,
+
+    /// Set once commits have waited longer than `push_alert_secs` for a
+    /// successful push, holding the time the oldest of them was made
+    #[serde(default)]
+    pub push_overdue_since: Option<SystemTime>,
+
+    /// Consecutive failures per path, relative to the repository root
+    #[serde(skip)]
+    pub path_failures: HashMap<String, u32>,
+
+    /// Paths skipped after repeated failures, relative to the repository root
+    #[serde(default)]
+    pub quarantined: BTreeMap<String, QuarantineRecord>,
+
+    /// Time of the oldest commit waiting to be pushed
+    #[serde(skip)]
+    pub unpushed_since: Option<SystemTime>,
+
+    /// Branch the waiting commits were made on
+    #[serde(skip)]
+    pub unpushed_branch: Option<String>,
+
+    /// Tags made on the waiting commits, pushed along with them
+    #[serde(skip)]
+    pub unpushed_tags: Vec<String>,
+
+    /// Retries of the failed push made so far
+    #[serde(skip)]
+    pub push_retries: u32,
+
+    /// When the failed push is retried, see `network_retries`
+    #[serde(skip)]
+    pub push_retry_at: Option<SystemTime>,
+
+    /// Whether the `origin` remote was found (or created) during this session
+    #[serde(skip)]
+    pub remote_verified: bool,
+
+    /// Whether creating the missing `origin` repository was declined during
+    /// this session, so it is neither checked nor asked for again
+    #[serde(skip)]
+    pub remote_creation_declined: bool,
+
+    /// Paths of events deferred while a manual git operation or a build was in progress
+    #[serde(default)]
+    pub deferred_paths: BTreeSet<PathBuf>,
+
+    /// Paths of events replayed as deferred events once their time has
+    /// come, e.g. changes held back as reverts of the latest commit
+    #[serde(skip)]
+    pub retry_at: BTreeMap<PathBuf, SystemTime>,
+
+    /// Time the output of a build running in the repository last changed
+    #[serde(default)]
+    pub build_activity_at: Option<SystemTime>,
+
+    /// Paths of low priority events waiting to be processed as one batch
+    #[serde(default)]
+    pub batched_paths: BTreeSet<PathBuf>,
+
+    /// Time the oldest waiting low priority event was received
+    #[serde(default)]
+    pub batched_since: Option<SystemTime>,
+}
+
+impl RepoState {
+    /// Remembers a commit, keeping only the most recent ones
+    pub fn record_commit(&mut self, message: &str) {
+        self.recent_commits.push_front(CommitRecord {
+            at: SystemTime::now(),
+            message: message.to_string(),
+        });
+        self.recent_commits.truncate(RECENT_COMMITS_LIMIT);
+    }
+
+    /// Remembers a path withheld because it is sensitive, listing at most
+    /// `SENSITIVE_PATHS_LIMIT` of them
+    pub fn record_sensitive_path(&mut self, path: &str) {
+        if self.sensitive_paths.contains(path) {
+            return;
+        }
+        if self.sensitive_paths.len() < SENSITIVE_PATHS_LIMIT {
+            self.sensitive_paths.insert(path.to_string());
+        } else {
+            self.sensitive_paths_truncated = true;
+        }
+    }
+
+    /// Checks whether a path is quarantined, lifting an expired quarantine
+    pub fn is_quarantined(&mut self, path: &str) -> bool {
+        match self.quarantined.get(path) {
+            Some(record) if record.until > SystemTime::now() => true,
+            Some(_) => {
+                self.quarantined.remove(path);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Records a failed attempt to process a path
+    ///
+    /// # Arguments
+    /// - `path`: Path relative to the repository root.
+    /// - `error`: The error of the attempt.
+    /// - `threshold`: Consecutive failures quarantining the path, `0` never quarantines.
+    /// - `cooldown`: How long the path stays quarantined.
+    ///
+    /// # Returns
+    /// Returns `true` if this failure quarantined the path.
+    pub fn record_failure(
+        &mut self,
+        path: &str,
+        error: &str,
+        threshold: u32,
+        cooldown: Duration,
+    ) -> bool {
+        let failures = self.path_failures.entry(path.to_string()).or_default();
+        *failures += 1;
+        if threshold == 0 || *failures < threshold {
+            return false;
+        }
+
+        let failures = self.path_failures.remove(path).unwrap_or_default();
+        self.quarantined.insert(
+            path.to_string(),
+            QuarantineRecord {
+                until: SystemTime::now() + cooldown,
+                failures,
+                error: error.to_string(),
+            },
+        );
+        true
+    }
+
+    /// Records a successful attempt to process a path, resetting its failures
+    pub fn record_success(&mut self, path: &str) {
+        self.path_failures.remove(path);
+    }
+}
+
+/// Status of a single repository as published by the daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoStatus {
+    /// Path of the repository
+    pub path: PathBuf,
+
+    /// Runtime state of the repository
+    #[serde(flatten)]
+    pub state: RepoState,
+}
+
+/// Snapshot of the daemon state shared with client commands
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusSnapshot {
+    /// Process id of the daemon
+    pub pid: u32,
+
+    /// Time the snapshot was taken
+    pub updated_at: SystemTime,
+
+    /// Status of every watched repository
+    pub repos: Vec<RepoStatus>,
+}
+
+/// Normalizes a repository path for use as a state key
+///
+/// Collecting the components drops trailing separators, so `/repo` and
+/// `/repo/` (as returned by `Repository::workdir`) map to the same key.
+pub fn repo_key(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+/// Thread-safe collection of per-repository runtime state
+#[derive(Debug, Default)]
+pub struct StateStore {
+    repos: Mutex<HashMap<PathBuf, RepoState>>,
+
+    /// Locks serializing git operations (stage, commit, push) per repository
+    operations: Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>,
+}
+
+impl StateStore {
+    /// Runs a closure with mutable access to the state of a repository
+    ///
+    /// The state is created with default values on first access.
+    ///
+    /// # Arguments
+    /// - `repo`: Path identifying the repository.
+    /// - `f`: Closure receiving the mutable repository state.
+    ///
+    /// # Returns
+    /// Returns the value produced by the closure.
+    pub fn with_repo<T>(&self, repo: &Path, f: impl FnOnce(&mut RepoState) -> T) -> T {
+        let mut repos = self
+            .repos
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(repos.entry(repo_key(repo)).or_default())
+    }
+
+    /// Returns the lock serializing git operations on a repository
+    ///
+    /// Hold the lock while touching the index or pushing, so that two tasks
+    /// never operate on the same repository at once.
+    ///
+    /// # Arguments
+    /// - `repo`: Path identifying the repository.
+    pub fn operation_lock(&self, repo: &Path) -> Arc<Mutex<()>> {
+        let mut operations = self
+            .operations
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        operations.entry(repo_key(repo)).or_default().clone()
+    }
+
+    /// Takes a snapshot of the state of all repositories
+    ///
+    /// # Returns
+    /// Returns a `StatusSnapshot` with repositories sorted by path.
+    pub fn snapshot(&self) -> StatusSnapshot {
+        let repos = self
+            .repos
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let mut repos: Vec<RepoStatus> = repos
+            .iter()
+            .map(|(path, state)| RepoStatus {
+                path: path.clone(),
+                state: state.clone(),
+            })
+            .collect();
+        repos.sort_by(|a, b| a.path.cmp(&b.path));
+
+        StatusSnapshot {
+            pid: std::process::id(),
+            updated_at: SystemTime::now(),
+            repos,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quarantine_after_repeated_failures() {
+        let mut state = RepoState::default();
+        let cooldown = Duration::from_secs(60);
+
+        assert!(!state.record_failure("a.txt", "index locked", 3, cooldown));
+        state.record_success("a.txt");
+        assert!(!state.record_failure("a.txt", "index locked", 3, cooldown));
+        assert!(!state.record_failure("a.txt", "index locked", 3, cooldown));
+        assert!(!state.is_quarantined("a.txt"));
+
+        assert!(state.record_failure("a.txt", "index locked", 3, cooldown));
+        assert!(state.is_quarantined("a.txt"));
+        assert!(!state.is_quarantined("b.txt"));
+
+        // An expired quarantine is lifted
+        state.quarantined.get_mut("a.txt").unwrap().until = SystemTime::now();
+        assert!(!state.is_quarantined("a.txt"));
+        assert!(state.quarantined.is_empty());
+    }
+
+    #[test]
+    fn test_sensitive_paths_are_capped() {
+        let mut state = RepoState::default();
+        for _ in 0..3 {
+            state.record_sensitive_path(".env");
+        }
+        assert_eq!(state.sensitive_paths.len(), 1);
+        assert!(!state.sensitive_paths_truncated);
+
+        for index in 0..SENSITIVE_PATHS_LIMIT {
+            state.record_sensitive_path(&format!("service-{}/.env", index));
+        }
+        assert_eq!(state.sensitive_paths.len(), SENSITIVE_PATHS_LIMIT);
+        assert!(state.sensitive_paths_truncated);
+    }
+}
@@ -0,0 +1,215 @@
+//! # Statistics Module
+//!
+//! Counts the autopilot commits of every repository across restarts: the
+//! commits made today, the total, the current daily streak and the commits of
+//! each file made today. The counters are kept in `stats.json` in the state
+//! directory and exposed to templates as `{{TODAY_COMMITS}}`,
+//! `{{TOTAL_COMMITS}}`, `{{STREAK}}` and `{{SEQ}}`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::ConfigError;
+use crate::error::GitAutoPilotError;
+
+/// Name of the file holding the statistics in the state directory
+const STATS_FILE: &str = "stats.json";
+
+/// Seconds per day
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Commit counters of a repository
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepoStats {
+    /// Number of autopilot commits made so far
+    pub total_commits: u64,
+
+    /// Day of the latest commit, in days since the Unix epoch (local time)
+    pub last_commit_day: Option<i64>,
+
+    /// Number of commits made on `last_commit_day`
+    pub day_commits: u64,
+
+    /// Number of consecutive days with commits, ending on `last_commit_day`
+    pub streak: u64,
+
+    /// Number of commits of each file made on `last_commit_day`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub day_files: BTreeMap<String, u64>,
+}
+
+impl RepoStats {
+    /// Counts a commit made on `day`, of `file` if it changed a single file
+    pub fn record_commit(&mut self, day: i64, file: Option<&str>) {
+        if self.last_commit_day != Some(day) {
+            self.day_files.clear();
+        }
+        if let Some(file) = file {
+            *self.day_files.entry(file.to_string()).or_default() += 1;
+        }
+        match self.last_commit_day {
+            Some(last) if last == day => self.day_commits += 1,
+            Some(last) if last + 1 == day => {
+                self.streak += 1;
+                self.day_commits = 1;
+            }
+            _ => {
+                self.streak = 1;
+                self.day_commits = 1;
+            }
+        }
+        self.last_commit_day = Some(day);
+        self.total_commits += 1;
+    }
+
+    /// Number of commits made on `today`
+    pub fn today_commits(&self, today: i64) -> u64 {
+        if self.last_commit_day == Some(today) {
+            self.day_commits
+        } else {
+            0
+        }
+    }
+
+    /// Number of commits of `file` made on `today`
+    pub fn file_commits(&self, today: i64, file: &str) -> u64 {
+        if self.last_commit_day == Some(today) {
+            self.day_files.get(file).copied().unwrap_or_default()
+        } else {
+            0
+        }
+    }
+
+    /// Current daily streak as of `today`, broken if yesterday had no commit
+    pub fn current_streak(&self, today: i64) -> u64 {
+        match self.last_commit_day {
+            Some(last) if last == today || last + 1 == today => self.streak,
+            _ => 0,
+        }
+    }
+}
+
+/// Returns the local day of a point in time, in days since the Unix epoch
+///
+/// # Arguments
+/// - `time`: The point in time.
+/// - `offset_minutes`: Offset of the local timezone from UTC.
+pub fn local_day(time: SystemTime, offset_minutes: i32) -> i64 {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs() as i64);
+    (seconds + i64::from(offset_minutes) * 60).div_euclid(SECONDS_PER_DAY)
+}
+
+/// Reads the statistics of all repositories, empty if none were recorded yet
+fn read_stats(state_dir: &str) -> BTreeMap<PathBuf, RepoStats> {
+    fs::read_to_string(Path::new(state_dir).join(STATS_FILE))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Returns the statistics of a repository
+///
+/// # Arguments
+/// - `state_dir`: The state directory.
+/// - `repo`: Key of the repository (see `state::repo_key`).
+pub fn repo_stats(state_dir: &str, repo: &Path) -> RepoStats {
+    read_stats(state_dir).remove(repo).unwrap_or_default()
+}
+
+/// Counts a commit made in a repository on `day`
+///
+/// # Arguments
+/// - `state_dir`: The state directory.
+/// - `repo`: Key of the repository (see `state::repo_key`).
+/// - `day`: Local day of the commit (see `local_day`).
+/// - `file`: The file changed by the commit, if it changed a single one.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the statistics cannot be written.
+pub fn record_commit(
+    state_dir: &str,
+    repo: &Path,
+    day: i64,
+    file: Option<&str>,
+) -> Result<(), GitAutoPilotError> {
+    let mut stats = read_stats(state_dir);
+    stats
+        .entry(repo.to_path_buf())
+        .or_default()
+        .record_commit(day, file);
+
+    let path = Path::new(state_dir).join(STATS_FILE);
+    let temp_path = path.with_extension("json.tmp");
+    let contents = serde_json::to_string_pretty(&stats).map_err(ConfigError::from)?;
+    fs::write(&temp_path, contents)?;
+    fs::rename(&temp_path, &path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_daily_streaks() {
+        let mut stats = RepoStats::default();
+        stats.record_commit(100, None);
+        stats.record_commit(100, None);
+        stats.record_commit(101, None);
+        assert_eq!(stats.today_commits(101), 1);
+        assert_eq!(stats.current_streak(101), 2);
+        assert_eq!(stats.current_streak(102), 2);
+        assert_eq!(stats.current_streak(103), 0);
+
+        stats.record_commit(105, None);
+        assert_eq!(stats.streak, 1);
+        assert_eq!(stats.total_commits, 4);
+    }
+
+    #[test]
+    fn test_counts_file_commits_per_day() {
+        let mut stats = RepoStats::default();
+        stats.record_commit(100, Some("notes.md"));
+        stats.record_commit(100, Some("notes.md"));
+        stats.record_commit(100, Some("todo.md"));
+        stats.record_commit(100, None);
+        assert_eq!(stats.file_commits(100, "notes.md"), 2);
+        assert_eq!(stats.file_commits(100, "todo.md"), 1);
+        assert_eq!(stats.file_commits(101, "notes.md"), 0);
+
+        stats.record_commit(101, Some("todo.md"));
+        assert_eq!(stats.file_commits(101, "notes.md"), 0);
+        assert_eq!(stats.file_commits(101, "todo.md"), 1);
+    }
+
+    #[test]
+    fn test_persists_statistics() {
+        let state_dir = tempfile::tempdir().unwrap();
+        let state_dir = state_dir.path().to_str().unwrap();
+        let repo = Path::new("/home/user/notes");
+
+        record_commit(state_dir, repo, 10, None).unwrap();
+        record_commit(state_dir, repo, 11, None).unwrap();
+
+        let stats = repo_stats(state_dir, repo);
+        assert_eq!(stats.total_commits, 2);
+        assert_eq!(stats.current_streak(11), 2);
+        assert_eq!(
+            repo_stats(state_dir, Path::new("/other")),
+            RepoStats::default()
+        );
+    }
+
+    #[test]
+    fn test_local_day_applies_offset() {
+        let just_before_midnight = UNIX_EPOCH + std::time::Duration::from_secs(86_399);
+        assert_eq!(local_day(just_before_midnight, 0), 0);
+        assert_eq!(local_day(just_before_midnight, 60), 1);
+        assert_eq!(local_day(UNIX_EPOCH, -60), -1);
+    }
+}
@@ -0,0 +1,417 @@
+//! # Template Test Module
+//!
+//! Renders the configured message templates against a fixed matrix of
+//! synthetic changes and compares the result with golden files, so edits that
+//! change the resulting commit messages are noticed (e.g. in CI). Every
+//! scenario is stored in `<golden dir>/<scenario>.txt` as the summary, a blank
+//! line and the description.
+//!
+//! Each scenario is made in a scratch repository of its own and runs through
+//! the variable builder and message pipeline of real commits, including
+//! `repeat_annotation` and the commit convention. The changes are fixed, so
+//! the output only changes with the configuration; the message generator is
+//! always the template one.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use git2::{Repository, Status};
+use tracing::warn;
+
+use crate::config::{CommitConventions, Config};
+use crate::error::GitAutoPilotError;
+use crate::generator::ChangeKind;
+use crate::git::{self, FileChangeStats};
+use crate::pipeline::Trigger;
+use crate::{paths, state, stats, GitAutoPilot};
+
+/// Line the changed file of a scenario is made of
+const SAMPLE_LINE: &str = "sample line\n";
+
+/// Number of lines of the changed file of a scenario
+const SAMPLE_LINES: usize = 100;
+
+/// A synthetic change the templates are rendered for
+#[derive(Debug)]
+pub struct Scenario {
+    /// Name of the scenario, also the name of its golden file
+    pub name: &'static str,
+
+    /// Kind of the change
+    kind: ChangeKind,
+
+    /// Changed path, relative to the repository root
+    path: &'static str,
+
+    /// Previous path of a renamed file or directory
+    old_path: Option<&'static str>,
+
+    /// Whether the file was already committed once today
+    repeated: bool,
+}
+
+/// The scenarios every template is rendered for
+pub const SCENARIOS: &[Scenario] = &[
+    Scenario {
+        name: "create",
+        kind: ChangeKind::Create,
+        path: "src/main.rs",
+        old_path: None,
+        repeated: false,
+    },
+    Scenario {
+        name: "modify",
+        kind: ChangeKind::Modify,
+        path: "docs/notes.md",
+        old_path: None,
+        repeated: false,
+    },
+    Scenario {
+        name: "modify-again",
+        kind: ChangeKind::Modify,
+        path: "docs/notes.md",
+        old_path: None,
+        repeated: true,
+    },
+    Scenario {
+        name: "remove",
+        kind: ChangeKind::Remove,
+        path: "tmp/scratch.txt",
+        old_path: None,
+        repeated: false,
+    },
+    Scenario {
+        name: "rename",
+        kind: ChangeKind::Rename,
+        path: "docs/guide.md",
+        old_path: Some("docs/intro.md"),
+        repeated: false,
+    },
+    Scenario {
+        name: "long-file-name",
+        kind: ChangeKind::Modify,
+        path: "src/components/navigation/sidebar/collapsible_section_with_nested_items_and_keyboard_navigation_support.tsx",
+        old_path: None,
+        repeated: false,
+    },
+    Scenario {
+        name: "unicode",
+        kind: ChangeKind::Create,
+        path: "notes/réunion-café-会議.md",
+        old_path: None,
+        repeated: false,
+    },
+    Scenario {
+        name: "rename-dir",
+        kind: ChangeKind::RenameDir,
+        path: "docs/guides",
+        old_path: Some("docs/tutorials"),
+        repeated: false,
+    },
+    Scenario {
+        name: "scaffold",
+        kind: ChangeKind::Scaffold,
+        path: "packages/api",
+        old_path: None,
+        repeated: false,
+    },
+];
+
+/// Result of comparing a scenario with its golden file
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The output matches the golden file
+    Match,
+
+    /// The output differs from the golden file
+    Mismatch { expected: String, actual: String },
+
+    /// There is no golden file for the scenario
+    Missing,
+
+    /// The golden file was (re)written
+    Updated,
+}
+
+impl Scenario {
+    /// Status of the changed file
+    fn status(&self) -> Status {
+        match self.kind {
+            ChangeKind::Create | ChangeKind::Scaffold => Status::WT_NEW,
+            ChangeKind::Remove => Status::WT_DELETED,
+            ChangeKind::Rename | ChangeKind::RenameDir => Status::WT_RENAMED,
+            ChangeKind::Modify => Status::WT_MODIFIED,
+        }
+    }
+
+    /// Renders the summary and description of the scenario
+    ///
+    /// # Arguments
+    /// - `git_auto_pilot`: Instance holding the configuration under test.
+    /// - `workdir`: Scratch directory the repository of the scenario is made in.
+    /// - `conventions_from`: Working tree the `commit_conventions` file is
+    ///   copied from.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the scratch repository cannot be set
+    /// up or the templates cannot be rendered.
+    fn render(
+        &self,
+        git_auto_pilot: &GitAutoPilot,
+        workdir: &Path,
+        conventions_from: Option<&Path>,
+    ) -> Result<String, GitAutoPilotError> {
+        let repo = Repository::init(workdir)?;
+        if let (Some(conventions), Some(source)) = (
+            git_auto_pilot.config.commit_conventions.as_ref(),
+            conventions_from,
+        ) {
+            copy_convention(&repo, conventions, source)?;
+        }
+
+        let full_path = workdir.join(self.path);
+        match self.kind {
+            ChangeKind::Remove => {}
+            ChangeKind::RenameDir | ChangeKind::Scaffold => fs::create_dir_all(&full_path)?,
+            _ => {
+                if let Some(parent) = full_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&full_path, SAMPLE_LINE.repeat(SAMPLE_LINES))?;
+            }
+        }
+        if self.repeated {
+            stats::record_commit(
+                &git_auto_pilot.state_dir_location,
+                &state::repo_key(repo.workdir().unwrap_or(workdir)),
+                GitAutoPilot::stats_day(&repo),
+                Some(self.path),
+            )?;
+        }
+
+        let file_change_stats = FileChangeStats {
+            lines_added: 12,
+            lines_deleted: 3,
+            lines_modified: 15,
+            status: self.status(),
+            old_name: self.old_path.map(str::to_string),
+        };
+        let trigger = Trigger {
+            received_at: SystemTime::now(),
+            kind: "data-modify",
+            source: "test-templates".to_string(),
+        };
+        let mut values = GitAutoPilot::prepare_dynamic_values(
+            git_auto_pilot,
+            &repo,
+            "main",
+            self.path.to_string(),
+            full_path.display().to_string(),
+            &file_change_stats,
+            &trigger,
+        );
+        // The scratch directory changes between runs, the golden files must not
+        values.insert(
+            "FILE_NAME_FULL".to_string(),
+            format!("/home/user/project/{}", self.path),
+        );
+        match self.kind {
+            ChangeKind::RenameDir => {
+                values.insert(
+                    "OLD_DIR".to_string(),
+                    self.old_path.unwrap_or_default().to_string(),
+                );
+                values.insert("NEW_DIR".to_string(), self.path.to_string());
+                values.insert("FILE_COUNT".to_string(), "4".to_string());
+            }
+            ChangeKind::Scaffold => {
+                values.insert("DIR".to_string(), self.path.to_string());
+                values.insert("FILE_COUNT".to_string(), "7".to_string());
+            }
+            _ => {}
+        }
+
+        let (message, description) =
+            GitAutoPilot::commit_summary(git_auto_pilot, &repo, self.kind, &values)?;
+        Ok(format!("{}\n\n{}\n", message, description))
+    }
+}
+
+/// Gives a scratch repository the commit convention of a working tree
+///
+/// The `commit.template` of the working tree is referenced, the convention
+/// `file` is copied.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the file cannot be copied or the
+/// template cannot be configured.
+fn copy_convention(
+    repo: &Repository,
+    conventions: &CommitConventions,
+    source: &Path,
+) -> Result<(), GitAutoPilotError> {
+    let template = conventions
+        .commit_template
+        .then(|| Repository::open(source).ok())
+        .flatten()
+        .and_then(|source_repo| git::commit_template_path(&source_repo));
+    if let Some(template) = template {
+        repo.config()?
+            .set_str("commit.template", &template.to_string_lossy())?;
+    }
+
+    let (Some(file), Some(workdir)) = (conventions.file.as_ref(), repo.workdir()) else {
+        return Ok(());
+    };
+    if source.join(file).is_file() {
+        if let Some(parent) = workdir.join(file).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(source.join(file), workdir.join(file))?;
+    }
+    Ok(())
+}
+
+/// Compares the rendered scenarios with their golden files
+///
+/// # Arguments
+/// - `config`: The configuration whose templates are tested.
+/// - `golden_dir`: Directory of the golden files.
+/// - `scratch_dir`: Directory the scenario repositories are made in, emptied
+///   first and removed afterwards.
+/// - `conventions_from`: Working tree the `commit_conventions` file is copied
+///   from, usually the first repository of the configuration.
+/// - `update`: Writes the current output as the new golden files instead.
+///
+/// # Returns
+/// The outcome of every scenario, in the order of `SCENARIOS`.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if a scenario cannot be rendered or a golden
+/// file cannot be written.
+pub fn check(
+    config: &Config,
+    golden_dir: &Path,
+    scratch_dir: &Path,
+    conventions_from: Option<&Path>,
+    update: bool,
+) -> Result<Vec<(&'static str, Outcome)>, GitAutoPilotError> {
+    if update {
+        fs::create_dir_all(golden_dir)?;
+    }
+    // An interrupted run may have left its repositories behind
+    if scratch_dir.exists() {
+        fs::remove_dir_all(scratch_dir)?;
+    }
+    let dirs = paths::Dirs::in_dir(scratch_dir.join("dot"));
+    fs::create_dir_all(&dirs.state)?;
+    let git_auto_pilot = GitAutoPilot::detached_with(
+        Config {
+            repos: Vec::new(),
+            groups: BTreeMap::new(),
+            ..config.clone()
+        },
+        &dirs,
+        dirs.config.join("config.json").display().to_string(),
+    )?;
+
+    let outcomes: Result<Vec<_>, GitAutoPilotError> = SCENARIOS
+        .iter()
+        .map(|scenario| {
+            let workdir = scratch_dir.join(scenario.name);
+            let actual = scenario.render(&git_auto_pilot, &workdir, conventions_from)?;
+            let golden_file = golden_dir.join(format!("{}.txt", scenario.name));
+            let outcome = if update {
+                fs::write(&golden_file, &actual)?;
+                Outcome::Updated
+            } else {
+                match fs::read_to_string(&golden_file) {
+                    Ok(expected) if expected == actual => Outcome::Match,
+                    Ok(expected) => Outcome::Mismatch { expected, actual },
+                    Err(_) => Outcome::Missing,
+                }
+            };
+            Ok((scenario.name, outcome))
+        })
+        .collect();
+
+    if let Err(err) = fs::remove_dir_all(scratch_dir) {
+        warn!("Failed to remove {}: {}", scratch_dir.display(), err);
+    }
+    outcomes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ConventionMode, RepeatAnnotation};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_detects_template_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let golden = dir.path().join("golden");
+        let scratch = dir.path().join("scratch");
+        let run = |config: &Config, update| check(config, &golden, &scratch, None, update).unwrap();
+        let mut config = Config::default();
+
+        let outcomes = run(&config, false);
+        assert!(outcomes
+            .iter()
+            .all(|(_, outcome)| *outcome == Outcome::Missing));
+
+        run(&config, true);
+        let outcomes = run(&config, false);
+        assert!(outcomes
+            .iter()
+            .all(|(_, outcome)| *outcome == Outcome::Match));
+
+        config.message.remove.comment = "Drop {{FILE_NAME_SHORT}}".to_string();
+        let mismatched: Vec<_> = run(&config, false)
+            .into_iter()
+            .filter(|(_, outcome)| matches!(outcome, Outcome::Mismatch { .. }))
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(mismatched, ["remove"]);
+        assert!(!scratch.exists());
+    }
+
+    #[test]
+    fn test_renders_repeats_and_conventions() {
+        let dir = tempfile::tempdir().unwrap();
+        let golden = dir.path().join("golden");
+        let repo_dir = dir.path().join("repo");
+        fs::create_dir_all(&repo_dir).unwrap();
+        fs::write(repo_dir.join("CONVENTION.txt"), "docs:\n").unwrap();
+        let config = Config {
+            repeat_annotation: RepeatAnnotation::Counter,
+            commit_conventions: Some(CommitConventions {
+                commit_template: false,
+                file: Some(PathBuf::from("CONVENTION.txt")),
+                mode: ConventionMode::Prefix,
+            }),
+            ..Config::default()
+        };
+
+        check(
+            &config,
+            &golden,
+            &dir.path().join("scratch"),
+            Some(&repo_dir),
+            true,
+        )
+        .unwrap();
+
+        let summary = |scenario: &str| {
+            let output = fs::read_to_string(golden.join(format!("{}.txt", scenario))).unwrap();
+            output.lines().next().unwrap().to_string()
+        };
+        let first = summary("modify");
+        let again = summary("modify-again");
+        assert!(first.starts_with("docs: "), "{}", first);
+        assert!(!first.ends_with(" #2"), "{}", first);
+        assert_eq!(again, format!("{} #2", first));
+    }
+}
@@ -0,0 +1,130 @@
+//! # Trash Module
+//!
+//! Deletions are committed (and pushed) like any other change, so a file
+//! removed by accident is only recoverable from the history. With `trash`
+//! configured, the last committed version of a deleted file is copied to
+//! `<dot dir>/trash/<repository path>/<timestamp>/<file path>` before the
+//! deletion is committed. Entries older than the retention are deleted
+//! whenever a new one is added to the trash of the repository.
+
+use git2::Repository;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, warn};
+
+use crate::error::GitAutoPilotError;
+use crate::git;
+use crate::helper;
+
+/// Name of the trash directory inside the dot directory
+pub const TRASH_DIR_NAME: &str = "trash";
+
+/// Copies the last committed version of a deleted file to the trash
+///
+/// # Arguments
+/// - `trash_dir`: The trash directory.
+/// - `repo`: The repository the file is deleted from.
+/// - `file_path`: Path of the file relative to the repository root.
+/// - `retention`: How long entries are kept in the trash.
+///
+/// # Returns
+/// The path of the copy, or `None` if the file was never committed.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the committed version cannot be read or
+/// the copy cannot be written.
+pub fn save(
+    trash_dir: &Path,
+    repo: &Repository,
+    file_path: &str,
+    retention: Duration,
+) -> Result<Option<PathBuf>, GitAutoPilotError> {
+    let (_, Some(blob_id)) = git::blob_ids(repo, file_path) else {
+        debug!("{} was never committed, nothing to keep", file_path);
+        return Ok(None);
+    };
+    let blob = repo.find_blob(blob_id)?;
+
+    let repo_dir = trash_dir.join(helper::path_dir_name(repo.workdir().unwrap_or(repo.path())));
+    let target = repo_dir
+        .join(helper::file_timestamp(SystemTime::now()))
+        .join(file_path);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&target, blob.content())?;
+    info!(
+        "Kept a copy of deleted {} in {}",
+        file_path,
+        target.display()
+    );
+
+    if let Err(err) = purge(&repo_dir, retention) {
+        warn!(
+            "Failed to purge the trash in {}: {}",
+            repo_dir.display(),
+            err
+        );
+    }
+    Ok(Some(target))
+}
+
+/// Deletes the entries of a repository's trash older than the retention
+///
+/// # Arguments
+/// - `repo_dir`: The trash directory of the repository.
+/// - `retention`: How long entries are kept.
+///
+/// # Errors
+/// Returns the IO error if the directory cannot be read or an entry cannot be
+/// deleted.
+fn purge(repo_dir: &Path, retention: Duration) -> std::io::Result<()> {
+    let now = SystemTime::now();
+    for entry in fs::read_dir(repo_dir)?.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() > retention);
+        if expired {
+            debug!("Removing expired trash entry {}", entry.path().display());
+            fs::remove_dir_all(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_committed_version_of_deleted_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        {
+            let mut config = repo.config().unwrap();
+            config.set_str("user.name", "Test").unwrap();
+            config.set_str("user.email", "test@example.com").unwrap();
+        }
+        fs::create_dir(dir.path().join("docs")).unwrap();
+        fs::write(dir.path().join("docs/notes.md"), "todo\n").unwrap();
+        git::stage_file(&repo, "docs/notes.md", false).unwrap();
+        git::commit(&repo, "initial", None, None, None, None, None).unwrap();
+        fs::remove_file(dir.path().join("docs/notes.md")).unwrap();
+
+        let week = Duration::from_secs(7 * 24 * 60 * 60);
+        let copy = save(trash.path(), &repo, "docs/notes.md", week)
+            .unwrap()
+            .unwrap();
+        assert!(copy.ends_with("docs/notes.md"));
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "todo\n");
+        assert_eq!(save(trash.path(), &repo, "draft.md", week).unwrap(), None);
+
+        let repo_dir = copy.ancestors().nth(3).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        purge(repo_dir, Duration::ZERO).unwrap();
+        assert!(!copy.exists());
+    }
+}
@@ -0,0 +1,214 @@
+//! # Terminal Dashboard Module
+//!
+//! Implements the `tui` command: a live dashboard of the running daemon built
+//! on the same status snapshot and control queue as the `status` command.
+
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::error::GitAutoPilotError;
+use crate::helper::format_age;
+use crate::ipc::{self, ControlAction, ControlCommand};
+use crate::state::StatusSnapshot;
+
+/// How often the dashboard re-reads the daemon status
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Runs the dashboard until the user quits
+///
+/// # Arguments
+/// - `state_dir` - The state directory the daemon publishes its status to.
+///
+/// # Errors
+/// Returns a `GitAutoPilotError` if the terminal cannot be drawn or a command cannot be queued.
+pub fn run(state_dir: &str) -> Result<(), GitAutoPilotError> {
+    let mut terminal = ratatui::init();
+    let result = run_app(&mut terminal, state_dir);
+    ratatui::restore();
+    result
+}
+
+/// Main loop of the dashboard: draw, wait for input, apply keybindings
+fn run_app(terminal: &mut DefaultTerminal, state_dir: &str) -> Result<(), GitAutoPilotError> {
+    let mut table_state = TableState::default().with_selected(0);
+    let mut message = String::from("Ready");
+
+    loop {
+        let snapshot = ipc::read_snapshot(state_dir).ok();
+        terminal.draw(|frame| draw(frame, snapshot.as_ref(), &mut table_state, &message))?;
+
+        if !event::poll(REFRESH_INTERVAL)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        let action = match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Down | KeyCode::Char('j') => {
+                table_state.select_next();
+                None
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                table_state.select_previous();
+                None
+            }
+            KeyCode::Char('p') => Some(ControlAction::Pause),
+            KeyCode::Char('r') => Some(ControlAction::Resume),
+            KeyCode::Char('s') => Some(ControlAction::ForceSync),
+            _ => None,
+        };
+
+        let selected_repo = snapshot
+            .as_ref()
+            .zip(table_state.selected())
+            .and_then(|(snapshot, index)| snapshot.repos.get(index))
+            .map(|repo| repo.path.clone());
+
+        if let (Some(action), Some(repo)) = (action, selected_repo) {
+            message = format!("Queued {:?} for {}", action, repo.display());
+            ipc::queue_command(state_dir, &ControlCommand { repo, action })?;
+        }
+    }
+}
+
+/// Renders the dashboard
+fn draw(
+    frame: &mut Frame,
+    snapshot: Option<&StatusSnapshot>,
+    table_state: &mut TableState,
+    message: &str,
+) {
+    let [header_area, repos_area, commits_area, footer_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(5),
+        Constraint::Length(12),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let Some(snapshot) = snapshot else {
+        frame.render_widget(
+            Paragraph::new("No daemon status found, is git-auto-pilot running?"),
+            header_area,
+        );
+        frame.render_widget(Paragraph::new("q: quit"), footer_area);
+        return;
+    };
+
+    frame.render_widget(
+        Paragraph::new(format!(
+            "git-auto-pilot (pid {}) - updated {}",
+            snapshot.pid,
+            format_age(snapshot.updated_at)
+        )),
+        header_area,
+    );
+
+    let rows = snapshot.repos.iter().map(|repo| {
+        let state = &repo.state;
+        let last_event = state
+            .last_event
+            .as_ref()
+            .map(|event| format!("{} ({})", event.kind, format_age(event.at)))
+            .unwrap_or_else(|| "-".to_string());
+        let push_health = match (&state.last_push, state.last_successful_push) {
+            (Some(push), _) if push.error.is_none() => format!("ok ({})", format_age(push.at)),
+            (Some(push), Some(pushed_at)) => format!(
+                "failed ({}), ok {}",
+                format_age(push.at),
+                format_age(pushed_at)
+            ),
+            (Some(push), None) => format!("failed ({})", format_age(push.at)),
+            (None, _) => "-".to_string(),
+        };
+        let push_style = if state.push_overdue_since.is_some() {
+            Style::default().fg(Color::Red)
+        } else {
+            Style::default()
+        };
+        let upstream = state
+            .upstream
+            .as_ref()
+            .map(|upstream| format!("+{} -{}", upstream.ahead, upstream.behind))
+            .unwrap_or_else(|| "-".to_string());
+        let (state_label, state_style) = if state.paused {
+            ("paused", Style::default().fg(Color::Yellow))
+        } else if state.storage_error.is_some() {
+            ("suspended", Style::default().fg(Color::Red))
+        } else {
+            ("active", Style::default().fg(Color::Green))
+        };
+
+        Row::new(vec![
+            Line::from(repo.path.display().to_string()),
+            Line::styled(state_label, state_style),
+            Line::from(state.pending_changes.to_string()),
+            Line::from(last_event),
+            Line::styled(push_health, push_style),
+            Line::from(upstream),
+        ])
+    });
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Percentage(30),
+            Constraint::Length(9),
+            Constraint::Length(8),
+            Constraint::Percentage(28),
+            Constraint::Percentage(18),
+            Constraint::Length(10),
+        ],
+    )
+    .header(
+        Row::new(vec![
+            "Repository",
+            "State",
+            "Pending",
+            "Last event",
+            "Push",
+            "Upstream",
+        ])
+        .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    .block(Block::bordered().title("Repositories"));
+    frame.render_stateful_widget(table, repos_area, table_state);
+
+    let commits: Vec<ListItem> = table_state
+        .selected()
+        .and_then(|index| snapshot.repos.get(index))
+        .map(|repo| {
+            repo.state
+                .recent_commits
+                .iter()
+                .map(|commit| {
+                    ListItem::new(format!("{:>10}  {}", format_age(commit.at), commit.message))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    frame.render_widget(
+        List::new(commits).block(Block::bordered().title("Recent commits")),
+        commits_area,
+    );
+
+    frame.render_widget(
+        Paragraph::new(format!(
+            "q: quit  j/k: select  p: pause  r: resume  s: force sync | {}",
+            message
+        )),
+        footer_area,
+    );
+}
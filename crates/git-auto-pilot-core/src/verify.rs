@@ -0,0 +1,154 @@
+//! # Verify Module
+//!
+//! Runs the `verify_command` of a repository (e.g. `cargo check` or
+//! `prettier --check .`) in its working tree, which holds the change, before
+//! it is committed. What happens with a change failing verification is
+//! decided by `verify_failure`. Hooks such as `post_push_command` run the same
+//! way, with their variables in the environment.
+
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use tracing::{debug, trace};
+
+/// Number of trailing output lines kept to describe a failure
+const FAILURE_OUTPUT_LINES: usize = 5;
+
+/// Runs a verify command in a working tree
+///
+/// # Arguments
+/// - `workdir`: The working tree of the repository.
+/// - `command`: The shell command.
+/// - `timeout`: Time after which the command is killed and counts as failed.
+///
+/// # Errors
+/// Returns a description of the failure (exit status and the last lines of
+/// the output) if the command cannot be run, fails or times out.
+pub fn run(workdir: &Path, command: &str, timeout: Duration) -> Result<(), String> {
+    run_with_env(workdir, command, &[], timeout)
+}
+
+/// Runs a command in a working tree with additional environment variables,
+/// see `run`
+pub fn run_with_env(
+    workdir: &Path,
+    command: &str,
+    env: &[(&str, String)],
+    timeout: Duration,
+) -> Result<(), String> {
+    trace!("Running command in {}: {}", workdir.display(), command);
+    // Both streams go through one pipe, read on its own thread so a chatty
+    // command cannot block on a full pipe
+    let mut shell = Command::new("sh");
+    shell
+        .arg("-c")
+        .arg(format!("exec 2>&1\n{}", command))
+        .current_dir(workdir)
+        .envs(env.iter().map(|(name, value)| (*name, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped());
+    // The command gets its own process group, so a timeout kills the
+    // processes it started as well
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut shell, 0);
+    let mut child = shell
+        .spawn()
+        .map_err(|err| format!("failed to run `{}`: {}", command, err))?;
+    let mut stdout = child.stdout.take();
+    let reader = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        if let Some(stdout) = stdout.as_mut() {
+            let _ = stdout.read_to_end(&mut output);
+        }
+        output
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if started.elapsed() > timeout => {
+                kill_process_group(&mut child);
+                let _ = child.wait();
+                return Err(format!("`{}` timed out after {:?}", command, timeout));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+            Err(err) => return Err(format!("failed to wait for `{}`: {}", command, err)),
+        }
+    };
+    let output = reader.join().unwrap_or_default();
+    let output = String::from_utf8_lossy(&output);
+    if status.success() {
+        debug!("Command `{}` passed", command);
+        return Ok(());
+    }
+
+    let lines: Vec<&str> = output.trim().lines().collect();
+    let tail = lines[lines.len().saturating_sub(FAILURE_OUTPUT_LINES)..].join("\n");
+    Err(format!("`{}` failed ({}): {}", command, status, tail))
+}
+
+/// Kills a command and every process of its process group
+fn kill_process_group(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        // A negative PID addresses the process group
+        let killed = Command::new("kill")
+            .args(["-s", "KILL", "--", &format!("-{}", child.id())])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if killed {
+            return;
+        }
+    }
+    let _ = child.kill();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reports_failing_command_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let timeout = Duration::from_secs(10);
+        assert_eq!(run(dir.path(), "test -d .", timeout), Ok(()));
+
+        let err = run(
+            dir.path(),
+            "echo checking; echo broken >&2; exit 3",
+            timeout,
+        )
+        .unwrap_err();
+        assert!(err.contains("checking\nbroken"));
+
+        let err = run(dir.path(), "sleep 5", Duration::from_millis(100)).unwrap_err();
+        assert!(err.contains("timed out"));
+
+        let env = [("COMMIT_SHA", "abc123".to_string())];
+        assert_eq!(
+            run_with_env(dir.path(), "test \"$COMMIT_SHA\" = abc123", &env, timeout),
+            Ok(())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_timeout_kills_started_processes() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let err = run(
+            dir.path(),
+            "(sleep 1; touch survived) & wait",
+            Duration::from_millis(100),
+        )
+        .unwrap_err();
+        assert!(err.contains("timed out"));
+
+        std::thread::sleep(Duration::from_millis(1500));
+        assert!(!dir.path().join("survived").exists());
+    }
+}
@@ -0,0 +1,1339 @@
+//! # Watcher Module
+//!
+//! The watch loop of the daemon: resolving the configured repositories,
+//! receiving file system events, batching low priority changes, deferring
+//! events during builds or storage errors and answering control commands.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+
+use git2::Repository;
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecursiveMode};
+use tokio::task;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, info_span, trace, warn};
+
+use crate::config::{self, Priority};
+use crate::git;
+use crate::pipeline::Trigger;
+use crate::{
+    health, helper, ipc, journal, onboarding, queue, state, GitAutoPilot, GitAutoPilotError,
+};
+
+/// How often the daemon checks for commands queued by `status`/`tui` clients
+const CONTROL_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long no further event may arrive for a batch of events to be complete
+const BATCH_QUIET_PERIOD: Duration = Duration::from_secs(1);
+
+/// When `watch_until` stops without being cancelled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WatchExit {
+    /// Runs until cancelled
+    #[default]
+    Never,
+
+    /// Stops once the first batch of events was processed, i.e. no further
+    /// event arrived for `BATCH_QUIET_PERIOD`
+    AfterBatch,
+
+    /// Stops once this many commits were made
+    AfterCommits(u64),
+}
+
+/// How often glob repository entries are expanded again
+const REPO_RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Repositories found by expanding the configured repository entries
+struct ResolvedRepos {
+    /// Canonical paths of the repositories to watch
+    repos: Vec<PathBuf>,
+
+    /// Settings of the repositories in groups or with their own variables
+    repo_configs: HashMap<PathBuf, config::Config>,
+
+    /// Watched files of the repositories configured through single files
+    watched_files: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+/// Interval between checks whether a full or read-only file system recovered
+const STORAGE_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long shutdown waits for background jobs (fetches, bundles, ...)
+const JOB_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl GitAutoPilot {
+    /// Expands the configured repository entries into the repositories to watch
+    ///
+    /// Glob entries (e.g. `~/projects/*`) match the repositories currently below
+    /// them, so the watch loop expands them again every `REPO_RESCAN_INTERVAL`
+    /// to pick up newly cloned repositories. Also resolves the group settings
+    /// of every repository.
+    ///
+    /// # Returns
+    /// The canonical paths of all repositories, including those of all groups,
+    /// limited to the `--repo` subset if one is set
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the settings of a group are invalid
+    pub(crate) fn resolve_repos(&mut self) -> Result<Vec<PathBuf>, GitAutoPilotError> {
+        let resolved = Self::expand_repos(&self.config, &self.repo_filter)?;
+        Ok(Self::apply_resolved_repos(self, resolved))
+    }
+
+    /// Walks the configured repository entries, without touching the instance
+    ///
+    /// Expanding `**` entries walks whole directory trees, so the watch loop
+    /// runs this on a blocking task.
+    ///
+    /// # Arguments
+    /// - `config` - The configuration holding the entries.
+    /// - `repo_filter` - The `--repo` subset, empty for all repositories.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the settings of a group are invalid
+    fn expand_repos(
+        config: &config::Config,
+        repo_filter: &[PathBuf],
+    ) -> Result<ResolvedRepos, GitAutoPilotError> {
+        let mut entries: Vec<(PathBuf, Option<config::Config>)> = Vec::new();
+        let mut scopes = vec![(&config.repos, None)];
+        for (name, group) in &config.groups {
+            scopes.push((&group.repos, Some(config.for_group(name)?)));
+        }
+        for (repo_entries, group_config) in scopes {
+            for repo_entry in repo_entries {
+                // Variables of the entry are layered over those of its scope
+                let entry_config = repo_entry.variables().map(|variables| {
+                    let mut entry_config = group_config.clone().unwrap_or_else(|| config.clone());
+                    entry_config.layer_variables(variables);
+                    entry_config
+                });
+                entries.extend(
+                    helper::expand_repo_entries(&[repo_entry.path().to_path_buf()])
+                        .into_iter()
+                        .map(|entry| (entry, entry_config.clone().or(group_config.clone()))),
+                );
+            }
+        }
+
+        let mut repos = Vec::new();
+        let mut repo_configs = HashMap::new();
+        let mut watched_files: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+        let mut whole_repos = Vec::new();
+        for (entry, group_config) in entries {
+            // An entry pointing at a file watches only that file of its repository
+            let repo = if entry.is_file() {
+                let Some(repo) = helper::file_repository(&entry) else {
+                    warn!("{} is not inside a git repository", entry.display());
+                    continue;
+                };
+                watched_files
+                    .entry(state::repo_key(&repo))
+                    .or_default()
+                    .push(entry);
+                repo
+            } else {
+                whole_repos.push(state::repo_key(&entry));
+                entry
+            };
+            if let Some(group_config) = group_config {
+                repo_configs.insert(state::repo_key(&repo), group_config);
+            }
+            if !repos.contains(&repo) {
+                repos.push(repo);
+            }
+        }
+        watched_files.retain(|repo, _| !whole_repos.contains(repo));
+        if !repo_filter.is_empty() {
+            repos.retain(|repo| repo_filter.contains(repo));
+        }
+
+        Ok(ResolvedRepos {
+            repos,
+            repo_configs,
+            watched_files,
+        })
+    }
+
+    /// Takes over the settings and watched files of expanded repositories
+    ///
+    /// # Returns
+    /// The canonical paths of the repositories to watch
+    fn apply_resolved_repos(&mut self, resolved: ResolvedRepos) -> Vec<PathBuf> {
+        self.repo_configs = resolved.repo_configs;
+        self.watched_files = resolved.watched_files;
+        resolved.repos
+    }
+
+    /// Registers a repository with the file system watcher
+    ///
+    /// Repositories configured through single files only have the directories
+    /// of those files watched, without their subdirectories.
+    ///
+    /// # Errors
+    /// Returns a `notify::Error` if a path cannot be watched.
+    fn watch_repo(
+        &self,
+        watcher: &mut (dyn notify::Watcher + Send),
+        repo: &Path,
+    ) -> Result<(), notify::Error> {
+        let Some(files) = self.watched_files.get(&state::repo_key(repo)) else {
+            return watcher.watch(repo, RecursiveMode::Recursive);
+        };
+        let mut parents: Vec<&Path> = files.iter().filter_map(|file| file.parent()).collect();
+        parents.sort();
+        parents.dedup();
+        for parent in parents {
+            watcher.watch(parent, RecursiveMode::NonRecursive)?;
+        }
+        Ok(())
+    }
+
+    /// Removes a repository registered by `watch_repo` from the watcher
+    ///
+    /// # Errors
+    /// Returns a `notify::Error` if a path cannot be unwatched.
+    fn unwatch_repo(
+        &self,
+        watcher: &mut (dyn notify::Watcher + Send),
+        repo: &Path,
+    ) -> Result<(), notify::Error> {
+        let Some(files) = self.watched_files.get(&state::repo_key(repo)) else {
+            return watcher.unwatch(repo);
+        };
+        let mut parents: Vec<&Path> = files.iter().filter_map(|file| file.parent()).collect();
+        parents.sort();
+        parents.dedup();
+        for parent in parents {
+            watcher.unwatch(parent)?;
+        }
+        Ok(())
+    }
+
+    /// Whether a path lies in one of the `ignored_dirs`
+    pub(crate) fn is_in_ignored_dir(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy();
+        self.config
+            .ignored_dirs
+            .iter()
+            .any(|ignored| path.contains(&format!("/{}", ignored)))
+    }
+
+    /// Whether an event concerns the watched part of a repository
+    ///
+    /// Repositories configured through single files only react to events on
+    /// those exact files.
+    pub(crate) fn is_watched(&self, repo: &Path, paths: &[PathBuf]) -> bool {
+        self.watched_files
+            .get(&state::repo_key(repo))
+            .is_none_or(|files| helper::is_watched_file(paths, files))
+    }
+
+    /// Watches file system changes in specified repositories and processes the events.
+    ///
+    /// Runs until the watcher stops; use `watch_with_cancellation` to be able to stop it.
+    ///
+    /// # Arguments
+    /// - `self` - The current instance of the struct containing configuration and other details.
+    ///
+    /// # Returns
+    /// - `Result<(), GitAutoPilotError>` - Returns `Ok(())` if successful, otherwise an error of type `GitAutoPilotError`.
+    ///
+    /// # Errors
+    /// - Returns an error if the watcher setup or event processing fails.
+    pub async fn watch(self) -> Result<(), GitAutoPilotError> {
+        self.watch_with_cancellation(CancellationToken::new()).await
+    }
+
+    /// Watches file system changes in specified repositories until cancelled.
+    ///
+    /// # Arguments
+    /// - `self` - The current instance of the struct containing configuration and other details.
+    /// - `cancellation` - Token stopping the watch once cancelled.
+    ///
+    /// # Returns
+    /// - `Result<(), GitAutoPilotError>` - Returns `Ok(())` once the watch stopped, otherwise an error of type `GitAutoPilotError`.
+    ///
+    /// # Behavior
+    /// 1. Creates a standard library channel and a Tokio channel for event handling.
+    /// 2. Configures a file watcher for directories specified in the configuration.
+    /// 3. Bridges events from the standard channel to the Tokio channel.
+    /// 4. Processes events asynchronously to handle file system changes.
+    /// 5. On cancellation, drops the watcher and waits for the bridge task to finish.
+    ///
+    /// # Errors
+    /// - Returns an error if the watcher setup or event processing fails.
+    ///
+    /// # Examples
+    /// ```ignore
+    /// let cancellation = CancellationToken::new();
+    /// let handle = tokio::spawn(git_auto_pilot.watch_with_cancellation(cancellation.clone()));
+    /// // ...
+    /// cancellation.cancel();
+    /// handle.await??;
+    /// ```
+    pub async fn watch_with_cancellation(
+        self,
+        cancellation: CancellationToken,
+    ) -> Result<(), GitAutoPilotError> {
+        self.watch_until(cancellation, WatchExit::Never).await
+    }
+
+    /// Watches file system changes in specified repositories until cancelled
+    /// or until the exit condition is met, for scripts and end-to-end tests.
+    ///
+    /// # Arguments
+    /// - `self` - The current instance of the struct containing configuration and other details.
+    /// - `cancellation` - Token stopping the watch once cancelled.
+    /// - `exit` - Condition stopping the watch on its own.
+    ///
+    /// # Returns
+    /// - `Result<(), GitAutoPilotError>` - Returns `Ok(())` once the watch stopped, otherwise an error of type `GitAutoPilotError`.
+    ///
+    /// # Errors
+    /// - Returns an error if the watcher setup or event processing fails.
+    pub async fn watch_until(
+        mut self,
+        cancellation: CancellationToken,
+        exit: WatchExit,
+    ) -> Result<(), GitAutoPilotError> {
+        trace!("Starting watch function...");
+        self.watching = true;
+
+        // Serve the health probes while watching
+        let health_cancellation = cancellation.child_token();
+        if let Some(port) = self.config.health_port {
+            let address = (self.config.health_address, port);
+            let listener = tokio::net::TcpListener::bind(address).await?;
+            info!("Serving health probes on {}:{}", address.0, port);
+            task::spawn(health::serve(
+                listener,
+                self.health.clone(),
+                health_cancellation.clone(),
+            ));
+        }
+
+        // Create a standard library channel for file system events
+        let (tx, rx) = mpsc::channel();
+
+        // Tokio channel for async processing
+        let (async_tx, mut async_rx) = tokio::sync::mpsc::channel(100);
+
+        // Configure watcher
+        // The poll watcher need not compare contents if every repository hashes them
+        let compare_contents = !(self.config.content_hashing
+            && self
+                .repo_configs
+                .values()
+                .all(|config| config.content_hashing));
+        let mut watcher = helper::create_watcher(tx, compare_contents)?;
+
+        // Directories to watch
+        let mut watch_paths = Self::resolve_repos(&mut self)?;
+
+        // Watch multiple directories
+        for path in &watch_paths {
+            info!("Adding watch for path: {:#?}", path);
+            Self::watch_repo(&self, watcher.as_mut(), path)?;
+            // Register the repository so it shows up in the published status
+            self.state.with_repo(path, |_| ());
+            if let Ok(repo) = Repository::open(path) {
+                Self::refresh_upstream(&self, &repo);
+                Self::seed_push_state(&self, &repo);
+            }
+            let onboarded =
+                onboarding::is_onboarded(&self.state_dir_location, &state::repo_key(path));
+            if let Err(err) = Self::onboard_repo(&self, path, true) {
+                error!("Failed to onboard {}: {}", path.display(), err);
+            }
+            let catch_up_on_start = self
+                .repo_configs
+                .get(&state::repo_key(path))
+                .unwrap_or(&self.config)
+                .catch_up_on_start;
+            if onboarded && catch_up_on_start {
+                if let Err(err) = Self::catch_up(&self, path) {
+                    error!("Failed to catch up on {}: {}", path.display(), err);
+                }
+            }
+        }
+        // Events queued when the previous run shut down are replayed as deferred
+        // events; those of repositories not watched now stay queued
+        let mut unreplayed = queue::take(&self.state_dir_location);
+        for path in &watch_paths {
+            if let Some(paths) = unreplayed.remove(&state::repo_key(path)) {
+                info!(
+                    "Replaying {} events queued before the last shutdown in {}",
+                    paths.len(),
+                    path.display()
+                );
+                self.state
+                    .with_repo(path, |state| state.deferred_paths.extend(paths));
+            }
+        }
+        Self::publish_status(&self);
+        self.health.set_ready();
+
+        // Spawn a blocking task to bridge standard channel to Tokio channel,
+        // it ends once the watcher (owning the sender) is dropped
+        let bridge_handle = task::spawn_blocking(move || {
+            for event in rx {
+                trace!("Received event: {:?}", event);
+                if async_tx.blocking_send((SystemTime::now(), event)).is_err() {
+                    debug!("Async channel closed, stopping event bridge");
+                    break;
+                }
+            }
+        });
+
+        // Poll for commands queued by client commands
+        let mut control_interval = tokio::time::interval(CONTROL_POLL_INTERVAL);
+
+        // Re-expand glob repository entries to pick up new repositories
+        let mut rescan_interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + REPO_RESCAN_INTERVAL,
+            REPO_RESCAN_INTERVAL,
+        );
+
+        // Expansion of the repository entries running on a blocking task
+        let mut rescan: Option<task::JoinHandle<Result<ResolvedRepos, GitAutoPilotError>>> = None;
+
+        // Deadline of the current batch of events, only tracked for `WatchExit::AfterBatch`
+        let mut batch_deadline: Option<tokio::time::Instant> = None;
+
+        // Process events
+        loop {
+            if let WatchExit::AfterCommits(limit) = exit {
+                if self.session_commits.load(Ordering::Relaxed) >= limit {
+                    info!("Made {} commits, shutting down", limit);
+                    break;
+                }
+            }
+
+            let (received_at, result) = tokio::select! {
+                _ = cancellation.cancelled() => {
+                    info!("Watch cancelled, shutting down");
+                    break;
+                }
+                _ = tokio::time::sleep_until(
+                    batch_deadline.unwrap_or_else(tokio::time::Instant::now)
+                ), if batch_deadline.is_some() => {
+                    info!("Processed a batch of events, shutting down");
+                    break;
+                }
+                received = async_rx.recv() => match received {
+                    Some(received) => received,
+                    None => break,
+                },
+                _ = control_interval.tick() => {
+                    self.health.beat();
+                    for job_done in self.jobs.finished() {
+                        job_done(&self);
+                    }
+                    Self::process_control_commands(&self);
+                    for repo in Self::probe_storage_recovery(&self) {
+                        // The watches were dropped with the device
+                        let _ = watcher.unwatch(&repo);
+                        if let Err(err) = Self::watch_repo(&self, watcher.as_mut(), &repo) {
+                            error!("Failed to watch {}: {}", repo.display(), err);
+                            continue;
+                        }
+                        if let Err(err) = Self::catch_up(&self, &repo) {
+                            error!("Failed to catch up on {}: {}", repo.display(), err);
+                        }
+                    }
+                    Self::retry_deferred_events(&self);
+                    Self::follow_remotes(&self);
+                    Self::flush_low_priority_batches(&self, false);
+                    Self::flush_pending_pushes(&self, false);
+                    Self::check_disk_usage(&self);
+                    Self::check_push_lag(&self);
+                    Self::run_due_maintenance(&self);
+                    Self::write_due_bundles(&self);
+                    continue;
+                }
+                _ = rescan_interval.tick(), if rescan.is_none() => {
+                    let config = self.config.clone();
+                    let repo_filter = self.repo_filter.clone();
+                    rescan = Some(task::spawn_blocking(move || {
+                        Self::expand_repos(&config, &repo_filter)
+                    }));
+                    continue;
+                }
+                expanded = async { rescan.as_mut().expect("rescan is running").await },
+                    if rescan.is_some() =>
+                {
+                    rescan = None;
+                    match expanded {
+                        Ok(Ok(resolved)) => {
+                            // Repositories no longer matched are unwatched with
+                            // the watched files they were registered with
+                            let (kept, removed): (Vec<PathBuf>, Vec<PathBuf>) = watch_paths
+                                .drain(..)
+                                .partition(|repo| resolved.repos.contains(repo));
+                            for repo in removed {
+                                info!("Removing watch for repository: {}", repo.display());
+                                if let Err(err) =
+                                    Self::unwatch_repo(&self, watcher.as_mut(), &repo)
+                                {
+                                    warn!("Failed to unwatch {}: {}", repo.display(), err);
+                                }
+                            }
+                            watch_paths = kept;
+
+                            for repo in Self::apply_resolved_repos(&mut self, resolved) {
+                                if watch_paths.contains(&repo) {
+                                    continue;
+                                }
+                                info!("Adding watch for new repository: {}", repo.display());
+                                if let Err(err) = Self::watch_repo(&self, watcher.as_mut(), &repo) {
+                                    error!("Failed to watch {}: {}", repo.display(), err);
+                                    continue;
+                                }
+                                self.state.with_repo(&repo, |_| ());
+                                if let Err(err) = Self::onboard_repo(&self, &repo, false) {
+                                    error!("Failed to onboard {}: {}", repo.display(), err);
+                                }
+                                watch_paths.push(repo);
+                            }
+                        }
+                        Ok(Err(err)) => error!("Failed to resolve repositories: {}", err),
+                        Err(err) => error!("Repository rescan failed: {}", err),
+                    }
+                    continue;
+                }
+            };
+            match result {
+                Ok(event) => {
+                    // Build output is usually in an ignored directory, so it is
+                    // checked first; events only touching build output are dropped
+                    let build_output = event
+                        .paths
+                        .first()
+                        .and_then(|path| helper::get_matching_repository(path, &watch_paths))
+                        .is_some_and(|repo| Self::record_build_activity(&self, &event, repo));
+                    if build_output {
+                        continue;
+                    }
+
+                    // Check if the event is in an ignored directory
+                    if event
+                        .paths
+                        .iter()
+                        .any(|path| Self::is_in_ignored_dir(&self, path))
+                    {
+                        continue;
+                    }
+
+                    // Skip our own status and control files
+                    if event.paths.iter().all(|path| {
+                        path.starts_with(&self.dot_dir_location)
+                            || path.starts_with(&self.state_dir_location)
+                    }) {
+                        continue;
+                    }
+
+                    debug!("Handling event: {:?}", event);
+                    trace!("Finding correct repo that triggered event");
+
+                    if let Some(repo) =
+                        helper::get_matching_repository(&event.paths[0], &watch_paths)
+                    {
+                        debug!("Matched repository for event: {:?}", repo);
+                        if !Self::is_watched(&self, repo, &event.paths) {
+                            trace!("Ignoring event outside the watched files of {:?}", repo);
+                            continue;
+                        }
+                        Self::record_in_journal(&self, || {
+                            let entry = journal::JournalEntry::new(repo, &event, received_at);
+                            match Repository::open(repo) {
+                                Ok(repository) => entry.with_files(&repository),
+                                Err(_) => entry,
+                            }
+                        });
+                        // Outcomes of `verify_command` no longer apply
+                        self.state
+                            .with_repo(repo, |state| state.tree_generation += 1);
+                        if Self::event_priority(&self, repo, &event.paths) == Priority::Low
+                            && matches!(
+                                event.kind,
+                                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                            )
+                        {
+                            trace!("Batching low priority event: {:?}", event.paths);
+                            self.state.with_repo(repo, |state| {
+                                state.batched_paths.extend(event.paths.iter().cloned());
+                                state.batched_since.get_or_insert(received_at);
+                            });
+                            continue;
+                        }
+                        let event_span = info_span!(
+                            "event",
+                            repo = %repo.display(),
+                            kind = ?event.kind
+                        );
+                        let result = event_span
+                            .in_scope(|| Self::handle_event(&self, &event, repo, received_at));
+                        if let Err(err) = &result {
+                            Self::pause_on_storage_error(&self, repo, err);
+                        }
+                        Self::publish_status(&self);
+                        if exit == WatchExit::AfterBatch {
+                            batch_deadline = Some(tokio::time::Instant::now() + BATCH_QUIET_PERIOD);
+                        }
+                    } else {
+                        debug!("No matching repository found for paths: {:?}", event.paths);
+                    }
+                }
+                Err(e) => error!("Watch error: {:?}", e),
+            }
+        }
+
+        // Apply the results of the jobs still running, e.g. a fetch
+        for job_done in self.jobs.wait_finished(JOB_SHUTDOWN_TIMEOUT) {
+            job_done(&self);
+        }
+
+        // Process low priority events and push commits still waiting in a batch
+        Self::flush_low_priority_batches(&self, true);
+        Self::flush_pending_pushes(&self, true);
+        Self::persist_queue(&self, unreplayed);
+        health_cancellation.cancel();
+
+        // Stop the watcher and close the channel so the bridge task ends
+        drop(watcher);
+        drop(async_rx);
+
+        // Wait for the bridge task to complete
+        bridge_handle.await?;
+        info!("Watch function completed successfully.");
+        Ok(())
+    }
+
+    /// Records changes of build output for `build_detection`
+    ///
+    /// # Returns
+    /// `true` if every path of the event is build output, so the event carries
+    /// nothing to commit.
+    fn record_build_activity(&self, event: &Event, repo: &Path) -> bool {
+        let config = self
+            .repo_configs
+            .get(&state::repo_key(repo))
+            .unwrap_or(&self.config);
+        let Some(settings) = config.build_detection.as_ref() else {
+            return false;
+        };
+        let is_build_output = |path: &PathBuf| {
+            path.strip_prefix(repo).is_ok_and(|relative| {
+                let relative = relative.to_string_lossy();
+                settings
+                    .indicators
+                    .iter()
+                    .any(|pattern| helper::matches_path_pattern(&relative, pattern))
+            })
+        };
+        let build_outputs = event
+            .paths
+            .iter()
+            .filter(|path| is_build_output(path))
+            .count();
+        if build_outputs == 0 {
+            return false;
+        }
+
+        let quiet = Duration::from_secs(settings.quiet_secs);
+        let previous = self.state.with_repo(repo, |state| {
+            state.build_activity_at.replace(SystemTime::now())
+        });
+        if previous.is_none_or(|at| at.elapsed().unwrap_or_default() >= quiet) {
+            info!("Build detected in {}, pausing auto-commits", repo.display());
+        }
+        build_outputs == event.paths.len()
+    }
+
+    /// Checks whether a build is running in a repository, see `build_detection`
+    pub(crate) fn build_in_progress(&self, repo: &Path) -> bool {
+        let config = self
+            .repo_configs
+            .get(&state::repo_key(repo))
+            .unwrap_or(&self.config);
+        let Some(settings) = config.build_detection.as_ref() else {
+            return false;
+        };
+        if settings
+            .busy_file
+            .as_ref()
+            .is_some_and(|busy_file| repo.join(busy_file).exists())
+        {
+            return true;
+        }
+        self.state
+            .with_repo(repo, |state| state.build_activity_at)
+            .is_some_and(|at| {
+                at.elapsed().unwrap_or_default() < Duration::from_secs(settings.quiet_secs)
+            })
+    }
+
+    /// Processes the low priority events of every repository whose batch is due
+    ///
+    /// # Arguments
+    /// - `force`: Process all waiting events regardless of their age.
+    fn flush_low_priority_batches(&self, force: bool) {
+        let mut flushed = false;
+        for repo_status in self.state.snapshot().repos {
+            let Some(since) = repo_status.state.batched_since else {
+                continue;
+            };
+            let batch_secs = self
+                .repo_configs
+                .get(&state::repo_key(&repo_status.path))
+                .unwrap_or(&self.config)
+                .low_priority_batch_secs;
+            if !force && since.elapsed().unwrap_or_default() < Duration::from_secs(batch_secs) {
+                continue;
+            }
+
+            let paths = self.state.with_repo(&repo_status.path, |state| {
+                state.batched_since = None;
+                std::mem::take(&mut state.batched_paths)
+            });
+            debug!(
+                "Processing {} batched low priority paths of {}",
+                paths.len(),
+                repo_status.path.display()
+            );
+            let mut event = Event::new(EventKind::Modify(ModifyKind::Any));
+            event.paths = paths.into_iter().collect();
+            let batch_by_package = self
+                .repo_configs
+                .get(&state::repo_key(&repo_status.path))
+                .unwrap_or(&self.config)
+                .batch_by_package;
+            let handled = if batch_by_package {
+                Self::commit_batch_by_package(self, &event, &repo_status.path, since)
+            } else {
+                Self::handle_event(self, &event, &repo_status.path, since)
+            };
+            if let Err(err) = handled {
+                error!(
+                    "Failed to handle batched events of {}: {}",
+                    repo_status.path.display(),
+                    err
+                );
+                Self::pause_on_storage_error(self, &repo_status.path, &err);
+            }
+            flushed = true;
+        }
+        if flushed {
+            Self::publish_status(self);
+        }
+    }
+
+    /// Commits the low priority batch of a repository as one commit per
+    /// package, summarized as a Conventional Commit scoped by the package
+    ///
+    /// A batch that cannot be committed right away (paused, busy or settling
+    /// repository) goes through `handle_event` instead, which defers it.
+    ///
+    /// # Arguments
+    /// - `event` - The batched paths.
+    /// - `repo_path` - The repository.
+    /// - `since` - Time the oldest batched event was received.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the changes cannot be analyzed or a
+    /// commit fails.
+    fn commit_batch_by_package(
+        &self,
+        event: &Event,
+        repo_path: &Path,
+        since: SystemTime,
+    ) -> Result<(), GitAutoPilotError> {
+        let repo = Repository::open(repo_path)?;
+        let config = Self::repo_config(self, &repo);
+        let unsettled = config.write_settle_ms > 0
+            && helper::write_settle_remaining(
+                &event.paths,
+                Duration::from_millis(config.write_settle_ms),
+            )
+            .is_some();
+        let held = self.state.with_repo(repo_path, |state| {
+            state.paused || state.storage_error.is_some()
+        });
+        if held
+            || unsettled
+            || helper::is_automation_disabled(&repo)
+            || git::busy_marker(&repo).is_some()
+            || Self::build_in_progress(self, repo_path)
+        {
+            return Self::handle_event(self, event, repo_path, since);
+        }
+
+        let operation_lock = self.state.operation_lock(repo_path);
+        let _operation_guard = operation_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut changes =
+            self.analysis_cache
+                .analyze(&state::repo_key(repo_path), &repo, &event.paths)?;
+        changes.retain(|path, _| {
+            Self::is_watched(self, repo_path, &[repo_path.join(path)])
+                && !Self::has_skip_marker(self, &repo, path)
+                && !Self::is_sensitive(self, &repo, path)
+        });
+        let workdir = repo.workdir().unwrap_or(repo_path);
+        let trigger = Trigger::from_event(event, since);
+        for (package, files) in onboarding::group_by_package(workdir, changes.keys()) {
+            let subject = match files.as_slice() {
+                [file] => format!("update {}", file),
+                files => format!("update {} files", files.len()),
+            };
+            let message = match package.as_str() {
+                onboarding::ROOT_DIR => format!("chore: {}", subject),
+                package => format!("chore({}): {}", package, subject),
+            };
+            Self::commit_group(
+                self,
+                &repo,
+                &changes,
+                files,
+                &message,
+                |file_count| format!("Committed {} low priority files as one batch", file_count),
+                &trigger,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Persists the events still queued at shutdown for the next start
+    ///
+    /// # Arguments
+    /// - `queue` - Queued events of repositories not watched by this run.
+    fn persist_queue(&self, mut queue: queue::Queue) {
+        for repo_status in self.state.snapshot().repos {
+            let paths: BTreeSet<PathBuf> = repo_status
+                .state
+                .deferred_paths
+                .into_iter()
+                .chain(repo_status.state.batched_paths)
+                .chain(repo_status.state.retry_at.into_keys())
+                .collect();
+            if !paths.is_empty() {
+                info!(
+                    "Keeping {} queued events of {} for the next start",
+                    paths.len(),
+                    repo_status.path.display()
+                );
+                queue
+                    .entry(state::repo_key(&repo_status.path))
+                    .or_default()
+                    .extend(paths);
+            }
+        }
+        if let Err(err) = queue::save(&self.state_dir_location, &queue) {
+            error!("Failed to persist the queued events: {}", err);
+        }
+    }
+
+    /// Replays the events deferred by `handle_event` of every repository whose
+    /// manual git operation or background verification has finished, along
+    /// with the events whose retry time has come
+    pub(crate) fn retry_deferred_events(&self) {
+        let mut retried = false;
+        let now = SystemTime::now();
+        for repo_status in self.state.snapshot().repos {
+            let due: Vec<PathBuf> = repo_status
+                .state
+                .retry_at
+                .iter()
+                .filter(|(_, at)| **at <= now)
+                .map(|(path, _)| path.clone())
+                .collect();
+            if repo_status.state.deferred_paths.is_empty() && due.is_empty() {
+                continue;
+            }
+            let Ok(repo) = Repository::open(&repo_status.path) else {
+                continue;
+            };
+            if git::busy_marker(&repo).is_some()
+                || Self::build_in_progress(self, &repo_status.path)
+                || self.jobs.is_running(&repo_status.path, "verify")
+            {
+                continue;
+            }
+
+            let paths = self.state.with_repo(&repo_status.path, |state| {
+                for path in &due {
+                    state.retry_at.remove(path);
+                }
+                let mut paths = std::mem::take(&mut state.deferred_paths);
+                paths.extend(due);
+                paths
+            });
+            debug!(
+                "Retrying {} deferred paths of {}",
+                paths.len(),
+                repo_status.path.display()
+            );
+            let mut event = Event::new(EventKind::Modify(ModifyKind::Any));
+            event.paths = paths.into_iter().collect();
+            if let Err(err) = Self::handle_event(self, &event, &repo_status.path, SystemTime::now())
+            {
+                error!(
+                    "Failed to handle deferred event of {}: {}",
+                    repo_status.path.display(),
+                    err
+                );
+                Self::pause_on_storage_error(self, &repo_status.path, &err);
+            }
+            retried = true;
+        }
+        if retried {
+            Self::publish_status(self);
+        }
+    }
+
+    /// Measures the object database of every repository that is due for a check
+    ///
+    /// Warns once the growth since the first measurement exceeds `warn_growth_mb`
+    /// and runs `git gc` if `auto_gc` is enabled, which also resets the baseline.
+    fn check_disk_usage(&self) {
+        for repo_status in self.state.snapshot().repos {
+            let Ok(repo) = Repository::open(&repo_status.path) else {
+                continue;
+            };
+            let Some(settings) = Self::repo_config(self, &repo).disk_usage.as_ref() else {
+                continue;
+            };
+            let previous = repo_status.state.disk_usage.as_ref();
+            let interval = Duration::from_secs(settings.check_interval_secs);
+            if previous
+                .is_some_and(|record| record.checked_at.elapsed().unwrap_or_default() < interval)
+            {
+                continue;
+            }
+
+            let size = match git::object_store_size(&repo) {
+                Ok(size) => size,
+                Err(err) => {
+                    error!("Failed to measure {}: {}", repo_status.path.display(), err);
+                    continue;
+                }
+            };
+            let baseline = previous.map_or(size, |record| record.baseline_bytes);
+            let growth = size.saturating_sub(baseline);
+            let over_threshold = growth > settings.warn_growth_mb * 1024 * 1024;
+            let mut record = state::DiskUsageRecord {
+                baseline_bytes: baseline,
+                current_bytes: size,
+                checked_at: SystemTime::now(),
+                over_threshold,
+            };
+
+            if over_threshold && settings.auto_gc {
+                let operation_lock = self.state.operation_lock(&repo_status.path);
+                let _operation_guard = operation_lock
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+                info!(
+                    "Object database of {} grew by {}, running git gc",
+                    repo_status.path.display(),
+                    helper::format_size(growth)
+                );
+                match git::gc(&repo)
+                    .map_err(GitAutoPilotError::from)
+                    .and_then(|()| git::object_store_size(&repo).map_err(GitAutoPilotError::from))
+                {
+                    Ok(size) => {
+                        info!(
+                            "git gc done, object database is {}",
+                            helper::format_size(size)
+                        );
+                        record.baseline_bytes = size;
+                        record.current_bytes = size;
+                        record.over_threshold = false;
+                    }
+                    Err(err) => error!("git gc failed for {}: {}", repo_status.path.display(), err),
+                }
+            } else if over_threshold && !previous.is_some_and(|record| record.over_threshold) {
+                warn!(
+                    "Object database of {} grew by {} (now {}), consider running `git gc`",
+                    repo_status.path.display(),
+                    helper::format_size(growth),
+                    helper::format_size(size)
+                );
+            }
+
+            self.state
+                .with_repo(&repo_status.path, |state| state.disk_usage = Some(record));
+        }
+    }
+
+    /// Suspends automation for a repository whose file system is full or
+    /// read-only, or whose device disappeared (e.g. an unplugged drive)
+    ///
+    /// Other errors are ignored. The user is alerted once; the repository is
+    /// resumed by `probe_storage_recovery`.
+    pub(crate) fn pause_on_storage_error(&self, repo: &Path, err: &GitAutoPilotError) {
+        // A vanished working directory means the device holding it is gone
+        let device_gone = err.is_device_gone() || !repo.exists();
+        if !device_gone && !err.is_storage_error() {
+            return;
+        }
+        let newly_paused = self.state.with_repo(repo, |state| {
+            if state.storage_error.is_some() {
+                return false;
+            }
+            let now = SystemTime::now();
+            state.storage_error = Some(state::StorageErrorRecord {
+                since: now,
+                error: err.to_string(),
+                probed_at: now,
+                device_gone,
+            });
+            true
+        });
+        if newly_paused {
+            if device_gone {
+                error!(
+                    "Suspending automation for {} until its device returns: {}",
+                    repo.display(),
+                    err
+                );
+            } else {
+                error!(
+                    "Suspending automation for {}, the file system is full or read-only: {}",
+                    repo.display(),
+                    err
+                );
+            }
+            Self::publish_status(self);
+        }
+    }
+
+    /// Resumes repositories suspended by `pause_on_storage_error` once their
+    /// git directory is writable again
+    ///
+    /// # Returns
+    /// The resumed repositories whose device had disappeared; their watches
+    /// are gone and changes made meanwhile were missed.
+    fn probe_storage_recovery(&self) -> Vec<PathBuf> {
+        let mut resumed = false;
+        let mut returned_devices = Vec::new();
+        for repo_status in self.state.snapshot().repos {
+            let Some(storage_error) = &repo_status.state.storage_error else {
+                continue;
+            };
+            if storage_error.probed_at.elapsed().unwrap_or_default() < STORAGE_PROBE_INTERVAL {
+                continue;
+            }
+            let probed = Repository::open(&repo_status.path)
+                .map_err(GitAutoPilotError::from)
+                .and_then(|repo| Ok(helper::probe_writable(repo.path())?));
+
+            match probed {
+                Ok(()) => {
+                    info!(
+                        "Storage recovered, resuming automation for {}",
+                        repo_status.path.display()
+                    );
+                    self.state
+                        .with_repo(&repo_status.path, |state| state.storage_error = None);
+                    if storage_error.device_gone {
+                        returned_devices.push(repo_status.path);
+                    }
+                    resumed = true;
+                }
+                Err(err) => {
+                    trace!(
+                        "Storage still unavailable for {}: {}",
+                        repo_status.path.display(),
+                        err
+                    );
+                    self.state.with_repo(&repo_status.path, |state| {
+                        if let Some(record) = &mut state.storage_error {
+                            record.probed_at = SystemTime::now();
+                        }
+                    });
+                }
+            }
+        }
+        if resumed {
+            Self::publish_status(self);
+        }
+        returned_devices
+    }
+
+    /// Publishes the current runtime state for `status`/`tui` clients
+    pub(crate) fn publish_status(&self) {
+        let snapshot = self.state.snapshot();
+        if let Err(err) = ipc::write_snapshot(&self.state_dir_location, &snapshot) {
+            error!("Failed to publish status: {}", err);
+        }
+        self.health.update(snapshot);
+    }
+
+    /// Applies the commands queued by `status`/`tui` clients
+    fn process_control_commands(&self) {
+        let commands = ipc::take_commands(&self.state_dir_location);
+        if commands.is_empty() {
+            return;
+        }
+
+        for command in commands {
+            info!(
+                "Received {:?} command for {}",
+                command.action,
+                command.repo.display()
+            );
+            match command.action {
+                ipc::ControlAction::Pause => self
+                    .state
+                    .with_repo(&command.repo, |state| state.paused = true),
+                ipc::ControlAction::Resume => self.state.with_repo(&command.repo, |state| {
+                    state.paused = false;
+                    state.remote_pause = None;
+                }),
+                ipc::ControlAction::ForceSync => {
+                    if let Err(err) = Self::force_sync(self, &command.repo) {
+                        error!("Force sync of {} failed: {}", command.repo.display(), err);
+                    }
+                }
+            }
+        }
+        Self::publish_status(self);
+    }
+
+    /// Commits (and pushes) every pending change of a repository right away
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the repository cannot be opened or analyzed.
+    pub(crate) fn force_sync(&self, repo_path: &Path) -> Result<(), GitAutoPilotError> {
+        let operation_lock = self.state.operation_lock(repo_path);
+        let _operation_guard = operation_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let repo = Repository::open(repo_path)?;
+
+        // A force-sync retries quarantined paths right away
+        let workdir = repo.workdir().unwrap_or(repo_path).to_path_buf();
+        self.state
+            .with_repo(&workdir, |state| state.quarantined.clear());
+
+        Self::commit_pending_changes(self, &repo, repo_path, &Trigger::force_sync())?;
+
+        // Force-sync also pushes the commits waiting in a batch
+        if let Err(err) = Self::push_pending(self, repo_path, true) {
+            error!("Failed to push batched commits: {}", err);
+        }
+
+        let pending = self.backend.changed_paths(&workdir, &[])?.len();
+        self.state
+            .with_repo(repo_path, |state| state.pending_changes = pending);
+        Ok(())
+    }
+
+    /// Commits the changes made to a repository while the daemon was not running
+    ///
+    /// Runs once per repository on startup when `catch_up_on_start` is set.
+    /// Repositories seen for the first time are left to the `backlog_policy`.
+    ///
+    /// # Errors
+    /// Returns a `GitAutoPilotError` if the repository cannot be opened or analyzed.
+    pub(crate) fn catch_up(&self, repo_path: &Path) -> Result<(), GitAutoPilotError> {
+        let operation_lock = self.state.operation_lock(repo_path);
+        let _operation_guard = operation_lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let repo = Repository::open(repo_path)?;
+        if self.state.with_repo(repo_path, |state| state.paused)
+            || helper::is_automation_disabled(&repo)
+        {
+            debug!(
+                "Not catching up on paused repository {}",
+                repo_path.display()
+            );
+            return Ok(());
+        }
+        info!("Catching up on changes in {}", repo_path.display());
+        Self::commit_pending_changes(self, &repo, repo_path, &Trigger::catch_up())?;
+
+        let pending = self
+            .backend
+            .changed_paths(repo.workdir().unwrap_or(repo_path), &[])?
+            .len();
+        self.state
+            .with_repo(repo_path, |state| state.pending_changes = pending);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{committed_paths, head_summary, test_instance};
+    use std::fs;
+
+    #[test]
+    fn test_busy_repository_defers_without_waiting() {
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config {
+            busy_wait_ms: 60_000,
+            ..config::Config::default()
+        });
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        fs::write(repo.path().join("index.lock"), "").unwrap();
+        fs::write(workdir.join("notes.md"), "content\n").unwrap();
+        let mut event = Event::new(EventKind::Create(notify::event::CreateKind::File));
+        event.paths = vec![workdir.join("notes.md")];
+
+        let started = std::time::Instant::now();
+        GitAutoPilot::handle_event(&git_auto_pilot, &event, &workdir, SystemTime::now()).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(30));
+        assert_eq!(head_summary(&repo), "initial");
+        let retry_at = git_auto_pilot.state.with_repo(&workdir, |state| {
+            state.retry_at.get(&workdir.join("notes.md")).copied()
+        });
+        assert!(retry_at.is_some_and(|at| at > SystemTime::now()));
+    }
+
+    #[test]
+    fn test_batch_is_committed_per_package() {
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config {
+            batch_by_package: true,
+            ..config::Config::default()
+        });
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        fs::create_dir_all(workdir.join("api/src")).unwrap();
+        fs::write(workdir.join("api/Cargo.toml"), "[package]\n").unwrap();
+        git::stage_file(&repo, "api/Cargo.toml", false).unwrap();
+        git::commit(&repo, "api", None, None, None, None, None).unwrap();
+
+        let paths =
+            ["api/src/lib.rs", "api/src/main.rs", "notes.md"].map(|path| workdir.join(path));
+        for path in &paths {
+            fs::write(path, "content\n").unwrap();
+        }
+        git_auto_pilot.state.with_repo(&workdir, |state| {
+            state.batched_paths.extend(paths.iter().cloned());
+            state.batched_since = Some(SystemTime::now());
+        });
+        GitAutoPilot::flush_low_priority_batches(&git_auto_pilot, true);
+
+        let mut walk = repo.revwalk().unwrap();
+        walk.push_head().unwrap();
+        let summaries: Vec<String> = walk
+            .take(2)
+            .map(|id| {
+                let commit = repo.find_commit(id.unwrap()).unwrap();
+                commit.summary().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(
+            summaries,
+            ["chore(api): update 2 files", "chore: update notes.md"]
+        );
+    }
+
+    #[test]
+    fn test_catch_up_skips_ignored_dirs() {
+        let (_dir, repo, git_auto_pilot) = test_instance(config::Config {
+            ignored_dirs: vec![".git".to_string(), "build".to_string()],
+            ..config::Config::default()
+        });
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        fs::create_dir(workdir.join("build")).unwrap();
+        fs::write(workdir.join("build/out.txt"), "artifact\n").unwrap();
+        fs::write(workdir.join("notes.md"), "content\n").unwrap();
+
+        GitAutoPilot::catch_up(&git_auto_pilot, &workdir).unwrap();
+
+        assert_eq!(committed_paths(&repo), ["README.md", "notes.md"]);
+    }
+
+    /// Configuration detecting builds from cargo output
+    fn build_detection_config(busy_file: Option<&str>) -> config::Config {
+        config::Config {
+            build_detection: Some(config::BuildDetectionSettings {
+                indicators: vec!["target/".to_string(), ".cargo-lock".to_string()],
+                busy_file: busy_file.map(str::to_string),
+                quiet_secs: 60,
+            }),
+            ..config::Config::default()
+        }
+    }
+
+    #[test]
+    fn test_build_output_pauses_until_quiet() {
+        let (_dir, repo, git_auto_pilot) = test_instance(build_detection_config(None));
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        assert!(!GitAutoPilot::build_in_progress(&git_auto_pilot, &workdir));
+
+        // Events mixing build output and sources still carry something to commit
+        let mixed = Event::new(EventKind::Modify(ModifyKind::Any))
+            .add_path(workdir.join("target/debug/.cargo-lock"))
+            .add_path(workdir.join("notes.md"));
+        assert!(!GitAutoPilot::record_build_activity(
+            &git_auto_pilot,
+            &mixed,
+            &workdir
+        ));
+        assert!(GitAutoPilot::build_in_progress(&git_auto_pilot, &workdir));
+
+        let lock_file = Event::new(EventKind::Modify(ModifyKind::Any))
+            .add_path(workdir.join("target/debug/.cargo-lock"));
+        assert!(GitAutoPilot::record_build_activity(
+            &git_auto_pilot,
+            &lock_file,
+            &workdir
+        ));
+
+        git_auto_pilot.state.with_repo(&workdir, |state| {
+            state.build_activity_at = Some(SystemTime::now() - Duration::from_secs(61));
+        });
+        assert!(!GitAutoPilot::build_in_progress(&git_auto_pilot, &workdir));
+    }
+
+    #[test]
+    fn test_busy_file_pauses_while_it_exists() {
+        let (_dir, repo, git_auto_pilot) = test_instance(build_detection_config(Some(".building")));
+        let workdir = repo.workdir().unwrap().to_path_buf();
+        assert!(!GitAutoPilot::build_in_progress(&git_auto_pilot, &workdir));
+
+        fs::write(workdir.join(".building"), "").unwrap();
+        assert!(GitAutoPilot::build_in_progress(&git_auto_pilot, &workdir));
+
+        fs::remove_file(workdir.join(".building")).unwrap();
+        assert!(!GitAutoPilot::build_in_progress(&git_auto_pilot, &workdir));
+    }
+
+    #[test]
+    fn test_rescan_drops_removed_repositories() {
+        let dir = tempfile::tempdir().unwrap();
+        let projects = dir.path().canonicalize().unwrap().join("projects");
+        for name in ["app", "site"] {
+            Repository::init(projects.join(name)).unwrap();
+        }
+        let config = config::Config {
+            repos: vec![projects.join("*").into()],
+            ..config::Config::default()
+        };
+
+        let resolved = GitAutoPilot::expand_repos(&config, &[]).unwrap();
+        assert_eq!(
+            resolved.repos,
+            [projects.join("app"), projects.join("site")]
+        );
+
+        fs::remove_dir_all(projects.join("site")).unwrap();
+        let resolved = GitAutoPilot::expand_repos(&config, &[]).unwrap();
+        assert_eq!(resolved.repos, [projects.join("app")]);
+    }
+}
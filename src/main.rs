@@ -1,13 +1,28 @@
-use git_auto_pilot::GitAutoPilot;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
-mod config;
-mod error;
-mod git;
-mod helper;
-mod logger;
+use git_auto_pilot_core::config::LogTarget;
+use git_auto_pilot_core::{CancellationToken, GitAutoPilot, GitAutoPilotError, WatchExit};
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            // Machine-readable prefix, e.g. `error[auth]: ...`
+            let category = err.category();
+            let message = err.to_string();
+            eprintln!(
+                "error[{}]: {}",
+                category,
+                git_auto_pilot_core::redact::redact(&message)
+            );
+            ExitCode::from(category.exit_code())
+        }
+    }
+}
+
+async fn run() -> Result<(), GitAutoPilotError> {
     let cmd_arguments = clap::Command::new("cmd-program")
         .arg(
             clap::Arg::new("verbose")
@@ -16,12 +31,248 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .action(clap::ArgAction::Count) // This is the new way to count occurrences
                 .help("Increases logging verbosity each use for up to 3 times"),
         )
+        .arg(
+            clap::Arg::new("force")
+                .long("force")
+                .action(clap::ArgAction::SetTrue)
+                .help("Starts even if another instance is already running"),
+        )
+        .arg(
+            clap::Arg::new("config")
+                .short('c')
+                .long("config")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Uses this configuration file (default: $GIT_AUTO_PILOT_CONFIG, then $XDG_CONFIG_HOME/git-auto-pilot/config.json)"),
+        )
+        .arg(
+            clap::Arg::new("dot-dir")
+                .long("dot-dir")
+                .value_name("DIR")
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Keeps the configuration, state and cache in this directory, e.g. when there is no home directory (default: $GIT_AUTO_PILOT_DOT_DIR, then the XDG directories)"),
+        )
+        .arg(
+            clap::Arg::new("health-port")
+                .long("health-port")
+                .value_name("PORT")
+                .value_parser(clap::value_parser!(u16))
+                .help("Serves /healthz and /readyz probes on this port (default: health_port of the configuration)"),
+        )
+        .arg(
+            clap::Arg::new("repo")
+                .long("repo")
+                .value_name("PATH")
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::value_parser!(PathBuf))
+                .help("Watches only this configured repository, may be repeated (default: all repositories)"),
+        )
+        .arg(
+            clap::Arg::new("once")
+                .long("once")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("exit-after")
+                .help("Exits after processing the first batch of events"),
+        )
+        .arg(
+            clap::Arg::new("exit-after")
+                .long("exit-after")
+                .value_name("N")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .help("Exits after making N commits"),
+        )
+        .arg(
+            clap::Arg::new("log-file")
+                .long("log-file")
+                .value_name("PATH")
+                .value_parser(clap::value_parser!(PathBuf))
+                .conflicts_with("log-target")
+                .help("Appends the log to this file instead of stdout"),
+        )
+        .arg(
+            clap::Arg::new("log-target")
+                .long("log-target")
+                .value_parser(["stdout", "syslog", "journald"])
+                .help("Sends the log to stdout, syslog or the systemd journal (default: log_target of the configuration)"),
+        )
+        .subcommand(clap::Command::new("status").about("Shows the state of the running daemon"))
+        .subcommand(clap::Command::new("tui").about("Opens a live dashboard of the running daemon"))
+        .subcommand(clap::Command::new("doctor").about("Checks the configuration for problems"))
+        .subcommand(
+            clap::Command::new("preview")
+                .about("Prints the commit message the templates render for a change")
+                .arg(
+                    clap::Arg::new("repo")
+                        .long("repo")
+                        .value_name("PATH")
+                        .required(true)
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Repository to render the message for"),
+                )
+                .arg(
+                    clap::Arg::new("file")
+                        .long("file")
+                        .value_name("F")
+                        .help("Changed file, relative to the repository root (default: sample values)"),
+                )
+                .arg(
+                    clap::Arg::new("status")
+                        .long("status")
+                        .value_parser(["new", "modified", "deleted", "renamed"])
+                        .help("Overrides the status of the change"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("test-templates")
+                .about("Renders the templates for sample changes and compares them with golden files")
+                .arg(
+                    clap::Arg::new("golden")
+                        .long("golden")
+                        .value_name("DIR")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Directory of the golden files (default: golden in the dot directory)"),
+                )
+                .arg(
+                    clap::Arg::new("update")
+                        .long("update")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Writes the current output as the new golden files"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("commit")
+                .about("Commits the changes of the given paths once, using the configured templates")
+                .arg(
+                    clap::Arg::new("paths")
+                        .value_name("PATH")
+                        .required(true)
+                        .num_args(1..)
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Files or directories to commit"),
+                )
+                .arg(
+                    clap::Arg::new("push")
+                        .long("push")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Pushes the commits afterwards"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("replay")
+                .about("Re-runs journaled events in dry-run mode, printing the commits they would make")
+                .arg(
+                    clap::Arg::new("journal")
+                        .long("journal")
+                        .value_name("FILE")
+                        .required(true)
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Journal file to replay"),
+                )
+                .arg(
+                    clap::Arg::new("repo")
+                        .long("repo")
+                        .value_name("PATH")
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .help("Only replays the events of this repository"),
+                ),
+        )
         .get_matches();
 
+    // Every command resolves its directories below the dot directory
+    if let Some(dot_dir) = cmd_arguments.get_one::<PathBuf>("dot-dir") {
+        git_auto_pilot_core::set_dot_dir(dot_dir.clone());
+    }
+
     // Get the number of times the verbose flag was passed
     let verbosity: u64 = cmd_arguments.get_count("verbose") as u64;
+    let force = cmd_arguments.get_flag("force");
+    let config_path = cmd_arguments.get_one::<PathBuf>("config").cloned();
+    let log_target = match (
+        cmd_arguments.get_one::<PathBuf>("log-file"),
+        cmd_arguments
+            .get_one::<String>("log-target")
+            .map(String::as_str),
+    ) {
+        (Some(path), _) => Some(LogTarget::File(path.clone())),
+        (None, Some("syslog")) => Some(LogTarget::Syslog),
+        (None, Some("journald")) => Some(LogTarget::Journald),
+        (None, Some(_)) => Some(LogTarget::Stdout),
+        (None, None) => None,
+    };
+    let exit = match cmd_arguments.get_one::<u64>("exit-after") {
+        Some(&commits) => WatchExit::AfterCommits(commits),
+        None if cmd_arguments.get_flag("once") => WatchExit::AfterBatch,
+        None => WatchExit::Never,
+    };
+
+    match cmd_arguments.subcommand() {
+        Some(("status", _)) => git_auto_pilot_core::print_status()?,
+        Some(("tui", _)) => git_auto_pilot_core::run_tui()?,
+        Some(("doctor", _)) => git_auto_pilot_core::run_doctor(config_path)?,
+        Some(("preview", preview)) => git_auto_pilot_core::run_preview(
+            config_path,
+            preview
+                .get_one::<PathBuf>("repo")
+                .expect("--repo is required"),
+            preview.get_one::<String>("file").map(String::as_str),
+            preview.get_one::<String>("status").map(String::as_str),
+        )?,
+        Some(("test-templates", test)) => git_auto_pilot_core::run_test_templates(
+            config_path,
+            test.get_one::<PathBuf>("golden").cloned(),
+            test.get_flag("update"),
+        )?,
+        Some(("commit", commit)) => git_auto_pilot_core::run_commit(
+            verbosity,
+            config_path,
+            &commit
+                .get_many::<PathBuf>("paths")
+                .expect("paths are required")
+                .cloned()
+                .collect::<Vec<_>>(),
+            commit.get_flag("push"),
+        )?,
+        Some(("replay", replay)) => git_auto_pilot_core::run_replay(
+            verbosity,
+            config_path,
+            replay
+                .get_one::<PathBuf>("journal")
+                .expect("--journal is required"),
+            replay.get_one::<PathBuf>("repo").map(PathBuf::as_path),
+        )?,
+        _ => {
+            let mut git_auto_pilot = GitAutoPilot::new(verbosity, force, config_path, log_target)?;
+            if let Some(&port) = cmd_arguments.get_one::<u16>("health-port") {
+                git_auto_pilot.set_health_port(port);
+            }
+            if let Some(repos) = cmd_arguments.get_many::<PathBuf>("repo") {
+                git_auto_pilot.set_repo_filter(&repos.cloned().collect::<Vec<_>>())?;
+            }
+
+            // Shut down cleanly on Ctrl-C
+            let cancellation = CancellationToken::new();
+            let ctrl_c_cancellation = cancellation.clone();
+            tokio::spawn(async move {
+                if tokio::signal::ctrl_c().await.is_ok() {
+                    ctrl_c_cancellation.cancel();
+                }
+            });
+
+            // and when stopped by a service manager, keeping queued events
+            #[cfg(unix)]
+            {
+                use tokio::signal::unix::{signal, SignalKind};
+                let term_cancellation = cancellation.clone();
+                let mut terminate = signal(SignalKind::terminate())?;
+                tokio::spawn(async move {
+                    if terminate.recv().await.is_some() {
+                        term_cancellation.cancel();
+                    }
+                });
+            }
 
-    let git_auto_pilot = GitAutoPilot::new(verbosity)?;
-    GitAutoPilot::watch(git_auto_pilot).await?;
+            GitAutoPilot::watch_until(git_auto_pilot, cancellation, exit).await?;
+        }
+    }
     Ok(())
 }
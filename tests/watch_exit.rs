@@ -0,0 +1,123 @@
+//! End-to-end tests of `--once` and `--exit-after`: the watcher must exit on
+//! its own once the first batch of events is processed or enough commits
+//! were made.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+use git2::Repository;
+
+/// Time the watcher gets to start, commit or exit before a test fails
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Interval between checks of the watcher and the repository
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Creates a repository with a committed `README.md` below `dir`
+fn init_repo(dir: &Path) -> PathBuf {
+    let path = dir.join("repo");
+    let repo = Repository::init(&path).unwrap();
+    fs::write(path.join("README.md"), "readme\n").unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("README.md")).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+    repo.commit(Some("HEAD"), &signature, &signature, "initial", &tree, &[])
+        .unwrap();
+    path.canonicalize().unwrap()
+}
+
+/// Starts the watcher on `repo`, with its directories below `dir`, and waits
+/// until it watches the repository
+///
+/// The home directory points at `dir` as well, so nothing of the real one is
+/// read or migrated.
+fn start_watcher(dir: &Path, repo: &Path, args: &[&str]) -> Child {
+    let dot_dir = dir.join("dot");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_git-auto-pilot"))
+        .arg("--dot-dir")
+        .arg(&dot_dir)
+        .args(args)
+        .env("HOME", dir)
+        .env("GIT_AUTO_PILOT_REPOS", repo)
+        .env("GIT_AUTO_PILOT_PUSH_ENABLED", "false")
+        .env("GIT_AUTHOR_NAME", "Test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // The status is published once every repository is watched
+    let status_file = dot_dir.join("state").join("status.json");
+    wait_until(&mut child, || status_file.exists());
+    child
+}
+
+/// Polls `condition` until it holds, failing if the watcher exits or the
+/// condition does not hold within `TIMEOUT`
+fn wait_until(child: &mut Child, mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + TIMEOUT;
+    while !condition() {
+        if let Some(status) = child.try_wait().unwrap() {
+            panic!("watcher exited early: {}", status);
+        }
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            panic!("timed out waiting for the watcher");
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Waits for the watcher to exit on its own, killing it after `TIMEOUT`
+fn wait_for_exit(child: &mut Child) -> ExitStatus {
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            return status;
+        }
+        if Instant::now() > deadline {
+            let _ = child.kill();
+            panic!("watcher did not exit");
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Number of commits reachable from `HEAD`
+fn commit_count(repo: &Path) -> usize {
+    let repo = Repository::open(repo).unwrap();
+    let mut revwalk = repo.revwalk().unwrap();
+    revwalk.push_head().unwrap();
+    revwalk.count()
+}
+
+#[test]
+fn test_once_exits_after_the_first_batch() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = init_repo(dir.path());
+    let mut watcher = start_watcher(dir.path(), &repo, &["--once"]);
+
+    fs::write(repo.join("notes.md"), "content\n").unwrap();
+
+    assert!(wait_for_exit(&mut watcher).success());
+    assert!(commit_count(&repo) > 1);
+}
+
+#[test]
+fn test_exit_after_stops_after_the_given_commits() {
+    let dir = tempfile::tempdir().unwrap();
+    let repo = init_repo(dir.path());
+    let mut watcher = start_watcher(dir.path(), &repo, &["--exit-after", "2"]);
+
+    fs::write(repo.join("first.md"), "content\n").unwrap();
+    wait_until(&mut watcher, || commit_count(&repo) > 1);
+    fs::write(repo.join("second.md"), "content\n").unwrap();
+
+    assert!(wait_for_exit(&mut watcher).success());
+    assert_eq!(commit_count(&repo), 3);
+}